@@ -0,0 +1,135 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+//! `cargo build-fw` rebuilds the device firmware from the CCS projects under
+//! `src/fw/gcc` with `scripts/firmware.sh`, and refreshes the SHA-256 digests
+//! in the device family descriptors for the new binaries, e.g. after changing
+//! the doorbell protocol of both sides. The binaries and the descriptors are
+//! committed together afterwards:
+//!
+//! ```bash
+//! $ CCS_ROOT=/opt/ti/ccs cargo build-fw
+//! $ git add src/assets
+//! ```
+//!
+//! With `--digests-only` the binaries under `src/assets/fw` are kept, and only
+//! the digests are refreshed, e.g. for binaries built in CCS directly.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+
+use sha2::{Digest, Sha256};
+
+const FAMILIES_DIR: &str = "src/assets/families";
+const ASSETS_DIR: &str = "src/assets/fw";
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("Error: {}", err);
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let mut digests_only = false;
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--digests-only" => digests_only = true,
+            _ => {
+                return Err(format!(
+                    "Unexpected argument {}, usage: cargo build-fw [--digests-only]",
+                    arg
+                ))
+            }
+        }
+    }
+
+    let root = root();
+
+    if !digests_only {
+        let status = Command::new("bash")
+            .arg("scripts/firmware.sh")
+            .current_dir(&root)
+            .status()
+            .map_err(|err| format!("Unable to run scripts/firmware.sh: {}", err))?;
+        if !status.success() {
+            return Err(format!(
+                "Building the firmware failed ({}), see the output above",
+                status
+            ));
+        }
+    }
+
+    let families = root.join(FAMILIES_DIR);
+    let mut descriptors: Vec<PathBuf> = fs::read_dir(&families)
+        .and_then(|entries| {
+            entries
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect()
+        })
+        .map_err(|err| format!("Unable to list {}: {}", families.display(), err))?;
+    descriptors.retain(|path| path.extension().is_some_and(|ext| ext == "toml"));
+    descriptors.sort();
+
+    for descriptor in descriptors {
+        refresh_digest(&root, &descriptor)?;
+    }
+
+    Ok(())
+}
+
+/// Root of the repository, which this crate is a member of.
+fn root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .expect("The crate is in the repository")
+        .to_path_buf()
+}
+
+/// Replace the `sha256` of the `[firmware]` table of the descriptor with the
+/// digest of its binary, keeping the rest of the file as written.
+fn refresh_digest(root: &Path, descriptor: &Path) -> Result<(), String> {
+    let text = fs::read_to_string(descriptor)
+        .map_err(|err| format!("Unable to read {}: {}", descriptor.display(), err))?;
+
+    let file = text
+        .lines()
+        .skip_while(|line| line.trim() != "[firmware]")
+        .find_map(|line| {
+            let value = line.trim().strip_prefix("file")?.trim().strip_prefix('=')?;
+            Some(value.trim().trim_matches('"').to_string())
+        })
+        .ok_or_else(|| format!("No firmware file in {}", descriptor.display()))?;
+    let binary = fs::read(root.join(ASSETS_DIR).join(&file))
+        .map_err(|err| format!("Unable to read firmware {}: {}", file, err))?;
+    let digest: String = Sha256::digest(&binary)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+
+    let mut in_firmware = false;
+    let mut updated = String::new();
+    for line in text.lines() {
+        if line.starts_with('[') {
+            in_firmware = line.trim() == "[firmware]";
+        }
+        if in_firmware && line.trim_start().starts_with("sha256") {
+            updated.push_str(&format!("sha256 = \"{}\"\n", digest));
+        } else {
+            updated.push_str(line);
+            updated.push('\n');
+        }
+    }
+
+    if updated != text {
+        fs::write(descriptor, updated)
+            .map_err(|err| format!("Unable to write {}: {}", descriptor.display(), err))?;
+    }
+    println!("{}: {}", file, digest);
+
+    Ok(())
+}
@@ -11,7 +11,7 @@ use jni::{objects::JObject, JNIEnv};
 
 use crate::com::ti::debug::engine::scripting::DebugServer;
 
-type Result<T, E = jni::errors::Error> = std::result::Result<T, E>;
+use crate::error::{CheckException, Result};
 
 #[derive(Copy, Clone, Debug)]
 pub enum TraceLevel {
@@ -76,7 +76,10 @@ impl<'a> ScriptingEnvironment<'a> {
 
         let class = env.find_class(ScriptingEnvironment::CLASS)?;
 
-        let instance = env.call_static_method(class, METHOD, SIGNATURE, &[])?.l()?;
+        let instance = env
+            .call_static_method(class, METHOD, SIGNATURE, &[])
+            .check_exception(&env)?
+            .l()?;
 
         Ok(Self { env, instance })
     }
@@ -89,7 +92,8 @@ impl<'a> ScriptingEnvironment<'a> {
 
         let debug_server = self
             .env
-            .call_method(self.instance, METHOD, SIGNATURE, &[From::from(file_name)])?
+            .call_method(self.instance, METHOD, SIGNATURE, &[From::from(file_name)])
+            .check_exception(&self.env)?
             .l()?;
 
         assert!(self
@@ -113,7 +117,8 @@ impl<'a> ScriptingEnvironment<'a> {
                 METHOD,
                 SIGNATURE,
                 &[From::from(file_name), From::from(stylesheet_name)],
-            )?
+            )
+            .check_exception(&self.env)?
             .v()?;
 
         Ok(())
@@ -124,7 +129,8 @@ impl<'a> ScriptingEnvironment<'a> {
         const SIGNATURE: &str = "()V";
 
         self.env
-            .call_method(self.instance, METHOD, SIGNATURE, &[])?
+            .call_method(self.instance, METHOD, SIGNATURE, &[])
+            .check_exception(&self.env)?
             .v()?;
 
         Ok(())
@@ -137,7 +143,8 @@ impl<'a> ScriptingEnvironment<'a> {
         let level = JObject::from(self.env.new_string(trace_level.to_string())?);
 
         self.env
-            .call_method(self.instance, METHOD, SIGNATURE, &[From::from(level)])?
+            .call_method(self.instance, METHOD, SIGNATURE, &[From::from(level)])
+            .check_exception(&self.env)?
             .v()?;
 
         Ok(())
@@ -150,20 +157,46 @@ impl<'a> ScriptingEnvironment<'a> {
         let level = JObject::from(self.env.new_string(trace_level.to_string())?);
 
         self.env
-            .call_method(self.instance, METHOD, SIGNATURE, &[From::from(level)])?
+            .call_method(self.instance, METHOD, SIGNATURE, &[From::from(level)])
+            .check_exception(&self.env)?
             .v()?;
 
         Ok(())
     }
 
     pub fn set_script_timeout(&self, timeout: Duration) -> Result<()> {
+        self.set_script_timeout_millis(timeout.as_millis() as jni::sys::jint)
+    }
+
+    pub fn disable_script_timeout(&self) -> Result<()> {
+        self.set_script_timeout_millis(-1)
+    }
+
+    /// Get the current script timeout, or `None` if the timeout is disabled.
+    pub fn get_script_timeout(&self) -> Result<Option<Duration>> {
+        const METHOD: &str = "getScriptTimeout";
+        const SIGNATURE: &str = "()I";
+
+        let timeout = self
+            .env
+            .call_method(self.instance, METHOD, SIGNATURE, &[])
+            .check_exception(&self.env)?
+            .i()?;
+
+        if timeout > 0 {
+            Ok(Some(Duration::from_millis(timeout as u64)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn set_script_timeout_millis(&self, timeout: jni::sys::jint) -> Result<()> {
         const METHOD: &str = "setScriptTimeout";
         const SIGNATURE: &str = "(I)V";
 
-        let timeout = timeout.as_millis() as jni::sys::jint;
-
         self.env
-            .call_method(self.instance, METHOD, SIGNATURE, &[From::from(timeout)])?
+            .call_method(self.instance, METHOD, SIGNATURE, &[From::from(timeout)])
+            .check_exception(&self.env)?
             .v()?;
 
         Ok(())
@@ -4,14 +4,18 @@
 // notice may not be copied, modified, or distributed except according to those terms.
 
 use std::string;
+use std::thread;
+use std::time::Duration;
 
 use jni::{
-    objects::JObject,
-    sys::{jboolean, jint, jlong, jlongArray, jsize},
+    objects::{JObject, JString},
+    sys::{jboolean, jint, jlong, jlongArray, jobjectArray, jsize},
     JNIEnv,
 };
 
-pub type Result<T, E = jni::errors::Error> = std::result::Result<T, E>;
+use crate::com::ti::ccstudio::scripting::environment::ScriptingEnvironment;
+pub use crate::error::Result;
+use crate::error::{CheckException, Error};
 
 pub struct DebugServer<'a> {
     env: JNIEnv<'a>,
@@ -33,12 +37,36 @@ impl<'a> DebugServer<'a> {
         let config_file = JObject::from(self.env.new_string(config_file)?);
 
         self.env
-            .call_method(self.instance, METHOD, SIGNATURE, &[From::from(config_file)])?
+            .call_method(self.instance, METHOD, SIGNATURE, &[From::from(config_file)])
+            .check_exception(&self.env)?
             .v()?;
 
         Ok(())
     }
 
+    // java.lang.String[] getListOfCPUs();
+    pub fn get_list_of_cpus(&self) -> Result<Vec<String>> {
+        const METHOD: &str = "getListOfCPUs";
+        const SIGNATURE: &str = "()[Ljava/lang/String;";
+
+        let array_obj = self
+            .env
+            .call_method(self.instance, METHOD, SIGNATURE, &[])
+            .check_exception(&self.env)?
+            .l()?;
+
+        let array: jobjectArray = array_obj.into_inner();
+        let length = self.env.get_array_length(array)?;
+
+        let mut res = Vec::with_capacity(length as usize);
+        for i in 0..length {
+            let element = JString::from(self.env.get_object_array_element(array, i)?);
+            res.push(self.env.get_string(element)?.into());
+        }
+
+        Ok(res)
+    }
+
     pub fn open_session(&self, pattern: &str) -> Result<DebugSession<'a>> {
         const METHOD: &str = "openSession";
         const SIGNATURE: &str = "(Ljava/lang/String;)Lcom/ti/debug/engine/scripting/DebugSession;";
@@ -47,7 +75,8 @@ impl<'a> DebugServer<'a> {
 
         let debug_session = self
             .env
-            .call_method(self.instance, METHOD, SIGNATURE, &[From::from(pattern)])?
+            .call_method(self.instance, METHOD, SIGNATURE, &[From::from(pattern)])
+            .check_exception(&self.env)?
             .l()?;
 
         Ok(DebugSession::new(self.env.clone(), debug_session)?)
@@ -58,7 +87,8 @@ impl<'a> DebugServer<'a> {
         const SIGNATURE: &str = "()V";
 
         self.env
-            .call_method(self.instance, METHOD, SIGNATURE, &[])?
+            .call_method(self.instance, METHOD, SIGNATURE, &[])
+            .check_exception(&self.env)?
             .v()?;
 
         Ok(())
@@ -112,18 +142,51 @@ impl<'a> Target<'a> {
         const SIGNATURE: &str = "()V";
 
         self.env
-            .call_method(self.instance, METHOD, SIGNATURE, &[])?
+            .call_method(self.instance, METHOD, SIGNATURE, &[])
+            .check_exception(&self.env)?
             .v()?;
 
         Ok(())
     }
 
+    /// Connect to the target, retrying up to `attempts` times in total. Each
+    /// attempt is bounded by `timeout` through the script timeout, which is
+    /// restored afterwards. Returns the number of retries needed.
+    pub fn connect_with_retry(&self, timeout: Duration, attempts: u32) -> Result<u32> {
+        const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+        let script = ScriptingEnvironment::new(self.env)?;
+        let prev_timeout = script.get_script_timeout()?;
+        script.set_script_timeout(timeout)?;
+
+        let mut res = self.connect();
+        let mut retries = 0;
+        while retries + 1 < attempts {
+            match res {
+                Err(Error::JavaException { .. }) => {
+                    thread::sleep(RETRY_DELAY);
+                    res = self.connect();
+                    retries += 1;
+                }
+                _ => break,
+            }
+        }
+
+        match prev_timeout {
+            Some(prev_timeout) => script.set_script_timeout(prev_timeout)?,
+            None => script.disable_script_timeout()?,
+        }
+
+        res.map(|_| retries)
+    }
+
     pub fn disconnect(&self) -> Result<()> {
         const METHOD: &str = "disconnect";
         const SIGNATURE: &str = "()V";
 
         self.env
-            .call_method(self.instance, METHOD, SIGNATURE, &[])?
+            .call_method(self.instance, METHOD, SIGNATURE, &[])
+            .check_exception(&self.env)?
             .v()?;
 
         Ok(())
@@ -146,7 +209,8 @@ impl<'a> Target<'a> {
         const SIGNATURE: &str = "()V";
 
         self.env
-            .call_method(self.instance, METHOD, SIGNATURE, &[])?
+            .call_method(self.instance, METHOD, SIGNATURE, &[])
+            .check_exception(&self.env)?
             .v()?;
 
         Ok(())
@@ -158,7 +222,8 @@ impl<'a> Target<'a> {
 
         let ret = self
             .env
-            .call_method(self.instance, METHOD, SIGNATURE, &[])?
+            .call_method(self.instance, METHOD, SIGNATURE, &[])
+            .check_exception(&self.env)?
             .z()?;
 
         Ok(ret)
@@ -169,7 +234,8 @@ impl<'a> Target<'a> {
         const SIGNATURE: &str = "()V";
 
         self.env
-            .call_method(self.instance, METHOD, SIGNATURE, &[])?
+            .call_method(self.instance, METHOD, SIGNATURE, &[])
+            .check_exception(&self.env)?
             .v()?;
 
         Ok(())
@@ -198,6 +264,29 @@ pub enum Register {
     XPSR,
 }
 
+impl Register {
+    pub const ALL: &'static [Register] = &[
+        Register::R0,
+        Register::R1,
+        Register::R2,
+        Register::R3,
+        Register::R4,
+        Register::R5,
+        Register::R6,
+        Register::R7,
+        Register::R8,
+        Register::R9,
+        Register::R10,
+        Register::R11,
+        Register::R12,
+        Register::MSP,
+        Register::PSP,
+        Register::LR,
+        Register::PC,
+        Register::XPSR,
+    ];
+}
+
 impl string::ToString for Register {
     fn to_string(&self) -> String {
         let res = match self {
@@ -262,7 +351,24 @@ impl<'a> Memory<'a> {
                     From::from(type_size),
                     From::from(byte_swap),
                 ],
-            )?
+            )
+            .check_exception(&self.env)?
+            .v()?;
+
+        Ok(())
+    }
+
+    /// Load an executable, e.g. a TI ELF .out file, together with its
+    /// symbols. The PC is set to the entry point of the program.
+    pub fn load_program(&self, filename: &str) -> Result<()> {
+        const METHOD: &str = "loadProgram";
+        const SIGNATURE: &str = "(Ljava/lang/String;)V";
+
+        let filename = JObject::from(self.env.new_string(filename)?);
+
+        self.env
+            .call_method(self.instance, METHOD, SIGNATURE, &[From::from(filename)])
+            .check_exception(&self.env)?
             .v()?;
 
         Ok(())
@@ -289,7 +395,8 @@ impl<'a> Memory<'a> {
                     From::from(value),
                     From::from(type_size),
                 ],
-            )?
+            )
+            .check_exception(&self.env)?
             .v()?;
 
         Ok(())
@@ -320,7 +427,8 @@ impl<'a> Memory<'a> {
                     From::from(array_obj),
                     From::from(type_size),
                 ],
-            )?
+            )
+            .check_exception(&self.env)?
             .v()?;
 
         Ok(())
@@ -348,7 +456,8 @@ impl<'a> Memory<'a> {
                     From::from(type_size),
                     From::from(signed),
                 ],
-            )?
+            )
+            .check_exception(&self.env)?
             .j()?;
 
         Ok(res)
@@ -378,7 +487,8 @@ impl<'a> Memory<'a> {
                     From::from(num_values),
                     From::from(signed),
                 ],
-            )?
+            )
+            .check_exception(&self.env)?
             .l()?;
 
         let array: jlongArray = array_obj.into_inner();
@@ -389,6 +499,44 @@ impl<'a> Memory<'a> {
         Ok(res)
     }
 
+    /// Whether the word at `address` is RAM of the connected device, as
+    /// reported by the target: the memory map of the debugger, which is set up
+    /// for the device when connecting, allows accessing it and a written value
+    /// reads back. The original value of the word is restored.
+    pub fn is_ram(&self, page: jint, address: jlong) -> Result<bool> {
+        let original = match self.read_data(page, address, 32, false as _) {
+            Ok(value) => value,
+            Err(Error::JavaException { .. }) => return Ok(false),
+            Err(err) => return Err(err),
+        };
+
+        let pattern = !original & 0xFFFF_FFFF;
+        match self.write_data(page, address, pattern, 32) {
+            Ok(()) => (),
+            Err(Error::JavaException { .. }) => return Ok(false),
+            Err(err) => return Err(err),
+        }
+        let read_back = self.read_data(page, address, 32, false as _)?;
+        self.write_data(page, address, original, 32)?;
+
+        Ok(read_back == pattern)
+    }
+
+    pub fn read_register(&self, register: Register) -> Result<jlong> {
+        const METHOD: &str = "readRegister";
+        const SIGNATURE: &str = "(Ljava/lang/String;)J";
+
+        let register = JObject::from(self.env.new_string(register.to_string())?);
+
+        let res = self
+            .env
+            .call_method(self.instance, METHOD, SIGNATURE, &[From::from(register)])
+            .check_exception(&self.env)?
+            .j()?;
+
+        Ok(res)
+    }
+
     pub fn write_register(&self, register: Register, value: jlong) -> Result<()> {
         const METHOD: &str = "writeRegister";
         const SIGNATURE: &str = "(Ljava/lang/String;J)V";
@@ -401,7 +549,8 @@ impl<'a> Memory<'a> {
                 METHOD,
                 SIGNATURE,
                 &[From::from(register), From::from(value)],
-            )?
+            )
+            .check_exception(&self.env)?
             .v()?;
 
         Ok(())
@@ -428,9 +577,27 @@ impl<'a> Expression<'a> {
 
         let res = self
             .env
-            .call_method(self.instance, METHOD, SIGNATURE, &[From::from(expression)])?
+            .call_method(self.instance, METHOD, SIGNATURE, &[From::from(expression)])
+            .check_exception(&self.env)?
             .j()?;
 
         Ok(res)
     }
+
+    /// Evaluate an expression, e.g. a GEL function, returning the result as a
+    /// string.
+    pub fn evaluate_string(&self, expression: &str) -> Result<String> {
+        const METHOD: &str = "evaluateToString";
+        const SIGNATURE: &str = "(Ljava/lang/String;)Ljava/lang/String;";
+
+        let expression = JObject::from(self.env.new_string(expression)?);
+
+        let res = self
+            .env
+            .call_method(self.instance, METHOD, SIGNATURE, &[From::from(expression)])
+            .check_exception(&self.env)?
+            .l()?;
+
+        Ok(self.env.get_string(JString::from(res))?.into())
+    }
 }
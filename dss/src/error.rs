@@ -0,0 +1,122 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+use std::error;
+use std::fmt;
+use std::path::PathBuf;
+
+use jni::{
+    objects::{JObject, JString},
+    JNIEnv,
+};
+
+#[derive(Debug)]
+pub enum Error {
+    /// An error in the JNI layer.
+    Jni(jni::errors::Error),
+    /// A Java exception was thrown by a DSS call.
+    JavaException {
+        class: String,
+        message: Option<String>,
+    },
+    /// The DSS worker thread has stopped.
+    WorkerStopped,
+    /// A path which cannot be passed to DSS or the JVM.
+    InvalidPath(PathBuf),
+    /// An invalid option of the JVM.
+    JvmOption(jni::JvmError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Jni(err) => write!(f, "JNI error: {}", err),
+            Error::JavaException {
+                class,
+                message: Some(message),
+            } => write!(f, "{}: {}", class, message),
+            Error::JavaException {
+                class,
+                message: None,
+            } => write!(f, "{}", class),
+            Error::WorkerStopped => write!(f, "The DSS worker thread has stopped"),
+            Error::InvalidPath(path) => write!(
+                f,
+                "Path {} cannot be passed to DSS, it is not valid Unicode or contains a class \
+                 path separator",
+                path.display()
+            ),
+            Error::JvmOption(err) => write!(f, "Invalid JVM option: {}", err),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Jni(err) => Some(err),
+            Error::JvmOption(err) => Some(err),
+            Error::JavaException { .. } | Error::WorkerStopped | Error::InvalidPath(_) => None,
+        }
+    }
+}
+
+impl From<jni::errors::Error> for Error {
+    fn from(err: jni::errors::Error) -> Self {
+        Error::Jni(err)
+    }
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+pub(crate) trait CheckException<T> {
+    /// Convert a pending Java exception into [`Error::JavaException`] with the
+    /// class and message of the thrown exception, clearing the exception.
+    fn check_exception(self, env: &JNIEnv) -> Result<T>;
+}
+
+impl<T> CheckException<T> for jni::errors::Result<T> {
+    fn check_exception(self, env: &JNIEnv) -> Result<T> {
+        self.map_err(|err| match err {
+            jni::errors::Error::JavaException => take_exception(env).unwrap_or(Error::Jni(err)),
+            err => Error::Jni(err),
+        })
+    }
+}
+
+fn take_exception(env: &JNIEnv) -> Option<Error> {
+    let throwable = env.exception_occurred().ok()?;
+    if throwable.is_null() {
+        return None;
+    }
+    env.exception_clear().ok()?;
+
+    let throwable = JObject::from(throwable);
+
+    let class = env
+        .call_method(throwable, "getClass", "()Ljava/lang/Class;", &[])
+        .ok()?
+        .l()
+        .ok()?;
+    let class = env
+        .call_method(class, "getName", "()Ljava/lang/String;", &[])
+        .ok()?
+        .l()
+        .ok()?;
+    let class: String = env.get_string(JString::from(class)).ok()?.into();
+
+    let message = env
+        .call_method(throwable, "getMessage", "()Ljava/lang/String;", &[])
+        .ok()?
+        .l()
+        .ok()?;
+    let message = if message.is_null() {
+        None
+    } else {
+        Some(env.get_string(JString::from(message)).ok()?.into())
+    };
+
+    Some(Error::JavaException { class, message })
+}
@@ -8,6 +8,8 @@ extern crate path_clean;
 extern crate path_slash;
 
 pub mod com;
+mod error;
+pub mod path;
 
 use std::path::Path;
 
@@ -17,10 +19,9 @@ use path_clean::PathClean;
 use com::ti::ccstudio::scripting::environment::ScriptingEnvironment;
 
 pub mod sys;
+pub mod worker;
 
-pub type Error = jni::errors::Error;
-
-pub type Result<T, E = Error> = std::result::Result<T, E>;
+pub use error::{Error, Result};
 
 pub struct Dss {
     jvm: jni::JavaVM,
@@ -34,12 +35,15 @@ impl Dss {
 
         let jvm_args = jni::InitArgsBuilder::new()
             .version(JNIVersion::V8)
-            .option(&format!("-Djava.class.path={}", dss_classpath.display()))
+            .option(&format!(
+                "-Djava.class.path={}",
+                path::to_class_path(&dss_classpath)?
+            ))
             .option("-Dfile.encoding=UTF8")
             .option("-Xms40m")
             .option("-Xmx384m")
             .build()
-            .unwrap();
+            .map_err(Error::JvmOption)?;
 
         let jvm = jni::JavaVM::new(jvm_args)?;
         jvm.attach_current_thread_permanently()?;
@@ -48,7 +52,8 @@ impl Dss {
     }
 
     pub fn scripting_environment(&self) -> Result<ScriptingEnvironment> {
-        let env = self.jvm.get_env()?;
+        // Attaches the calling thread to the JVM, if not already attached
+        let env = self.jvm.attach_current_thread_permanently()?;
         Ok(ScriptingEnvironment::new(env)?)
     }
 }
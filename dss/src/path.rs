@@ -0,0 +1,107 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+//! Conversion of host paths into the strings passed to DSS and the JVM.
+//!
+//! DSS takes paths as Java strings, which hold any Unicode path, but does not
+//! understand the verbatim `\\?\` prefix of canonicalized Windows paths. The
+//! options of the JVM are C strings decoded in the platform encoding, which on
+//! Windows is the ANSI code page rather than UTF-8, so a CCS installed under a
+//! path with non-ASCII characters fails to load `dss.jar`. On Windows the short
+//! (8.3) form of such a path is passed to the JVM instead, which is ASCII.
+
+use std::path::{Component, Path, PathBuf, Prefix};
+
+use path_slash::PathExt;
+
+use crate::error::{Error, Result};
+
+/// Separator of the entries of the `java.class.path` option, which cannot be
+/// escaped within an entry.
+#[cfg(windows)]
+const CLASS_PATH_SEPARATOR: char = ';';
+#[cfg(not(windows))]
+const CLASS_PATH_SEPARATOR: char = ':';
+
+/// Path as passed to DSS calls, e.g. the target configuration or a program to
+/// load, with forward slashes.
+pub fn to_dss(path: &Path) -> Result<String> {
+    strip_verbatim(path)
+        .to_slash()
+        .ok_or_else(|| Error::InvalidPath(path.to_path_buf()))
+}
+
+/// Path as an entry of the `java.class.path` option of the JVM.
+pub fn to_class_path(path: &Path) -> Result<String> {
+    // A path which is not valid Unicode cannot be passed as is either, so its
+    // short form is tried as for a non-ASCII path
+    let path = if path.to_str().is_some_and(str::is_ascii) {
+        path.to_path_buf()
+    } else {
+        short_path(path).unwrap_or_else(|| path.to_path_buf())
+    };
+
+    let entry = to_dss(&path)?;
+    if entry.contains(CLASS_PATH_SEPARATOR) {
+        return Err(Error::InvalidPath(path));
+    }
+
+    Ok(entry)
+}
+
+/// Plain form of a verbatim path of a local drive, e.g. `C:\ti` for
+/// `\\?\C:\ti`. Other paths are kept as is.
+fn strip_verbatim(path: &Path) -> PathBuf {
+    let mut components = path.components();
+    match components.next() {
+        Some(Component::Prefix(prefix)) => match prefix.kind() {
+            Prefix::VerbatimDisk(drive) => {
+                PathBuf::from(format!("{}:", drive as char)).join(components.as_path())
+            }
+            _ => path.to_path_buf(),
+        },
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Short (8.3) form of an existing path, unless short names are disabled on
+/// the volume.
+#[cfg(windows)]
+fn short_path(path: &Path) -> Option<PathBuf> {
+    use std::ffi::OsString;
+    use std::os::windows::ffi::{OsStrExt, OsStringExt};
+    use std::ptr;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetShortPathNameW(long_path: *const u16, short_path: *mut u16, length: u32) -> u32;
+    }
+
+    let long: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+
+    // The first call returns the length of the short path including the
+    // terminating null, the second the length without it
+    let length = unsafe { GetShortPathNameW(long.as_ptr(), ptr::null_mut(), 0) };
+    if length == 0 {
+        return None;
+    }
+    let mut short = vec![0; length as usize];
+    let length = unsafe { GetShortPathNameW(long.as_ptr(), short.as_mut_ptr(), length) };
+    if length == 0 || length as usize >= short.len() {
+        return None;
+    }
+    short.truncate(length as usize);
+
+    let short = PathBuf::from(OsString::from_wide(&short));
+    short.to_str().filter(|short| short.is_ascii())?;
+
+    Some(short)
+}
+
+/// Paths are passed to the JVM in UTF-8 on other platforms.
+#[cfg(not(windows))]
+fn short_path(_path: &Path) -> Option<PathBuf> {
+    None
+}
@@ -0,0 +1,75 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::thread;
+
+use crate::com::ti::ccstudio::scripting::environment::ScriptingEnvironment;
+use crate::{Dss, Error, Result};
+
+type Job = Box<dyn FnOnce(&ScriptingEnvironment) + Send>;
+
+/// A `Send` and `Clone` handle to a DSS scripting environment running on a
+/// dedicated worker thread.
+///
+/// JNI objects are bound to the thread they were created on, so all calls are
+/// serialized through the worker thread, which owns the JVM attachment. The
+/// worker thread stops once all handles are dropped.
+#[derive(Clone)]
+pub struct DssHandle {
+    sender: mpsc::Sender<Job>,
+}
+
+impl DssHandle {
+    /// Start the JVM and the scripting environment on a new worker thread.
+    /// Only one JVM can exist per process, so this must not be combined with
+    /// [`Dss::new`].
+    pub fn spawn(ccs_path: PathBuf) -> Result<Self> {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let (ready_sender, ready_receiver) = mpsc::channel::<Result<()>>();
+
+        thread::Builder::new()
+            .name("dss-worker".to_string())
+            .spawn(move || {
+                let dss = match Dss::new(&ccs_path) {
+                    Ok(dss) => dss,
+                    Err(err) => return ready_sender.send(Err(err)).unwrap_or_default(),
+                };
+                let script = match dss.scripting_environment() {
+                    Ok(script) => script,
+                    Err(err) => return ready_sender.send(Err(err)).unwrap_or_default(),
+                };
+                ready_sender.send(Ok(())).unwrap_or_default();
+
+                for job in receiver {
+                    job(&script);
+                }
+            })
+            .map_err(|_| Error::WorkerStopped)?;
+
+        ready_receiver.recv().map_err(|_| Error::WorkerStopped)??;
+
+        Ok(Self { sender })
+    }
+
+    /// Run `f` with the scripting environment on the worker thread and wait
+    /// for its result.
+    pub fn call<F, R>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce(&ScriptingEnvironment) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (res_sender, res_receiver) = mpsc::channel();
+
+        self.sender
+            .send(Box::new(move |script| {
+                res_sender.send(f(script)).unwrap_or_default();
+            }))
+            .map_err(|_| Error::WorkerStopped)?;
+
+        res_receiver.recv().map_err(|_| Error::WorkerStopped)
+    }
+}
@@ -0,0 +1,51 @@
+/*
+ * Copyright (c) 2020 , Texas Instruments.
+ * Licensed under the BSD-3-Clause license
+ * (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+ * notice may not be copied, modified, or distributed except according to those terms.
+ */
+
+#ifndef FLASH_ROVER_H
+#define FLASH_ROVER_H
+
+#include <stdbool.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Functions returning int return 0 on success and -1 on failure, in which
+ * case flash_rover_last_error() describes the failure. */
+
+typedef struct FlashRoverSession FlashRoverSession;
+
+/* Open a session with the device, returning NULL on failure. */
+FlashRoverSession *flash_rover_open(const char *ccs_path, const char *xds_id,
+                                    const char *device);
+
+/* Read length bytes starting at offset into buf. */
+int flash_rover_read(FlashRoverSession *session, uint32_t offset,
+                     uint32_t length, uint8_t *buf);
+
+/* Write length bytes from data starting at offset, preserving the rest of the
+ * touched sectors. */
+int flash_rover_write(FlashRoverSession *session, uint32_t offset,
+                      const uint8_t *data, uint32_t length, bool verify);
+
+/* Erase the sectors covering length bytes starting at offset. */
+int flash_rover_erase(FlashRoverSession *session, uint32_t offset,
+                      uint32_t length);
+
+/* Close the session, restoring the internal flash and resetting the device. */
+void flash_rover_close(FlashRoverSession *session);
+
+/* Message of the last failure on the calling thread, or NULL. The string is
+ * valid until the next failing call on the same thread. */
+const char *flash_rover_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* FLASH_ROVER_H */
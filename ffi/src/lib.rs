@@ -0,0 +1,166 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+//! C API of the flash-rover library, declared in `include/flash_rover.h`.
+//!
+//! Functions returning `int` return 0 on success and -1 on failure, in which
+//! case [`flash_rover_last_error`] describes the failure.
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+use std::slice;
+use std::str::FromStr;
+
+use rover::session::{Config, Session};
+use rover::types::Device;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(err: impl ToString) {
+    // Interior nul bytes would truncate the message, replace them instead
+    let message = err.to_string().replace('\0', " ");
+    let message = CString::new(message).unwrap_or_default();
+    LAST_ERROR.with(|last_error| last_error.replace(Some(message)));
+}
+
+fn status<E: ToString>(res: Result<(), E>) -> c_int {
+    match res {
+        Ok(()) => 0,
+        Err(err) => {
+            set_last_error(err);
+            -1
+        }
+    }
+}
+
+unsafe fn str_arg<'a>(arg: *const c_char, name: &str) -> Result<&'a str, String> {
+    if arg.is_null() {
+        return Err(format!("{} must not be NULL", name));
+    }
+    CStr::from_ptr(arg)
+        .to_str()
+        .map_err(|_| format!("{} is not valid UTF-8", name))
+}
+
+/// Open a session with the device, returning NULL on failure.
+///
+/// # Safety
+///
+/// The arguments must be valid nul-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn flash_rover_open(
+    ccs_path: *const c_char,
+    xds_id: *const c_char,
+    device: *const c_char,
+) -> *mut Session {
+    let open = || -> Result<Session, String> {
+        let ccs_path = str_arg(ccs_path, "ccs_path")?;
+        let xds_id = str_arg(xds_id, "xds_id")?;
+        let device = Device::from_str(str_arg(device, "device")?).map_err(|e| e.to_string())?;
+
+        Session::open(Config::new(ccs_path, xds_id, device)).map_err(|e| e.to_string())
+    };
+
+    match open() {
+        Ok(session) => Box::into_raw(Box::new(session)),
+        Err(err) => {
+            set_last_error(err);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Read `length` bytes starting at `offset` into `buf`.
+///
+/// # Safety
+///
+/// `session` must be returned by [`flash_rover_open`] and not yet closed, and
+/// `buf` must be valid for writes of `length` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn flash_rover_read(
+    session: *mut Session,
+    offset: u32,
+    length: u32,
+    buf: *mut u8,
+) -> c_int {
+    let session = match session.as_ref() {
+        Some(session) if !buf.is_null() => session,
+        _ => return status(Err("session and buf must not be NULL")),
+    };
+
+    status(session.read(offset, length, |_| {}).map(|data| {
+        ptr::copy_nonoverlapping(data.as_ptr(), buf, data.len().min(length as _));
+    }))
+}
+
+/// Write `length` bytes from `data` starting at `offset`, preserving the rest
+/// of the touched sectors.
+///
+/// # Safety
+///
+/// `session` must be returned by [`flash_rover_open`] and not yet closed, and
+/// `data` must be valid for reads of `length` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn flash_rover_write(
+    session: *mut Session,
+    offset: u32,
+    data: *const u8,
+    length: u32,
+    verify: bool,
+) -> c_int {
+    let session = match session.as_ref() {
+        Some(session) if !data.is_null() => session,
+        _ => return status(Err("session and data must not be NULL")),
+    };
+
+    let data = slice::from_raw_parts(data, length as _).to_vec();
+    status(session.write(offset, data, verify, |_| {}))
+}
+
+/// Erase the sectors covering `length` bytes starting at `offset`.
+///
+/// # Safety
+///
+/// `session` must be returned by [`flash_rover_open`] and not yet closed.
+#[no_mangle]
+pub unsafe extern "C" fn flash_rover_erase(
+    session: *mut Session,
+    offset: u32,
+    length: u32,
+) -> c_int {
+    match session.as_ref() {
+        Some(session) => status(session.erase(offset, length)),
+        None => status(Err("session must not be NULL")),
+    }
+}
+
+/// Close the session, restoring the internal flash and resetting the device.
+///
+/// # Safety
+///
+/// `session` must be returned by [`flash_rover_open`] and not yet closed, or
+/// NULL.
+#[no_mangle]
+pub unsafe extern "C" fn flash_rover_close(session: *mut Session) {
+    if !session.is_null() {
+        drop(Box::from_raw(session));
+    }
+}
+
+/// Message of the last failure on the calling thread, or NULL. The string is
+/// valid until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn flash_rover_last_error() -> *const c_char {
+    LAST_ERROR.with(|last_error| {
+        last_error
+            .borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
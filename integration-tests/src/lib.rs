@@ -0,0 +1,98 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+//! Integration tests of the host side of flash-rover, run without a device or
+//! CCS installation: parsing of the input formats, the stream adapters the
+//! data passes through on its way to and from the flash, and loading and
+//! verification of manifests and partition tables.
+//!
+//! The subcommands are driven through [`MockDevice`], which stands in for the
+//! debug session of a LaunchPad running the firmware.
+
+use std::cell::RefCell;
+use std::io::{self, Read, Write};
+use std::rc::Rc;
+
+use rover::command::{Command, Subcommand};
+use rover::flash_rover::{self, FlashRover};
+use rover::mock::MockDevice;
+use rover::session::Config;
+use rover::types::Device;
+
+/// LaunchPad of the mock device, with the external flash emulated by it.
+pub const LAUNCHPAD: &str = "LAUNCHXL-CC1352R1";
+
+/// Pseudo-random test data, not aligned to any line, word or chunk size.
+pub fn pattern(len: usize) -> Vec<u8> {
+    (0..len)
+        .map(|i| (i.wrapping_mul(31) ^ (i >> 8)) as u8)
+        .collect()
+}
+
+/// Output of a writer adapter, shared with the test after the adapter is
+/// dropped.
+#[derive(Clone, Default)]
+pub struct Output(Rc<RefCell<Vec<u8>>>);
+
+impl Output {
+    /// Bytes written so far.
+    pub fn contents(&self) -> Vec<u8> {
+        self.0.borrow().clone()
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Pass the data through a writer adapter, writing in chunks of `chunk`
+/// bytes, and return what reached the inner writer once it is dropped.
+pub fn write_through<W, F>(data: &[u8], chunk: usize, wrap: F) -> Vec<u8>
+where
+    W: Write,
+    F: FnOnce(Output) -> W,
+{
+    let out = Output::default();
+    {
+        let mut writer = wrap(out.clone());
+        for part in data.chunks(chunk) {
+            writer.write_all(part).unwrap();
+        }
+        writer.flush().unwrap();
+    }
+    out.0.take()
+}
+
+/// Read all of a reader adapter in chunks of `chunk` bytes.
+pub fn read_through<R: Read>(mut reader: R, chunk: usize) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut buf = vec![0; chunk];
+    loop {
+        match reader.read(&mut buf).unwrap() {
+            0 => return out,
+            read => out.extend_from_slice(&buf[..read]),
+        }
+    }
+}
+
+pub fn launchpad() -> Device {
+    LAUNCHPAD.parse().unwrap()
+}
+
+/// Command running `subcommand` on the LaunchPad, with the default options.
+pub fn command(subcommand: Subcommand) -> Command {
+    Config::new("", "MOCK", launchpad()).into_command(subcommand)
+}
+
+pub fn run(device: &Rc<MockDevice>, command: Command) -> flash_rover::Result<()> {
+    FlashRover::with_backend(device.clone(), command)?.run()
+}
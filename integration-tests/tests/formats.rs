@@ -0,0 +1,138 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+use flash_rover_integration_tests::{pattern, read_through, write_through};
+use rover::base64_stream::{Base64Reader, Base64Writer};
+use rover::byte_swap::{self, SwapReader, SwapWriter};
+use rover::image::{self, Relocation};
+use rover::split::{self, SplitReader, SplitWriter};
+use rover::ti_txt::TiTxtWriter;
+use rover::types::InputFormat;
+
+#[test]
+fn hex_with_extended_linear_address() {
+    let hex = b":020000040001F9\n\
+                :0400000001020304F2\n\
+                :0400040005060708DE\n\
+                :00000001FF\n";
+
+    let segments = image::parse(InputFormat::Hex, hex).unwrap();
+
+    assert_eq!(segments.len(), 1);
+    assert_eq!(segments[0].address, 0x0001_0000);
+    assert_eq!(segments[0].data, [1, 2, 3, 4, 5, 6, 7, 8]);
+}
+
+#[test]
+fn hex_checksum_mismatch() {
+    let hex = b":0400000001020304F3\n:00000001FF\n";
+
+    let err = image::parse(InputFormat::Hex, hex).unwrap_err();
+
+    assert!(err.to_string().contains("line 1"), "{}", err);
+    assert!(err.to_string().contains("checksum"), "{}", err);
+}
+
+#[test]
+fn ti_txt_round_trip_at_line_boundaries() {
+    for len in &[1, 15, 16, 17, 4096 + 3] {
+        let data = pattern(*len);
+        let text = write_through(&data, 7, |out| TiTxtWriter::new(out, 0x1000));
+
+        let segments = image::parse(InputFormat::TiTxt, &text).unwrap();
+
+        assert_eq!(segments.len(), 1, "length {}", len);
+        assert_eq!(segments[0].address, 0x1000);
+        assert_eq!(segments[0].data, data, "length {}", len);
+    }
+}
+
+#[test]
+fn ti_txt_merges_adjacent_sections() {
+    let text = b"@0010\n03 04\n@0000\n00 01\n@0002\n02\nq\n";
+
+    let segments = image::parse(InputFormat::TiTxt, text).unwrap();
+
+    assert_eq!(segments.len(), 2);
+    assert_eq!(
+        (segments[0].address, &segments[0].data[..]),
+        (0x0, &[0, 1, 2][..])
+    );
+    assert_eq!(
+        (segments[1].address, &segments[1].data[..]),
+        (0x10, &[3, 4][..])
+    );
+}
+
+#[test]
+fn relocation_out_of_range() {
+    let relocation = Relocation {
+        base: 0x1000_0000,
+        shift: 0,
+    };
+
+    assert_eq!(relocation.apply(0x1000_0400).unwrap(), 0x400);
+    assert!(relocation.apply(0x0FFF_FFFF).is_err());
+}
+
+#[test]
+fn base64_round_trip() {
+    for len in &[0, 1, 2, 3, 47, 48, 49, 10_000] {
+        let data = pattern(*len);
+        let text = write_through(&data, 5, Base64Writer::new);
+
+        let decoded = read_through(Base64Reader::new(&text[..]), 3);
+
+        assert_eq!(decoded, data, "length {}", len);
+    }
+}
+
+#[test]
+fn base64_invalid_input() {
+    let mut reader = Base64Reader::new(&b"not*base64"[..]);
+
+    assert!(std::io::Read::read(&mut reader, &mut [0; 16]).is_err());
+}
+
+#[test]
+fn byte_swap_round_trip() {
+    for size in &[2, 4] {
+        let data = pattern(0x1000 * 3 + 8);
+        let mut swapped = data.clone();
+        byte_swap::swap(&mut swapped, *size);
+
+        let written = write_through(&data, 3, |out| SwapWriter::new(out, *size));
+        let read = read_through(SwapReader::new(&data[..], *size), 5);
+
+        assert_eq!(written, swapped, "word size {}", size);
+        assert_eq!(read, swapped, "word size {}", size);
+    }
+}
+
+#[test]
+fn byte_swap_partial_word() {
+    let data = pattern(7);
+    let mut reader = SwapReader::new(&data[..], 4);
+
+    assert!(std::io::Read::read_to_end(&mut reader, &mut Vec::new()).is_err());
+}
+
+#[test]
+fn split_round_trip_across_parts() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("dump.bin");
+    let data = pattern(2500);
+
+    {
+        let mut writer = SplitWriter::new(&path, 1000);
+        for part in data.chunks(333) {
+            std::io::Write::write_all(&mut writer, part).unwrap();
+        }
+    }
+
+    assert!(split::part_path(&path, 2).exists());
+    assert!(!split::part_path(&path, 3).exists());
+    assert_eq!(read_through(SplitReader::open(&path).unwrap(), 256), data);
+}
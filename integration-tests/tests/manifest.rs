@@ -0,0 +1,103 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+use std::fs;
+use std::path::Path;
+
+use flash_rover_integration_tests::pattern;
+use rover::manifest::{self, Manifest, Step};
+use rover::partition::PartitionTable;
+
+const APP_SHA256: &str = "0d8ea4b8c0d5d6e4e3b7e2a7b1f3b6d6f0e7fd4d1b1b9b9dd2f2d6c8d1b9a6f1";
+
+fn write_manifest(dir: &Path, text: &str) -> std::path::PathBuf {
+    let path = dir.join("station.toml");
+    fs::write(&path, text).unwrap();
+    path
+}
+
+#[test]
+fn partition_table() {
+    let table: PartitionTable = "# name offset length flags\n\
+                                 oad-meta     0x0      0x10000\n\
+                                 factory-cal  0xF0000  0x1000   protected=true\n"
+        .parse()
+        .unwrap();
+
+    assert_eq!(table.partitions.len(), 2);
+    assert_eq!(table.protected_in(0x0..0xF0000).count(), 0);
+    assert_eq!(table.protected_in(0xEFFFF..0xF0001).count(), 1);
+}
+
+#[test]
+fn partition_table_invalid_line() {
+    let err = "oad-meta 0x0\n".parse::<PartitionTable>().unwrap_err();
+
+    assert!(err.to_string().contains("line 1"), "{}", err);
+}
+
+#[test]
+fn manifest_steps() {
+    let dir = tempfile::tempdir().unwrap();
+    let app = pattern(1000);
+    fs::write(dir.path().join("app.bin"), &app).unwrap();
+    let digest = Step::Image {
+        path: "app.bin".into(),
+        offset: 0,
+        data: app.clone(),
+    }
+    .expected_digest();
+    let path = write_manifest(
+        dir.path(),
+        &format!(
+            "[[image]]\nfile = \"app.bin\"\noffset = 0x0\nsha256 = \"{}\"\n\n\
+             [[fill]]\noffset = 0x1000\nlength = 0x10\nvalue = 0xFF\n\n\
+             [[expect]]\noffset = 0xF0000\nlength = 0x1000\nsha256 = \"{}\"\n",
+            digest, APP_SHA256
+        ),
+    );
+
+    let manifest = Manifest::load(&path).unwrap();
+
+    assert_eq!(manifest.steps.len(), 3);
+    assert_eq!(manifest.steps[0].range(), 0x0..1000);
+    assert_eq!(manifest.steps[1].range(), 0x1000..0x1010);
+    assert!(manifest.steps[1].writes());
+    assert!(!manifest.steps[2].writes());
+    assert_eq!(manifest.steps[2].expected_digest(), APP_SHA256);
+}
+
+#[test]
+fn manifest_image_digest_mismatch() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("app.bin"), pattern(1000)).unwrap();
+    let path = write_manifest(
+        dir.path(),
+        &format!(
+            "[[image]]\nfile = \"app.bin\"\noffset = 0x0\nsha256 = \"{}\"\n",
+            APP_SHA256
+        ),
+    );
+
+    match Manifest::load(&path) {
+        Err(manifest::Error::DigestMismatch { expected, .. }) => assert_eq!(expected, APP_SHA256),
+        other => panic!("Expected a digest mismatch, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn manifest_overlap() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_manifest(
+        dir.path(),
+        "[[fill]]\noffset = 0x0\nlength = 0x100\nvalue = 0xFF\n\n\
+         [[fill]]\noffset = 0xFF\nlength = 0x100\nvalue = 0x00\n",
+    );
+
+    match Manifest::load(&path) {
+        Err(manifest::Error::Overlap { .. }) => {}
+        other => panic!("Expected overlapping regions, got {:?}", other.map(|_| ())),
+    }
+}
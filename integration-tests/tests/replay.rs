@@ -0,0 +1,145 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+use std::cell::RefCell;
+use std::io::Cursor;
+use std::path::Path;
+use std::rc::Rc;
+
+use flash_rover_integration_tests::{command, launchpad, pattern, run, Output};
+use rover::command::{Command, Subcommand, Verify};
+use rover::image::Relocation;
+use rover::mock::MockDevice;
+use rover::replay;
+use rover::types::InputFormat;
+
+fn read(offset: u32, length: u32, output: &Output) -> Command {
+    command(Subcommand::Read {
+        offset,
+        length,
+        output: RefCell::new(Box::new(output.clone())),
+    })
+}
+
+fn write(offset: u32, data: &[u8]) -> Command {
+    command(Subcommand::Write {
+        verify: Verify::Full,
+        recheck: None,
+        in_place: false,
+        offset,
+        auto_offset: false,
+        length: None,
+        input: RefCell::new(Box::new(Cursor::new(data.to_vec()))),
+        format: InputFormat::Bin,
+        relocation: Relocation::default(),
+    })
+}
+
+fn record(
+    device: &Rc<MockDevice>,
+    mut command: Command,
+    trace: &Path,
+) -> rover::flash_rover::Result<()> {
+    command.record = Some(trace.into());
+    run(device, command)
+}
+
+#[test]
+fn replay_read() {
+    let dir = tempfile::tempdir().unwrap();
+    let trace = dir.path().join("session.frr");
+    let device = Rc::new(MockDevice::new(launchpad()));
+    let data = pattern(0x2345);
+    device.set_flash(0x1000, &data);
+    record(
+        &device,
+        read(0x1000, data.len() as u32, &Output::default()),
+        &trace,
+    )
+    .unwrap();
+
+    let output = Output::default();
+    replay::run(&trace, read(0x1000, data.len() as u32, &output)).unwrap();
+
+    assert_eq!(output.contents(), data);
+}
+
+#[test]
+fn replay_write() {
+    let dir = tempfile::tempdir().unwrap();
+    let trace = dir.path().join("session.frr");
+    let device = Rc::new(MockDevice::new(launchpad()));
+    device.set_flash(0, &[0x00; 0x2000]);
+    let data = pattern(0x1801);
+    record(&device, write(0x0800, &data), &trace).unwrap();
+
+    replay::run(&trace, write(0x0800, &data)).unwrap();
+}
+
+#[test]
+fn replay_reproduces_the_error_of_the_run() {
+    let dir = tempfile::tempdir().unwrap();
+    let trace = dir.path().join("session.frr");
+    let device = Rc::new(MockDevice::new(launchpad()));
+    device.stick_bits(0x10, 0x01);
+    let data = [0x00; 0x100];
+    record(&device, write(0, &data), &trace).unwrap_err();
+
+    let err = replay::run(&trace, write(0, &data)).unwrap_err();
+
+    assert_eq!(err.code(), "verification_failed");
+}
+
+#[test]
+fn replay_of_the_last_session() {
+    let dir = tempfile::tempdir().unwrap();
+    let trace = dir.path().join("session.frr");
+    let device = Rc::new(MockDevice::new(launchpad()));
+    device.set_flash(0, &pattern(0x100));
+    record(&device, read(0x80, 0x40, &Output::default()), &trace).unwrap();
+    record(&device, read(0, 0x100, &Output::default()), &trace).unwrap();
+
+    let output = Output::default();
+    replay::run(&trace, read(0, 0x100, &output)).unwrap();
+
+    assert_eq!(output.contents(), pattern(0x100));
+}
+
+#[test]
+fn replay_of_a_different_write_diverges() {
+    let dir = tempfile::tempdir().unwrap();
+    let trace = dir.path().join("session.frr");
+    let device = Rc::new(MockDevice::new(launchpad()));
+    record(&device, write(0, &[0x12; 0x100]), &trace).unwrap();
+
+    let err = replay::run(&trace, write(0, &[0x34; 0x100])).unwrap_err();
+
+    assert_eq!(err.code(), "replay_diverged");
+    assert!(err.to_string().contains("W8 0x"), "{}", err);
+}
+
+#[test]
+fn replay_of_a_shorter_run_ends_before_the_trace() {
+    let dir = tempfile::tempdir().unwrap();
+    let trace = dir.path().join("session.frr");
+    let device = Rc::new(MockDevice::new(launchpad()));
+    record(&device, read(0, 0x3000, &Output::default()), &trace).unwrap();
+
+    let err = replay::run(&trace, read(0, 0x1000, &Output::default())).unwrap_err();
+
+    assert_eq!(err.code(), "replay_diverged");
+}
+
+#[test]
+fn replay_of_an_invalid_trace() {
+    let dir = tempfile::tempdir().unwrap();
+    let trace = dir.path().join("session.frr");
+    std::fs::write(&trace, "# flash-rover\n0.000100 R32 0x20003100\n").unwrap();
+
+    let err = replay::run(&trace, command(Subcommand::Info)).unwrap_err();
+
+    assert_eq!(err.code(), "invalid_trace");
+    assert!(err.to_string().contains("Line 2"), "{}", err);
+}
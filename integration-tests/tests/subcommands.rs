@@ -0,0 +1,618 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+use std::cell::RefCell;
+use std::fs;
+use std::io::{Cursor, Read};
+use std::rc::Rc;
+
+use flash_rover_integration_tests::{command, launchpad, pattern, run, write_through, Output};
+use rover::base64_stream::{Base64Reader, Base64Writer};
+use rover::command::{InternalAction, MemAction, StatusAction, Subcommand, Verify};
+use rover::image::Relocation;
+use rover::manifest::{Manifest, Step};
+use rover::mock::MockDevice;
+use rover::ti_txt::TiTxtWriter;
+use rover::types::InputFormat;
+
+const SECTOR_ERASE: u32 = 0xC1;
+const READ_BLOCK: u32 = 0xC3;
+const WRITE_BLOCK: u32 = 0xC4;
+const ECHO: u32 = 0xC5;
+const CHECKSUM: u32 = 0xB0;
+
+/// Metadata pages of the OAD external flash layout, of 51 bytes at the start
+/// of each sector
+const OAD_META_PAGES: usize = 16;
+
+fn read(device: &Rc<MockDevice>, offset: u32, length: u32) -> Vec<u8> {
+    let output = Output::default();
+    let subcommand = Subcommand::Read {
+        offset,
+        length,
+        output: RefCell::new(Box::new(output.clone())),
+    };
+    run(device, command(subcommand)).unwrap();
+    output.contents()
+}
+
+fn write(offset: u32, data: &[u8], verify: Verify, in_place: bool) -> Subcommand {
+    Subcommand::Write {
+        verify,
+        recheck: None,
+        in_place,
+        offset,
+        auto_offset: false,
+        length: None,
+        input: RefCell::new(Box::new(Cursor::new(data.to_vec()))),
+        format: InputFormat::Bin,
+        relocation: Relocation::default(),
+    }
+}
+
+fn write_image(format: InputFormat, input: impl Read + 'static) -> Subcommand {
+    Subcommand::Write {
+        verify: Verify::Full,
+        recheck: None,
+        in_place: false,
+        offset: 0,
+        auto_offset: false,
+        length: None,
+        input: RefCell::new(Box::new(input)),
+        format,
+        relocation: Relocation::default(),
+    }
+}
+
+/// Intel HEX of `data` at `address`, in records of 16 bytes.
+fn hex(address: u32, data: &[u8]) -> Vec<u8> {
+    fn record(text: &mut String, kind: u8, address: u16, data: &[u8]) {
+        let mut bytes = vec![data.len() as u8, (address >> 8) as u8, address as u8, kind];
+        bytes.extend_from_slice(data);
+        let checksum = bytes
+            .iter()
+            .fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+            .wrapping_neg();
+        bytes.push(checksum);
+        text.push(':');
+        for byte in bytes {
+            text.push_str(&format!("{:02X}", byte));
+        }
+        text.push('\n');
+    }
+
+    let mut text = String::new();
+    let mut upper = None;
+    for (index, chunk) in data.chunks(16).enumerate() {
+        let chunk_address = address + 16 * index as u32;
+        if upper != Some(chunk_address >> 16) {
+            upper = Some(chunk_address >> 16);
+            let upper = (chunk_address >> 16) as u16;
+            record(&mut text, 0x04, 0, &upper.to_be_bytes());
+        }
+        record(&mut text, 0x00, chunk_address as u16, chunk);
+    }
+    record(&mut text, 0x01, 0, &[]);
+    text.into_bytes()
+}
+
+/// 32-bit little-endian ELF with a single loadable segment of `data` at
+/// `address`.
+fn elf(address: u32, data: &[u8]) -> Vec<u8> {
+    const HEADER_LEN: usize = 0x34;
+    const PROGRAM_HEADER_LEN: usize = 0x20;
+
+    let mut elf = vec![0; HEADER_LEN + PROGRAM_HEADER_LEN];
+    elf[..7].copy_from_slice(b"\x7FELF\x01\x01\x01");
+    elf[0x1C..0x20].copy_from_slice(&(HEADER_LEN as u32).to_le_bytes());
+    elf[0x2A..0x2C].copy_from_slice(&(PROGRAM_HEADER_LEN as u16).to_le_bytes());
+    elf[0x2C..0x2E].copy_from_slice(&1u16.to_le_bytes());
+
+    let header = &mut elf[HEADER_LEN..];
+    let fields = [
+        // PT_LOAD
+        1,
+        (HEADER_LEN + PROGRAM_HEADER_LEN) as u32,
+        address,
+        address,
+        data.len() as u32,
+        data.len() as u32,
+    ];
+    for (index, field) in fields.iter().enumerate() {
+        header[4 * index..4 * index + 4].copy_from_slice(&field.to_le_bytes());
+    }
+
+    elf.extend_from_slice(data);
+    elf
+}
+
+fn count(device: &MockDevice, kind: u32) -> usize {
+    device
+        .commands()
+        .iter()
+        .filter(|command| **command == kind)
+        .count()
+}
+
+#[test]
+fn info_identifies_the_launchpad_flash() {
+    let device = Rc::new(MockDevice::new(launchpad()));
+
+    run(&device, command(Subcommand::Info)).unwrap();
+
+    assert_eq!(device.commands()[0], 0xC0);
+}
+
+#[test]
+fn read_unaligned_range_across_chunks() {
+    let device = Rc::new(MockDevice::new(launchpad()));
+    let data = pattern(3 * 0x1000 + 123);
+    device.set_flash(0x1234, &data);
+
+    assert_eq!(read(&device, 0x1234, data.len() as u32), data);
+    assert!(count(&device, READ_BLOCK) >= 4);
+}
+
+#[test]
+fn read_with_legacy_firmware() {
+    let device = Rc::new(MockDevice::legacy(launchpad()));
+    let data = pattern(0x2345);
+    device.set_flash(0x10_0000, &data);
+
+    assert_eq!(read(&device, 0x10_0000, data.len() as u32), data);
+}
+
+#[test]
+fn safe_transport_rereads_corrupted_buffer() {
+    let device = Rc::new(MockDevice::new(launchpad()));
+    let data = pattern(0x800);
+    device.set_flash(0, &data);
+    device.corrupt_reads(1);
+
+    let output = Output::default();
+    let mut command = command(Subcommand::Read {
+        offset: 0,
+        length: data.len() as u32,
+        output: RefCell::new(Box::new(output.clone())),
+    });
+    command.safe_transport = true;
+    run(&device, command).unwrap();
+
+    assert_eq!(output.contents(), data);
+}
+
+#[test]
+fn safe_transport_with_legacy_firmware_is_unsupported() {
+    let device = Rc::new(MockDevice::legacy(launchpad()));
+
+    let mut command = command(Subcommand::Read {
+        offset: 0,
+        length: 0x800,
+        output: RefCell::new(Box::new(Output::default())),
+    });
+    command.safe_transport = true;
+    let err = run(&device, command).unwrap_err();
+
+    assert_eq!(err.code(), "unsupported_command");
+    assert!(device.commands().is_empty());
+}
+
+#[test]
+fn write_keeps_the_rest_of_the_sectors() {
+    let device = Rc::new(MockDevice::new(launchpad()));
+    let old = vec![0x5A; 0x4000];
+    device.set_flash(0, &old);
+    let data = pattern(0x1800 + 7);
+
+    run(&device, command(write(0x1100, &data, Verify::Full, false))).unwrap();
+
+    let flash = device.flash(0, 0x4000);
+    assert_eq!(&flash[..0x1100], &old[..0x1100]);
+    assert_eq!(&flash[0x1100..0x1100 + data.len()], &data[..]);
+    assert_eq!(&flash[0x1100 + data.len()..], &old[0x1100 + data.len()..]);
+}
+
+#[test]
+fn write_skips_erasing_blank_sectors() {
+    let device = Rc::new(MockDevice::new(launchpad()));
+    let data = pattern(0x3000);
+
+    run(&device, command(write(0x2000, &data, Verify::Full, false))).unwrap();
+
+    assert_eq!(device.flash(0x2000, 0x3000), data);
+    assert_eq!(count(&device, SECTOR_ERASE), 0);
+    assert!(count(&device, WRITE_BLOCK) >= 3);
+}
+
+#[test]
+fn write_in_place_over_programmed_data_fails_verification() {
+    let device = Rc::new(MockDevice::new(launchpad()));
+    device.set_flash(0, &[0x0F; 0x100]);
+
+    let err = run(
+        &device,
+        command(write(0, &[0xF0; 0x100], Verify::Full, true)),
+    )
+    .unwrap_err();
+
+    assert_eq!(err.code(), "verification_failed");
+    assert_eq!(count(&device, SECTOR_ERASE), 0);
+}
+
+#[test]
+fn write_to_stuck_bits_fails_verification() {
+    let device = Rc::new(MockDevice::new(launchpad()));
+    device.stick_bits(0x0FFF, 0x80);
+
+    let err = run(
+        &device,
+        command(write(0, &[0x00; 0x1000], Verify::Full, false)),
+    )
+    .unwrap_err();
+
+    assert_eq!(err.code(), "verification_failed");
+}
+
+#[test]
+fn write_past_the_end_fails_before_erasing() {
+    let device = Rc::new(MockDevice::new(launchpad()));
+
+    let err = run(
+        &device,
+        command(write(0x80_0000, &[0x00; 16], Verify::Off, false)),
+    )
+    .unwrap_err();
+
+    assert_eq!(err.code(), "out_of_bounds");
+    assert_eq!(count(&device, SECTOR_ERASE), 0);
+    assert_eq!(count(&device, WRITE_BLOCK), 0);
+}
+
+#[test]
+fn write_hex_across_a_64k_boundary() {
+    let device = Rc::new(MockDevice::new(launchpad()));
+    let data = pattern(0x1234);
+
+    let input = Cursor::new(hex(0x1_FF80, &data));
+    run(&device, command(write_image(InputFormat::Hex, input))).unwrap();
+
+    assert_eq!(device.flash(0x1_FF80, data.len() as u32), data);
+}
+
+#[test]
+fn write_elf_at_the_load_address() {
+    let device = Rc::new(MockDevice::new(launchpad()));
+    let data = pattern(0x2001);
+
+    let input = Cursor::new(elf(0x3010, &data));
+    run(&device, command(write_image(InputFormat::Elf, input))).unwrap();
+
+    assert_eq!(device.flash(0x3010, data.len() as u32), data);
+}
+
+#[test]
+fn write_ti_txt_at_the_section_address() {
+    let device = Rc::new(MockDevice::new(launchpad()));
+    let data = pattern(0x801);
+
+    let text = write_through(&data, 64, |out| TiTxtWriter::new(out, 0x5000));
+    run(
+        &device,
+        command(write_image(InputFormat::TiTxt, Cursor::new(text))),
+    )
+    .unwrap();
+
+    assert_eq!(device.flash(0x5000, data.len() as u32), data);
+}
+
+#[test]
+fn write_base64() {
+    let device = Rc::new(MockDevice::new(launchpad()));
+    let data = pattern(0x1003);
+
+    let text = write_through(&data, 64, Base64Writer::new);
+    let subcommand = Subcommand::Write {
+        verify: Verify::Full,
+        recheck: None,
+        in_place: false,
+        offset: 0x6000,
+        auto_offset: false,
+        length: None,
+        input: RefCell::new(Box::new(Base64Reader::new(Cursor::new(text)))),
+        format: InputFormat::Base64,
+        relocation: Relocation::default(),
+    };
+    run(&device, command(subcommand)).unwrap();
+
+    assert_eq!(device.flash(0x6000, data.len() as u32), data);
+}
+
+#[test]
+fn flash_verifies_the_crc() {
+    let device = Rc::new(MockDevice::new(launchpad()));
+    device.set_flash(0x8000, &[0x00; 0x1000]);
+    let data = pattern(0x1801);
+
+    let subcommand = Subcommand::Flash {
+        offset: 0x8000,
+        input: RefCell::new(Box::new(Cursor::new(data.clone()))),
+    };
+    run(&device, command(subcommand)).unwrap();
+
+    assert_eq!(device.flash(0x8000, data.len() as u32), data);
+    assert_eq!(count(&device, CHECKSUM), 1);
+}
+
+#[test]
+fn flash_to_stuck_bits_fails_the_crc() {
+    let device = Rc::new(MockDevice::new(launchpad()));
+    device.stick_bits(0x8010, 0x01);
+
+    let subcommand = Subcommand::Flash {
+        offset: 0x8000,
+        input: RefCell::new(Box::new(Cursor::new(vec![0x00; 0x100]))),
+    };
+    let err = run(&device, command(subcommand)).unwrap_err();
+
+    assert_eq!(err.code(), "crc_mismatch");
+}
+
+#[test]
+fn patch_only_changes_the_masked_bits() {
+    let device = Rc::new(MockDevice::new(launchpad()));
+    let old = pattern(0x1000);
+    device.set_flash(0x1000, &old);
+
+    let subcommand = Subcommand::Patch {
+        offset: 0x1100,
+        bytes: vec![0xFF, 0x00],
+        mask: Some(vec![0x0F, 0xF0]),
+    };
+    run(&device, command(subcommand)).unwrap();
+
+    let mut expected = old;
+    expected[0x100] |= 0x0F;
+    expected[0x101] &= 0x0F;
+    assert_eq!(device.flash(0x1000, 0x1000), expected);
+}
+
+#[test]
+fn apply_writes_the_manifest_and_reports() {
+    let dir = tempfile::tempdir().unwrap();
+    let app = pattern(0x1500);
+    fs::write(dir.path().join("app.bin"), &app).unwrap();
+    let digest = Step::Image {
+        path: "app.bin".into(),
+        offset: 0,
+        data: app.clone(),
+    }
+    .expected_digest();
+    let path = dir.path().join("station.toml");
+    fs::write(
+        &path,
+        format!(
+            "[[image]]\nfile = \"app.bin\"\noffset = 0x2000\nsha256 = \"{}\"\n\n\
+             [[fill]]\noffset = 0x4000\nlength = 0x100\nvalue = 0xA5\n",
+            digest
+        ),
+    )
+    .unwrap();
+    let report = dir.path().join("report.txt");
+    let device = Rc::new(MockDevice::new(launchpad()));
+
+    let subcommand = Subcommand::Apply {
+        manifest: Manifest::load(&path).unwrap(),
+        report: Some(report.clone()),
+    };
+    run(&device, command(subcommand)).unwrap();
+
+    assert_eq!(device.flash(0x2000, app.len() as u32), app);
+    assert_eq!(device.flash(0x4000, 0x100), vec![0xA5; 0x100]);
+    let report = fs::read_to_string(report).unwrap();
+    assert!(report.contains("PASS"), "{}", report);
+    assert!(!report.contains("FAIL"), "{}", report);
+}
+
+#[test]
+fn oad_list_reads_every_metadata_page() {
+    let device = Rc::new(MockDevice::new(launchpad()));
+    let mut meta = vec![0xFF; 51];
+    meta[..8].copy_from_slice(b"OAD NVM1");
+    device.set_flash(0x1000, &meta);
+
+    run(&device, command(Subcommand::OadList)).unwrap();
+
+    assert_eq!(count(&device, READ_BLOCK), OAD_META_PAGES);
+}
+
+#[test]
+fn mem_write() {
+    let device = Rc::new(MockDevice::new(launchpad()));
+
+    let subcommand = Subcommand::Mem {
+        action: MemAction::Write {
+            address: 0x4002_2090,
+            value: 0x1234_5678,
+        },
+    };
+    run(&device, command(subcommand)).unwrap();
+
+    assert_eq!(device.memory(0x4002_2090, 4), 0x1234_5678_u32.to_le_bytes());
+    assert!(device.commands().is_empty());
+}
+
+#[test]
+fn internal_write_and_read_back() {
+    let device = Rc::new(MockDevice::new(launchpad()));
+    let data = pattern(0x123);
+
+    let subcommand = Subcommand::Internal {
+        action: InternalAction::Write {
+            input: RefCell::new(Box::new(Cursor::new(data.clone()))),
+        },
+        bank: 0,
+        offset: 0x1_0000,
+    };
+    run(&device, command(subcommand)).unwrap();
+    assert_eq!(device.memory(0x1_0000, data.len() as u32), data);
+
+    let output = Output::default();
+    let subcommand = Subcommand::Internal {
+        action: InternalAction::Read {
+            length: data.len() as u32,
+            output: RefCell::new(Box::new(output.clone())),
+        },
+        bank: 0,
+        offset: 0x1_0000,
+    };
+    run(&device, command(subcommand)).unwrap();
+    assert_eq!(output.contents(), data);
+}
+
+#[test]
+fn internal_past_the_end_of_the_bank() {
+    let device = Rc::new(MockDevice::new(launchpad()));
+
+    let subcommand = Subcommand::Internal {
+        action: InternalAction::Write {
+            input: RefCell::new(Box::new(Cursor::new(vec![0x00; 0x10]))),
+        },
+        bank: 0,
+        offset: 0x5_7FF8,
+    };
+    let err = run(&device, command(subcommand)).unwrap_err();
+
+    assert_eq!(err.code(), "out_of_bounds");
+}
+
+#[test]
+fn provision_mac_keeps_the_rest_of_the_ccfg() {
+    const CCFG: u32 = 0x5_6000;
+    const IEEE_MAC: u32 = 0x5_7FC8;
+    const BLE_MAC: u32 = 0x5_7FD0;
+
+    let device = Rc::new(MockDevice::new(launchpad()));
+    let old = pattern(0x2000);
+    device.set_memory(CCFG, &old);
+    let ieee = vec![0x00, 0x12, 0x4B, 0x00, 0x01, 0x02, 0x03, 0x04];
+    let ble = vec![0x80, 0x6F, 0xB0, 0x01, 0x02, 0x03];
+
+    let subcommand = Subcommand::ProvisionMac {
+        ieee: Some(ieee.clone()),
+        ble: Some(ble.clone()),
+    };
+    run(&device, command(subcommand)).unwrap();
+
+    let mut expected = old;
+    let ieee_offset = (IEEE_MAC - CCFG) as usize;
+    let ble_offset = (BLE_MAC - CCFG) as usize;
+    expected[ieee_offset..ieee_offset + 8]
+        .copy_from_slice(&ieee.iter().rev().copied().collect::<Vec<_>>());
+    expected[ble_offset..ble_offset + 6]
+        .copy_from_slice(&ble.iter().rev().copied().collect::<Vec<_>>());
+    assert_eq!(device.memory(CCFG, 0x2000), expected);
+}
+
+#[test]
+fn fcfg_dump_exports_the_mac_addresses() {
+    const FCFG: u32 = 0x5000_1000;
+
+    let device = Rc::new(MockDevice::new(launchpad()));
+    device.set_memory(FCFG + 0x2E8, &[0x06, 0x05, 0x04, 0x03, 0x02, 0x01]);
+
+    let output = Output::default();
+    let subcommand = Subcommand::FcfgDump {
+        output: RefCell::new(Box::new(output.clone())),
+    };
+    run(&device, command(subcommand)).unwrap();
+
+    let json = String::from_utf8(output.contents()).unwrap();
+    assert!(
+        json.contains(r#""mac_ble": "01:02:03:04:05:06""#),
+        "{}",
+        json
+    );
+    assert!(json.contains(r#""device": "#), "{}", json);
+}
+
+#[test]
+fn sector_erase_only_touches_the_range() {
+    let device = Rc::new(MockDevice::new(launchpad()));
+    device.set_flash(0, &[0x00; 0x3000]);
+
+    let subcommand = Subcommand::SectorErase {
+        offset: 0x1000,
+        length: 0x1000,
+    };
+    run(&device, command(subcommand)).unwrap();
+
+    let flash = device.flash(0, 0x3000);
+    assert!(flash[..0x1000].iter().all(|byte| *byte == 0x00));
+    assert!(flash[0x1000..0x2000].iter().all(|byte| *byte == 0xFF));
+    assert!(flash[0x2000..].iter().all(|byte| *byte == 0x00));
+}
+
+#[test]
+fn mass_erase() {
+    let device = Rc::new(MockDevice::new(launchpad()));
+    device.set_flash(0x7F_F000, &[0x00; 0x1000]);
+
+    run(&device, command(Subcommand::MassErase)).unwrap();
+
+    assert!(device
+        .flash(0x7F_F000, 0x1000)
+        .iter()
+        .all(|byte| *byte == 0xFF));
+}
+
+#[test]
+fn checksum_by_firmware() {
+    let device = Rc::new(MockDevice::new(launchpad()));
+
+    let subcommand = Subcommand::Checksum {
+        offset: 0,
+        length: 0x1_0000,
+    };
+    run(&device, command(subcommand)).unwrap();
+
+    assert_eq!(count(&device, CHECKSUM), 1);
+    assert_eq!(count(&device, READ_BLOCK), 0);
+}
+
+#[test]
+fn checksum_with_legacy_firmware_reads_back() {
+    let device = Rc::new(MockDevice::legacy(launchpad()));
+
+    let subcommand = Subcommand::Checksum {
+        offset: 0,
+        length: 0x1_0000,
+    };
+    run(&device, command(subcommand)).unwrap();
+
+    assert_eq!(count(&device, CHECKSUM), 0);
+    assert!(count(&device, READ_BLOCK) > 0);
+}
+
+#[test]
+fn status_write() {
+    let device = Rc::new(MockDevice::new(launchpad()));
+
+    let subcommand = Subcommand::Status {
+        action: StatusAction::Write(0x40),
+        config: false,
+    };
+    run(&device, command(subcommand)).unwrap();
+
+    assert_eq!(device.status(), 0x40);
+}
+
+#[test]
+fn ping() {
+    let device = Rc::new(MockDevice::new(launchpad()));
+
+    run(&device, command(Subcommand::Ping { count: 3 })).unwrap();
+
+    assert_eq!(count(&device, ECHO), 3);
+}
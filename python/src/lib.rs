@@ -0,0 +1,194 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+//! Python bindings of the flash-rover library, built with maturin:
+//!
+//! ```python
+//! import flash_rover
+//!
+//! with flash_rover.connect(ccs_path, "L200005Z", "cc1352r") as session:
+//!     print(session.info())
+//!     data = session.read(0, 0x1000, progress=lambda done, total: print(done, total))
+//! ```
+
+use std::convert::TryFrom;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use rover::session::{self, Config, Progress};
+use rover::types::{Device, SpiPins};
+
+fn session_error(err: session::Error) -> PyErr {
+    PyRuntimeError::new_err(err.to_string())
+}
+
+/// Forward progress reports to an optional Python callable taking the bytes
+/// done and the total bytes. The first exception raised by the callable is
+/// kept and raised once the operation has completed.
+struct ProgressCallback<'py> {
+    py: Python<'py>,
+    callback: Option<PyObject>,
+    err: Option<PyErr>,
+}
+
+impl<'py> ProgressCallback<'py> {
+    fn new(py: Python<'py>, callback: Option<PyObject>) -> Self {
+        Self {
+            py,
+            callback,
+            err: None,
+        }
+    }
+
+    fn report(&mut self, progress: Progress) {
+        if let (Some(callback), None) = (&self.callback, &self.err) {
+            if let Err(err) = callback.call1(self.py, (progress.done, progress.total)) {
+                self.err = Some(err);
+            }
+        }
+    }
+
+    fn finish<T>(self, res: session::Result<T>) -> PyResult<T> {
+        let value = res.map_err(session_error)?;
+        match self.err {
+            Some(err) => Err(err),
+            None => Ok(value),
+        }
+    }
+}
+
+/// A debug session with the flash-rover firmware running on the device. The
+/// internal flash is restored and the device reset on close.
+#[pyclass]
+struct Session {
+    inner: Option<session::Session>,
+}
+
+impl Session {
+    fn inner(&self) -> PyResult<&session::Session> {
+        self.inner
+            .as_ref()
+            .ok_or_else(|| PyRuntimeError::new_err("Session is closed"))
+    }
+}
+
+#[pymethods]
+impl Session {
+    #[new]
+    #[args(spi_pins = "None")]
+    fn new(
+        ccs_path: PathBuf,
+        xds_id: &str,
+        device: &str,
+        spi_pins: Option<Vec<u8>>,
+    ) -> PyResult<Self> {
+        let device =
+            Device::from_str(device).map_err(|err| PyValueError::new_err(err.to_string()))?;
+
+        let mut config = Config::new(ccs_path, xds_id, device);
+        if let Some(spi_pins) = spi_pins {
+            let pins = <[u8; 4]>::try_from(spi_pins.as_slice()).map_err(|_| {
+                PyValueError::new_err("spi_pins must be given as [miso, mosi, clk, csn]")
+            })?;
+            config = config.spi_pins(SpiPins(pins));
+        }
+
+        let inner = session::Session::open(config).map_err(session_error)?;
+
+        Ok(Self { inner: Some(inner) })
+    }
+
+    /// Identify the external flash.
+    fn info(&self) -> PyResult<String> {
+        let xflash = self.inner()?.info().map_err(session_error)?;
+        Ok(xflash.to_string())
+    }
+
+    /// Read `length` bytes starting at `offset`.
+    #[args(progress = "None")]
+    fn read<'py>(
+        &self,
+        py: Python<'py>,
+        offset: u32,
+        length: u32,
+        progress: Option<PyObject>,
+    ) -> PyResult<&'py PyBytes> {
+        let mut callback = ProgressCallback::new(py, progress);
+        let res = self
+            .inner()?
+            .read(offset, length, |progress| callback.report(progress));
+        let data = callback.finish(res)?;
+        Ok(PyBytes::new(py, &data))
+    }
+
+    /// Write `data` starting at `offset`, preserving the rest of the touched
+    /// sectors.
+    #[args(verify = "false", progress = "None")]
+    fn write(
+        &self,
+        py: Python,
+        offset: u32,
+        data: &[u8],
+        verify: bool,
+        progress: Option<PyObject>,
+    ) -> PyResult<()> {
+        let mut callback = ProgressCallback::new(py, progress);
+        let res = self
+            .inner()?
+            .write(offset, data.to_vec(), verify, |progress| {
+                callback.report(progress)
+            });
+        callback.finish(res)
+    }
+
+    /// Erase the sectors covering `length` bytes starting at `offset`.
+    fn erase(&self, offset: u32, length: u32) -> PyResult<()> {
+        self.inner()?.erase(offset, length).map_err(session_error)
+    }
+
+    fn mass_erase(&self) -> PyResult<()> {
+        self.inner()?.mass_erase().map_err(session_error)
+    }
+
+    /// Close the session. Further operations raise an exception.
+    fn close(&mut self) {
+        self.inner.take();
+    }
+
+    fn __enter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    fn __exit__(
+        &mut self,
+        _exc_type: Option<PyObject>,
+        _exc_value: Option<PyObject>,
+        _traceback: Option<PyObject>,
+    ) {
+        self.close();
+    }
+}
+
+/// Connect to the device and inject the flash-rover firmware.
+#[pyfunction(spi_pins = "None")]
+fn connect(
+    ccs_path: PathBuf,
+    xds_id: &str,
+    device: &str,
+    spi_pins: Option<Vec<u8>>,
+) -> PyResult<Session> {
+    Session::new(ccs_path, xds_id, device, spi_pins)
+}
+
+#[pymodule]
+fn flash_rover(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_class::<Session>()?;
+    m.add_function(wrap_pyfunction!(connect, m)?)?;
+    Ok(())
+}
@@ -28,8 +28,7 @@ else
     exit 1
 fi
 
-PROJECTSPECS=$(ls "${FW_DIR}"/gcc/cc13x0-cc26x0/*.projectspec \
-                  "${FW_DIR}"/gcc/cc13x2-cc26x2/*.projectspec | \
+PROJECTSPECS=$(ls "${FW_DIR}"/gcc/*/*.projectspec | \
                sed 's/^/-ccs.location /')
 
 ccs_import() {
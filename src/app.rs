@@ -5,6 +5,9 @@
 
 use clap::{App, AppSettings, Arg, SubCommand};
 
+use flash_rover::remote::Remote;
+use flash_rover::types::Device;
+
 pub fn app() -> App<'static, 'static> {
     App::new(crate_name!())
         .author(crate_authors!())
@@ -12,6 +15,10 @@ pub fn app() -> App<'static, 'static> {
         .about("Read and write to the external flash on a CC13xx/CC26xx device")
         .max_term_width(100)
         .setting(AppSettings::SubcommandRequiredElseHelp)
+        .setting(AppSettings::AllowExternalSubcommands)
+        .after_help(
+"Other subcommands are forwarded to a flash-rover-<SUBCOMMAND> executable found in PATH, with the \
+global options passed in FLASH_ROVER_* environment variables.")
         .arg(Arg::with_name("log-dss")
             .help("Log DSS script with a specified log level")
             .long_help(
@@ -31,40 +38,35 @@ file will be saved and the path will be displayed. If OFF is specified then no l
                 "FINEST", 
                 "ALL",
             ]))
+        .arg(Arg::with_name("ccs")
+            .help("Path to the CCS installation")
+            .long_help(
+"Path to the CCS installation, i.e. the folder containing ccs_base and eclipse. Defaults to the CCS \
+installation the executable is located in.")
+            .long("ccs")
+            .value_name("PATH")
+            .env("CCS_ROOT")
+            .takes_value(true))
         .arg(Arg::with_name("xds")
             .help("The serial number ID of the XDS110 debugger connected to the device, e.g. L4100847")
             .short("x")
             .long("xds")
             .value_name("ID")
-            .required(true))
+            .required_unless("wait-for-probe"))
         .arg(Arg::with_name("device")
-            .help("The kind of device connected to the XDS110 debugger")
+            .help("The kind of device connected to the XDS110 debugger, or the LaunchPad it is on")
+            .long_help(
+"The kind of device connected to the XDS110 debugger, or the LaunchPad it is on, e.g. LP-CC2652R7 for \
+the cc2652r7. A LaunchPad also selects the SPI DIOs of its external flash, unless --spi-pins is given. \
+Names are not case sensitive.")
             .short("d")
             .long("device")
             .value_name("KIND")
-            .possible_values(&[
-                "cc1310", 
-                "cc1312r", 
-                "cc1350", 
-                "cc1352p", 
-                "cc1352r", 
-                "cc2640",
-                "cc2640r2f",
-                "cc2642r",
-                "cc2650",
-                "cc2652p",
-                "cc2652r",
-                "cc2652rb",
-                "cc1312r7",
-                "cc1352p7",
-                "cc2652p7",
-                "cc2652r7",
-                "cc1354p10",
-                "cc1314r10"
-            ])
+            .possible_values(&Device::names())
+            .case_insensitive(true)
             .required(true))
         .arg(Arg::with_name("spi-pins")
-            .help("Override default SPI DIOs for external flash access, defaults to DIOs used for external flash on LaunchPads [8,9,10,20]")
+            .help("Override default SPI DIOs for external flash access, defaults to DIOs used for external flash on the LaunchPad given by --device, or [8,9,10,20]")
             .short("s")
             .long("spi-pins")
             .value_names(&[
@@ -76,10 +78,368 @@ file will be saved and the path will be displayed. If OFF is specified then no l
             .value_delimiter(",")
             .require_delimiter(true)
             .validator(spi_pins_validate))
+        .arg(Arg::with_name("mux-select")
+            .help("DIO and level (0 or 1) selecting the external flash on boards where its pins are shared through a mux")
+            .long_help(
+"DIO and level (0 or 1) selecting the external flash on boards where its pins are shared with other \
+peripherals through a mux or an I/O expander. The firmware drives the DIO to the level before \
+accessing the external flash, e.g. --mux-select 15,1.")
+            .long("mux-select")
+            .value_names(&[
+                "DIO",
+                "LEVEL"
+            ])
+            .value_delimiter(",")
+            .require_delimiter(true)
+            .validator(spi_pins_validate))
+        .arg(Arg::with_name("firmware-elf")
+            .help("Inject the firmware from an ELF (.out) executable instead of the embedded binary")
+            .long_help(
+"Inject the firmware from an ELF (.out) executable built from src/fw instead of the embedded \
+binary. The executable is loaded with its symbols and entry point through the DSS loadProgram API, \
+for debugging problems in the firmware with symbols.")
+            .long("firmware-elf")
+            .value_name("FILE")
+            .takes_value(true))
+        .arg(Arg::with_name("asset-dir")
+            .help("Directory of firmware binaries and ccxml templates overriding the embedded ones")
+            .long_help(
+"Directory of firmware binaries and ccxml templates overriding the embedded ones, for patching them \
+without rebuilding flash-rover, e.g. on air-gapped hosts. Firmware binaries are looked up as \
+fw/<family>.bin and fw/stub/<family>.bin, and are not verified against the digests of the release. \
+A ccxml template ccxml/<device>.ccxml replaces the generated target configuration, with $SERIAL \
+replaced by the serial number of the XDS110. Assets missing from the directory are the embedded ones.")
+            .long("asset-dir")
+            .value_name("DIR")
+            .env("FLASH_ROVER_ASSET_DIR")
+            .takes_value(true))
+        .arg(Arg::with_name("data-port")
+            .help("Serial port used for data transfers instead of the debug probe, e.g. the XDS110 backchannel UART")
+            .long_help(
+"Serial port used for data transfers instead of the debug probe, e.g. the XDS110 backchannel UART. \
+The firmware moves read and write data over the device UART, which is considerably faster than memory \
+accesses through the debug probe for large images.")
+            .long("data-port")
+            .value_name("PORT"))
+        .arg(Arg::with_name("data-baud")
+            .help("Baud rate of the data port [default: 921600]")
+            .long("data-baud")
+            .value_name("BAUD")
+            .requires("data-port")
+            .validator(is_positive))
+        .arg(Arg::with_name("data-pins")
+            .help("Override default UART DIOs of the data port, defaults to DIOs of the backchannel UART on LaunchPads")
+            .long("data-pins")
+            .value_names(&[
+                "RX",
+                "TX",
+            ])
+            .value_delimiter(",")
+            .require_delimiter(true)
+            .requires("data-port")
+            .validator(spi_pins_validate))
+        .arg(Arg::with_name("interface")
+            .help("Debug interface used to connect to the device")
+            .long("interface")
+            .value_name("KIND")
+            .default_value("cjtag")
+            .possible_values(&[
+                "jtag",
+                "cjtag",
+                "swd",
+            ]))
+        .arg(Arg::with_name("jtag-speed")
+            .help("Fixed JTAG TCLK frequency in kHz, from 100 to 14000")
+            .long_help(
+"Fixed JTAG TCLK frequency in kHz, from 100 to 14000. Lowering the frequency may be required on setups \
+with long cables or low target voltage. Uses the XDS110 default frequency if omitted.")
+            .long("jtag-speed")
+            .value_name("KHZ")
+            .validator(jtag_speed_validate))
+        .arg(Arg::with_name("probe-voltage")
+            .help("Voltage level in volts supplied by the XDS110 to the target, from 1.8 to 3.6")
+            .long("probe-voltage")
+            .value_name("VOLTS")
+            .validator(probe_voltage_validate))
+        .arg(Arg::with_name("core")
+            .help("Regular expression matching the debug session to open")
+            .long_help(
+"Regular expression matching the debug session to open, e.g. \".*/Cortex_M4_0\". Defaults to the first \
+Cortex-M core of the XDS110. Use the sessions subcommand to list the available debug sessions.")
+            .long("core")
+            .value_name("PATTERN"))
+        .arg(Arg::with_name("script-timeout")
+            .help("Timeout in seconds of each DSS call, 0 disables the timeout [default: 15]")
+            .long_help(
+"Timeout in seconds of each DSS call, 0 disables the timeout [default: 15]. Increase the timeout if \
+slow operations, e.g. mass erase of large flash parts, time out through slow probes.")
+            .long("script-timeout")
+            .value_name("SECS")
+            .validator(is_zero_or_positive)
+            .takes_value(true))
+        .arg(Arg::with_name("max-duration")
+            .help("Abort the operation if it takes longer than the duration, e.g. 120s or 5m")
+            .long_help(
+"Abort the operation if it takes longer than the duration, e.g. 120s or 5m, so that a CI job does \
+not hang on a wedged probe. The operation is aborted cleanly with the abort command of the firmware, \
+the same way as Ctrl-C. If it has not stopped 30 seconds later, the process exits with code 124.")
+            .long("max-duration")
+            .value_name("DURATION")
+            .env("FLASH_ROVER_MAX_DURATION")
+            .validator(is_duration)
+            .takes_value(true))
+        .arg(Arg::with_name("reconnect")
+            .help("Number of attempts to reconnect if the XDS110 drops off USB mid-operation")
+            .long_help(
+"Number of attempts to reconnect if the XDS110 drops off USB mid-operation, where zero disables \
+reconnecting. Each attempt waits for the probe to be enumerated again with exponential backoff, \
+reconnects, injects the firmware anew and resumes the interrupted transfer from the last chunk \
+acknowledged by the firmware.")
+            .long("reconnect")
+            .value_name("ATTEMPTS")
+            .default_value("3")
+            .validator(is_zero_or_positive)
+            .takes_value(true))
+        .arg(Arg::with_name("cooperative")
+            .help("Access the external flash without resetting the application, arbitrated through a RAM word")
+            .long_help(
+"Access the external flash without resetting the application, arbitrated through the 32-bit word at \
+ADDRESS in the RAM of the application. The tool requests access by writing 1 to the word and waits \
+for the application to finish its flash accesses and grant access by writing 2. The application is \
+then halted while the firmware accesses the flash, and resumed where it was halted with its RAM and \
+registers restored and the word cleared to 0. Only the read, info, erase, write and patch \
+subcommands are supported. Erasing or writing a range the application executes in place from, the \
+partitions flagged xip or any range if the flash was found in XIP mode, is refused unless --force \
+is given.")
+            .long("cooperative")
+            .value_name("ADDRESS")
+            .validator(address_validate)
+            .takes_value(true))
+        .arg(Arg::with_name("power-down")
+            .help("Put the external flash in deep power-down when the operation has completed")
+            .long_help(
+"Put the external flash in deep power-down when the operation has completed, instead of leaving \
+it in standby, restoring the low-power state battery-powered applications keep the flash in \
+between accesses. The application is to wake the flash up with Release Power-Down (0xAB) before \
+accessing it. Ignored if the flash was taken over from XIP mode, which the application enters \
+again after the reset.")
+            .long("power-down")
+            .conflicts_with("cooperative"))
+        .arg(Arg::with_name("fw-log")
+            .help("Print the debug log of the firmware to stderr after the operation")
+            .long_help(
+"Print the debug log of the firmware to stderr after the operation, and before the firmware is \
+re-injected, e.g. for verification. The firmware logs failed commands and SPI driver issues to a \
+ring buffer in RAM, which is read through the debugger, so no second debugger needs to be attached. \
+Messages overwritten before they are read are reported as lost.")
+            .long("fw-log"))
+        .arg(Arg::with_name("stats")
+            .help("Print timing and transfer statistics to stderr at the end of the run")
+            .long("stats"))
+        .arg(Arg::with_name("progress-format")
+            .help("Print transfer progress to stderr in the given format")
+            .long_help(
+"Print transfer progress to stderr in the given format. The jsonl format prints one JSON object per \
+update with the fields phase (read, write or erase), done and total in bytes, and bytes_per_sec, \
+for integration in GUI wrappers.")
+            .long("progress-format")
+            .value_name("FORMAT")
+            .possible_values(&[
+                "human",
+                "jsonl",
+            ]))
+        .arg(Arg::with_name("output-format")
+            .help("Print errors to stderr in the given format")
+            .long_help(
+"Print errors to stderr in the given format. The json format prints the error as one JSON object \
+with the fields code, a stable identifier to branch on, message, and context with the offset, chip \
+and probe the error relates to, for automation.")
+            .long("output-format")
+            .value_name("FORMAT")
+            .possible_values(&[
+                "human",
+                "json",
+            ]))
+        .arg(Arg::with_name("pre-cmd")
+            .help("Shell command to run before connecting to the device")
+            .long_help(
+"Shell command to run before connecting to the device, e.g. to power-cycle a programmable supply or \
+toggle a relay. The operation is aborted if the command fails.")
+            .long("pre-cmd")
+            .value_name("CMD")
+            .takes_value(true))
+        .arg(Arg::with_name("post-cmd")
+            .help("Shell command to run after disconnecting from the device")
+            .long_help(
+"Shell command to run after disconnecting from the device. The command is run regardless of whether \
+the operation succeeded.")
+            .long("post-cmd")
+            .value_name("CMD")
+            .takes_value(true))
+        .arg(Arg::with_name("history")
+            .help("Append a record of the operation to the history file")
+            .long_help(
+"Append a record of the operation to the history file, as a tab separated line with the fields \
+timestamp, user, probe, device, operation, range, result and SHA-256 digest of the data read or \
+written.")
+            .long("history")
+            .value_name("FILE")
+            .env("FLASH_ROVER_HISTORY")
+            .takes_value(true))
+        .arg(Arg::with_name("record")
+            .help("Record the memory accesses and doorbell exchanges to a trace file")
+            .long_help(
+"Record every memory access through the debugger and every doorbell command and response exchanged \
+with the firmware to a trace file, e.g. session.frr, to be attached to bug reports. The trace is \
+text with one access per line, and is appended to if the file exists.")
+            .long("record")
+            .value_name("FILE")
+            .takes_value(true))
+        .arg(Arg::with_name("replay")
+            .help("Run the operation against a trace recorded with --record instead of a device")
+            .long_help(
+"Run the operation against the last session of a trace recorded with --record instead of a device, \
+without a probe or CCS. The reads of the device return the recorded data, and the run fails at the \
+first access which differs from the trace. Give the options and subcommand of the recorded run.")
+            .long("replay")
+            .value_name("FILE")
+            .conflicts_with_all(&["remote", "handle"])
+            .takes_value(true))
+        .arg(Arg::with_name("handle")
+            .help("Run the operation through the session opened with session open")
+            .long_help(
+"Run the operation through the debug session opened with session open and the same handle file, \
+avoiding the JVM startup and connect cost of each operation. Supports the info, erase, read and \
+write subcommands.")
+            .long("handle")
+            .value_name("FILE")
+            .env("FLASH_ROVER_HANDLE")
+            .takes_value(true))
+        .arg(Arg::with_name("remote")
+            .help("Run the operation on the probe of a remote host, e.g. ssh://user@lab-host")
+            .long_help(
+"Run the operation on the probe attached to a remote host, given as \
+ssh://[user@]host[:port][/path/to/ti-xflash]. flash-rover is run through ssh on the remote host with \
+the other global options, and the data is streamed over the connection. Input and output files stay \
+on the local machine. Supports the info, erase, read and write subcommands.")
+            .long("remote")
+            .value_name("URL")
+            .conflicts_with("handle")
+            .validator(remote_validate)
+            .takes_value(true))
+        .arg(Arg::with_name("power-cycle")
+            .help("Power-cycle the target through the XDS110 before connecting")
+            .long_help(
+"Power-cycle the target through the XDS110 before connecting, in order to recover a stuck target. \
+Requires the XDS110 target power switch to be wired to the target supply.")
+            .long("power-cycle"))
+        .arg(Arg::with_name("wait-for-probe")
+            .help("Wait for the XDS110 to be plugged in before starting, e.g. --wait-for-probe=60s to give up after a minute")
+            .long_help(
+"Wait for the XDS110 to be plugged in before starting, so that a script can start flash-rover \
+before the board is connected. The probes are polled until the XDS110 given by --xds, or any XDS110 \
+if --xds is not given, is enumerated, or until the optional TIMEOUT, e.g. --wait-for-probe=60s.")
+            .long("wait-for-probe")
+            .value_name("TIMEOUT")
+            .min_values(0)
+            .require_equals(true)
+            .validator(is_duration))
+        .arg(Arg::with_name("force")
+            .help("Proceed with erase and write operations despite safety checks")
+            .long_help(
+"Proceed with erase and write operations despite safety checks, e.g. when the target range is write \
+protected by the block protect bits of the external flash. The failed checks are reported as \
+warnings.")
+            .long("force"))
+        .arg(Arg::with_name("strict")
+            .help("Refuse to erase or program external flash parts which are not known")
+            .long_help(
+"Refuse to erase or program external flash parts which are not in the table of known parts, instead \
+of proceeding with default assumptions on their size, page size and timing, e.g. in production where \
+only validated parts may be programmed. Not overridden by --force.")
+            .long("strict"))
+        .arg(Arg::with_name("board")
+            .help("Board profile, of which the init sequence is run before the firmware is injected")
+            .long_help(
+"Board profile, as a TOML file or the name of a file in the flash-rover/boards folder of the user \
+configuration directory. Its init list of register writes and delays is run after every reset of \
+the device, before the firmware is injected, e.g. to enable a flash power switch, instead of a \
+per-board GEL script.")
+            .long("board")
+            .value_name("BOARD")
+            .env("FLASH_ROVER_BOARD")
+            .takes_value(true))
+        .arg(Arg::with_name("partitions")
+            .help("Partition table of the external flash, refusing writes over protected partitions")
+            .long_help(
+"Partition table of the external flash, as a text file with one partition per line of name, offset, \
+length and optional flags, e.g. 'factory-cal 0xF0000 0x1000 protected=true'. Erase and write \
+operations overlapping a protected partition, e.g. holding per-unit factory calibration, are refused \
+unless --force is given.")
+            .long("partitions")
+            .value_name("FILE")
+            .env("FLASH_ROVER_PARTITIONS")
+            .takes_value(true))
+        .arg(Arg::with_name("safe-transport")
+            .help("Protect transfers between the tool and the firmware with a CRC, retrying corrupted transfers")
+            .long_help(
+"Protect transfers between the tool and the firmware with a CRC, retrying corrupted transfers. Every \
+command, response and data block through the debug probe is checksummed, so that an unreliable debug \
+connection is detected instead of silently reading or writing wrong data. Slightly slower.")
+            .long("safe-transport")
+            .conflicts_with("data-port"))
+        .arg(Arg::with_name("throttle")
+            .help("Cap the transfer speed to and from the external flash, in KB/s")
+            .long_help(
+"Cap the transfer speed to and from the external flash, in KB/s of 1024 bytes, by pausing between \
+the chunks of reads and writes through the debug probe. Useful when sharing a USB hub with latency \
+sensitive equipment, or when debugging thermal or power issues triggered by sustained programming.")
+            .long("throttle")
+            .value_name("KBPS")
+            .validator(is_positive)
+            .conflicts_with("data-port")
+            .takes_value(true))
+        .arg(Arg::with_name("crc-algorithm")
+            .help("CRC algorithm of the checksums printed and verified")
+            .long_help(
+"CRC algorithm of the checksums printed and verified, i.e. by checksum, flash, read --crc and \
+write --verify=sample, to match what the bootloader of the application expects: CRC-32 (IEEE \
+802.3), CRC-32C (Castagnoli) or CRC-16/CCITT-FALSE. The firmware computes the checksums of the \
+external flash with the same algorithm.")
+            .long("crc-algorithm")
+            .value_name("ALGORITHM")
+            .default_value("crc32")
+            .possible_values(&[
+                "crc32",
+                "crc32c",
+                "crc16-ccitt",
+            ]))
         .subcommand(subcommand_info())
         .subcommand(subcommand_erase())
         .subcommand(subcommand_read())
+        .subcommand(subcommand_checksum())
         .subcommand(subcommand_write())
+        .subcommand(subcommand_flash())
+        .subcommand(subcommand_patch())
+        .subcommand(subcommand_apply())
+        .subcommand(subcommand_production())
+        .subcommand(subcommand_hil_test())
+        .subcommand(subcommand_oad())
+        .subcommand(subcommand_status())
+        .subcommand(subcommand_internal())
+        .subcommand(subcommand_mem())
+        .subcommand(subcommand_power())
+        .subcommand(subcommand_sessions())
+        .subcommand(subcommand_export_uniflash())
+        .subcommand(subcommand_regs())
+        .subcommand(subcommand_fcfg())
+        .subcommand(subcommand_provision())
+        .subcommand(subcommand_recover())
+        .subcommand(subcommand_tui())
+        .subcommand(subcommand_ping())
+        .subcommand(subcommand_session())
+        .subcommand(SubCommand::with_name("daemon")
+            .setting(AppSettings::Hidden))
 }
 
 fn subcommand_info() -> App<'static, 'static> {
@@ -114,6 +474,51 @@ fn subcommand_erase() -> App<'static, 'static> {
         )
 }
 
+fn subcommand_checksum() -> App<'static, 'static> {
+    SubCommand::with_name("checksum")
+        .about("Print the CRC of an address range on the external flash")
+        .long_about(
+"Print the CRC of an address range on the external flash, with the algorithm of --crc-algorithm. The \
+firmware computes the CRC on the device, without transferring the range. With --partitions, writes \
+a JSON manifest of the CRC of every partition instead.")
+        .arg(
+            Arg::with_name("offset")
+                .help("Offset of bytes into external flash device to start the checksum")
+                .value_name("OFFSET")
+                .index(1)
+                .validator(is_zero_or_positive)
+                .required_unless("partitions"),
+        )
+        .arg(
+            Arg::with_name("length")
+                .help("Length of bytes to checksum from offset")
+                .value_name("LENGTH")
+                .index(2)
+                .validator(is_zero_or_positive)
+                .required_unless("partitions"),
+        )
+        .arg(
+            Arg::with_name("partitions")
+                .help("Write a JSON manifest of the CRC of every partition")
+                .long_help(
+"Write a JSON manifest of the CRC of every partition of the partition table, given with the global \
+--partitions option or a manifest, with the device, the detected external flash and the CRC \
+algorithm. Comparing the manifest against a golden one tells whether the device holds the expected \
+data, without dumping the external flash.")
+                .long("partitions")
+                .conflicts_with_all(&["offset", "length"]),
+        )
+        .arg(
+            Arg::with_name("output")
+                .help("Write the manifest to a file instead of stdout")
+                .short("o")
+                .long("output")
+                .value_name("FILE")
+                .requires("partitions")
+                .takes_value(true),
+        )
+}
+
 fn subcommand_read() -> App<'static, 'static> {
     SubCommand::with_name("read")
         .about("Read data from an address range on the external flash")
@@ -141,6 +546,49 @@ fn subcommand_read() -> App<'static, 'static> {
                 .value_name("FILE")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("format")
+                .help("Format of the output, base64 for binary-safe text in terminals, logs and JSON payloads")
+                .long_help(
+"Format of the output. Base64 passes binary data safely through terminals, SSH sessions, CI logs and \
+JSON payloads. TI-TXT is used by TI manufacturing flows and legacy tools, with the external flash \
+offsets as addresses.")
+                .long("format")
+                .value_name("FORMAT")
+                .default_value("bin")
+                .possible_values(&["bin", "base64", "ti-txt"]),
+        )
+        .arg(
+            Arg::with_name("split-size")
+                .help("Split the output into numbered part files FILE.000, FILE.001, ... of at most SIZE bytes")
+                .long_help(
+"Split the output into numbered part files FILE.000, FILE.001, ... of at most SIZE bytes, e.g. for \
+full-chip dumps larger than the file size limit of FAT32 or of CI artifacts. SIZE takes a K, M or G \
+suffix, e.g. 16M. Write the parts back with write --split.")
+                .long("split-size")
+                .value_name("SIZE")
+                .requires("output")
+                .validator(size_validate),
+        )
+        .arg(
+            Arg::with_name("crc")
+                .help("Print the CRC and SHA-256 of the output to stderr")
+                .long_help(
+"Print the length, CRC and SHA-256 of the output to stderr, computed as the data streams to the \
+output, to get a dump and its integrity hashes in one pass for archival. The hashes are of the \
+output as written, i.e. after --format and --byte-swap, and of all parts with --split-size.")
+                .long("crc"),
+        )
+        .arg(
+            Arg::with_name("byte-swap")
+                .help("Swap the byte order within 16-bit or 32-bit words of the data")
+                .long_help(
+"Swap the byte order within 16-bit (2) or 32-bit (4) words of the data, e.g. for images exchanged \
+with big-endian tools. The length of the data must be a multiple of the word size.")
+                .long("byte-swap")
+                .value_name("SIZE")
+                .possible_values(&["2", "4"]),
+        )
 }
 
 fn subcommand_write() -> App<'static, 'static> {
@@ -148,11 +596,18 @@ fn subcommand_write() -> App<'static, 'static> {
         .about("Write data to an address range on the external flash")
         .arg(
             Arg::with_name("verify")
-                .help("Verify the integrity of the written data")
+                .help("Verify the integrity of the written data, all of it or a sample, e.g. --verify=sample:10%")
                 .long_help(
 "Verify that the written data was successfully written by reading back the data from external flash \
-and compare. Verification is done per sector basis.")
+and compare. Verification is done per sector basis. With --verify=sample:PERCENT%[:SEED], only the \
+CRC of a random percentage of the written sectors, computed by the firmware, is compared, trading \
+completeness for speed on large images. The seed is random unless given, and is printed for reproducing the sample.")
                 .long("verify")
+                .value_name("MODE")
+                .takes_value(true)
+                .min_values(0)
+                .require_equals(true)
+                .validator(verify_validate)
         )
         .arg(
             Arg::with_name("in-place")
@@ -165,13 +620,42 @@ back the same address range may yield different data than initially written.")
                 .short("p")
                 .long("in-place")
         )
+        .arg(
+            Arg::with_name("recheck")
+                .help("Re-check a percentage of the written sectors after a delay")
+                .long_help(
+"Re-check a percentage of the written sectors, spread evenly over the write range, by reading back \
+the data after a delay. Catches corruption caused by brown-outs on boards with a marginal supply, \
+e.g. battery-powered boards, which can go unnoticed by --verify.")
+                .long("recheck")
+                .value_name("PERCENT")
+                .validator(percent_validate)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("recheck-delay")
+                .help("Delay in milliseconds before re-checking the written sectors [default: 1000]")
+                .long("recheck-delay")
+                .value_name("MS")
+                .validator(is_zero_or_positive)
+                .requires("recheck"),
+        )
         .arg(
             Arg::with_name("offset")
-                .help("Offset of bytes into external flash device to start write")
+                .help("Offset of bytes into external flash device to start write, required for binary input")
                 .value_name("OFFSET")
                 .index(1)
-                .validator(is_zero_or_positive)
-                .required(true),
+                .validator(is_zero_or_positive),
+        )
+        .arg(
+            Arg::with_name("auto-offset")
+                .help("Write at the first blank region large enough for the data, and print its offset")
+                .long_help(
+"Write at the first sector-aligned blank region large enough for the data instead of at OFFSET, and \
+print the chosen offset. Handy for appending log or image blobs without tracking offsets. The \
+regions are found with the blank check of the firmware, without reading back the flash.")
+                .long("auto-offset")
+                .conflicts_with("offset"),
         )
         .arg(
             Arg::with_name("length")
@@ -182,12 +666,496 @@ back the same address range may yield different data than initially written.")
         )
         .arg(
             Arg::with_name("input")
-                .help("File to read contents of data to write. Reads from stdin if omitted.")
+                .help("File or http(s) URL to read contents of data to write. Reads from stdin if omitted.")
                 .short("i")
                 .long("input")
                 .value_name("FILE")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("input-sha256")
+                .help("Expected SHA-256 digest of the input in hex, nothing is written if it does not match")
+                .long_help(
+"Expected SHA-256 digest of the input file or stream in hex, e.g. to pin an artifact fetched from a \
+URL. The input is verified as a whole before connecting to the device, so a truncated or wrong \
+artifact fails the write before any erase begins.")
+                .long("input-sha256")
+                .value_name("DIGEST")
+                .validator(sha256_validate),
+        )
+        .arg(
+            Arg::with_name("split")
+                .help("Read the input from the numbered part files FILE.000, FILE.001, ... written by read --split-size")
+                .long("split")
+                .requires("input"),
+        )
+        .arg(
+            Arg::with_name("input-format")
+                .help("Format of the input, defaults to the format implied by the input file extension or bin")
+                .long_help(
+"Format of the input. Intel HEX (.hex, .ihex) and ELF (.elf, .out, .axf) inputs are written at the \
+addresses they carry, translated with --relocate and --offset-shift, instead of at OFFSET, as are \
+TI-TXT inputs. Base64 (.b64, .base64) input is decoded, ignoring line breaks, and written at OFFSET. \
+Defaults to the format implied by the input file extension, or bin. TI-TXT is never implied, as .txt \
+files are commonly raw data.")
+                .long("input-format")
+                .visible_alias("format")
+                .value_name("FORMAT")
+                .possible_values(&["bin", "hex", "elf", "base64", "ti-txt"]),
+        )
+        .arg(
+            Arg::with_name("relocate")
+                .help("Image address of external flash offset 0, e.g. the memory-mapped XIP address of the external flash")
+                .long("relocate")
+                .value_name("BASE")
+                .validator(address_validate),
+        )
+        .arg(
+            Arg::with_name("offset-shift")
+                .help("Signed delta added to the external flash offsets of the image addresses")
+                .long("offset-shift")
+                .value_name("DELTA")
+                .allow_hyphen_values(true)
+                .validator(offset_shift_validate),
+        )
+        .arg(
+            Arg::with_name("byte-swap")
+                .help("Swap the byte order within 16-bit or 32-bit words of the data")
+                .long_help(
+"Swap the byte order within 16-bit (2) or 32-bit (4) words of the data, e.g. for images exchanged \
+with big-endian tools. The length of the data must be a multiple of the word size.")
+                .long("byte-swap")
+                .value_name("SIZE")
+                .possible_values(&["2", "4"]),
+        )
+}
+
+fn subcommand_flash() -> App<'static, 'static> {
+    SubCommand::with_name("flash")
+        .about("Write an image to the external flash and verify it, in one go")
+        .long_about(
+"Write an image to the external flash and verify it, in one go. Detects the external flash, checks \
+that the image fits and that the range is writable, erases the touched sectors, writes the image \
+and verifies the CRC of the written range, computed by the firmware. Progress is shown unless --progress-format is given.")
+        .arg(
+            Arg::with_name("input")
+                .help("Image file to write")
+                .short("i")
+                .long("input")
+                .value_name("FILE")
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("offset")
+                .help("Offset of bytes into external flash device to write the image")
+                .long("offset")
+                .value_name("OFFSET")
+                .default_value("0")
+                .validator(is_zero_or_positive),
+        )
+}
+
+fn subcommand_patch() -> App<'static, 'static> {
+    SubCommand::with_name("patch")
+        .about("Change a few bytes on the external flash, keeping the rest of the touched sectors")
+        .long_about(
+"Change a few bytes on the external flash, e.g. to toggle a feature flag, keeping the rest of the \
+touched sectors. The bytes are read first, and the touched sectors are only erased and rewritten if \
+a bit is to be set, as programming can only clear bits. The touched sectors are read back and \
+verified. Nothing is written if the bytes already hold the patched value.")
+        .arg(
+            Arg::with_name("offset")
+                .help("Offset of bytes into external flash device to patch")
+                .value_name("OFFSET")
+                .index(1)
+                .validator(is_zero_or_positive)
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("bytes")
+                .help("Bytes to write, in hexadecimal, e.g. \"DE AD BE EF\"")
+                .long("bytes")
+                .value_name("BYTES")
+                .validator(bytes_validate)
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("mask")
+                .help("Bits of each byte to change, in hexadecimal, e.g. \"01 00 00 00\"")
+                .long_help(
+"Bits of each byte to change, in hexadecimal with one mask byte per byte of --bytes, e.g. \
+\"01 00 00 00\" to set or clear bit 0 of the first byte only. The other bits keep their current value.")
+                .long("mask")
+                .value_name("MASK")
+                .validator(bytes_validate),
+        )
+}
+
+fn subcommand_apply() -> App<'static, 'static> {
+    SubCommand::with_name("apply")
+        .about("Program the images and fill regions of a manifest, and verify them")
+        .long_about(
+"Program the images and fill regions of a manifest, and verify them. The manifest is a TOML file \
+with [[partition]], [[image]], [[fill]] and [[expect]] tables, see the README. Image digests are \
+checked before connecting, every step runs in one session, and afterwards every region is read back \
+and compared against its expected SHA-256 digest. The resulting verification report names the \
+manifest, device, probe, operator and time, and ends with the digest of the report itself.")
+        .arg(
+            Arg::with_name("manifest")
+                .help("Manifest file")
+                .value_name("MANIFEST")
+                .index(1)
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("report")
+                .help("Also write the verification report to the file")
+                .long("report")
+                .value_name("FILE"),
+        )
+}
+
+fn subcommand_production() -> App<'static, 'static> {
+    SubCommand::with_name("production")
+        .about("Apply the manifest of a profile to one unit after the other")
+        .long_about(
+"Apply the manifest of a profile to one unit after the other. Waits for the probe given with --xds \
+to be connected, applies and verifies the manifest, shows PASS or FAIL in large colored text, logs \
+the result to the log of the profile or else to --history, and waits for the probe to be \
+disconnected before waiting for the next unit. Runs until Ctrl-C is pressed.")
+        .arg(
+            Arg::with_name("profile")
+                .help("Profile file, or name of a profile in the profiles directory")
+                .long("profile")
+                .value_name("PROFILE")
+                .required(true),
+        )
+}
+
+fn subcommand_hil_test() -> App<'static, 'static> {
+    SubCommand::with_name("hil-test")
+        .about("Run a self test against the attached board and write a JUnit XML report")
+        .long_about(
+"Run a self test against the attached board and write a JUnit XML report. Gets the device info, \
+erases the region and checks that it is blank, writes random data, verifies its CRC32, and verifies \
+it again after power cycling the board through the XDS110. Every case connects to the device anew. \
+The region is erased and overwritten, its previous contents are lost.")
+        .arg(
+            Arg::with_name("offset")
+                .help("Offset into the external flash of the region to test")
+                .long("offset")
+                .value_name("OFFSET")
+                .default_value("0")
+                .validator(is_zero_or_positive),
+        )
+        .arg(
+            Arg::with_name("length")
+                .help("Length of the region to test")
+                .long("length")
+                .value_name("LENGTH")
+                .default_value("65536")
+                .validator(is_positive),
+        )
+        .arg(
+            Arg::with_name("report")
+                .help("JUnit XML report file")
+                .long("report")
+                .value_name("FILE")
+                .default_value("hil-test.xml"),
+        )
+}
+
+fn subcommand_oad() -> App<'static, 'static> {
+    SubCommand::with_name("oad")
+        .about("Inspect the OAD image layout of the external flash")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(SubCommand::with_name("ls")
+            .about("List the images stored for off-chip OAD")
+            .long_about(
+"List the images stored for off-chip OAD, from the metadata pages at the start of the external \
+flash, with their type, number, software version, length, address and CRC status."))
+}
+
+fn subcommand_status() -> App<'static, 'static> {
+    let config = Arg::with_name("config")
+        .help("Access the configuration register of Macronix parts instead of the status register")
+        .long_help(
+"Access the configuration register of Macronix parts instead of the status register. It holds the \
+dummy cycle (DC) bit in bit 6 and the L/H switch (high performance mode) bit in bit 9, a wrong L/H \
+switch state breaks reads at higher SPI clocks.")
+        .long("config");
+
+    SubCommand::with_name("status")
+        .about("Read or write the status or configuration register of the external flash")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+            SubCommand::with_name("read")
+                .about("Read the register")
+                .arg(config.clone()),
+        )
+        .subcommand(
+            SubCommand::with_name("write")
+                .about("Write the register, which is checked by reading it back")
+                .arg(
+                    Arg::with_name("value")
+                        .help("Value to write, e.g. 0x0200")
+                        .value_name("VALUE")
+                        .index(1)
+                        .required(true)
+                        .validator(address_validate),
+                )
+                .arg(config),
+        )
+}
+
+fn subcommand_internal() -> App<'static, 'static> {
+    let bank = Arg::with_name("bank")
+        .help("Bank of the internal flash the offset is into, CC13x4/CC26x4 have banks 0 and 1")
+        .long("bank")
+        .value_name("BANK")
+        .default_value("0")
+        .validator(is_zero_or_positive);
+    let offset = Arg::with_name("offset")
+        .help("Offset of bytes into the internal flash bank")
+        .value_name("OFFSET")
+        .index(1)
+        .validator(is_zero_or_positive)
+        .required(true);
+
+    SubCommand::with_name("internal")
+        .about("Read or write the internal flash through the debugger")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+            SubCommand::with_name("read")
+                .about("Read data from an address range of the internal flash")
+                .arg(offset.clone())
+                .arg(
+                    Arg::with_name("length")
+                        .help("Length of bytes to read from offset")
+                        .value_name("LENGTH")
+                        .index(2)
+                        .validator(is_zero_or_positive)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .help("File to store read data. Will overwrite file. Writes to stdout if omitted.")
+                        .short("o")
+                        .long("output")
+                        .value_name("FILE"),
+                )
+                .arg(bank.clone()),
+        )
+        .subcommand(
+            SubCommand::with_name("write")
+                .about("Program the internal flash and verify it")
+                .long_about(
+"Program the internal flash with the flash loader of the debugger, invalidate the flash cache line \
+buffers, and verify the data read back.")
+                .arg(offset)
+                .arg(
+                    Arg::with_name("input")
+                        .help("File to program")
+                        .short("i")
+                        .long("input")
+                        .value_name("FILE")
+                        .required(true),
+                )
+                .arg(bank),
+        )
+}
+
+fn subcommand_mem() -> App<'static, 'static> {
+    let address = Arg::with_name("address")
+        .help("Address of the first 32-bit word, e.g. 0x40022090")
+        .value_name("ADDR")
+        .index(1)
+        .required(true)
+        .validator(address_validate);
+
+    SubCommand::with_name("mem")
+        .about("Read or write memory-mapped registers of the device")
+        .long_about(
+"Read or write memory-mapped registers of the device through the debugger, a 32-bit word at a time, \
+e.g. to enable a flash power switch GPIO. The core is halted for the access, and the device is \
+reset when the debugger disconnects, which undoes the writes.")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+            SubCommand::with_name("read")
+                .about("Read and print words")
+                .arg(address.clone())
+                .arg(
+                    Arg::with_name("count")
+                        .help("Number of words to read")
+                        .value_name("COUNT")
+                        .index(2)
+                        .default_value("1")
+                        .validator(is_positive),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("write")
+                .about("Write a word")
+                .arg(address)
+                .arg(
+                    Arg::with_name("value")
+                        .help("Value to write, e.g. 0x00000001")
+                        .value_name("VALUE")
+                        .index(2)
+                        .required(true)
+                        .validator(address_validate),
+                ),
+        )
+}
+
+fn subcommand_power() -> App<'static, 'static> {
+    SubCommand::with_name("power")
+        .about("Switch the target power supplied by the XDS110")
+        .arg(
+            Arg::with_name("state")
+                .help("Target power state")
+                .value_name("STATE")
+                .index(1)
+                .possible_values(&["on", "off"])
+                .required(true),
+        )
+}
+
+fn subcommand_sessions() -> App<'static, 'static> {
+    SubCommand::with_name("sessions")
+        .about("List the available debug sessions without connecting to the device")
+}
+
+fn subcommand_export_uniflash() -> App<'static, 'static> {
+    SubCommand::with_name("export-uniflash")
+        .about("Write the target configuration of the probe, device and connection options for UniFlash")
+        .long_about(
+"Write the target configuration (ccxml) of the probe, device, interface, JTAG speed and probe voltage \
+for UniFlash, to hand the configuration to colleagues who use the GUI or the UniFlash command line. \
+The board profile, SPI pins and mux select are specific to flash-rover and are not exported.")
+        .arg(
+            Arg::with_name("output")
+                .help("File to write the target configuration to [default: <DEVICE>_<XDS>.ccxml]")
+                .short("o")
+                .long("output")
+                .value_name("FILE")
+                .takes_value(true),
+        )
+}
+
+fn subcommand_tui() -> App<'static, 'static> {
+    SubCommand::with_name("tui")
+        .about("Interactive dashboard with a block occupancy map of the external flash")
+        .long_about(
+"Interactive dashboard showing the external flash info and a map of the blank and used blocks, where \
+regions are selected with the keyboard or the mouse to be read, erased or written. The debug session \
+is kept open until the dashboard is closed.")
+}
+
+fn subcommand_regs() -> App<'static, 'static> {
+    SubCommand::with_name("regs")
+        .about("Halt the device and dump the core registers, without injecting the firmware")
+        .long_about(
+"Halt the device and dump the core registers, without injecting the firmware. Useful for diagnosing \
+devices where firmware injection hangs, e.g. bricked or secured devices.")
+}
+
+fn subcommand_fcfg() -> App<'static, 'static> {
+    SubCommand::with_name("fcfg")
+        .about("Inspect the factory configuration page of the device")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(SubCommand::with_name("dump")
+            .about("Export the factory configuration fields as JSON, without injecting the firmware")
+            .long_about(
+"Halt the device and read the factory configuration (FCFG1) page through the debugger, then export \
+its key fields as JSON: the BLE and IEEE 802.15.4 MAC addresses, the device identification and the \
+oscillator and ADC trim values, along with the raw page in base64.")
+            .arg(
+                Arg::with_name("output")
+                    .help("File to store the JSON. Will overwrite file. Writes to stdout if omitted.")
+                    .short("o")
+                    .long("output")
+                    .value_name("FILE")
+                    .takes_value(true),
+            ))
+}
+
+fn subcommand_provision() -> App<'static, 'static> {
+    SubCommand::with_name("provision")
+        .about("Provision per-unit data of the device")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(SubCommand::with_name("mac")
+            .about("Write MAC address overrides to the CCFG, without injecting the firmware")
+            .long_about(
+"Write MAC address overrides to the customer configuration (CCFG) of the device through the \
+debugger, which the radio stacks use instead of the MAC addresses of the factory configuration. The \
+CCFG sector is read, the overrides are changed in it, and the sector is programmed and verified as a \
+whole. CC13x4/CC26x4 devices are not supported.")
+            .arg(
+                Arg::with_name("ieee")
+                    .help("IEEE 802.15.4 MAC address of 8 bytes, e.g. 00:12:4B:00:1C:AA:4F:0E")
+                    .long("ieee")
+                    .value_name("MAC")
+                    .validator(mac_validate)
+                    .required_unless("ble"),
+            )
+            .arg(
+                Arg::with_name("ble")
+                    .help("BLE MAC address of 6 bytes, e.g. 80:6F:B0:EE:A4:12")
+                    .long("ble")
+                    .value_name("MAC")
+                    .validator(mac_validate),
+            ))
+}
+
+fn subcommand_recover() -> App<'static, 'static> {
+    SubCommand::with_name("recover")
+        .about("Recover a firmware which no longer completes commands, without unplugging the board")
+        .long_about(
+"Recover a firmware which no longer completes commands, without unplugging the board. Halts the \
+core, clears the doorbell, re-injects the firmware and identifies the external flash again.")
+}
+
+fn subcommand_ping() -> App<'static, 'static> {
+    SubCommand::with_name("ping")
+        .about("Measure the round-trip latency to the firmware")
+        .long_about(
+"Measure the round-trip latency to the firmware by sending echo commands through the doorbell \
+protocol. Useful for checking that the link to the firmware is alive before long operations.")
+        .arg(
+            Arg::with_name("count")
+                .help("Number of echo commands to send")
+                .short("c")
+                .long("count")
+                .value_name("COUNT")
+                .default_value("4")
+                .validator(is_positive),
+        )
+}
+
+fn subcommand_session() -> App<'static, 'static> {
+    let handle = Arg::with_name("handle")
+        .help("Handle file identifying the session")
+        .long("handle")
+        .value_name("FILE")
+        .default_value(".fr-session");
+
+    SubCommand::with_name("session")
+        .about("Keep a debug session open across invocations")
+        .long_about(
+"Keep a debug session open across invocations. session open starts a background process holding the \
+debug session and the probe, which later invocations use when given the same --handle file. \
+session close restores the device and stops the background process.")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(SubCommand::with_name("open")
+            .about("Open a debug session in the background")
+            .arg(handle.clone()))
+        .subcommand(SubCommand::with_name("close")
+            .about("Close the debug session")
+            .arg(handle.clone()))
+        .subcommand(SubCommand::with_name("serve")
+            .setting(AppSettings::Hidden)
+            .arg(handle))
 }
 
 fn spi_pins_validate(dio: String) -> Result<(), String> {
@@ -203,6 +1171,173 @@ fn spi_pins_validate(dio: String) -> Result<(), String> {
     Ok(())
 }
 
+fn remote_validate(val: String) -> Result<(), String> {
+    match Remote::parse(&val) {
+        Some(_) => Ok(()),
+        None => Err(String::from(
+            "Value must be an ssh URL, e.g. ssh://user@lab-host",
+        )),
+    }
+}
+
+fn jtag_speed_validate(val: String) -> Result<(), String> {
+    match val.parse::<u32>() {
+        Ok(100..=14000) => Ok(()),
+        _ => Err(String::from("Value must be an integer from 100 to 14000")),
+    }
+}
+
+fn probe_voltage_validate(val: String) -> Result<(), String> {
+    match val.parse::<f32>() {
+        Ok(volts) if (1.8..=3.6).contains(&volts) => Ok(()),
+        _ => Err(String::from("Value must be a voltage from 1.8 to 3.6")),
+    }
+}
+
+fn verify_validate(val: String) -> Result<(), String> {
+    if val == "full" || parse_verify_sample(&val).is_some() {
+        Ok(())
+    } else {
+        Err(String::from(
+            "Value must be full or sample:PERCENT%[:SEED], e.g. sample:10%",
+        ))
+    }
+}
+
+/// Parse a sampled verification, `sample:PERCENT%[:SEED]`, into the
+/// percentage of sectors from 1 to 100 and the seed if given.
+pub fn parse_verify_sample(val: &str) -> Option<(u32, Option<u32>)> {
+    let mut parts = val.strip_prefix("sample:")?.splitn(2, ':');
+    let percent = parts.next()?.strip_suffix('%')?.parse().ok()?;
+    let seed = match parts.next() {
+        Some(seed) => Some(parse_address(seed)?),
+        None => None,
+    };
+
+    match percent {
+        1..=100 => Some((percent, seed)),
+        _ => None,
+    }
+}
+
+fn percent_validate(val: String) -> Result<(), String> {
+    match val.parse::<u32>() {
+        Ok(1..=100) => Ok(()),
+        _ => Err(String::from("Value must be an integer from 1 to 100")),
+    }
+}
+
+/// Parse an unsigned 32-bit value, in hexadecimal with a `0x` prefix.
+pub fn parse_address(val: &str) -> Option<u32> {
+    match val.strip_prefix("0x").or_else(|| val.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => val.parse().ok(),
+    }
+}
+
+/// Parse a size in bytes, with an optional binary K, M or G suffix.
+pub fn parse_size(val: &str) -> Option<u64> {
+    let val = val.trim_end_matches("iB").trim_end_matches('B');
+    let (number, unit) = match val.char_indices().last()? {
+        (i, 'K') | (i, 'k') => (&val[..i], 1 << 10),
+        (i, 'M') | (i, 'm') => (&val[..i], 1 << 20),
+        (i, 'G') | (i, 'g') => (&val[..i], 1 << 30),
+        _ => (val, 1),
+    };
+    number.parse::<u64>().ok()?.checked_mul(unit)
+}
+
+/// Parse a signed value, in hexadecimal with a `0x` prefix.
+pub fn parse_offset_shift(val: &str) -> Option<i64> {
+    let (negative, magnitude) = match val.strip_prefix('-') {
+        Some(magnitude) => (true, magnitude),
+        None => (false, val.strip_prefix('+').unwrap_or(val)),
+    };
+    let magnitude = i64::from(parse_address(magnitude)?);
+    Some(if negative { -magnitude } else { magnitude })
+}
+
+fn address_validate(val: String) -> Result<(), String> {
+    match parse_address(&val) {
+        Some(_) => Ok(()),
+        None => Err(String::from(
+            "Value must be a 32-bit address, e.g. 0x10000000",
+        )),
+    }
+}
+
+fn sha256_validate(val: String) -> Result<(), String> {
+    if val.len() == 64 && val.chars().all(|c| c.is_ascii_hexdigit()) {
+        Ok(())
+    } else {
+        Err(String::from(
+            "Value must be a SHA-256 digest of 64 hex digits",
+        ))
+    }
+}
+
+/// Parse bytes in hexadecimal, as pairs of digits optionally separated by
+/// whitespace, e.g. `DE AD BE EF` or `DEADBEEF`.
+pub fn parse_bytes(val: &str) -> Option<Vec<u8>> {
+    let digits: Vec<char> = val.chars().filter(|c| !c.is_whitespace()).collect();
+    if digits.is_empty() {
+        return None;
+    }
+
+    digits
+        .chunks(2)
+        .map(|pair| match pair {
+            [high, low] => Some((high.to_digit(16)? << 4 | low.to_digit(16)?) as u8),
+            _ => None,
+        })
+        .collect()
+}
+
+fn bytes_validate(val: String) -> Result<(), String> {
+    match parse_bytes(&val) {
+        Some(_) => Ok(()),
+        None => Err(String::from(
+            "Value must be bytes in hexadecimal, e.g. \"DE AD BE EF\"",
+        )),
+    }
+}
+
+/// Parse a MAC address of bytes in hexadecimal separated by colons, most
+/// significant byte first, e.g. `80:6F:B0:EE:A4:12`.
+pub fn parse_mac(val: &str) -> Option<Vec<u8>> {
+    val.split(':')
+        .map(|byte| match byte.len() {
+            2 => u8::from_str_radix(byte, 16).ok(),
+            _ => None,
+        })
+        .collect()
+}
+
+fn mac_validate(val: String) -> Result<(), String> {
+    match parse_mac(&val) {
+        Some(_) => Ok(()),
+        None => Err(String::from(
+            "Value must be a MAC address of bytes separated by colons, e.g. \"80:6F:B0:EE:A4:12\"",
+        )),
+    }
+}
+
+fn size_validate(val: String) -> Result<(), String> {
+    match parse_size(&val) {
+        Some(size) if size > 0 => Ok(()),
+        _ => Err(String::from("Value must be a positive size, e.g. 16M")),
+    }
+}
+
+fn offset_shift_validate(val: String) -> Result<(), String> {
+    match parse_offset_shift(&val) {
+        Some(_) => Ok(()),
+        None => Err(String::from(
+            "Value must be a signed 32-bit offset, e.g. -0x1000",
+        )),
+    }
+}
+
 fn is_zero_or_positive(val: String) -> Result<(), String> {
     if val.parse::<u32>().is_err() {
         return Err(String::from("Value must be a zero or positive integer"));
@@ -210,3 +1345,16 @@ fn is_zero_or_positive(val: String) -> Result<(), String> {
 
     Ok(())
 }
+
+fn is_duration(val: String) -> Result<(), String> {
+    humantime::parse_duration(&val)
+        .map(|_| ())
+        .map_err(|err| err.to_string())
+}
+
+fn is_positive(val: String) -> Result<(), String> {
+    match val.parse::<u32>() {
+        Ok(val) if val > 0 => Ok(()),
+        _ => Err(String::from("Value must be a positive integer")),
+    }
+}
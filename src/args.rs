@@ -5,16 +5,38 @@
 
 use std::cell::RefCell;
 use std::convert::TryFrom;
-use std::fs::File;
-use std::io;
+use std::env;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
 use std::str;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+use flash_rover::base64_stream::{Base64Reader, Base64Writer};
+use flash_rover::board::{self, Board};
+use flash_rover::byte_swap::{SwapReader, SwapWriter};
+use flash_rover::checksum::ChecksumWriter;
+use flash_rover::command::{
+    Command, DataPort, InternalAction, MemAction, Recheck, SessionAction, StatusAction, Subcommand,
+    Verify,
+};
+use flash_rover::fetch;
+use flash_rover::image::Relocation;
+use flash_rover::manifest::{self, Manifest};
+use flash_rover::partition::PartitionTable;
+use flash_rover::profile::{self, Profile};
+use flash_rover::remote::Remote;
+use flash_rover::split::{SplitReader, SplitWriter};
+use flash_rover::ti_txt::TiTxtWriter;
+use flash_rover::types::{
+    CrcAlgorithm, Device, ErrorFormat, InputFormat, Interface, MuxSelect, OutputFormat,
+    ProgressFormat, SpiPins,
+};
+use flash_rover::xds110::Power;
 use snafu::{Backtrace, OptionExt, ResultExt, Snafu};
 
 use crate::app;
-use crate::command::{Command, Subcommand};
-use crate::types::{Device, SpiPins};
 
 #[derive(Debug, Snafu)]
 pub enum Error {
@@ -45,6 +67,31 @@ pub enum Error {
         source: io::Error,
         backtrace: Backtrace,
     },
+    #[snafu(display(
+        "Input SHA-256 digest {} does not match the expected {}, nothing was written",
+        actual,
+        expected
+    ))]
+    InputDigestMismatch {
+        expected: String,
+        actual: String,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("{}", source))]
+    InvalidManifest {
+        source: manifest::Error,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("{}", source))]
+    InvalidProfile {
+        source: profile::Error,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("{}", source))]
+    InvalidBoard {
+        source: board::Error,
+        backtrace: Backtrace,
+    },
     #[snafu(display("Invalid subcommand: {}", subcmd))]
     InvalidSubcommand {
         subcmd: String,
@@ -54,6 +101,36 @@ pub enum Error {
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
+impl Error {
+    /// Stable identifier of the error, for automation.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::ParseArgMatch { .. }
+            | Error::ParseArgument { .. }
+            | Error::InvalidArgument { .. } => "invalid_argument",
+            Error::MissingArgument { .. } => "missing_argument",
+            Error::InvalidPath { .. } => "invalid_path",
+            Error::CreateStreamError { .. } => "io_error",
+            Error::InputDigestMismatch { .. } => "input_digest_mismatch",
+            Error::InvalidManifest {
+                source: manifest::Error::DigestMismatch { .. },
+                ..
+            } => "input_digest_mismatch",
+            Error::InvalidManifest { .. } => "invalid_manifest",
+            Error::InvalidProfile {
+                source:
+                    profile::Error::ManifestError {
+                        source: manifest::Error::DigestMismatch { .. },
+                    },
+                ..
+            } => "input_digest_mismatch",
+            Error::InvalidProfile { .. } => "invalid_profile",
+            Error::InvalidBoard { .. } => "invalid_board",
+            Error::InvalidSubcommand { .. } => "invalid_subcommand",
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 struct ArgMatches(clap::ArgMatches<'static>);
 
@@ -71,6 +148,11 @@ impl ArgMatches {
         self.0.value_of_lossy(name).map(|s| s.into_owned())
     }
 
+    /// Path given by the argument as is, which may not be valid Unicode.
+    fn path_of(&self, name: &str) -> Option<PathBuf> {
+        self.0.value_of_os(name).map(PathBuf::from)
+    }
+
     fn values_of_lossy(&self, name: &str) -> Option<Vec<String>> {
         self.0.values_of_lossy(name)
     }
@@ -94,6 +176,82 @@ impl ArgMatches {
     }
 }
 
+/// Word size of the `--byte-swap` option of read and write, which must divide
+/// the length of the data if given.
+fn byte_swap(matches: &ArgMatches, length: Option<u32>) -> Result<Option<usize>> {
+    let size = match matches.parse_of_lossy::<usize>("byte-swap")? {
+        Some(size) => size,
+        None => return Ok(None),
+    };
+    ensure!(
+        length.is_none_or(|length| (length as usize).is_multiple_of(size)),
+        InvalidArgument {
+            arg: "length",
+            reason: format!("must be a multiple of the byte swap size of {} bytes", size),
+        }
+    );
+    Ok(Some(size))
+}
+
+/// MAC address of the argument, which must have the given number of bytes.
+/// All ones is rejected, as the radio stacks then fall back to the factory
+/// MAC address.
+fn mac_of(matches: &ArgMatches, arg: &'static str, length: usize) -> Result<Option<Vec<u8>>> {
+    let mac = match matches.value_of_lossy(arg) {
+        Some(mac) => app::parse_mac(&mac).context(ParseArgument {
+            arg,
+            reason: "Invalid MAC address",
+        })?,
+        None => return Ok(None),
+    };
+    ensure!(
+        mac.len() == length,
+        InvalidArgument {
+            arg,
+            reason: format!("must be {} bytes", length),
+        }
+    );
+    ensure!(
+        mac.iter().any(|byte| *byte != 0xFF),
+        InvalidArgument {
+            arg,
+            reason: "must not be all ones, which selects the factory MAC address",
+        }
+    );
+    Ok(Some(mac))
+}
+
+/// Verification of the `--verify` option of write, where a sample without a
+/// seed gets a random one.
+fn parse_verify(matches: &ArgMatches) -> Result<Verify> {
+    const ARG: &str = "verify";
+    if !matches.is_present(ARG) {
+        return Ok(Verify::Off);
+    }
+
+    match matches.value_of_lossy(ARG) {
+        None => Ok(Verify::Full),
+        Some(mode) if mode == "full" => Ok(Verify::Full),
+        Some(mode) => {
+            let (percent, seed) = app::parse_verify_sample(&mode).context(ParseArgument {
+                arg: ARG,
+                reason: "Invalid verification mode",
+            })?;
+            let seed = seed.unwrap_or_else(|| {
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|time| time.subsec_nanos() | 1)
+                    .unwrap_or(1)
+            });
+            Ok(Verify::Sample { percent, seed })
+        }
+    }
+}
+
+/// Default of `--recheck-delay`, which is not given to clap as the default
+/// would require `--recheck`.
+const RECHECK_DELAY_MS: u64 = 1000;
+
 pub struct Args {
     matches: ArgMatches,
 }
@@ -106,6 +264,45 @@ impl Args {
         Ok(Self { matches })
     }
 
+    /// Remote host given with `--remote`.
+    pub fn remote(&self) -> Option<Remote> {
+        self.matches
+            .value_of_lossy("remote")
+            .and_then(|url| Remote::parse(&url))
+    }
+
+    /// Trace given with `--replay`, run against instead of a device.
+    pub fn replay(&self) -> Option<PathBuf> {
+        self.matches.path_of("replay")
+    }
+
+    /// Arguments of the invocation before the subcommand, i.e. the global
+    /// options.
+    pub fn global_args(&self) -> Vec<OsString> {
+        let (subcommand, _) = self.matches.subcommand();
+        env::args_os()
+            .skip(1)
+            .take_while(|arg| arg != subcommand)
+            .collect()
+    }
+
+    /// Path to the CCS installation, if given with `--ccs` or `CCS_ROOT`.
+    pub fn ccs_path(&self) -> Result<Option<PathBuf>> {
+        match self.matches.path_of("ccs") {
+            Some(path) => {
+                ensure!(
+                    path.join("ccs_base").exists(),
+                    InvalidArgument {
+                        arg: "ccs",
+                        reason: format!("{} is not a CCS installation", path.display()),
+                    }
+                );
+                Ok(Some(path))
+            }
+            None => Ok(None),
+        }
+    }
+
     fn log_dss(&self) -> Result<String> {
         const ARG: &str = "log-dss";
         let arg = self
@@ -115,17 +312,74 @@ impl Args {
         Ok(arg)
     }
 
+    /// Serial number of the probe, empty if any probe is waited for.
     fn xds_id(&self) -> Result<String> {
         const ARG: &str = "xds";
+        match self.matches.value_of_lossy(ARG) {
+            Some(arg) => Ok(arg),
+            None if self.matches.is_present("wait-for-probe") => Ok(String::new()),
+            None => MissingArgument { arg: ARG }.fail(),
+        }
+    }
+
+    fn wait_for_probe(&self) -> Option<Option<Duration>> {
+        if !self.matches.is_present("wait-for-probe") {
+            return None;
+        }
+        Some(
+            self.matches
+                .value_of_lossy("wait-for-probe")
+                .and_then(|timeout| humantime::parse_duration(&timeout).ok()),
+        )
+    }
+
+    fn device(&self) -> Result<Device> {
+        const ARG: &str = "device";
         let arg = self
             .matches
-            .value_of_lossy(ARG)
+            .parse_of_lossy(ARG)?
             .context(MissingArgument { arg: ARG })?;
         Ok(arg)
     }
 
-    fn device(&self) -> Result<Device> {
-        const ARG: &str = "device";
+    fn interface(&self) -> Result<Interface> {
+        const ARG: &str = "interface";
+        let arg = self
+            .matches
+            .parse_of_lossy(ARG)?
+            .context(MissingArgument { arg: ARG })?;
+        Ok(arg)
+    }
+
+    fn jtag_speed(&self) -> Result<Option<u32>> {
+        self.matches.parse_of_lossy("jtag-speed")
+    }
+
+    fn script_timeout(&self) -> Result<Option<Duration>> {
+        let secs: Option<u64> = self.matches.parse_of_lossy("script-timeout")?;
+        Ok(secs.map(Duration::from_secs))
+    }
+
+    fn cooperative(&self) -> Result<Option<u32>> {
+        let address = match self.matches.value_of_lossy("cooperative") {
+            Some(address) => app::parse_address(&address).context(ParseArgument {
+                arg: "cooperative",
+                reason: "Invalid address passed",
+            })?,
+            None => return Ok(None),
+        };
+        ensure!(
+            address % 4 == 0,
+            InvalidArgument {
+                arg: "cooperative",
+                reason: "must be aligned to a 32-bit word",
+            }
+        );
+        Ok(Some(address))
+    }
+
+    fn crc_algorithm(&self) -> Result<CrcAlgorithm> {
+        const ARG: &str = "crc-algorithm";
         let arg = self
             .matches
             .parse_of_lossy(ARG)?
@@ -145,6 +399,110 @@ impl Args {
         Ok(arg)
     }
 
+    fn mux_select(&self) -> Result<Option<MuxSelect>> {
+        const ARG: &str = "mux-select";
+        let arg = match self.matches.values_of_lossy(ARG) {
+            Some(arg) => Some(MuxSelect::try_from(arg).ok().context(ParseArgument {
+                arg: ARG,
+                reason: "Invalid arguments passed",
+            })?),
+            None => None,
+        };
+        Ok(arg)
+    }
+
+    fn partitions(&self) -> Result<Option<PartitionTable>> {
+        const ARG: &str = "partitions";
+        let path = match self.matches.value_of_lossy(ARG) {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        let table = fs::read_to_string(&path).context(CreateStreamError {})?;
+        let table = table
+            .parse()
+            .map_err(|err: flash_rover::partition::Error| {
+                ParseArgument {
+                    arg: ARG,
+                    reason: err.to_string(),
+                }
+                .build()
+            })?;
+        Ok(Some(table))
+    }
+
+    fn board(&self) -> Result<Option<Board>> {
+        match self.matches.value_of_lossy("board") {
+            Some(name) => Ok(Some(Board::load(&name).context(InvalidBoard {})?)),
+            None => Ok(None),
+        }
+    }
+
+    fn data_port(&self) -> Result<Option<DataPort>> {
+        const DEFAULT_BAUD_RATE: u32 = 921_600;
+
+        let path = match self.matches.value_of_lossy("data-port") {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        let pins = match self.matches.values_of_lossy("data-pins") {
+            Some(pins) => {
+                let pins: Vec<u8> = pins.iter().filter_map(|pin| pin.parse().ok()).collect();
+                Some(
+                    <[u8; 2]>::try_from(pins.as_slice())
+                        .ok()
+                        .context(ParseArgument {
+                            arg: "data-pins",
+                            reason: "Invalid arguments passed",
+                        })?,
+                )
+            }
+            None => None,
+        };
+
+        Ok(Some(DataPort {
+            path,
+            baud_rate: self
+                .matches
+                .parse_of_lossy("data-baud")?
+                .unwrap_or(DEFAULT_BAUD_RATE),
+            pins,
+        }))
+    }
+
+    /// The flash subcommand shows progress unless told otherwise.
+    fn progress_format(&self) -> Result<Option<ProgressFormat>> {
+        Ok(match self.matches.parse_of_lossy("progress-format")? {
+            Some(format) => Some(format),
+            None if self.matches.subcommand().0 == "flash" => Some(ProgressFormat::Human),
+            None => None,
+        })
+    }
+
+    /// Format of the errors of the run, human readable if not given.
+    pub fn error_format(&self) -> ErrorFormat {
+        self.matches
+            .parse_of_lossy("output-format")
+            .ok()
+            .flatten()
+            .unwrap_or(ErrorFormat::Human)
+    }
+
+    /// Device and probe of the run, as context of its errors.
+    pub fn error_context(&self) -> (Option<String>, Option<String>) {
+        (
+            self.device().ok().map(|device| device.to_string()),
+            self.xds_id().ok().filter(|xds_id| !xds_id.is_empty()),
+        )
+    }
+
+    fn pre_cmd(&self) -> Option<String> {
+        self.matches.value_of_lossy("pre-cmd")
+    }
+
+    fn post_cmd(&self) -> Option<String> {
+        self.matches.value_of_lossy("post-cmd")
+    }
+
     fn subcommand(&self) -> Result<Subcommand> {
         Ok(match self.matches.subcommand() {
             ("info", _) => Subcommand::Info,
@@ -162,46 +520,480 @@ impl Args {
                     }
                 }
             }
-            ("read", Some(matches)) => Subcommand::Read {
+            ("checksum", Some(matches)) if matches.is_present("partitions") => {
+                let output: Box<dyn Write> = match matches.value_of_lossy("output") {
+                    Some(output_path) => {
+                        Box::new(File::create(output_path).context(CreateStreamError {})?)
+                    }
+                    None => Box::new(io::stdout()),
+                };
+                Subcommand::PartitionChecksums {
+                    output: RefCell::new(output),
+                }
+            }
+            ("checksum", Some(matches)) => Subcommand::Checksum {
                 offset: matches
                     .parse_of_lossy("offset")?
                     .context(MissingArgument { arg: "offset" })?,
                 length: matches
                     .parse_of_lossy("length")?
                     .context(MissingArgument { arg: "length" })?,
-                output: RefCell::new(
-                    if let Some(output_path) = matches.value_of_lossy("output") {
+            },
+            ("read", Some(matches)) => {
+                let offset = matches
+                    .parse_of_lossy("offset")?
+                    .context(MissingArgument { arg: "offset" })?;
+                let length = matches
+                    .parse_of_lossy("length")?
+                    .context(MissingArgument { arg: "length" })?;
+                let byte_swap = byte_swap(&matches, Some(length))?;
+                let split_size = matches
+                    .value_of_lossy("split-size")
+                    .and_then(|size| app::parse_size(&size));
+                let output: Box<dyn Write> = match (matches.value_of_lossy("output"), split_size) {
+                    (Some(output_path), Some(size)) => {
+                        Box::new(SplitWriter::new(output_path, size))
+                    }
+                    (Some(output_path), None) => {
                         Box::new(File::create(output_path).context(CreateStreamError {})?)
-                    } else {
-                        Box::new(io::stdout())
+                    }
+                    (None, _) => Box::new(io::stdout()),
+                };
+                let output: Box<dyn Write> = if matches.is_present("crc") {
+                    Box::new(ChecksumWriter::new(output, self.crc_algorithm()?))
+                } else {
+                    output
+                };
+                let output: Box<dyn Write> = match matches.parse_of_lossy("format")? {
+                    Some(OutputFormat::Base64) => Box::new(Base64Writer::new(output)),
+                    Some(OutputFormat::TiTxt) => Box::new(TiTxtWriter::new(output, offset)),
+                    _ => output,
+                };
+                Subcommand::Read {
+                    offset,
+                    length,
+                    output: RefCell::new(match byte_swap {
+                        Some(size) => Box::new(SwapWriter::new(output, size)),
+                        None => output,
+                    }),
+                }
+            }
+            ("write", Some(matches)) => {
+                let format = match matches.parse_of_lossy("input-format")? {
+                    Some(format) => format,
+                    None => matches
+                        .value_of_lossy("input")
+                        .map_or(InputFormat::Bin, |input| {
+                            InputFormat::from_file_name(&input)
+                        }),
+                };
+                let offset = matches.parse_of_lossy("offset")?;
+                if !format.has_addresses() {
+                    for arg in &["relocate", "offset-shift"] {
+                        ensure!(
+                            !matches.is_present(arg),
+                            InvalidArgument {
+                                arg: *arg,
+                                reason: "only applies to address-bearing input formats",
+                            }
+                        );
+                    }
+                } else {
+                    ensure!(
+                        !matches.is_present("auto-offset"),
+                        InvalidArgument {
+                            arg: "auto-offset",
+                            reason: "the offsets are taken from the addresses of the input image",
+                        }
+                    );
+                    ensure!(
+                        offset.is_none(),
+                        InvalidArgument {
+                            arg: "offset",
+                            reason: "the offsets are taken from the addresses of the input image",
+                        }
+                    );
+                }
+                Subcommand::Write {
+                    verify: parse_verify(&matches)?,
+                    recheck: match matches.parse_of_lossy("recheck")? {
+                        Some(percent) => Some(Recheck {
+                            percent,
+                            delay: Duration::from_millis(
+                                matches
+                                    .parse_of_lossy("recheck-delay")?
+                                    .unwrap_or(RECHECK_DELAY_MS),
+                            ),
+                        }),
+                        None => None,
+                    },
+                    in_place: matches.is_present("in-place"),
+                    offset: match offset {
+                        Some(offset) => offset,
+                        None if format.has_addresses() || matches.is_present("auto-offset") => 0,
+                        None => MissingArgument { arg: "offset" }.fail()?,
                     },
-                ),
+                    auto_offset: matches.is_present("auto-offset"),
+                    length: matches.parse_of_lossy("length")?,
+                    input: RefCell::new({
+                        let input: Box<dyn Read> = match matches.value_of_lossy("input") {
+                            Some(url) if fetch::is_url(&url) => {
+                                ensure!(
+                                    !matches.is_present("split"),
+                                    InvalidArgument {
+                                        arg: "split",
+                                        reason: "the parts of a split input must be files",
+                                    }
+                                );
+                                Box::new(fetch::open(&url).context(CreateStreamError {})?)
+                            }
+                            Some(input_path) if matches.is_present("split") => Box::new(
+                                SplitReader::open(input_path).context(CreateStreamError {})?,
+                            ),
+                            Some(input_path) => {
+                                Box::new(File::open(input_path).context(CreateStreamError {})?)
+                            }
+                            None => Box::new(io::stdin()),
+                        };
+                        // Verify the whole input up front, before connecting to the device
+                        let input: Box<dyn Read> = match matches.value_of_lossy("input-sha256") {
+                            Some(expected) => {
+                                let mut input = input;
+                                let mut data = Vec::new();
+                                input.read_to_end(&mut data).context(CreateStreamError {})?;
+
+                                let actual = fetch::sha256(&data);
+                                ensure!(
+                                    actual.eq_ignore_ascii_case(&expected),
+                                    InputDigestMismatch { expected, actual }
+                                );
+                                Box::new(io::Cursor::new(data))
+                            }
+                            None => input,
+                        };
+                        let input: Box<dyn Read> = match format {
+                            InputFormat::Base64 => Box::new(Base64Reader::new(input)),
+                            _ => input,
+                        };
+                        match byte_swap(&matches, matches.parse_of_lossy("length")?)? {
+                            Some(size) => Box::new(SwapReader::new(input, size)),
+                            None => input,
+                        }
+                    }),
+                    format,
+                    relocation: Relocation {
+                        base: matches
+                            .value_of_lossy("relocate")
+                            .and_then(|base| app::parse_address(&base))
+                            .unwrap_or(0),
+                        shift: matches
+                            .value_of_lossy("offset-shift")
+                            .and_then(|shift| app::parse_offset_shift(&shift))
+                            .unwrap_or(0),
+                    },
+                }
+            }
+            ("flash", Some(matches)) => Subcommand::Flash {
+                offset: matches
+                    .parse_of_lossy("offset")?
+                    .context(MissingArgument { arg: "offset" })?,
+                input: RefCell::new(Box::new(
+                    File::open(
+                        matches
+                            .value_of_lossy("input")
+                            .context(MissingArgument { arg: "input" })?,
+                    )
+                    .context(CreateStreamError {})?,
+                )),
+            },
+            ("patch", Some(matches)) => {
+                let bytes = matches
+                    .value_of_lossy("bytes")
+                    .and_then(|bytes| app::parse_bytes(&bytes))
+                    .context(MissingArgument { arg: "bytes" })?;
+                let mask = matches
+                    .value_of_lossy("mask")
+                    .and_then(|mask| app::parse_bytes(&mask));
+                if let Some(mask) = &mask {
+                    ensure!(
+                        mask.len() == bytes.len(),
+                        InvalidArgument {
+                            arg: "mask",
+                            reason: format!(
+                                "{} mask bytes given for {} bytes",
+                                mask.len(),
+                                bytes.len()
+                            ),
+                        }
+                    );
+                }
+                Subcommand::Patch {
+                    offset: matches
+                        .parse_of_lossy("offset")?
+                        .context(MissingArgument { arg: "offset" })?,
+                    bytes,
+                    mask,
+                }
+            }
+            ("apply", Some(matches)) => Subcommand::Apply {
+                manifest: Manifest::load(Path::new(
+                    &matches
+                        .value_of_lossy("manifest")
+                        .context(MissingArgument { arg: "manifest" })?,
+                ))
+                .context(InvalidManifest {})?,
+                report: matches.value_of_lossy("report").map(Into::into),
+            },
+            ("production", Some(matches)) => Subcommand::Production {
+                profile: Profile::load(
+                    &matches
+                        .value_of_lossy("profile")
+                        .context(MissingArgument { arg: "profile" })?,
+                )
+                .context(InvalidProfile {})?,
             },
-            ("write", Some(matches)) => Subcommand::Write {
-                verify: matches.is_present("verify"),
-                in_place: matches.is_present("in-place"),
+            ("hil-test", Some(matches)) => Subcommand::HilTest {
                 offset: matches
                     .parse_of_lossy("offset")?
-                    .expect("Missing required argument 'offset'"),
-                length: matches.parse_of_lossy("length")?,
-                input: RefCell::new(if let Some(input_path) = matches.value_of_lossy("input") {
-                    Box::new(File::open(input_path).context(CreateStreamError {})?)
-                } else {
-                    Box::new(io::stdin())
-                }),
+                    .context(MissingArgument { arg: "offset" })?,
+                length: matches
+                    .parse_of_lossy("length")?
+                    .context(MissingArgument { arg: "length" })?,
+                report: matches
+                    .value_of_lossy("report")
+                    .context(MissingArgument { arg: "report" })?
+                    .into(),
+            },
+            ("oad", Some(matches)) => match matches.subcommand() {
+                ("ls", _) => Subcommand::OadList,
+                (subcmd, _) => InvalidSubcommand { subcmd }.fail()?,
+            },
+            ("status", Some(matches)) => match matches.subcommand() {
+                ("read", Some(matches)) => Subcommand::Status {
+                    action: StatusAction::Read,
+                    config: matches.is_present("config"),
+                },
+                ("write", Some(matches)) => {
+                    let config = matches.is_present("config");
+                    let value = matches
+                        .value_of_lossy("value")
+                        .and_then(|value| app::parse_address(&value))
+                        .context(MissingArgument { arg: "value" })?;
+                    ensure!(
+                        value <= if config { 0xFFFF } else { 0xFF },
+                        InvalidArgument {
+                            arg: "value",
+                            reason: "must fit in the register",
+                        }
+                    );
+                    Subcommand::Status {
+                        action: StatusAction::Write(value as u16),
+                        config,
+                    }
+                }
+                (subcmd, _) => InvalidSubcommand { subcmd }.fail()?,
+            },
+            ("internal", Some(matches)) => {
+                let (action, matches) = match matches.subcommand() {
+                    ("read", Some(matches)) => (
+                        InternalAction::Read {
+                            length: matches
+                                .parse_of_lossy("length")?
+                                .context(MissingArgument { arg: "length" })?,
+                            output: RefCell::new(match matches.value_of_lossy("output") {
+                                Some(output) => {
+                                    Box::new(File::create(output).context(CreateStreamError {})?)
+                                }
+                                None => Box::new(io::stdout()),
+                            }),
+                        },
+                        matches,
+                    ),
+                    ("write", Some(matches)) => (
+                        InternalAction::Write {
+                            input: RefCell::new(Box::new(
+                                File::open(
+                                    matches
+                                        .value_of_lossy("input")
+                                        .context(MissingArgument { arg: "input" })?,
+                                )
+                                .context(CreateStreamError {})?,
+                            )),
+                        },
+                        matches,
+                    ),
+                    (subcmd, _) => InvalidSubcommand { subcmd }.fail()?,
+                };
+                Subcommand::Internal {
+                    action,
+                    bank: matches
+                        .parse_of_lossy("bank")?
+                        .context(MissingArgument { arg: "bank" })?,
+                    offset: matches
+                        .parse_of_lossy("offset")?
+                        .context(MissingArgument { arg: "offset" })?,
+                }
+            }
+            ("mem", Some(matches)) => {
+                let (matches, read) = match matches.subcommand() {
+                    ("read", Some(matches)) => (matches, true),
+                    ("write", Some(matches)) => (matches, false),
+                    (subcmd, _) => InvalidSubcommand { subcmd }.fail()?,
+                };
+                let address = matches
+                    .value_of_lossy("address")
+                    .and_then(|address| app::parse_address(&address))
+                    .context(MissingArgument { arg: "address" })?;
+                ensure!(
+                    address % 4 == 0,
+                    InvalidArgument {
+                        arg: "address",
+                        reason: "must be aligned to a 32-bit word",
+                    }
+                );
+                Subcommand::Mem {
+                    action: if read {
+                        MemAction::Read {
+                            address,
+                            count: matches
+                                .parse_of_lossy("count")?
+                                .context(MissingArgument { arg: "count" })?,
+                        }
+                    } else {
+                        MemAction::Write {
+                            address,
+                            value: matches
+                                .value_of_lossy("value")
+                                .and_then(|value| app::parse_address(&value))
+                                .context(MissingArgument { arg: "value" })?,
+                        }
+                    },
+                }
+            }
+            ("sessions", _) => Subcommand::Sessions,
+            ("export-uniflash", Some(matches)) => Subcommand::ExportUniflash {
+                output: match matches.path_of("output") {
+                    Some(output) => output,
+                    None => {
+                        format!("{}_{}.ccxml", self.device()?.to_string(), self.xds_id()?).into()
+                    }
+                },
             },
-            (subcmd, _) => InvalidSubcommand { subcmd }.fail()?,
+            ("daemon", _) => Subcommand::Daemon,
+            ("tui", _) => Subcommand::Tui,
+            ("regs", _) => Subcommand::Regs,
+            ("fcfg", Some(matches)) => match matches.subcommand() {
+                ("dump", Some(matches)) => Subcommand::FcfgDump {
+                    output: RefCell::new(match matches.value_of_lossy("output") {
+                        Some(output) => {
+                            Box::new(File::create(output).context(CreateStreamError {})?)
+                        }
+                        None => Box::new(io::stdout()),
+                    }),
+                },
+                (subcmd, _) => InvalidSubcommand { subcmd }.fail()?,
+            },
+            ("provision", Some(matches)) => match matches.subcommand() {
+                ("mac", Some(matches)) => Subcommand::ProvisionMac {
+                    ieee: mac_of(&matches, "ieee", 8)?,
+                    ble: mac_of(&matches, "ble", 6)?,
+                },
+                (subcmd, _) => InvalidSubcommand { subcmd }.fail()?,
+            },
+            ("recover", _) => Subcommand::Recover,
+            ("ping", Some(matches)) => Subcommand::Ping {
+                count: matches
+                    .parse_of_lossy("count")?
+                    .context(MissingArgument { arg: "count" })?,
+            },
+            ("power", Some(matches)) => Subcommand::Power {
+                power: match matches.value_of_lossy("state").as_deref() {
+                    Some("on") => Power::On,
+                    Some("off") => Power::Off,
+                    _ => MissingArgument { arg: "state" }.fail()?,
+                },
+            },
+            ("session", Some(matches)) => {
+                let (action, matches) = match matches.subcommand() {
+                    ("open", Some(matches)) => (SessionAction::Open, matches),
+                    ("close", Some(matches)) => (SessionAction::Close, matches),
+                    ("serve", Some(matches)) => (SessionAction::Serve, matches),
+                    (subcmd, _) => InvalidSubcommand { subcmd }.fail()?,
+                };
+                Subcommand::Session {
+                    action,
+                    handle: matches
+                        .value_of_lossy("handle")
+                        .context(MissingArgument { arg: "handle" })?
+                        .into(),
+                }
+            }
+            (name, Some(matches)) => Subcommand::External {
+                name: name.to_string(),
+                args: matches.values_of_lossy("").unwrap_or_default(),
+            },
+            (subcmd, None) => InvalidSubcommand { subcmd }.fail()?,
         })
     }
 
     pub fn command(&self, ccs_path: &Path) -> Result<Command, Error> {
+        let subcommand = self.subcommand()?;
+        // The partitions of a manifest are checked along with any given table
+        let manifest = match &subcommand {
+            Subcommand::Apply { manifest, .. } => Some(manifest),
+            Subcommand::Production { profile } => Some(&profile.manifest),
+            _ => None,
+        };
+        let partitions = match (self.partitions()?, manifest) {
+            (partitions, Some(manifest)) if !manifest.partitions.partitions.is_empty() => {
+                let mut partitions = partitions.unwrap_or_default();
+                partitions
+                    .partitions
+                    .extend(manifest.partitions.partitions.iter().cloned());
+                Some(partitions)
+            }
+            (partitions, _) => partitions,
+        };
         Ok(Command {
             ccs_path: ccs_path.into(),
             log_dss: self.log_dss()?,
             xds_id: self.xds_id()?,
             device: self.device()?,
+            interface: self.interface()?,
+            jtag_speed: self.jtag_speed()?,
+            probe_voltage: self.matches.value_of_lossy("probe-voltage"),
             spi_pins: self.spi_pins()?,
-            subcommand: self.subcommand()?,
+            mux_select: self.mux_select()?,
+            board: self.board()?,
+            firmware_elf: self.matches.path_of("firmware-elf"),
+            asset_dir: self.matches.path_of("asset-dir"),
+            data_port: self.data_port()?,
+            script_timeout: self.script_timeout()?,
+            max_duration: self
+                .matches
+                .value_of_lossy("max-duration")
+                .and_then(|duration| humantime::parse_duration(&duration).ok()),
+            reconnect: self.matches.parse_of_lossy("reconnect")?.unwrap_or(0),
+            cooperative: self.cooperative()?,
+            power_down: self.matches.is_present("power-down"),
+            fw_log: self.matches.is_present("fw-log"),
+            core: self.matches.value_of_lossy("core"),
+            power_cycle: self.matches.is_present("power-cycle"),
+            wait_for_probe: self.wait_for_probe(),
+            force: self.matches.is_present("force"),
+            strict: self.matches.is_present("strict"),
+            partitions,
+            safe_transport: self.matches.is_present("safe-transport"),
+            throttle: self.matches.parse_of_lossy("throttle")?,
+            crc_algorithm: self.crc_algorithm()?,
+            stats: self.matches.is_present("stats"),
+            progress_format: self.progress_format()?,
+            pre_cmd: self.pre_cmd(),
+            post_cmd: self.post_cmd(),
+            history: self.matches.value_of_lossy("history").map(Into::into),
+            record: self.matches.value_of_lossy("record").map(Into::into),
+            handle: self.matches.value_of_lossy("handle").map(Into::into),
+            subcommand,
         })
     }
 }
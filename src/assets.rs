@@ -4,44 +4,153 @@
 // notice may not be copied, modified, or distributed except according to those terms.
 
 use std::borrow::Cow;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
 
 use rust_embed::RustEmbed;
+use snafu::{Backtrace, ResultExt, Snafu};
 
+use crate::history;
 use crate::types::{Device, DeviceFamily};
 
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Firmware asset {} not found", name))]
+    AssetNotFound { name: String, backtrace: Backtrace },
+    #[snafu(display(
+        "Firmware asset {} is corrupted or does not belong to this release of flash-rover \
+         (SHA-256 {}, expected {}), reinstall flash-rover",
+        name,
+        actual,
+        expected
+    ))]
+    AssetCorrupted {
+        name: String,
+        expected: String,
+        actual: String,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("Unable to read asset {}: {}", path.display(), source))]
+    ReadAsset {
+        path: PathBuf,
+        source: io::Error,
+        backtrace: Backtrace,
+    },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
 #[derive(RustEmbed)]
 #[folder = "./src/assets"]
 struct Asset;
 
-pub fn get_ccxml_template(device: Device) -> Option<Cow<'static, [u8]>> {
-    use DeviceFamily::*;
+/// Placeholder for the serial number of the probe in a ccxml template.
+pub const CCXML_SERIAL_PLACEHOLDER: &str = "$SERIAL";
+
+/// Contents of the embedded device family descriptors, by asset name.
+pub fn family_descriptors() -> Vec<(String, String)> {
+    Asset::iter()
+        .filter(|name| name.starts_with("families/") && name.ends_with(".toml"))
+        .filter_map(|name| {
+            let text = String::from_utf8_lossy(&Asset::get(&name)?).into_owned();
+            Some((name.into_owned(), text))
+        })
+        .collect()
+}
 
-    const PATH: &str = "ccxml/";
+/// Firmware binary of the device, from the asset directory if it has one,
+/// which is not verified against the digest as it may be patched.
+pub fn get_firmware(device: Device, asset_dir: Option<&Path>) -> Result<Cow<'static, [u8]>> {
+    let (file, digest) = DeviceFamily::from(device).firmware();
+    let name = format!("fw/{}", file);
+    if let Some(asset) = read_override(asset_dir, &name)? {
+        return Ok(asset);
+    }
+    match Asset::get(&name) {
+        Some(asset) => verify(&name, asset, Some(digest)),
+        None => AssetNotFound { name }.fail(),
+    }
+}
 
-    let device_family: DeviceFamily = From::from(device);
-    let file = match device_family {
-        CC13x0 => "template_cc13x0.ccxml",
-        CC26x0 => "template_cc26x0.ccxml",
-        CC26x0R2 => "template_cc26x0r2.ccxml",
-        CC13x2_CC26x2 | CC13x2x7_CC26x2x7 => "template_cc13x2_cc26x2.ccxml",
-        CC13x4_CC26x4 => "template_cc13x4_cc26x4.ccxml",
-    };
-    Asset::get(format!("{}{}", PATH, file).as_str())
+/// Firmware stub linked to run from internal flash, at the start of
+/// [`DeviceFamily::flash_stub_region`]. Only built for devices where the SRAM
+/// is too small for the regular firmware and the data buffer.
+pub fn get_firmware_stub(
+    device: Device,
+    asset_dir: Option<&Path>,
+) -> Result<Option<Cow<'static, [u8]>>> {
+    let family = DeviceFamily::from(device);
+    let name = format!("fw/stub/{}", family.firmware().0);
+    if let Some(asset) = read_override(asset_dir, &name)? {
+        return Ok(Some(asset));
+    }
+    Asset::get(&name)
+        .map(|asset| verify(&name, asset, family.firmware_stub_digest()))
+        .transpose()
 }
 
-pub fn get_firmware(device: Device) -> Option<Cow<'static, [u8]>> {
-    use DeviceFamily::*;
+/// Template of the ccxml target configuration of the device from the asset
+/// directory, replacing the generated one. The template is used as is, except
+/// for [`CCXML_SERIAL_PLACEHOLDER`].
+pub fn get_ccxml_template(device: Device, asset_dir: Option<&Path>) -> Result<Option<String>> {
+    let name = format!("ccxml/{}.ccxml", device.to_string());
+    let template = read_override(asset_dir, &name)?
+        .map(|template| String::from_utf8_lossy(&template).into_owned());
 
-    const PATH: &str = "fw/";
+    Ok(template)
+}
 
-    let device_family: DeviceFamily = From::from(device);
-    let file = match device_family {
-        CC13x0 => "cc13x0.bin",
-        CC26x0 => "cc26x0.bin",
-        CC26x0R2 => "cc26x0r2.bin",
-        CC13x2_CC26x2 => "cc13x2_cc26x2.bin",
-        CC13x2x7_CC26x2x7 => "cc13x2x7_cc26x2x7.bin",
-        CC13x4_CC26x4 => "cc13x4_cc26x4.bin",
+/// Binary and digest of the firmware of the device, e.g. for `info`.
+pub fn firmware_description(device: Device, asset_dir: Option<&Path>) -> String {
+    let (file, digest) = DeviceFamily::from(device).firmware();
+    let name = format!("fw/{}", file);
+    if let (Some(asset_dir), Ok(Some(asset))) = (asset_dir, read_override(asset_dir, &name)) {
+        let actual = history::digest(&asset);
+        return format!(
+            "{} from {}, SHA-256 {}",
+            name,
+            asset_dir.display(),
+            &actual[..16]
+        );
+    }
+    format!("{}, SHA-256 {}", file, &digest[..16.min(digest.len())])
+}
+
+/// Asset `name` from the asset directory, which takes precedence over the
+/// embedded one, with the same layout, e.g. `fw/cc13x2_cc26x2.bin`.
+fn read_override(asset_dir: Option<&Path>, name: &str) -> Result<Option<Cow<'static, [u8]>>> {
+    let path = match asset_dir {
+        Some(asset_dir) => asset_dir.join(name),
+        None => return Ok(None),
     };
-    Asset::get(format!("{}{}", PATH, file).as_str())
+    if !path.is_file() {
+        return Ok(None);
+    }
+
+    let asset = fs::read(&path).context(ReadAsset { path })?;
+
+    Ok(Some(Cow::Owned(asset)))
+}
+
+/// Check the asset against the digest in its family descriptor, so that a
+/// binary repackaged with corrupted or mismatched assets fails before
+/// injecting them.
+fn verify(
+    name: &str,
+    asset: Cow<'static, [u8]>,
+    expected: Option<&str>,
+) -> Result<Cow<'static, [u8]>> {
+    let actual = history::digest(&asset);
+    let expected = expected.unwrap_or("unknown");
+    ensure!(
+        actual == expected,
+        AssetCorrupted {
+            name,
+            expected,
+            actual,
+        }
+    );
+
+    Ok(asset)
 }
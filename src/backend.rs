@@ -0,0 +1,180 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+//! Access to the device through the debugger, shared by the tool and the
+//! firmware driver.
+//!
+//! A connected device is accessed through the DSS debug session, while
+//! [`crate::mock::MockDevice`] stands in for a device running the firmware,
+//! e.g. in the integration tests.
+
+use std::path::Path;
+use std::time::Duration;
+
+use dss::com::ti::debug::engine::scripting::{DebugServer, DebugSession, Register};
+use dss::Result;
+
+/// Memory and execution control of the core of the debug session. Memory is
+/// accessed in the program memory page.
+pub trait Backend {
+    fn read_word(&self, address: u32) -> Result<u32>;
+
+    fn read_words(&self, address: u32, count: u32) -> Result<Vec<u32>>;
+
+    fn write_word(&self, address: u32, value: u32) -> Result<()>;
+
+    fn read_bytes(&self, address: u32, length: u32) -> Result<Vec<u8>>;
+
+    fn write_bytes(&self, address: u32, values: &[u8]) -> Result<()>;
+
+    /// Whether the address is RAM, as found by writing to it.
+    fn is_ram(&self, address: u32) -> Result<bool>;
+
+    /// Load a raw binary file at the address, in units of `type_size` bits.
+    /// The internal flash is programmed with the flash loader.
+    fn load_raw(&self, address: u32, path: &Path, type_size: u32) -> Result<()>;
+
+    /// Load an ELF executable with its symbols, setting the entry point.
+    fn load_program(&self, path: &Path) -> Result<()>;
+
+    fn read_register(&self, register: Register) -> Result<u32>;
+
+    fn write_register(&self, register: Register, value: u32) -> Result<()>;
+
+    /// Evaluate a GEL expression, e.g. a board reset.
+    fn evaluate(&self, expression: &str) -> Result<i64>;
+
+    /// Connect to the core, retrying as [`dss`] does. Returns the number of
+    /// retries.
+    fn connect_with_retry(&self, timeout: Duration, attempts: u32) -> Result<u32>;
+
+    fn disconnect(&self) -> Result<()>;
+
+    fn reset(&self) -> Result<()>;
+
+    fn halt(&self) -> Result<()>;
+
+    fn is_halted(&self) -> Result<bool>;
+
+    fn run_asynch(&self) -> Result<()>;
+}
+
+/// Connected device, accessed through the debug session of DSS. The debug
+/// server is stopped once the backend is dropped.
+pub struct DssBackend<'a> {
+    debug_server: DebugServer<'a>,
+    debug_session: DebugSession<'a>,
+}
+
+impl<'a> DssBackend<'a> {
+    pub fn new(debug_server: DebugServer<'a>, debug_session: DebugSession<'a>) -> Self {
+        Self {
+            debug_server,
+            debug_session,
+        }
+    }
+}
+
+impl<'a> Backend for DssBackend<'a> {
+    fn read_word(&self, address: u32) -> Result<u32> {
+        let value = self
+            .debug_session
+            .memory
+            .read_data(0, address as _, 32, false as _)?;
+        Ok(value as _)
+    }
+
+    fn read_words(&self, address: u32, count: u32) -> Result<Vec<u32>> {
+        let values =
+            self.debug_session
+                .memory
+                .read_datas(0, address as _, 32, count as _, false as _)?;
+        Ok(values.into_iter().map(|value| value as _).collect())
+    }
+
+    fn write_word(&self, address: u32, value: u32) -> Result<()> {
+        self.debug_session
+            .memory
+            .write_data(0, address as _, value as _, 32)
+    }
+
+    fn read_bytes(&self, address: u32, length: u32) -> Result<Vec<u8>> {
+        let values =
+            self.debug_session
+                .memory
+                .read_datas(0, address as _, 8, length as _, false as _)?;
+        Ok(values.into_iter().map(|value| value as _).collect())
+    }
+
+    fn write_bytes(&self, address: u32, values: &[u8]) -> Result<()> {
+        let datas: Vec<_> = values.iter().map(|n| *n as _).collect();
+        self.debug_session
+            .memory
+            .write_datas(0, address as _, &datas, 8)
+    }
+
+    fn is_ram(&self, address: u32) -> Result<bool> {
+        self.debug_session.memory.is_ram(0, address as _)
+    }
+
+    fn load_raw(&self, address: u32, path: &Path, type_size: u32) -> Result<()> {
+        let file_name = dss::path::to_dss(path)?;
+        self.debug_session
+            .memory
+            .load_raw(0, address as _, &file_name, type_size as _, false as _)
+    }
+
+    fn load_program(&self, path: &Path) -> Result<()> {
+        let file_name = dss::path::to_dss(path)?;
+        self.debug_session.memory.load_program(&file_name)
+    }
+
+    fn read_register(&self, register: Register) -> Result<u32> {
+        let value = self.debug_session.memory.read_register(register)?;
+        Ok(value as _)
+    }
+
+    fn write_register(&self, register: Register, value: u32) -> Result<()> {
+        self.debug_session
+            .memory
+            .write_register(register, value as _)
+    }
+
+    fn evaluate(&self, expression: &str) -> Result<i64> {
+        self.debug_session.expression.evaluate(expression)
+    }
+
+    fn connect_with_retry(&self, timeout: Duration, attempts: u32) -> Result<u32> {
+        self.debug_session
+            .target
+            .connect_with_retry(timeout, attempts)
+    }
+
+    fn disconnect(&self) -> Result<()> {
+        self.debug_session.target.disconnect()
+    }
+
+    fn reset(&self) -> Result<()> {
+        self.debug_session.target.reset()
+    }
+
+    fn halt(&self) -> Result<()> {
+        self.debug_session.target.halt()
+    }
+
+    fn is_halted(&self) -> Result<bool> {
+        self.debug_session.target.is_halted()
+    }
+
+    fn run_asynch(&self) -> Result<()> {
+        self.debug_session.target.run_asynch()
+    }
+}
+
+impl<'a> Drop for DssBackend<'a> {
+    fn drop(&mut self) {
+        self.debug_server.stop().unwrap_or_default();
+    }
+}
@@ -0,0 +1,92 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+//! Base64 encoded data, which passes safely through terminals, SSH sessions,
+//! CI logs and JSON payloads.
+
+use std::io::{self, Read, Write};
+
+/// Bytes encoded per line, which gives the 76 characters of MIME.
+const LINE_BYTES: usize = 57;
+
+/// Writer encoding the data in lines of base64 text. The last line is written
+/// once the writer is dropped.
+pub struct Base64Writer<W: Write> {
+    inner: W,
+    line: Vec<u8>,
+}
+
+impl<W: Write> Base64Writer<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            line: Vec::with_capacity(LINE_BYTES),
+        }
+    }
+
+    fn write_line(&mut self) -> io::Result<()> {
+        let mut text = base64::encode(&self.line);
+        text.push('\n');
+        self.inner.write_all(text.as_bytes())?;
+        self.line.clear();
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for Base64Writer<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let len = data.len().min(LINE_BYTES - self.line.len());
+        self.line.extend_from_slice(&data[..len]);
+        if self.line.len() == LINE_BYTES {
+            self.write_line()?;
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Drop for Base64Writer<W> {
+    fn drop(&mut self) {
+        if !self.line.is_empty() {
+            self.write_line().unwrap_or_default();
+        }
+        self.inner.flush().unwrap_or_default();
+    }
+}
+
+/// Reader decoding base64 text, ignoring whitespace such as line breaks. The
+/// text is decoded as a whole on the first read.
+pub struct Base64Reader<R: Read> {
+    inner: R,
+    data: Option<io::Cursor<Vec<u8>>>,
+}
+
+impl<R: Read> Base64Reader<R> {
+    pub fn new(inner: R) -> Self {
+        Self { inner, data: None }
+    }
+}
+
+impl<R: Read> Read for Base64Reader<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if self.data.is_none() {
+            let mut text = Vec::new();
+            self.inner.read_to_end(&mut text)?;
+            text.retain(|c| !c.is_ascii_whitespace());
+
+            let data = base64::decode(&text)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            self.data = Some(io::Cursor::new(data));
+        }
+
+        match &mut self.data {
+            Some(data) => data.read(out),
+            None => Ok(0),
+        }
+    }
+}
@@ -0,0 +1,194 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+//! Board profiles, describing what a board needs before its external flash can
+//! be accessed, instead of a per-board GEL script.
+//!
+//! A board profile is a TOML file, either given by path or by name from the
+//! `flash-rover/boards` directory of the user configuration directory. Its
+//! `init` list of register writes and delays is run after every reset of the
+//! device, before the firmware is injected. A write with a `mask` only changes
+//! the masked bits. The optional `supply_voltage` of the external flash is
+//! checked against the supply voltage range of the detected part:
+//!
+//! ```toml
+//! supply_voltage = 3300  # mV
+//!
+//! # Enable the flash power switch on DIO6
+//! [[init]]
+//! address = 0x40022090
+//! value = 0x40
+//!
+//! [[init]]
+//! delay = 5  # ms
+//!
+//! [[init]]
+//! address = 0x40081018
+//! value = 0x00000000
+//! mask = 0x00007000
+//! ```
+
+use std::convert::TryFrom;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use snafu::{Backtrace, OptionExt, ResultExt, Snafu};
+use toml::value::{Table, Value};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Unable to read board profile {}: {}", path.display(), source))]
+    ReadError {
+        path: PathBuf,
+        source: io::Error,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("Invalid board profile {}: {}", path.display(), source))]
+    ParseError {
+        path: PathBuf,
+        source: toml::de::Error,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("Invalid board profile {} entry init[{}]: {}", path.display(), index, reason))]
+    InvalidInit {
+        path: PathBuf,
+        index: usize,
+        reason: String,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("Invalid board profile {} entry {}: {}", path.display(), key, reason))]
+    InvalidEntry {
+        path: PathBuf,
+        key: String,
+        reason: String,
+        backtrace: Backtrace,
+    },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// A step of the init sequence of a board.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InitStep {
+    /// Write the bits of `value` selected by `mask` to the 32-bit register
+    Write {
+        address: u32,
+        value: u32,
+        mask: u32,
+    },
+    Delay(Duration),
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Board {
+    pub name: String,
+    pub init: Vec<InitStep>,
+    /// Supply voltage of the external flash on the board, in millivolts
+    pub supply_voltage: Option<u32>,
+}
+
+impl Board {
+    /// Load the board profile given by path, or else by name from the boards
+    /// directory.
+    pub fn load(name: &str) -> Result<Self> {
+        let path = if Path::new(name).is_file() {
+            PathBuf::from(name)
+        } else {
+            boards_dir().join(format!("{}.toml", name))
+        };
+
+        let text = fs::read_to_string(&path).context(ReadError { path: &path })?;
+        let value: Value = text.parse().context(ParseError { path: &path })?;
+
+        let entries = match value.get("init") {
+            Some(Value::Array(entries)) => entries.as_slice(),
+            Some(_) => InvalidInit {
+                path: &path,
+                index: 0_usize,
+                reason: "expected an array of tables",
+            }
+            .fail()?,
+            None => &[],
+        };
+
+        let mut init = Vec::new();
+        for (index, entry) in entries.iter().enumerate() {
+            let entry = entry.as_table().context(InvalidInit {
+                path: &path,
+                index,
+                reason: "expected a table",
+            })?;
+            let step = init_step(entry).map_err(|reason| {
+                InvalidInit {
+                    path: &path,
+                    index,
+                    reason,
+                }
+                .build()
+            })?;
+            init.push(step);
+        }
+
+        let supply_voltage = match value.get("supply_voltage") {
+            Some(value) => Some(
+                value
+                    .as_integer()
+                    .and_then(|value| u32::try_from(value).ok())
+                    .filter(|value| (1000..=5000).contains(value))
+                    .context(InvalidEntry {
+                        path: &path,
+                        key: "supply_voltage",
+                        reason: "expected millivolts from 1000 to 5000",
+                    })?,
+            ),
+            None => None,
+        };
+
+        Ok(Self {
+            name: name.to_string(),
+            init,
+            supply_voltage,
+        })
+    }
+}
+
+fn init_step(entry: &Table) -> Result<InitStep, String> {
+    let number = |key: &str| -> Result<Option<u32>, String> {
+        match entry.get(key) {
+            Some(value) => value
+                .as_integer()
+                .and_then(|value| u32::try_from(value).ok())
+                .map(Some)
+                .ok_or_else(|| format!("'{}' must be an unsigned 32-bit integer", key)),
+            None => Ok(None),
+        }
+    };
+
+    match (number("address")?, number("value")?, number("delay")?) {
+        (Some(address), Some(value), None) => {
+            if address % 4 != 0 {
+                return Err("'address' must be aligned to a 32-bit word".to_string());
+            }
+            Ok(InitStep::Write {
+                address,
+                value,
+                mask: number("mask")?.unwrap_or(0xFFFF_FFFF),
+            })
+        }
+        (None, None, Some(delay)) => Ok(InitStep::Delay(Duration::from_millis(delay.into()))),
+        _ => Err("expected either 'address' and 'value', or 'delay'".to_string()),
+    }
+}
+
+/// Directory board profiles given by name are looked up in.
+pub fn boards_dir() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(env::temp_dir)
+        .join("flash-rover")
+        .join("boards")
+}
@@ -0,0 +1,117 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+//! Byte order swapping within 16-bit or 32-bit words of the data read from or
+//! written to the external flash, for images exchanged with big-endian tools.
+
+use std::io::{self, Read, Write};
+
+/// Size of the chunks read from the wrapped reader.
+const CHUNK_SIZE: usize = 0x1000;
+
+/// Reverse the byte order within each word of `size` bytes. A trailing
+/// partial word is left untouched.
+pub fn swap(data: &mut [u8], size: usize) {
+    for word in data.chunks_exact_mut(size) {
+        word.reverse();
+    }
+}
+
+/// Reader swapping the byte order within words of the wrapped reader. Fails
+/// if the input ends in the middle of a word.
+pub struct SwapReader<R> {
+    inner: R,
+    size: usize,
+    /// Bytes read from the inner reader, of which the complete words up to
+    /// `ready` have been swapped
+    buf: Vec<u8>,
+    ready: usize,
+    pos: usize,
+}
+
+impl<R: Read> SwapReader<R> {
+    pub fn new(inner: R, size: usize) -> Self {
+        Self {
+            inner,
+            size,
+            buf: Vec::new(),
+            ready: 0,
+            pos: 0,
+        }
+    }
+}
+
+impl<R: Read> Read for SwapReader<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.ready {
+            // Keep the partial word at the end for the next round
+            self.buf.drain(..self.ready);
+            self.pos = 0;
+
+            let filled = self.buf.len();
+            self.buf.resize(filled + CHUNK_SIZE, 0);
+            let read = self.inner.read(&mut self.buf[filled..])?;
+            self.buf.truncate(filled + read);
+
+            if read == 0 {
+                self.ready = 0;
+                if self.buf.is_empty() {
+                    return Ok(0);
+                }
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "Input length is not a multiple of the byte swap size of {} bytes",
+                        self.size
+                    ),
+                ));
+            }
+
+            self.ready = self.buf.len() - self.buf.len() % self.size;
+            swap(&mut self.buf[..self.ready], self.size);
+        }
+
+        let len = out.len().min(self.ready - self.pos);
+        out[..len].copy_from_slice(&self.buf[self.pos..self.pos + len]);
+        self.pos += len;
+
+        Ok(len)
+    }
+}
+
+/// Writer swapping the byte order within words before passing them to the
+/// wrapped writer. A trailing partial word is never written.
+pub struct SwapWriter<W> {
+    inner: W,
+    size: usize,
+    partial: Vec<u8>,
+}
+
+impl<W: Write> SwapWriter<W> {
+    pub fn new(inner: W, size: usize) -> Self {
+        Self {
+            inner,
+            size,
+            partial: Vec::new(),
+        }
+    }
+}
+
+impl<W: Write> Write for SwapWriter<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.partial.extend_from_slice(data);
+
+        let complete = self.partial.len() - self.partial.len() % self.size;
+        swap(&mut self.partial[..complete], self.size);
+        self.inner.write_all(&self.partial[..complete])?;
+        self.partial.drain(..complete);
+
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
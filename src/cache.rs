@@ -0,0 +1,123 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use tempfile::NamedTempFile;
+
+const CACHE_DIR_NAME: &str = "flash-rover";
+
+/// Cached files which have not been used for this long are removed.
+const MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Directory for files generated by flash-rover which can be reused between
+/// runs. Falls back to the temporary directory if the platform has no user
+/// cache directory.
+pub fn cache_dir() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(env::temp_dir)
+        .join(CACHE_DIR_NAME)
+}
+
+/// Path of the cached file of `name` generated from the inputs hashed into
+/// `key`, if it is cached. The file is marked as used.
+pub fn lookup(name: &str, extension: &str, key: &[u8]) -> Option<PathBuf> {
+    let path = entry_path(name, extension, key);
+    if !path.is_file() {
+        return None;
+    }
+
+    // Failing to mark the file as used only gets it removed earlier
+    if let Ok(file) = File::options().write(true).open(&path) {
+        file.set_modified(SystemTime::now()).unwrap_or_default();
+    }
+
+    Some(path)
+}
+
+/// Store `content` generated from the inputs hashed into `key` in the cache,
+/// replacing the files cached for `name` from other inputs and removing the
+/// files which have not been used for a while. Returns the path of the cached
+/// file.
+pub fn store(name: &str, extension: &str, key: &[u8], content: &[u8]) -> io::Result<PathBuf> {
+    let dir = cache_dir();
+    fs::create_dir_all(&dir)?;
+
+    let path = entry_path(name, extension, key);
+    remove_old(&dir, &entry_prefix(name), &path);
+
+    // Write to a temporary file first and move it into place, so that a
+    // concurrent run never observes a partially written file
+    let mut file = NamedTempFile::new_in(&dir)?;
+    file.write_all(content)?;
+    file.persist(&path).map_err(|err| err.error)?;
+
+    Ok(path)
+}
+
+fn entry_path(name: &str, extension: &str, key: &[u8]) -> PathBuf {
+    cache_dir().join(format!(
+        "{}{:016x}.{}",
+        entry_prefix(name),
+        fnv1a(key),
+        extension
+    ))
+}
+
+/// Start of the file names cached for `name`, where characters which are not
+/// safe in file names, e.g. path separators in a probe serial number, are
+/// replaced.
+fn entry_prefix(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+            _ => '_',
+        })
+        .collect();
+    format!("{}.", name)
+}
+
+/// Remove the files starting with `prefix` other than `keep`, and the files
+/// unused for longer than `MAX_AGE`. Files in use by a concurrent run may
+/// fail to be removed on some platforms, and are left for a later run.
+fn remove_old(dir: &Path, prefix: &str, keep: &Path) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path == keep {
+            continue;
+        }
+        let superseded = entry
+            .file_name()
+            .to_str()
+            .is_some_and(|file_name| file_name.starts_with(prefix));
+        let expired = entry
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age > MAX_AGE);
+        if superseded || expired {
+            fs::remove_file(path).unwrap_or_default();
+        }
+    }
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    bytes.iter().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(PRIME)
+    })
+}
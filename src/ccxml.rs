@@ -0,0 +1,275 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+use std::fmt;
+
+use crate::types::{Device, DeviceFamily, Interface};
+
+const XML_VERSION: &str = "1.2";
+const XDS110_ID: &str = "Texas Instruments XDS110 USB Debug Probe";
+const INDENT: &str = "    ";
+
+/// Minimal XML element, sufficient to describe a ccxml target configuration.
+#[derive(Clone, Debug)]
+pub struct Element {
+    name: String,
+    attrs: Vec<(String, String)>,
+    children: Vec<Element>,
+}
+
+impl Element {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            attrs: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    pub fn attr(mut self, key: &str, value: &str) -> Self {
+        self.attrs.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn child(mut self, child: Element) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    pub fn children<I>(mut self, children: I) -> Self
+    where
+        I: IntoIterator<Item = Element>,
+    {
+        self.children.extend(children);
+        self
+    }
+
+    fn write_to(&self, f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+        for _ in 0..depth {
+            f.write_str(INDENT)?;
+        }
+
+        write!(f, "<{}", self.name)?;
+        for (key, value) in &self.attrs {
+            write!(f, " {}=\"{}\"", key, escape(value))?;
+        }
+
+        if self.children.is_empty() {
+            return f.write_str("/>\n");
+        }
+
+        f.write_str(">\n")?;
+        for child in &self.children {
+            child.write_to(f, depth + 1)?;
+        }
+        for _ in 0..depth {
+            f.write_str(INDENT)?;
+        }
+        writeln!(f, "</{}>", self.name)
+    }
+}
+
+impl fmt::Display for Element {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write_to(f, 0)
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.chars().fold(String::new(), |mut escaped, c| {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+        escaped
+    })
+}
+
+fn instance(desc: Option<&str>, href: &str, id: &str, xml: &str, xmlpath: &str) -> Element {
+    let element = Element::new("instance").attr("XML_version", XML_VERSION);
+    let element = match desc {
+        Some(desc) => element.attr("desc", desc),
+        None => element,
+    };
+    element
+        .attr("href", href)
+        .attr("id", id)
+        .attr("xml", xml)
+        .attr("xmlpath", xmlpath)
+}
+
+fn property(kind: &str, value: &str, id: &str) -> Element {
+    Element::new("property")
+        .attr("Type", kind)
+        .attr("Value", value)
+        .attr("id", id)
+}
+
+fn choice(name: &str, value: &str) -> Element {
+    Element::new("choice")
+        .attr("Name", name)
+        .attr("value", value)
+}
+
+fn driver(xml: &str) -> Element {
+    instance(None, &format!("drivers/{}", xml), "drivers", xml, "drivers")
+}
+
+/// Builder for a ccxml target configuration of a XDS110 connected device.
+#[derive(Clone, Debug)]
+pub struct Ccxml {
+    device: Device,
+    serial: String,
+    interface: Interface,
+    jtag_speed: Option<u32>,
+    probe_voltage: Option<String>,
+}
+
+impl Ccxml {
+    pub fn new(device: Device, serial: &str) -> Self {
+        let probe_voltage = DeviceFamily::from(device)
+            .ccxml_probe_voltage()
+            .map(str::to_string);
+
+        Self {
+            device,
+            serial: serial.to_string(),
+            interface: Interface::Cjtag,
+            jtag_speed: None,
+            probe_voltage,
+        }
+    }
+
+    pub fn interface(mut self, interface: Interface) -> Self {
+        self.interface = interface;
+        self
+    }
+
+    /// Fixed JTAG TCLK frequency in kHz, or the XDS110 default if `None`.
+    pub fn jtag_speed(mut self, jtag_speed: Option<u32>) -> Self {
+        self.jtag_speed = jtag_speed;
+        self
+    }
+
+    /// Voltage level in volts supplied by the probe to the target, or target
+    /// supplied power if `None`.
+    pub fn probe_voltage(mut self, probe_voltage: Option<&str>) -> Self {
+        self.probe_voltage = probe_voltage.map(str::to_string);
+        self
+    }
+
+    pub fn build(&self) -> Element {
+        let family = DeviceFamily::from(self.device);
+
+        let connection = Element::new("connection")
+            .attr("XML_version", XML_VERSION)
+            .attr("id", XDS110_ID)
+            .child(driver("tixds510icepick_c.xml"))
+            .child(driver("tixds510cs_dap.xml"))
+            .child(driver(family.ccxml_cpu_driver()))
+            .children(self.power_selection())
+            .child(self.swd_mode())
+            .child(self.tclk_frequency())
+            .child(self.probe_selection())
+            .child(
+                Element::new("platform")
+                    .attr("XML_version", XML_VERSION)
+                    .attr("id", "platform_0")
+                    .child(instance(
+                        Some(self.device.ccxml_desc()),
+                        &format!("devices/{}", self.device.ccxml_xml()),
+                        self.device.ccxml_id(),
+                        self.device.ccxml_xml(),
+                        "devices",
+                    )),
+            );
+
+        let configuration = Element::new("configuration")
+            .attr("XML_version", XML_VERSION)
+            .attr("id", family.ccxml_id())
+            .child(instance(
+                Some(XDS110_ID),
+                "connections/TIXDS110_Connection.xml",
+                XDS110_ID,
+                "TIXDS110_Connection.xml",
+                "connections",
+            ))
+            .child(connection);
+
+        Element::new("configurations")
+            .attr("XML_version", XML_VERSION)
+            .attr("id", "cc13xx_cc26xx")
+            .child(configuration)
+    }
+
+    fn power_selection(&self) -> Vec<Element> {
+        match &self.probe_voltage {
+            Some(voltage) => vec![
+                property("choicelist", "1", "Power Selection").child(
+                    choice("Probe supplied power", "1").child(property(
+                        "stringfield",
+                        voltage,
+                        "Voltage Level",
+                    )),
+                ),
+                property("choicelist", "0", "JTAG Signal Isolation"),
+            ],
+            None => Vec::new(),
+        }
+    }
+
+    fn swd_mode(&self) -> Element {
+        property(
+            "choicelist",
+            self.interface.ccxml_swd_mode(),
+            "SWD Mode Settings",
+        )
+        .child(
+            choice("cJTAG (1149.7) 2-pin advanced modes", "enable").child(property(
+                "choicelist",
+                "1",
+                "XDS110 Aux Port",
+            )),
+        )
+    }
+
+    fn tclk_frequency(&self) -> Element {
+        // TCLK mode 0 is the XDS110 default frequency, mode 1 is a fixed user
+        // specified frequency
+        let (tclk_mode, tclk_frequency) = match self.jtag_speed {
+            Some(khz) => ("1", format!("{}.0kHz", khz)),
+            None => ("0", String::from("1.0MHz")),
+        };
+
+        property("choicelist", tclk_mode, "The JTAG TCLK Frequency (MHz)").child(
+            choice("Fixed with user specified value", "SPECIFIC").child(property(
+                "stringfield",
+                &tclk_frequency,
+                "-- Enter a value from 100.0kHz to 14.0MHz",
+            )),
+        )
+    }
+
+    fn probe_selection(&self) -> Element {
+        property("choicelist", "1", "Debug Probe Selection")
+            .child(choice("Only one XDS110 installed", "0"))
+            .child(choice("Select by serial number", "1").child(property(
+                "stringfield",
+                &self.serial,
+                "-- Enter the serial number",
+            )))
+    }
+}
+
+impl fmt::Display for Ccxml {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>\n")?;
+        write!(f, "{}", self.build())
+    }
+}
@@ -0,0 +1,142 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+//! Integrity hashes of a dump, computed while it streams to the output, and
+//! the CRC algorithms of the checksums compared with the firmware.
+
+use std::io::{self, Write};
+
+use sha2::{Digest, Sha256};
+
+use crate::types::CrcAlgorithm;
+
+/// Reflected polynomial of CRC-32C
+const CRC32C_POLY: u32 = 0x82F6_3B78;
+const CRC16_CCITT_POLY: u16 = 0x1021;
+
+/// CRC of data passed in pieces, computed the same way as by the Checksum
+/// command of the firmware.
+#[derive(Clone)]
+pub struct Crc {
+    algorithm: CrcAlgorithm,
+    crc32: crc32fast::Hasher,
+    /// Register of the bitwise algorithms
+    value: u32,
+}
+
+impl Crc {
+    pub fn new(algorithm: CrcAlgorithm) -> Self {
+        Self {
+            algorithm,
+            crc32: crc32fast::Hasher::new(),
+            value: match algorithm {
+                CrcAlgorithm::Crc32 | CrcAlgorithm::Crc32c => 0xFFFF_FFFF,
+                CrcAlgorithm::Crc16Ccitt => 0xFFFF,
+            },
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match self.algorithm {
+            CrcAlgorithm::Crc32 => self.crc32.update(data),
+            CrcAlgorithm::Crc32c => {
+                for byte in data {
+                    self.value ^= u32::from(*byte);
+                    for _ in 0..8 {
+                        self.value =
+                            (self.value >> 1) ^ (CRC32C_POLY & (self.value & 1).wrapping_neg());
+                    }
+                }
+            }
+            CrcAlgorithm::Crc16Ccitt => {
+                let mut crc = self.value as u16;
+                for byte in data {
+                    crc ^= u16::from(*byte) << 8;
+                    for _ in 0..8 {
+                        crc = if crc & 0x8000 != 0 {
+                            (crc << 1) ^ CRC16_CCITT_POLY
+                        } else {
+                            crc << 1
+                        };
+                    }
+                }
+                self.value = u32::from(crc);
+            }
+        }
+    }
+
+    pub fn finalize(&self) -> u32 {
+        match self.algorithm {
+            CrcAlgorithm::Crc32 => self.crc32.clone().finalize(),
+            CrcAlgorithm::Crc32c => !self.value,
+            CrcAlgorithm::Crc16Ccitt => self.value,
+        }
+    }
+}
+
+/// CRC of the data with the algorithm.
+pub fn crc(algorithm: CrcAlgorithm, data: &[u8]) -> u32 {
+    let mut crc = Crc::new(algorithm);
+    crc.update(data);
+    crc.finalize()
+}
+
+/// Writer computing the CRC and SHA-256 of the data passed through it. The
+/// hashes are printed to stderr once the writer is dropped, so they cover
+/// everything written to the output.
+pub struct ChecksumWriter<W: Write> {
+    inner: W,
+    crc: Crc,
+    algorithm: CrcAlgorithm,
+    sha256: Sha256,
+    length: u64,
+}
+
+impl<W: Write> ChecksumWriter<W> {
+    pub fn new(inner: W, algorithm: CrcAlgorithm) -> Self {
+        Self {
+            inner,
+            crc: Crc::new(algorithm),
+            algorithm,
+            sha256: Sha256::new(),
+            length: 0,
+        }
+    }
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let len = self.inner.write(data)?;
+        self.crc.update(&data[..len]);
+        self.sha256.update(&data[..len]);
+        self.length += len as u64;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Drop for ChecksumWriter<W> {
+    fn drop(&mut self) {
+        self.inner.flush().unwrap_or_default();
+
+        let sha256: String = self
+            .sha256
+            .clone()
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        eprintln!("Length:  {} bytes", self.length);
+        eprintln!(
+            "{:<8} {}",
+            format!("{}:", self.algorithm.label()),
+            self.algorithm.format(self.crc.finalize())
+        );
+        eprintln!("SHA-256: {}", sha256);
+    }
+}
@@ -6,8 +6,17 @@
 use std::cell::RefCell;
 use std::io::{Read, Write};
 use std::path::PathBuf;
+use std::time::Duration;
 
-use crate::types::{Device, SpiPins};
+use crate::board::Board;
+use crate::image::Relocation;
+use crate::manifest::Manifest;
+use crate::partition::PartitionTable;
+use crate::profile::Profile;
+use crate::types::{
+    CrcAlgorithm, Device, InputFormat, Interface, MuxSelect, ProgressFormat, SpiPins,
+};
+use crate::xds110::Power;
 
 pub enum Subcommand {
     Info,
@@ -21,13 +30,306 @@ pub enum Subcommand {
         length: u32,
         output: RefCell<Box<dyn Write>>,
     },
+    /// CRC of a range of the external flash, computed by the firmware
+    Checksum {
+        offset: u32,
+        length: u32,
+    },
+    /// Manifest of the CRC of every partition, as JSON
+    PartitionChecksums {
+        output: RefCell<Box<dyn Write>>,
+    },
     Write {
-        verify: bool,
+        verify: Verify,
+        recheck: Option<Recheck>,
         in_place: bool,
+        /// Unused for address-bearing input formats
         offset: u32,
+        /// Write at the first blank region large enough for the input instead
+        /// of at the offset
+        auto_offset: bool,
         length: Option<u32>,
         input: RefCell<Box<dyn Read>>,
+        format: InputFormat,
+        relocation: Relocation,
+    },
+    /// Detect, bounds check, erase, write and CRC verify in one go
+    Flash {
+        offset: u32,
+        input: RefCell<Box<dyn Read>>,
+    },
+    /// Change a few bytes, only in the bits selected by the mask if any,
+    /// keeping the rest of the touched sectors, and verify them
+    Patch {
+        offset: u32,
+        bytes: Vec<u8>,
+        mask: Option<Vec<u8>>,
+    },
+    /// Write the images and fill regions of a manifest in one session, then
+    /// read back every region and print a verification report
+    Apply {
+        manifest: Manifest,
+        report: Option<PathBuf>,
+    },
+    /// Apply the manifest of a profile to one unit after the other, waiting
+    /// for each unit's probe to be connected
+    Production {
+        profile: Profile,
+    },
+    /// Self test of the attached board on a region of the external flash,
+    /// with a JUnit XML report
+    HilTest {
+        offset: u32,
+        length: u32,
+        report: PathBuf,
+    },
+    /// List the images in the OAD metadata area
+    OadList,
+    /// Access the status register, or the configuration register of Macronix
+    /// parts
+    Status {
+        action: StatusAction,
+        config: bool,
+    },
+    /// Access the internal flash through the debugger, at offsets into the
+    /// given bank
+    Internal {
+        action: InternalAction,
+        bank: u32,
+        offset: u32,
+    },
+    /// Access memory-mapped registers through the debugger, a word at a time
+    Mem {
+        action: MemAction,
+    },
+    Power {
+        power: Power,
+    },
+    Sessions,
+    /// Write the target configuration of the probe, device and connection
+    /// options, for UniFlash
+    ExportUniflash {
+        output: PathBuf,
     },
+    Regs,
+    /// Export the fields of the factory configuration page as JSON, read
+    /// through the debugger
+    FcfgDump {
+        output: RefCell<Box<dyn Write>>,
+    },
+    /// Write MAC address overrides to the CCFG sector through the debugger,
+    /// the addresses given most significant byte first
+    ProvisionMac {
+        ieee: Option<Vec<u8>>,
+        ble: Option<Vec<u8>>,
+    },
+    /// Halt the core, clear the doorbell and re-inject the firmware, for a
+    /// firmware which no longer completes commands
+    Recover,
+    /// Interactive terminal dashboard, running its operations through a
+    /// [`crate::session::Session`]
+    Tui,
+    Ping {
+        count: u32,
+    },
+    Session {
+        action: SessionAction,
+        handle: PathBuf,
+    },
+    /// Serve session requests on the standard input and output, for
+    /// [`crate::remote`]
+    Daemon,
+    /// Forwarded to a `flash-rover-<name>` executable
+    External {
+        name: String,
+        args: Vec<String>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SessionAction {
+    Open,
+    Close,
+    /// Run the background process holding the session
+    Serve,
+}
+
+pub enum InternalAction {
+    Read {
+        length: u32,
+        output: RefCell<Box<dyn Write>>,
+    },
+    Write {
+        input: RefCell<Box<dyn Read>>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MemAction {
+    Read { address: u32, count: u32 },
+    Write { address: u32, value: u32 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StatusAction {
+    Read,
+    Write(u16),
+}
+
+/// Serial port used for data transfers instead of the debug probe, e.g. the
+/// XDS110 backchannel UART.
+#[derive(Clone, Debug)]
+pub struct DataPort {
+    pub path: String,
+    pub baud_rate: u32,
+    /// DIOs of the device UART RX and TX pins, defaults to the LaunchPad pins
+    pub pins: Option<[u8; 2]>,
+}
+
+/// Read back of the written data right after writing it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Verify {
+    Off,
+    /// Compare all of the written data
+    Full,
+    /// Compare the CRC of a random percentage of the written sectors,
+    /// chosen by the seed
+    Sample {
+        percent: u32,
+        seed: u32,
+    },
+}
+
+/// Delayed read back of a part of the written data.
+#[derive(Clone, Copy, Debug)]
+pub struct Recheck {
+    /// Percentage of the written sectors to read back
+    pub percent: u32,
+    pub delay: Duration,
+}
+
+impl Subcommand {
+    /// Whether the subcommand requires the firmware to be injected into the
+    /// device before running.
+    pub fn injects_firmware(&self) -> bool {
+        use Subcommand::*;
+
+        !matches!(
+            self,
+            Power { .. }
+                | Sessions
+                | ExportUniflash { .. }
+                | Regs
+                | FcfgDump { .. }
+                | ProvisionMac { .. }
+                | Recover
+                | Internal { .. }
+                | Mem { .. }
+                | Tui
+                | Production { .. }
+                | HilTest { .. }
+                | Session { .. }
+                | Daemon
+                | External { .. }
+        )
+    }
+
+    /// Name of the operation, as recorded in the history file.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Subcommand::Info => "info",
+            Subcommand::SectorErase { .. } => "erase",
+            Subcommand::MassErase => "mass-erase",
+            Subcommand::Read { .. } => "read",
+            Subcommand::Checksum { .. } | Subcommand::PartitionChecksums { .. } => "checksum",
+            Subcommand::Write { .. } => "write",
+            Subcommand::Flash { .. } => "flash",
+            Subcommand::Patch { .. } => "patch",
+            Subcommand::Apply { .. } => "apply",
+            Subcommand::Production { .. } => "production",
+            Subcommand::HilTest { .. } => "hil-test",
+            Subcommand::OadList => "oad-ls",
+            Subcommand::Status {
+                action: StatusAction::Read,
+                ..
+            } => "status-read",
+            Subcommand::Status {
+                action: StatusAction::Write(_),
+                ..
+            } => "status-write",
+            Subcommand::Internal {
+                action: InternalAction::Read { .. },
+                ..
+            } => "internal-read",
+            Subcommand::Internal {
+                action: InternalAction::Write { .. },
+                ..
+            } => "internal-write",
+            Subcommand::Mem {
+                action: MemAction::Read { .. },
+            } => "mem-read",
+            Subcommand::Mem {
+                action: MemAction::Write { .. },
+            } => "mem-write",
+            Subcommand::Power { power: Power::On } => "power-on",
+            Subcommand::Power { power: Power::Off } => "power-off",
+            Subcommand::Sessions => "sessions",
+            Subcommand::ExportUniflash { .. } => "export-uniflash",
+            Subcommand::Regs => "regs",
+            Subcommand::FcfgDump { .. } => "fcfg-dump",
+            Subcommand::ProvisionMac { .. } => "provision-mac",
+            Subcommand::Recover => "recover",
+            Subcommand::Tui => "tui",
+            Subcommand::Ping { .. } => "ping",
+            Subcommand::Session { .. } => "session",
+            Subcommand::Daemon => "daemon",
+            Subcommand::External { .. } => "external",
+        }
+    }
+}
+
+impl Command {
+    /// Copy of the options, running another subcommand.
+    pub fn with_subcommand(&self, subcommand: Subcommand) -> Self {
+        Self {
+            ccs_path: self.ccs_path.clone(),
+            log_dss: self.log_dss.clone(),
+            xds_id: self.xds_id.clone(),
+            device: self.device,
+            interface: self.interface,
+            jtag_speed: self.jtag_speed,
+            probe_voltage: self.probe_voltage.clone(),
+            spi_pins: self.spi_pins,
+            mux_select: self.mux_select,
+            board: self.board.clone(),
+            firmware_elf: self.firmware_elf.clone(),
+            asset_dir: self.asset_dir.clone(),
+            data_port: self.data_port.clone(),
+            script_timeout: self.script_timeout,
+            max_duration: self.max_duration,
+            reconnect: self.reconnect,
+            cooperative: self.cooperative,
+            power_down: self.power_down,
+            fw_log: self.fw_log,
+            core: self.core.clone(),
+            power_cycle: self.power_cycle,
+            wait_for_probe: self.wait_for_probe,
+            force: self.force,
+            strict: self.strict,
+            partitions: self.partitions.clone(),
+            safe_transport: self.safe_transport,
+            throttle: self.throttle,
+            crc_algorithm: self.crc_algorithm,
+            stats: self.stats,
+            progress_format: self.progress_format,
+            pre_cmd: self.pre_cmd.clone(),
+            post_cmd: self.post_cmd.clone(),
+            history: self.history.clone(),
+            record: self.record.clone(),
+            handle: self.handle.clone(),
+            subcommand,
+        }
+    }
 }
 
 pub struct Command {
@@ -35,6 +337,56 @@ pub struct Command {
     pub log_dss: String,
     pub xds_id: String,
     pub device: Device,
+    pub interface: Interface,
+    pub jtag_speed: Option<u32>,
+    pub probe_voltage: Option<String>,
     pub spi_pins: Option<SpiPins>,
+    pub mux_select: Option<MuxSelect>,
+    /// Board profile, of which the init sequence is run after every reset
+    pub board: Option<Board>,
+    /// ELF executable of the firmware to inject instead of the embedded binary
+    pub firmware_elf: Option<PathBuf>,
+    /// Directory of firmware binaries and ccxml templates overriding the
+    /// embedded ones
+    pub asset_dir: Option<PathBuf>,
+    pub data_port: Option<DataPort>,
+    /// Timeout of DSS calls, where zero disables the timeout
+    pub script_timeout: Option<Duration>,
+    /// Time budget of the whole run, after which the operation is aborted
+    pub max_duration: Option<Duration>,
+    /// Attempts to reconnect when the probe drops off mid-operation
+    pub reconnect: u32,
+    /// Address of the word in the RAM of the application through which access
+    /// to the external flash is arbitrated, instead of resetting the device
+    pub cooperative: Option<u32>,
+    /// Put the external flash in deep power-down at the end of the run
+    pub power_down: bool,
+    /// Print the debug log of the firmware after the operation
+    pub fw_log: bool,
+    pub core: Option<String>,
+    pub power_cycle: bool,
+    /// Wait for the probe to be enumerated before starting, until the timeout
+    /// if any. Without `--xds` the first probe found is used.
+    pub wait_for_probe: Option<Option<Duration>>,
+    pub force: bool,
+    /// Refuse to erase or program external flash parts which are not known
+    pub strict: bool,
+    /// Partitions of the external flash, of which the protected ones are
+    /// checked before erase and write
+    pub partitions: Option<PartitionTable>,
+    pub safe_transport: bool,
+    /// Cap of the transfer speed to and from the external flash, in KB/s
+    pub throttle: Option<u32>,
+    /// Algorithm of the checksums printed and compared
+    pub crc_algorithm: CrcAlgorithm,
+    pub stats: bool,
+    pub progress_format: Option<ProgressFormat>,
+    pub pre_cmd: Option<String>,
+    pub post_cmd: Option<String>,
+    pub history: Option<PathBuf>,
+    /// Trace file the memory accesses and doorbell exchanges are appended to
+    pub record: Option<PathBuf>,
+    /// Handle file of the session to run the operation through
+    pub handle: Option<PathBuf>,
     pub subcommand: Subcommand,
 }
@@ -45,14 +45,10 @@ impl DssLogger {
             .trace_set_console_level(TraceLevel::Off)
             .context(DssError {})?;
 
-        if let Some(file_path) = self
-            .file
-            .as_ref()
-            .map(|file| file.path().to_str())
-            .flatten()
-        {
+        if let Some(file) = &self.file {
+            let file_path = dss::path::to_dss(file.path()).context(DssError {})?;
             script
-                .trace_begin(file_path, DssLogger::STYLESHEET)
+                .trace_begin(&file_path, DssLogger::STYLESHEET)
                 .context(DssError {})?;
             script
                 .trace_set_file_level(self.trace_level)
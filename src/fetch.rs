@@ -0,0 +1,35 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+//! Write input fetched from a URL, e.g. an artifact server, and the digest
+//! for pinning the input.
+
+use std::io::{self, Read};
+use std::time::Duration;
+
+use crate::history;
+
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Whether the input names a URL rather than a file.
+pub fn is_url(input: &str) -> bool {
+    input.starts_with("http://") || input.starts_with("https://")
+}
+
+/// Start fetching the content of `url`.
+pub fn open(url: &str) -> io::Result<Box<dyn Read + Send + Sync>> {
+    let response = ureq::get(url)
+        .timeout(TIMEOUT)
+        .call()
+        .map_err(io::Error::other)?;
+
+    Ok(response.into_reader())
+}
+
+/// Hex encoded SHA-256 digest of an input, for pinning it with
+/// `--input-sha256`.
+pub fn sha256(input: &[u8]) -> String {
+    history::digest(input)
+}
@@ -3,18 +3,29 @@
 // (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
 // notice may not be copied, modified, or distributed except according to those terms.
 
-use std::io::{self, Write};
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::cmp::Ordering;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::thread;
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, Instant, SystemTime};
 
 use snafu::{Backtrace, ResultExt, Snafu};
 use tempfile::TempPath;
 
-use dss::com::ti::debug::engine::scripting::{Memory, Register};
+use dss::com::ti::debug::engine::scripting::Register;
 
 use crate::assets;
-use crate::types::{Device, SpiPin, SpiPins};
-use crate::xflash::Xflash;
+use crate::backend::Backend;
+use crate::interrupt;
+use crate::progress::{Phase, Progress};
+use crate::temp;
+use crate::trace::Recorder;
+use crate::types::{CrcAlgorithm, Device, DeviceFamily, MuxSelect, SpiPin, SpiPins};
+use crate::xflash::{JedecId, Timing, Xflash};
 
 #[derive(Debug, Snafu)]
 pub enum Error {
@@ -37,8 +48,90 @@ pub enum Error {
     },
     #[snafu(display("An error response received from firmware with value: {}", kind))]
     ErrorResponse { kind: u32, backtrace: Backtrace },
+    #[snafu(display(
+        "Transfer between the tool and the firmware was corrupted {} times in a row, check the \
+         debug probe connection",
+        TRANSPORT_ATTEMPTS
+    ))]
+    CorruptTransfer { backtrace: Backtrace },
+    #[snafu(display("Operation aborted"))]
+    Aborted { backtrace: Backtrace },
     #[snafu(display("Tool timed out waiting for a response from firmware"))]
     FirmwareTimeout { backtrace: Backtrace },
+    #[snafu(display(
+        "Firmware region 0x{:08X}..0x{:08X} ({}) does not fit in the device SRAM 0x{:08X}..0x{:08X}",
+        start,
+        end,
+        region,
+        SRAM_START,
+        sram_end
+    ))]
+    InvalidMemoryRegion {
+        region: &'static str,
+        start: u32,
+        end: u32,
+        sram_end: u32,
+        backtrace: Backtrace,
+    },
+    #[snafu(display(
+        "Firmware region 0x{:08X}..0x{:08X} ({}) is not RAM of the connected device, which \
+         refused access to 0x{:08X}",
+        start,
+        end,
+        region,
+        address
+    ))]
+    InaccessibleMemoryRegion {
+        region: &'static str,
+        start: u32,
+        end: u32,
+        address: u32,
+        backtrace: Backtrace,
+    },
+    #[snafu(display(
+        "Firmware stub of {} bytes does not fit in the internal flash region 0x{:08X}..0x{:08X}",
+        binary_len,
+        start,
+        end
+    ))]
+    InvalidFlashStubRegion {
+        start: u32,
+        end: u32,
+        binary_len: u32,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("Firmware {} overlaps the firmware {}", first, second))]
+    OverlappingMemoryRegions {
+        first: &'static str,
+        second: &'static str,
+        backtrace: Backtrace,
+    },
+    #[snafu(display(
+        "Firmware protocol version {} with transfer buffers of {} bytes is not supported by this \
+         version of flash-rover",
+        version,
+        buf_size
+    ))]
+    UnsupportedFirmware {
+        version: u32,
+        buf_size: u32,
+        backtrace: Backtrace,
+    },
+    #[snafu(display(
+        "The firmware does not support command 0x{:02X}, it predates the firmware descriptor. \
+         Rebuild the firmware from the sources",
+        kind
+    ))]
+    UnsupportedCommand { kind: u32, backtrace: Backtrace },
+    #[snafu(display("Data port error: {}", source))]
+    DataPort {
+        source: io::Error,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("Unexpected byte 0x{:02X} received from the data port", byte))]
+    InvalidCredit { byte: u8, backtrace: Backtrace },
+    #[snafu(display("{}", source))]
+    Asset { source: assets::Error },
     #[snafu(display("Unable to create the firmware binary asset: {}", source))]
     FirmwareAsset {
         source: io::Error,
@@ -48,16 +141,130 @@ pub enum Error {
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
+impl Error {
+    /// Stable identifier of the error, for automation.
+    pub(crate) fn code(&self) -> &'static str {
+        match self {
+            Error::DssError { .. } => "dss_error",
+            Error::NoResponse { .. } => "firmware_no_response",
+            Error::InvalidResponse { .. } | Error::BadResponse { .. } => {
+                "firmware_invalid_response"
+            }
+            Error::ErrorResponse { .. } => "firmware_error_response",
+            Error::CorruptTransfer { .. } => "corrupt_transfer",
+            Error::Aborted { .. } => "aborted",
+            Error::FirmwareTimeout { .. } => "firmware_timeout",
+            Error::InvalidMemoryRegion { .. }
+            | Error::InaccessibleMemoryRegion { .. }
+            | Error::InvalidFlashStubRegion { .. }
+            | Error::OverlappingMemoryRegions { .. }
+            | Error::UnsupportedFirmware { .. } => "firmware_layout",
+            Error::UnsupportedCommand { .. } => "unsupported_command",
+            Error::DataPort { .. } | Error::InvalidCredit { .. } => "data_port_error",
+            Error::Asset {
+                source: assets::Error::AssetCorrupted { .. },
+            } => "firmware_asset_corrupted",
+            Error::Asset { .. } | Error::FirmwareAsset { .. } => "firmware_asset",
+        }
+    }
+
+    /// Whether a chunk of a transfer failed in a way a flaky connection to
+    /// the external flash or the probe causes, which smaller chunks may get
+    /// through.
+    pub(crate) fn is_transfer_failure(&self) -> bool {
+        matches!(
+            self,
+            Error::CorruptTransfer { .. } | Error::ErrorResponse { .. } | Error::BadResponse { .. }
+        )
+    }
+}
+
 #[derive(Debug)]
 enum Command {
     GetXflashInfo,
-    SectorErase { offset: u32, length: u32 },
+    SectorErase {
+        offset: u32,
+        length: u32,
+    },
     MassErase,
-    ReadBlock { offset: u32, length: u32 },
-    WriteBlock { offset: u32, length: u32 },
+    ReadBlock {
+        offset: u32,
+        length: u32,
+        buffer: u32,
+    },
+    WriteBlock {
+        offset: u32,
+        length: u32,
+        buffer: u32,
+        /// CRC of the buffer data, with safe transport
+        crc: Option<u32>,
+    },
+    Echo {
+        nonce: u32,
+    },
+    Abort,
+    ReadStatus,
+    UartConfig {
+        rx: u32,
+        tx: u32,
+        baud_rate: u32,
+    },
+    UartRead {
+        offset: u32,
+        length: u32,
+    },
+    UartWrite {
+        offset: u32,
+        length: u32,
+    },
+    BlankCheck {
+        offset: u32,
+        length: u32,
+    },
+    ReadConfig,
+    WriteStatus {
+        status: u8,
+        config: Option<u16>,
+    },
+    Release,
+    PowerDown,
+    Checksum {
+        offset: u32,
+        length: u32,
+        algorithm: u32,
+    },
 }
 
 impl Command {
+    /// Whether the command is part of the original protocol, which is all
+    /// that firmware without a descriptor knows. Other commands are cleared
+    /// by such firmware without a response.
+    fn is_original(&self) -> bool {
+        use Command::*;
+
+        matches!(
+            self,
+            GetXflashInfo | SectorErase { .. } | MassErase | ReadBlock { .. } | WriteBlock { .. }
+        )
+    }
+
+    /// Whether the firmware can be requested to stop the command while it is
+    /// in progress.
+    fn is_abortable(&self) -> bool {
+        use Command::*;
+
+        matches!(
+            self,
+            SectorErase { .. }
+                | MassErase
+                | WriteBlock { .. }
+                | UartRead { .. }
+                | UartWrite { .. }
+                | BlankCheck { .. }
+                | Checksum { .. }
+        )
+    }
+
     fn to_bytes(&self) -> [u32; 4] {
         use Command::*;
 
@@ -65,8 +272,55 @@ impl Command {
             GetXflashInfo => [0xC0_u32.to_le(), 0, 0, 0],
             SectorErase { offset, length } => [0xC1_u32.to_le(), offset.to_le(), length.to_le(), 0],
             MassErase => [0xC2_u32.to_le(), 0, 0, 0],
-            ReadBlock { offset, length } => [0xC3_u32.to_le(), offset.to_le(), length.to_le(), 0],
-            WriteBlock { offset, length } => [0xC4_u32.to_le(), offset.to_le(), length.to_le(), 0],
+            ReadBlock {
+                offset,
+                length,
+                buffer,
+            } => [
+                0xC3_u32.to_le(),
+                offset.to_le(),
+                length.to_le(),
+                buffer.to_le(),
+            ],
+            WriteBlock {
+                offset,
+                length,
+                buffer,
+                ..
+            } => [
+                0xC4_u32.to_le(),
+                offset.to_le(),
+                length.to_le(),
+                buffer.to_le(),
+            ],
+            Echo { nonce } => [0xC5_u32.to_le(), nonce.to_le(), 0, 0],
+            Abort => [0xC6_u32.to_le(), 0, 0, 0],
+            ReadStatus => [0xC7_u32.to_le(), 0, 0, 0],
+            UartConfig { rx, tx, baud_rate } => {
+                [0xC8_u32.to_le(), rx.to_le(), tx.to_le(), baud_rate.to_le()]
+            }
+            UartRead { offset, length } => [0xC9_u32.to_le(), offset.to_le(), length.to_le(), 0],
+            UartWrite { offset, length } => [0xCA_u32.to_le(), offset.to_le(), length.to_le(), 0],
+            BlankCheck { offset, length } => [0xCB_u32.to_le(), offset.to_le(), length.to_le(), 0],
+            ReadConfig => [0xCC_u32.to_le(), 0, 0, 0],
+            WriteStatus { status, config } => [
+                0xCD_u32.to_le(),
+                u32::from(*status).to_le(),
+                u32::from(config.unwrap_or(0)).to_le(),
+                (config.is_some() as u32).to_le(),
+            ],
+            Release => [0xCE_u32.to_le(), 0, 0, 0],
+            PowerDown => [0xCF_u32.to_le(), 0, 0, 0],
+            Checksum {
+                offset,
+                length,
+                algorithm,
+            } => [
+                0xB0_u32.to_le(),
+                offset.to_le(),
+                length.to_le(),
+                algorithm.to_le(),
+            ],
         }
     }
 }
@@ -74,17 +328,68 @@ impl Command {
 #[derive(Debug)]
 pub enum Response {
     Ok,
-    XflashInfo(Xflash),
+    XflashInfo {
+        xflash: Xflash,
+        /// The firmware found the external flash in the XIP mode left by the
+        /// application, and took it over
+        took_over_xip: bool,
+    },
+    Echo(u32),
+    Status(u8),
+    /// CRC of the data read into the buffer, with safe transport, or of the
+    /// range of a checksum
+    Crc(u32),
+    /// Offset of the first non-blank byte of the checked range, or the end of
+    /// the range if it is blank
+    Blank(u32),
+    /// Configuration register of Macronix parts
+    Config(u16),
 }
 
 impl Response {
     fn from_bytes(bytes: &[u32; 4]) -> Result<Self> {
         const OK_VAL: u32 = 0xD0_u32.to_le();
         const XFLASHINFO_VAL: u32 = 0xD1_u32.to_le();
+        const ECHO_VAL: u32 = 0xD2_u32.to_le();
+        const STATUS_VAL: u32 = 0xD3_u32.to_le();
+        const CRC_VAL: u32 = 0xD4_u32.to_le();
+        const BLANK_VAL: u32 = 0xD5_u32.to_le();
+        const CONFIG_VAL: u32 = 0xD6_u32.to_le();
+        const ERROR_ABORTED_VAL: u32 = 0x84_u32.to_le();
+        const FLAG_SIGNATURE_ONLY: u32 = 0x1;
+        const FLAG_TOOK_OVER_XIP: u32 = 0x2;
+        const FLAG_JEDEC_ID: u32 = 0x4;
+        const ERROR_CRC_VAL: u32 = 0x85_u32.to_le();
 
         let rsp = match bytes {
             [OK_VAL, 0, 0, 0] => Response::Ok,
-            [XFLASHINFO_VAL, mid, did, 0] => Response::XflashInfo(Xflash::from_id(*mid, *did)),
+            [XFLASHINFO_VAL, mid, did, flags] => {
+                let mid = u32::from_le(*mid);
+                let did = u32::from_le(*did);
+                let flags = u32::from_le(*flags);
+                // Bit 31..8 of the manufacturer ID word hold the JEDEC ID
+                let jedec = (flags & FLAG_JEDEC_ID != 0).then_some(JedecId {
+                    bank: (flags >> 8) as u8,
+                    manufacturer: (mid >> 8) as u8,
+                    memory_type: (mid >> 16) as u8,
+                    capacity: (mid >> 24) as u8,
+                });
+                Response::XflashInfo {
+                    xflash: if flags & FLAG_SIGNATURE_ONLY != 0 {
+                        Xflash::from_signature(did & 0xFF)
+                    } else {
+                        Xflash::from_id(mid & 0xFF, did & 0xFF, jedec)
+                    },
+                    took_over_xip: flags & FLAG_TOOK_OVER_XIP != 0,
+                }
+            }
+            [ECHO_VAL, nonce, 0, 0] => Response::Echo(u32::from_le(*nonce)),
+            [STATUS_VAL, status, 0, 0] => Response::Status(u32::from_le(*status) as u8),
+            [CRC_VAL, crc, 0, 0] => Response::Crc(u32::from_le(*crc)),
+            [BLANK_VAL, offset, 0, 0] => Response::Blank(u32::from_le(*offset)),
+            [CONFIG_VAL, config, 0, 0] => Response::Config(u32::from_le(*config) as u16),
+            [ERROR_ABORTED_VAL, ..] => Aborted {}.fail()?,
+            [ERROR_CRC_VAL, ..] => CorruptTransfer {}.fail()?,
             _ => InvalidResponse { bytes: *bytes }.fail()?,
         };
         Ok(rsp)
@@ -92,63 +397,714 @@ impl Response {
 }
 
 const SRAM_START: u32 = 0x2000_0000;
-const STACK_ADDR: u32 = SRAM_START;
-const RESET_ISR: u32 = SRAM_START + 0x04;
 
-const CONF_START: u32 = 0x2000_3000;
+const STACK_ADDR: u32 = 0x00;
+const RESET_ISR: u32 = 0x04;
+
+const CONF_VALID: u32 = 0x00;
+const CONF_SPI_MISO: u32 = 0x04;
+const CONF_SPI_MOSI: u32 = 0x08;
+const CONF_SPI_CLK: u32 = 0x0C;
+const CONF_SPI_CSN: u32 = 0x10;
+const CONF_SAFE_TRANSPORT: u32 = 0x14;
+const CONF_MUX_DIO: u32 = 0x18;
+const CONF_MUX_LEVEL: u32 = 0x1C;
+const CONF_SIZE: u32 = 0x20;
+
+/// DIO value of an unused mux select, `IOID_UNUSED` of driverlib
+const IOID_UNUSED: u32 = 0xFFFF_FFFF;
+
+/// Descriptor of the shared sections exported by the firmware, at a fixed
+/// offset from the configuration
+const DESCRIPTOR_OFFSET: u32 = 0x80;
+const DESCRIPTOR_MAGIC: u32 = 0x00;
+const DESCRIPTOR_VERSION: u32 = 0x04;
+const DESCRIPTOR_DOORBELL: u32 = 0x08;
+const DESCRIPTOR_BUF: u32 = 0x0C;
+const DESCRIPTOR_BUF_SIZE: u32 = 0x10;
+const DESCRIPTOR_BUF_COUNT: u32 = 0x14;
+const DESCRIPTOR_LOG_MAGIC: u32 = 0x18;
+const DESCRIPTOR_LOG: u32 = 0x1C;
+const DESCRIPTOR_LOG_SIZE: u32 = 0x20;
+const DESCRIPTOR_RELEASE_MAGIC: u32 = 0x24;
+const DESCRIPTOR_RELEASE: u32 = 0x28;
+
+/// "FLRV" in little endian
+const FIRMWARE_MAGIC: u32 = 0x5652_4C46;
+const PROTOCOL_VERSION: u32 = 1;
+/// "FLOG" in little endian, exported by firmware with a debug log
+const LOG_MAGIC: u32 = 0x474F_4C46;
+/// "FREL" in little endian, exported by firmware which reports its release
+const RELEASE_MAGIC: u32 = 0x4C45_5246;
+
+/// Count of the bytes ever written to the debug log ring, followed by its data
+const LOG_HEAD: u32 = 0x00;
+const LOG_DATA: u32 = 0x04;
+
+const DOORBELL_CMD_KIND: u32 = 0x00;
+const DOORBELL_CMD_ARG0: u32 = 0x04;
+const DOORBELL_CMD_ARG1: u32 = 0x08;
+const DOORBELL_CMD_ARG2: u32 = 0x0C;
+
+const DOORBELL_RSP_KIND: u32 = 0x10;
+const DOORBELL_RSP_VAL0: u32 = 0x14;
+const DOORBELL_RSP_VAL1: u32 = 0x18;
+const DOORBELL_RSP_VAL2: u32 = 0x1C;
+const DOORBELL_CMD_CRC: u32 = 0x20;
+const DOORBELL_RSP_CRC: u32 = 0x24;
+const DOORBELL_DATA_CRC: u32 = 0x28;
+const DOORBELL_SIZE: u32 = 0x2C;
+
+pub const BUF_SIZE: u32 = 0x1000;
+/// Smallest chunk size a failed transfer is retried with, a page of the
+/// external flash.
+const MIN_CHUNK_SIZE: u32 = 0x100;
+
+const DWELL_TIME: Duration = Duration::from_millis(100);
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(3);
+/// Page program size assumed for parts which are not known
+const DEFAULT_PAGE_SIZE: u32 = 256;
+/// Attempts of a corrupted transfer with safe transport, including the first
+const TRANSPORT_ATTEMPTS: u32 = 3;
+
+/// CRC of the words of a command or response, as computed by the firmware.
+fn words_crc(words: &[u32; 4]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    for word in words {
+        hasher.update(&u32::from_le(*word).to_le_bytes());
+    }
+    hasher.finalize()
+}
+
+/// Addresses of the firmware image and the sections shared between the tool
+/// and the firmware.
+#[derive(Clone, Copy, Debug)]
+struct Layout {
+    /// Start of the image, with the vector table at its start
+    image: u32,
+    conf: u32,
+    doorbell: u32,
+    buf: u32,
+}
+
+/// Firmware running from SRAM, the default. The doorbell and buffer addresses
+/// are replaced by those of the firmware descriptor, if exported.
+const SRAM_LAYOUT: Layout = Layout {
+    image: SRAM_START,
+    conf: 0x2000_3000,
+    doorbell: 0x2000_3100,
+    buf: 0x2000_4000,
+};
+
+impl Layout {
+    /// Firmware stub running from internal flash, for devices where the SRAM
+    /// is too small for the firmware and the data buffer. Only the shared
+    /// sections are kept in SRAM.
+    fn flash_stub(family: DeviceFamily) -> Self {
+        Self {
+            image: family.flash_stub_region().0,
+            conf: SRAM_START,
+            doorbell: SRAM_START + 0x100,
+            buf: SRAM_START + 0x1000,
+        }
+    }
 
-const CONF_VALID: u32 = CONF_START;
-const CONF_SPI_MISO: u32 = CONF_START + 0x04;
-const CONF_SPI_MOSI: u32 = CONF_START + 0x08;
-const CONF_SPI_CLK: u32 = CONF_START + 0x0C;
-const CONF_SPI_CSN: u32 = CONF_START + 0x10;
+    fn is_flash_stub(&self) -> bool {
+        self.image < SRAM_START
+    }
+}
 
-const DOORBELL_START: u32 = 0x2000_3100;
+/// Tunes the size of the chunks transferred through the data buffer, from the
+/// throughput measured on the first chunks of the session. The overhead of a
+/// chunk is dominated by the polling of the doorbell, but smaller chunks
+/// overlap better when pipelined.
+#[derive(Debug, Default)]
+struct ChunkTuner {
+    /// Measured durations of the chunks per candidate size
+    samples: Vec<Duration>,
+    chosen: Option<u32>,
+}
 
-const DOORBELL_CMD_KIND: u32 = DOORBELL_START;
-const DOORBELL_CMD_ARG0: u32 = DOORBELL_START + 0x04;
-const DOORBELL_CMD_ARG1: u32 = DOORBELL_START + 0x08;
-const DOORBELL_CMD_ARG2: u32 = DOORBELL_START + 0x0C;
+impl ChunkTuner {
+    const CANDIDATES: [u32; 3] = [BUF_SIZE / 4, BUF_SIZE / 2, BUF_SIZE];
+    /// The first chunk of each candidate is discarded, as it is skewed by the
+    /// size of the previous chunk when pipelined
+    const SAMPLES_PER_CANDIDATE: usize = 3;
 
-const DOORBELL_RSP_KIND: u32 = DOORBELL_START + 0x10;
-const DOORBELL_RSP_VAL0: u32 = DOORBELL_START + 0x14;
-const DOORBELL_RSP_VAL1: u32 = DOORBELL_START + 0x18;
-const DOORBELL_RSP_VAL2: u32 = DOORBELL_START + 0x1C;
+    fn chunk_size(&self) -> u32 {
+        self.chosen
+            .unwrap_or_else(|| Self::CANDIDATES[self.samples.len() / Self::SAMPLES_PER_CANDIDATE])
+    }
 
-const BUF_START: u32 = 0x2000_4000;
-pub const BUF_SIZE: u32 = 0x1000;
+    fn record(&mut self, size: u32, elapsed: Duration) {
+        // Partial chunks at the end of a transfer are not representative
+        if self.chosen.is_some() || size != self.chunk_size() {
+            return;
+        }
+
+        self.samples.push(elapsed);
+
+        if self.samples.len() == Self::CANDIDATES.len() * Self::SAMPLES_PER_CANDIDATE {
+            let throughputs = self
+                .samples
+                .chunks(Self::SAMPLES_PER_CANDIDATE)
+                .zip(Self::CANDIDATES.iter())
+                .map(|(samples, size)| {
+                    let elapsed: Duration = samples.iter().skip(1).sum();
+                    let bytes = size * (Self::SAMPLES_PER_CANDIDATE as u32 - 1);
+                    (
+                        bytes as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+                        *size,
+                    )
+                });
+            self.chosen = throughputs
+                .max_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal))
+                .map(|(_, size)| size);
+        }
+    }
+}
+
+/// Counters of the traffic between the tool and the firmware.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Counters {
+    pub commands: u32,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    /// Transfers repeated because they were corrupted
+    pub retries: u32,
+}
+
+/// Ring buffer of the debug messages of the firmware.
+#[derive(Clone, Copy, Debug)]
+struct LogRing {
+    address: u32,
+    size: u32,
+}
+
+/// Messages logged by the firmware since the log was last drained.
+#[derive(Clone, Debug, Default)]
+pub struct FirmwareLog {
+    pub text: String,
+    /// Bytes overwritten in the ring before they were drained
+    pub lost: u32,
+}
+
+/// State of a halted application overwritten by the firmware.
+struct ApplicationBackup {
+    sram: Vec<u8>,
+    registers: Vec<(Register, u32)>,
+}
+
+/// Image of the firmware injected into the device.
+enum Image {
+    /// Embedded raw binary, loaded at the start of the layout
+    Raw(TempPath),
+    /// ELF executable loaded with its symbols, for debugging the firmware
+    Program(PathBuf),
+}
 
 pub struct Firmware<'a> {
-    memory: Memory<'a>,
-    binary: TempPath,
+    backend: Rc<dyn Backend + 'a>,
+    family: DeviceFamily,
+    image: Image,
+    /// Serial number of the probe, which temporary files are created for
+    xds_id: String,
+    layout: Cell<Layout>,
+    /// Set once the regions of the layout have been checked against the
+    /// memory of the connected device
+    checked_target_memory: Cell<bool>,
+    /// Original content of the internal flash region holding the firmware
+    /// stub, to be restored after the operation
+    flash_backup: RefCell<Option<Vec<u8>>>,
+    /// SRAM and core registers of the application, which is resumed after
+    /// the operation in cooperative mode
+    application_backup: RefCell<Option<ApplicationBackup>>,
+    /// Number of transfer buffers, as reported by the firmware descriptor.
+    /// Firmware without a descriptor only has the first buffer and ignores
+    /// the buffer index of commands
+    buf_count: Cell<u32>,
+    /// Set if the loaded firmware has a descriptor, which firmware supporting
+    /// the commands beyond the original protocol has
+    has_descriptor: Cell<bool>,
+    /// Release of flash-rover the loaded firmware is built from, as major,
+    /// minor and patch in bit 23..16, 15..8 and 7..0, if it reports one
+    release: Cell<Option<u32>>,
+    read_tuner: RefCell<ChunkTuner>,
+    write_tuner: RefCell<ChunkTuner>,
+    /// Upper limit of the chunk size, lowered after failed transfers and
+    /// kept across re-injections
+    chunk_limit: Cell<u32>,
+    counters: Cell<Counters>,
+    progress: RefCell<Option<Progress>>,
+    safe_transport: Cell<bool>,
+    /// Cap of the transfer speed, in bytes per second
+    throttle: Cell<Option<u32>>,
+    /// Set once the firmware has taken the external flash over from XIP
+    /// mode, which is kept across re-injections
+    took_over_xip: Cell<bool>,
+    /// External flash identified by the firmware, of which the erase and
+    /// program times give the command timeouts
+    xflash: Cell<Option<Xflash>>,
+    /// Debug log ring of the loaded firmware, if it has one, and the count of
+    /// bytes written to it which have been drained
+    log: Cell<Option<LogRing>>,
+    log_tail: Cell<u32>,
+    recorder: RefCell<Option<Rc<Recorder>>>,
 }
 
 impl<'a> Firmware<'a> {
-    pub fn new(memory: Memory<'a>, device: Device) -> Result<Firmware<'a>> {
-        let binary = Firmware::create_firmware_binary(device)?;
+    /// Firmware of the device, either the embedded binary or the ELF
+    /// executable `program` if given. The embedded binary is taken from
+    /// `asset_dir` instead if it has one. Temporary files are created in the
+    /// directory of the probe `xds_id`.
+    pub fn new(
+        backend: Rc<dyn Backend + 'a>,
+        device: Device,
+        xds_id: &str,
+        program: Option<&Path>,
+        asset_dir: Option<&Path>,
+    ) -> Result<Firmware<'a>> {
+        let family = DeviceFamily::from(device);
+
+        // The layout of an executable is given by its linker script, which is
+        // the one of the embedded binary
+        let (image, layout) = match program {
+            Some(program) => (Image::Program(program.into()), SRAM_LAYOUT),
+            None => Firmware::embedded_image(device, xds_id, asset_dir)?,
+        };
+
+        Ok(Self {
+            backend,
+            family,
+            image,
+            xds_id: xds_id.to_string(),
+            layout: Cell::new(layout),
+            checked_target_memory: Cell::new(false),
+            flash_backup: RefCell::new(None),
+            application_backup: RefCell::new(None),
+            buf_count: Cell::new(1),
+            has_descriptor: Cell::new(false),
+            release: Cell::new(None),
+            read_tuner: RefCell::new(ChunkTuner::default()),
+            write_tuner: RefCell::new(ChunkTuner::default()),
+            chunk_limit: Cell::new(BUF_SIZE),
+            counters: Cell::new(Counters::default()),
+            progress: RefCell::new(None),
+            safe_transport: Cell::new(false),
+            throttle: Cell::new(None),
+            took_over_xip: Cell::new(false),
+            xflash: Cell::new(None),
+            log: Cell::new(None),
+            log_tail: Cell::new(0),
+            recorder: RefCell::new(None),
+        })
+    }
+
+    fn embedded_image(
+        device: Device,
+        xds_id: &str,
+        asset_dir: Option<&Path>,
+    ) -> Result<(Image, Layout)> {
+        let family = DeviceFamily::from(device);
+
+        let asset = assets::get_firmware(device, asset_dir).context(Asset {})?;
+        let binary = Firmware::create_firmware_binary(asset, xds_id)?;
+        let binary_len = fs::metadata(&binary).context(FirmwareAsset {})?.len() as u32;
+
+        let (binary, layout) = match Firmware::check_memory_layout(family, &SRAM_LAYOUT, binary_len)
+        {
+            Ok(()) => (binary, SRAM_LAYOUT),
+            // Fall back to running a stub from internal flash, if one is
+            // available for the device
+            Err(err) => match assets::get_firmware_stub(device, asset_dir).context(Asset {})? {
+                Some(stub) => {
+                    let binary = Firmware::create_firmware_binary(stub, xds_id)?;
+                    let binary_len = fs::metadata(&binary).context(FirmwareAsset {})?.len() as u32;
+                    let layout = Layout::flash_stub(family);
+                    Firmware::check_memory_layout(family, &layout, binary_len)?;
+                    (binary, layout)
+                }
+                None => return Err(err),
+            },
+        };
+
+        Ok((Image::Raw(binary), layout))
+    }
+
+    /// Report the progress of erase, read and write operations.
+    pub fn set_progress(&self, progress: Progress) {
+        self.progress.replace(Some(progress));
+    }
+
+    /// Protect the doorbell and the transfer buffers with a CRC, retrying
+    /// corrupted transfers. Takes effect on the next [`Firmware::inject`].
+    pub fn set_safe_transport(&self, enabled: bool) {
+        self.safe_transport.set(enabled);
+    }
+
+    /// Cap the speed of reads and writes through the transfer buffers, in
+    /// KB/s.
+    pub fn set_throttle(&self, kbps: Option<u32>) {
+        self.throttle
+            .set(kbps.map(|kbps| kbps.saturating_mul(1024)));
+    }
+
+    /// Record every memory access and doorbell exchange in the trace.
+    pub fn set_recorder(&self, recorder: Rc<Recorder>) {
+        self.recorder.replace(Some(recorder));
+    }
+
+    fn trace(&self, f: impl FnOnce(&Recorder)) {
+        if let Some(recorder) = self.recorder.borrow().as_deref() {
+            f(recorder);
+        }
+    }
+
+    fn report_progress(&self, phase: Phase, done: u32, total: u32) {
+        if let Some(progress) = self.progress.borrow_mut().as_mut() {
+            progress.update(phase, done, total);
+        }
+    }
+
+    pub fn counters(&self) -> Counters {
+        self.counters.get()
+    }
+
+    fn update_counters(&self, f: impl FnOnce(&mut Counters)) {
+        let mut counters = self.counters.get();
+        f(&mut counters);
+        self.counters.set(counters);
+    }
+
+    /// Sanity check that the firmware image, configuration, doorbell and data
+    /// buffer all fall within the memory of the device.
+    fn check_memory_layout(family: DeviceFamily, layout: &Layout, binary_len: u32) -> Result<()> {
+        let sram_end = SRAM_START + family.sram_size();
+
+        let mut regions = vec![
+            ("configuration", layout.conf, layout.conf + CONF_SIZE),
+            ("doorbell", layout.doorbell, layout.doorbell + DOORBELL_SIZE),
+            (
+                "data buffer",
+                layout.buf,
+                layout.buf + family.firmware_buf_count() * BUF_SIZE,
+            ),
+        ];
+        if !layout.is_flash_stub() {
+            regions.insert(
+                0,
+                ("firmware image", layout.image, layout.image + binary_len),
+            );
+        }
+
+        for (region, start, end) in regions.iter().copied() {
+            ensure!(
+                start >= SRAM_START && end <= sram_end,
+                InvalidMemoryRegion {
+                    region,
+                    start,
+                    end,
+                    sram_end,
+                }
+            );
+        }
+
+        if layout.is_flash_stub() {
+            let (start, end) = family.flash_stub_region();
+            ensure!(
+                binary_len <= end - start,
+                InvalidFlashStubRegion {
+                    start,
+                    end,
+                    binary_len,
+                }
+            );
+        } else {
+            // The firmware image contains the configuration and doorbell
+            // sections, but must not extend into the data buffer
+            ensure!(
+                layout.image + binary_len <= layout.buf,
+                OverlappingMemoryRegions {
+                    first: "firmware image",
+                    second: "data buffer",
+                }
+            );
+        }
 
-        Ok(Self { memory, binary })
+        Ok(())
     }
 
-    pub fn inject(&self, spi_pins: Option<SpiPins>) -> Result<()> {
-        let binary_path = self.binary.to_string_lossy().to_owned();
+    /// Check that the firmware image, configuration, doorbell and data buffer
+    /// are RAM of the connected device, as reported by the debugger, which
+    /// catches a device with less SRAM than its family, e.g. a wrong --device.
+    /// The first and the last word of every region are probed.
+    fn check_target_memory(&self) -> Result<()> {
+        if self.checked_target_memory.get() {
+            return Ok(());
+        }
+
+        let layout = self.layout.get();
+        let mut regions = vec![
+            ("configuration", layout.conf, layout.conf + CONF_SIZE),
+            ("doorbell", layout.doorbell, layout.doorbell + DOORBELL_SIZE),
+            (
+                "data buffer",
+                layout.buf,
+                layout.buf + self.buf_count.get() * BUF_SIZE,
+            ),
+        ];
+        // The extent of an executable is given by its program headers, which
+        // are checked by DSS when loading it, and a firmware stub runs from
+        // internal flash
+        if let (Image::Raw(binary), false) = (&self.image, layout.is_flash_stub()) {
+            let binary_len = fs::metadata(binary).context(FirmwareAsset {})?.len() as u32;
+            regions.insert(
+                0,
+                ("firmware image", layout.image, layout.image + binary_len),
+            );
+        }
+
+        for (region, start, end) in regions {
+            for address in [start & !3, (end - 4) & !3] {
+                ensure!(
+                    self.dss_is_ram(address)?,
+                    InaccessibleMemoryRegion {
+                        region,
+                        start,
+                        end,
+                        address,
+                    }
+                );
+            }
+        }
 
-        self.dss_load_raw(&binary_path)?;
+        self.checked_target_memory.set(true);
+
+        Ok(())
+    }
+
+    pub fn inject(&self, spi_pins: Option<SpiPins>, mux_select: Option<MuxSelect>) -> Result<()> {
+        self.check_target_memory()?;
+        if self.layout.get().is_flash_stub() && self.flash_backup.borrow().is_none() {
+            let (start, end) = self.family.flash_stub_region();
+            let backup = self.dss_read_datas(start, end - start)?;
+            self.flash_backup.replace(Some(backup));
+        }
+
+        match &self.image {
+            Image::Raw(binary) => self.dss_load_raw(self.layout.get().image, binary)?,
+            Image::Program(program) => self.dss_load_program(program)?,
+        }
+        self.read_descriptor()?;
 
         if let Some(spi_pins) = spi_pins {
-            self.dss_write_data(CONF_VALID, 1)?;
-            self.dss_write_data(CONF_SPI_MISO, spi_pins[SpiPin::Miso] as _)?;
-            self.dss_write_data(CONF_SPI_MOSI, spi_pins[SpiPin::Mosi] as _)?;
-            self.dss_write_data(CONF_SPI_CLK, spi_pins[SpiPin::Clk] as _)?;
-            self.dss_write_data(CONF_SPI_CSN, spi_pins[SpiPin::Csn] as _)?;
+            let conf = self.layout.get().conf;
+            self.dss_write_data(conf + CONF_VALID, 1)?;
+            self.dss_write_data(conf + CONF_SPI_MISO, spi_pins[SpiPin::Miso] as _)?;
+            self.dss_write_data(conf + CONF_SPI_MOSI, spi_pins[SpiPin::Mosi] as _)?;
+            self.dss_write_data(conf + CONF_SPI_CLK, spi_pins[SpiPin::Clk] as _)?;
+            self.dss_write_data(conf + CONF_SPI_CSN, spi_pins[SpiPin::Csn] as _)?;
+        }
+        self.dss_write_data(
+            self.layout.get().conf + CONF_SAFE_TRANSPORT,
+            self.safe_transport.get() as _,
+        )?;
+        let (mux_dio, mux_level) = match mux_select {
+            Some(mux_select) => (u32::from(mux_select.dio), mux_select.level as u32),
+            None => (IOID_UNUSED, 0),
+        };
+        self.dss_write_data(self.layout.get().conf + CONF_MUX_DIO, mux_dio)?;
+        self.dss_write_data(self.layout.get().conf + CONF_MUX_LEVEL, mux_level)?;
+
+        // The loader of an executable sets the entry point, while a raw
+        // binary is started from its vector table
+        if let Image::Raw(_) = self.image {
+            let stack_addr = self.dss_read_data(self.layout.get().image + STACK_ADDR)?;
+            let reset_isr = self.dss_read_data(self.layout.get().image + RESET_ISR)?;
+
+            self.dss_write_register(Register::MSP, stack_addr)?;
+            self.dss_write_register(Register::PC, reset_isr)?;
+            self.dss_write_register(Register::LR, 0xFFFF_FFFF)?;
+        }
+
+        Ok(())
+    }
+
+    /// Take the addresses of the doorbell and the transfer buffers from the
+    /// descriptor exported by the loaded firmware. Firmware predating the
+    /// descriptor keeps the built-in layout.
+    fn read_descriptor(&self) -> Result<()> {
+        let mut layout = self.layout.get();
+        let descriptor = layout.conf + DESCRIPTOR_OFFSET;
+
+        if self.dss_read_data(descriptor + DESCRIPTOR_MAGIC)? != FIRMWARE_MAGIC {
+            return Ok(());
+        }
+
+        let version = self.dss_read_data(descriptor + DESCRIPTOR_VERSION)?;
+        let buf_size = self.dss_read_data(descriptor + DESCRIPTOR_BUF_SIZE)?;
+        let buf_count = self.dss_read_data(descriptor + DESCRIPTOR_BUF_COUNT)?;
+        ensure!(
+            version == PROTOCOL_VERSION && buf_size == BUF_SIZE && buf_count > 0,
+            UnsupportedFirmware { version, buf_size }
+        );
+
+        layout.doorbell = self.dss_read_data(descriptor + DESCRIPTOR_DOORBELL)?;
+        layout.buf = self.dss_read_data(descriptor + DESCRIPTOR_BUF)?;
+
+        // Firmware predating the debug log has no log magic
+        let log = if self.dss_read_data(descriptor + DESCRIPTOR_LOG_MAGIC)? == LOG_MAGIC {
+            Some(LogRing {
+                address: self.dss_read_data(descriptor + DESCRIPTOR_LOG)?,
+                size: self.dss_read_data(descriptor + DESCRIPTOR_LOG_SIZE)?,
+            })
+        } else {
+            None
+        };
+
+        // Firmware predating the release has no release magic
+        let release_magic = self.dss_read_data(descriptor + DESCRIPTOR_RELEASE_MAGIC)?;
+        let release = if release_magic == RELEASE_MAGIC {
+            Some(self.dss_read_data(descriptor + DESCRIPTOR_RELEASE)?)
+        } else {
+            None
+        };
+
+        let sram_end = SRAM_START + self.family.sram_size();
+        let mut regions = vec![
+            ("doorbell", layout.doorbell, layout.doorbell + DOORBELL_SIZE),
+            ("data buffer", layout.buf, layout.buf + buf_count * BUF_SIZE),
+        ];
+        if let Some(log) = log {
+            regions.push(("debug log", log.address, log.address + LOG_DATA + log.size));
         }
+        for (region, start, end) in regions {
+            ensure!(
+                start >= SRAM_START && end <= sram_end,
+                InvalidMemoryRegion {
+                    region,
+                    start,
+                    end,
+                    sram_end,
+                }
+            );
+        }
+
+        self.layout.set(layout);
+        self.buf_count.set(buf_count);
+        self.has_descriptor.set(true);
+        self.release.set(release);
+        // The ring starts out empty with each injection
+        self.log.set(log.filter(|log| log.size > 0));
+        self.log_tail.set(0);
+
+        Ok(())
+    }
+
+    /// Whether the loaded firmware has a debug log.
+    pub fn has_log(&self) -> bool {
+        self.log.get().is_some()
+    }
+
+    /// Read the messages the firmware logged since the log was last drained,
+    /// if it has a debug log.
+    pub fn drain_log(&self) -> Result<Option<FirmwareLog>> {
+        let log = match self.log.get() {
+            Some(log) => log,
+            None => return Ok(None),
+        };
+
+        let head = self.dss_read_data(log.address + LOG_HEAD)?;
+        let pending = head.wrapping_sub(self.log_tail.get());
+        let lost = pending.saturating_sub(log.size);
+        let length = pending - lost;
+
+        // The pending bytes wrap around the end of the ring at most once
+        let start = head.wrapping_sub(length) % log.size;
+        let first_length = length.min(log.size - start);
+        let mut bytes = Vec::with_capacity(length as _);
+        if first_length > 0 {
+            bytes.extend(self.dss_read_datas(log.address + LOG_DATA + start, first_length)?);
+        }
+        if length > first_length {
+            bytes.extend(self.dss_read_datas(log.address + LOG_DATA, length - first_length)?);
+        }
+        self.log_tail.set(head);
 
-        let stack_addr = self.dss_read_data(STACK_ADDR)?;
-        let reset_isr = self.dss_read_data(RESET_ISR)?;
+        Ok(Some(FirmwareLog {
+            text: String::from_utf8_lossy(&bytes).into_owned(),
+            lost,
+        }))
+    }
 
-        self.dss_write_register(Register::MSP, stack_addr)?;
-        self.dss_write_register(Register::PC, reset_isr)?;
-        self.dss_write_register(Register::LR, 0xFFFF_FFFF)?;
+    /// Clear the command and the response of the doorbell, e.g. left behind by
+    /// a hung firmware.
+    pub fn clear_doorbell(&self) -> Result<()> {
+        let doorbell = self.layout.get().doorbell;
+        for offset in (0..DOORBELL_SIZE).step_by(4) {
+            self.dss_write_data(doorbell + offset, 0)?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether the internal flash holds the firmware stub and must be
+    /// restored with [`Firmware::restore`].
+    pub fn needs_restore(&self) -> bool {
+        self.flash_backup.borrow().is_some()
+    }
+
+    /// Restore the original content of the internal flash overwritten by the
+    /// firmware stub. The target must be halted.
+    pub fn restore(&self) -> Result<()> {
+        let backup = match self.flash_backup.borrow_mut().take() {
+            Some(backup) => backup,
+            None => return Ok(()),
+        };
+
+        let mut file = temp::create(&self.xds_id, "backup.", ".bin").context(FirmwareAsset {})?;
+        file.write_all(&backup).context(FirmwareAsset {})?;
+        let path = file.into_temp_path();
+
+        let (start, _) = self.family.flash_stub_region();
+        self.dss_load_raw(start, &path)
+    }
+
+    /// Save the SRAM and the core registers of the halted application, which
+    /// the firmware overwrites, for [`Firmware::restore_application`]. Only
+    /// the first call saves, later ones would save the firmware.
+    pub fn save_application(&self) -> Result<()> {
+        if self.application_backup.borrow().is_some() {
+            return Ok(());
+        }
+
+        let sram = self.dss_read_datas(SRAM_START, self.family.sram_size())?;
+        let registers = Register::ALL
+            .iter()
+            .map(|register| Ok((*register, self.dss_read_register(*register)?)))
+            .collect::<Result<_>>()?;
+        self.application_backup
+            .replace(Some(ApplicationBackup { sram, registers }));
+
+        Ok(())
+    }
+
+    /// Restore the application saved by [`Firmware::save_application`], for
+    /// it to resume where it was halted. The target must be halted.
+    pub fn restore_application(&self) -> Result<()> {
+        let backup = match self.application_backup.borrow_mut().take() {
+            Some(backup) => backup,
+            None => return Ok(()),
+        };
+
+        let mut file = temp::create(&self.xds_id, "sram.", ".bin").context(FirmwareAsset {})?;
+        file.write_all(&backup.sram).context(FirmwareAsset {})?;
+        let path = file.into_temp_path();
+        self.dss_load_raw(SRAM_START, &path)?;
+
+        for (register, value) in backup.registers {
+            self.dss_write_register(register, value)?;
+        }
 
         Ok(())
     }
@@ -156,29 +1112,162 @@ impl<'a> Firmware<'a> {
     pub fn get_xflash_info(&self) -> Result<Xflash> {
         let command = Command::GetXflashInfo;
         match self.send_command(command, None)? {
-            Response::XflashInfo(xflash) => Ok(xflash),
+            Response::XflashInfo {
+                xflash,
+                took_over_xip,
+            } => {
+                if took_over_xip {
+                    self.took_over_xip.set(true);
+                }
+                self.xflash.set(Some(xflash));
+                Ok(xflash)
+            }
+            response => BadResponse { response }.fail(),
+        }
+    }
+
+    /// External flash last identified by the firmware, if any.
+    pub fn xflash(&self) -> Option<Xflash> {
+        self.xflash.get()
+    }
+
+    /// Whether the external flash was taken over from the XIP mode left by
+    /// the application, and is to be released before the device is reset.
+    pub fn took_over_xip(&self) -> bool {
+        self.took_over_xip.get()
+    }
+
+    /// Hand the external flash back to the application in its power-on state.
+    pub fn release_xflash(&self) -> Result<()> {
+        let command = Command::Release;
+        match self.send_command(command, None)? {
+            Response::Ok => Ok(()),
+            response => BadResponse { response }.fail(),
+        }
+    }
+
+    /// Put the external flash in deep power-down.
+    pub fn power_down_xflash(&self) -> Result<()> {
+        let command = Command::PowerDown;
+        match self.send_command(command, None)? {
+            Response::Ok => Ok(()),
+            response => BadResponse { response }.fail(),
+        }
+    }
+
+    /// Whether the loaded firmware supports the commands beyond the original
+    /// protocol, e.g. reading the status register.
+    pub fn has_descriptor(&self) -> bool {
+        self.has_descriptor.get()
+    }
+
+    /// Release of flash-rover the loaded firmware is built from, e.g.
+    /// `0.3.3`, if it reports one.
+    pub fn release(&self) -> Option<String> {
+        self.release.get().map(|release| {
+            format!(
+                "{}.{}.{}",
+                (release >> 16) & 0xFF,
+                (release >> 8) & 0xFF,
+                release & 0xFF
+            )
+        })
+    }
+
+    /// Read the status register of the external flash.
+    pub fn read_status(&self) -> Result<u8> {
+        let command = Command::ReadStatus;
+        match self.send_command(command, None)? {
+            Response::Status(status) => Ok(status),
+            response => BadResponse { response }.fail(),
+        }
+    }
+
+    /// Read the configuration register of Macronix parts.
+    pub fn read_config(&self) -> Result<u16> {
+        let command = Command::ReadConfig;
+        match self.send_command(command, None)? {
+            Response::Config(config) => Ok(config),
+            response => BadResponse { response }.fail(),
+        }
+    }
+
+    /// Write the status register, along with the configuration register of
+    /// Macronix parts if given.
+    pub fn write_status(&self, status: u8, config: Option<u16>) -> Result<()> {
+        let command = Command::WriteStatus { status, config };
+        match self.send_command(command, None)? {
+            Response::Ok => Ok(()),
+            response => BadResponse { response }.fail(),
+        }
+    }
+
+    /// Send a nonce to the firmware and wait for it to be echoed back, in
+    /// order to check that the doorbell protocol is alive.
+    pub fn echo(&self, nonce: u32) -> Result<()> {
+        let command = Command::Echo { nonce };
+        match self.send_command(command, None)? {
+            Response::Echo(echoed) if echoed == nonce => Ok(()),
             response => BadResponse { response }.fail(),
         }
     }
 
     pub fn sector_erase(&self, offset: u32, length: u32) -> Result<()> {
-        // Plus one for margin, as the write range can touch two sectors: one at
-        // the beginnning and one at the end
-        let num_sectors = length / BUF_SIZE + 1;
-        let timeout = num_sectors * Duration::from_millis(500);
+        // The range may start and end within a sector, which is erased whole
+        let first = u64::from(offset) / u64::from(BUF_SIZE);
+        let end = (u64::from(offset) + u64::from(length)).div_ceil(u64::from(BUF_SIZE));
+        let num_sectors = (end - first) as u32;
+        let timeout = DEFAULT_TIMEOUT + num_sectors * self.timing().sector_erase.max;
+
+        self.report_progress(Phase::Erase, 0, length);
 
         let command = Command::SectorErase { offset, length };
         match self.send_command(command, Some(timeout))? {
-            Response::Ok => Ok(()),
+            Response::Ok => {}
+            response => BadResponse { response }.fail()?,
+        }
+
+        self.report_progress(Phase::Erase, length, length);
+
+        Ok(())
+    }
+
+    /// Check that the range is blank, i.e. erased, without transferring it.
+    /// Returns the offset of the first non-blank byte, if any.
+    pub fn blank_check(&self, offset: u32, length: u32) -> Result<Option<u32>> {
+        let num_blocks = length / BUF_SIZE + 1;
+        let timeout = DEFAULT_TIMEOUT + num_blocks * Duration::from_millis(10);
+
+        let command = Command::BlankCheck { offset, length };
+        match self.send_command(command, Some(timeout))? {
+            Response::Blank(end) if end >= offset.saturating_add(length) => Ok(None),
+            Response::Blank(dirty) => Ok(Some(dirty)),
+            response => BadResponse { response }.fail(),
+        }
+    }
+
+    /// CRC of the range with the algorithm, computed by the firmware without
+    /// transferring the range.
+    pub fn checksum(&self, offset: u32, length: u32, algorithm: CrcAlgorithm) -> Result<u32> {
+        let num_blocks = length / BUF_SIZE + 1;
+        let timeout = DEFAULT_TIMEOUT + num_blocks * Duration::from_millis(50);
+
+        let command = Command::Checksum {
+            offset,
+            length,
+            algorithm: algorithm.id(),
+        };
+        match self.send_command(command, Some(timeout))? {
+            Response::Crc(crc) => Ok(crc),
             response => BadResponse { response }.fail(),
         }
     }
 
     pub fn mass_erase(&self) -> Result<()> {
-        const TIMEOUT: Duration = Duration::from_secs(240);
+        let timeout = DEFAULT_TIMEOUT + self.timing().chip_erase.max;
 
         let command = Command::MassErase;
-        match self.send_command(command, Some(TIMEOUT))? {
+        match self.send_command(command, Some(timeout))? {
             Response::Ok => {}
             response => BadResponse { response }.fail()?,
         }
@@ -186,84 +1275,410 @@ impl<'a> Firmware<'a> {
         Ok(())
     }
 
-    pub fn read_data(&self, offset: u32, length: u32) -> Result<Vec<u8>> {
-        if length == 0 {
-            return Ok(Vec::new());
+    /// Read `length` bytes from `offset` into `data`, continuing after the
+    /// bytes already in `data`, e.g. from a read which failed part way.
+    pub fn read_data_into(&self, offset: u32, length: u32, data: &mut Vec<u8>) -> Result<()> {
+        let total = length;
+        let done = std::cmp::min(data.len() as u32, total);
+        if done == total {
+            return Ok(());
         }
 
-        let mut data = Vec::with_capacity(length as _);
-
-        let mut offset = offset;
-        let mut length = length;
+        // With more than one buffer, the firmware reads the next chunk while
+        // the previous one is transferred
+        let pipelined = self.buf_count.get() > 1;
 
-        // let mut zero_vec = Vec::with_capacity(BUF_SIZE as _);
-        // zero_vec.resize_with(BUF_SIZE as _, || 0);
+        let mut offset = offset + done;
+        let mut length = total - done;
+        let mut index = 0;
+        let mut pending = None;
+        let transfer_start = Instant::now();
 
         while length > 0 {
-            let ilength = std::cmp::min(length, BUF_SIZE as _);
+            ensure!(!interrupt::is_interrupted(), Aborted {});
 
-            // self.dss_write_datas(BUF_START, &zero_vec)?;
+            let start = Instant::now();
+            let ilength = std::cmp::min(length, self.chunk_size(&self.read_tuner));
+            let buffer = index % self.buf_count.get();
 
+            let completed = self.wait_read_block(pending.take())?;
             let command = Command::ReadBlock {
                 offset,
                 length: ilength,
+                buffer,
             };
-            match self.send_command(command, None)? {
-                Response::Ok => {}
-                response => BadResponse { response }.fail()?,
+            if pipelined {
+                self.post_command(&command, None)?;
+                if let Err(err) = self.drain_read_block(completed, data) {
+                    // Leave no command in flight, for the read to be resumed
+                    self.wait_read_block(Some((command, buffer, ilength))).ok();
+                    return Err(err);
+                }
+            } else {
+                self.drain_read_block(completed, data)?;
+                self.post_command(&command, None)?;
             }
-
-            let values = self.dss_read_datas(BUF_START, ilength)?;
-            data.extend_from_slice(&values);
+            pending = Some((command, buffer, ilength));
 
             offset += ilength;
             length -= ilength;
+            index += 1;
+
+            self.read_tuner
+                .borrow_mut()
+                .record(ilength, start.elapsed());
+
+            self.report_progress(Phase::Read, data.len() as u32, total);
+            self.throttle(transfer_start, total - done - length);
         }
 
-        Ok(data)
+        let completed = self.wait_read_block(pending)?;
+        self.drain_read_block(completed, data)?;
+
+        self.report_progress(Phase::Read, total, total);
+
+        Ok(())
     }
 
-    pub fn write_data(&self, offset: u32, values: &[u8]) -> Result<()> {
-        if values.is_empty() {
+    /// Wait for a pending read command, returning the buffer and length of
+    /// the data read, and its CRC with safe transport.
+    fn wait_read_block(
+        &self,
+        pending: Option<(Command, u32, u32)>,
+    ) -> Result<Option<(u32, u32, Option<u32>)>> {
+        match pending {
+            Some((command, buffer, length)) => {
+                match self.wait_response_retry(&command, None, || Ok(()))? {
+                    Response::Ok => Ok(Some((buffer, length, None))),
+                    Response::Crc(crc) => Ok(Some((buffer, length, Some(crc)))),
+                    response => BadResponse { response }.fail(),
+                }
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn drain_read_block(
+        &self,
+        completed: Option<(u32, u32, Option<u32>)>,
+        data: &mut Vec<u8>,
+    ) -> Result<()> {
+        if let Some((buffer, length, crc)) = completed {
+            let mut attempt = 1;
+            let values = loop {
+                let values = self.dss_read_datas(self.buf_address(buffer), length)?;
+                self.update_counters(|counters| counters.bytes_read += u64::from(length));
+                match crc {
+                    // The buffer is left untouched by the firmware until it is
+                    // drained, read it again
+                    Some(crc) if crc32fast::hash(&values) != crc => {
+                        ensure!(attempt < TRANSPORT_ATTEMPTS, CorruptTransfer {});
+                        attempt += 1;
+                        self.update_counters(|counters| counters.retries += 1);
+                    }
+                    _ => break values,
+                }
+            };
+            data.extend_from_slice(&values);
+        }
+
+        Ok(())
+    }
+
+    /// Write `values` at `offset`, continuing after the `written` bytes, which
+    /// is updated as the firmware acknowledges the chunks, so that a write
+    /// which failed part way is resumed from the last acknowledged chunk.
+    pub fn write_data_from(&self, offset: u32, values: &[u8], written: &mut u32) -> Result<()> {
+        let total = values.len() as u32;
+        *written = std::cmp::min(*written, total);
+        if *written == total {
             return Ok(());
         }
 
-        let mut offset = offset;
+        // With more than one buffer, the next chunk is transferred while the
+        // firmware programs the previous one
+        let pipelined = self.buf_count.get() > 1;
 
-        for chunk in values.chunks(BUF_SIZE as _) {
-            self.dss_write_datas(BUF_START, chunk)?;
+        let mut offset = offset + *written;
+        let mut remaining = &values[*written as usize..];
+        let resumed = remaining.len();
+        let mut index = 0;
+        let mut pending = None;
+        let transfer_start = Instant::now();
+
+        while !remaining.is_empty() {
+            ensure!(!interrupt::is_interrupted(), Aborted {});
+
+            let start = Instant::now();
+            let size = std::cmp::min(self.chunk_size(&self.write_tuner) as usize, remaining.len());
+            let (chunk, rest) = remaining.split_at(size);
+
+            if !pipelined {
+                self.wait_write_block(pending.take(), written)?;
+            }
+
+            let buffer = index % self.buf_count.get();
+            self.fill_write_block(buffer, chunk)?;
+
+            self.wait_write_block(pending.take(), written)?;
 
             let command = Command::WriteBlock {
                 offset,
                 length: chunk.len() as _,
+                buffer,
+                crc: self.safe_transport.get().then(|| crc32fast::hash(chunk)),
             };
-            match self.send_command(command, None)? {
-                Response::Ok => {}
-                response => BadResponse { response }.fail()?,
-            }
+            self.post_command(&command, None)?;
+            pending = Some((command, chunk));
 
             offset += chunk.len() as u32;
+            remaining = rest;
+            index += 1;
+
+            self.write_tuner
+                .borrow_mut()
+                .record(chunk.len() as u32, start.elapsed());
+
+            // Bytes transferred to the device, of which the last chunk is
+            // still being programmed
+            self.report_progress(Phase::Write, (values.len() - remaining.len()) as u32, total);
+            self.throttle(transfer_start, (resumed - remaining.len()) as u32);
         }
 
+        self.wait_write_block(pending, written)?;
+
+        self.report_progress(Phase::Write, total, total);
+
         Ok(())
     }
 
+    /// Configure the UART of the device used as data port by
+    /// [`Firmware::read_data_uart`] and [`Firmware::write_data_uart`].
+    pub fn uart_config(&self, rx: u8, tx: u8, baud_rate: u32) -> Result<()> {
+        let command = Command::UartConfig {
+            rx: rx as _,
+            tx: tx as _,
+            baud_rate,
+        };
+        match self.send_command(command, None)? {
+            Response::Ok => Ok(()),
+            response => BadResponse { response }.fail(),
+        }
+    }
+
+    /// Read from the external flash, with the data streamed over the data
+    /// port instead of the transfer buffers.
+    pub fn read_data_uart(&self, port: &mut dyn Read, offset: u32, length: u32) -> Result<Vec<u8>> {
+        if length == 0 {
+            return Ok(Vec::new());
+        }
+
+        let command = Command::UartRead { offset, length };
+        self.post_command(&command, None)?;
+
+        let mut data = vec![0; length as usize];
+        let mut done = 0;
+        for chunk in data.chunks_mut(BUF_SIZE as _) {
+            // The abort is sent while waiting for the response
+            if interrupt::is_interrupted() {
+                break;
+            }
+
+            port.read_exact(chunk).context(DataPort {})?;
+            done += chunk.len() as u32;
+
+            self.update_counters(|counters| counters.bytes_read += chunk.len() as u64);
+            self.report_progress(Phase::Read, done, length);
+        }
+
+        match self.wait_response(&command, None)? {
+            Response::Ok => Ok(data),
+            response => BadResponse { response }.fail(),
+        }
+    }
+
+    /// Write to the external flash, with the data streamed over the data port
+    /// instead of the transfer buffers. Each block is sent once the firmware
+    /// has signaled that it is ready to receive it.
+    pub fn write_data_uart<P>(&self, port: &mut P, offset: u32, values: &[u8]) -> Result<()>
+    where
+        P: Read + Write + ?Sized,
+    {
+        const CREDIT: u8 = 0x06;
+
+        if values.is_empty() {
+            return Ok(());
+        }
+
+        let total = values.len() as u32;
+        let command = Command::UartWrite {
+            offset,
+            length: total,
+        };
+        self.post_command(&command, None)?;
+
+        let mut done = 0;
+        for chunk in values.chunks(BUF_SIZE as _) {
+            // The abort is sent while waiting for the response
+            if interrupt::is_interrupted() {
+                break;
+            }
+
+            let mut credit = [0; 1];
+            port.read_exact(&mut credit).context(DataPort {})?;
+            ensure!(credit[0] == CREDIT, InvalidCredit { byte: credit[0] });
+
+            port.write_all(chunk).context(DataPort {})?;
+            done += chunk.len() as u32;
+
+            self.update_counters(|counters| counters.bytes_written += chunk.len() as u64);
+            self.report_progress(Phase::Write, done, total);
+        }
+
+        match self.wait_response(&command, None)? {
+            Response::Ok => Ok(()),
+            response => BadResponse { response }.fail(),
+        }
+    }
+
+    /// Pause while the `bytes` transferred since `start` are ahead of the
+    /// throttle, if any.
+    fn throttle(&self, start: Instant, bytes: u32) {
+        if let Some(rate) = self.throttle.get() {
+            let due = Duration::from_secs_f64(f64::from(bytes) / f64::from(rate));
+            if let Some(wait) = due.checked_sub(start.elapsed()) {
+                thread::sleep(wait);
+            }
+        }
+    }
+
+    /// Size of the next chunk of a transfer, as tuned but at most the limit.
+    fn chunk_size(&self, tuner: &RefCell<ChunkTuner>) -> u32 {
+        std::cmp::min(tuner.borrow().chunk_size(), self.chunk_limit.get())
+    }
+
+    /// Halve the chunk size for the rest of the session, for a transfer to be
+    /// resumed after a failed chunk. Returns the new size, or `None` if the
+    /// size is already the smallest.
+    pub fn shrink_chunk_size(&self) -> Option<u32> {
+        let limit = self.chunk_limit.get();
+        if limit <= MIN_CHUNK_SIZE {
+            return None;
+        }
+
+        let limit = std::cmp::max(limit / 2, MIN_CHUNK_SIZE);
+        self.chunk_limit.set(limit);
+        Some(limit)
+    }
+
+    fn fill_write_block(&self, buffer: u32, chunk: &[u8]) -> Result<()> {
+        self.dss_write_datas(self.buf_address(buffer), chunk)?;
+        self.update_counters(|counters| counters.bytes_written += chunk.len() as u64);
+        Ok(())
+    }
+
+    /// Wait for a pending write command, transferring its data again if it
+    /// was corrupted.
+    fn wait_write_block(&self, pending: Option<(Command, &[u8])>, written: &mut u32) -> Result<()> {
+        if let Some((command, chunk)) = pending {
+            let refill = || match command {
+                Command::WriteBlock { buffer, .. } => self.fill_write_block(buffer, chunk),
+                _ => Ok(()),
+            };
+            let timeout = self.program_timeout(chunk.len() as u32);
+            match self.wait_response_retry(&command, Some(timeout), refill)? {
+                Response::Ok => *written += chunk.len() as u32,
+                response => BadResponse { response }.fail()?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Erase and program times of the external flash, or conservative times
+    /// until it is identified.
+    fn timing(&self) -> Timing {
+        self.xflash
+            .get()
+            .map_or(Timing::UNKNOWN, |xflash| xflash.timing())
+    }
+
+    /// Timeout of programming `length` bytes, page by page.
+    fn program_timeout(&self, length: u32) -> Duration {
+        let page_size = self
+            .xflash
+            .get()
+            .and_then(|xflash| xflash.page_size())
+            .unwrap_or(DEFAULT_PAGE_SIZE);
+        let num_pages = length.div_ceil(page_size);
+        DEFAULT_TIMEOUT + num_pages * self.timing().page_program.max
+    }
+
+    fn buf_address(&self, buffer: u32) -> u32 {
+        self.layout.get().buf + buffer * BUF_SIZE
+    }
+
     fn send_command(&self, command: Command, timeout: Option<Duration>) -> Result<Response> {
+        self.post_command(&command, timeout)?;
+        self.wait_response_retry(&command, timeout, || Ok(()))
+    }
+
+    /// Wait for the response to a posted command, posting the command again
+    /// if the transfer was corrupted. `resend` restores the data the command
+    /// operates on before it is posted again.
+    fn wait_response_retry(
+        &self,
+        command: &Command,
+        timeout: Option<Duration>,
+        resend: impl Fn() -> Result<()>,
+    ) -> Result<Response> {
+        let mut attempt = 1;
+        loop {
+            match self.wait_response(command, timeout) {
+                Err(Error::CorruptTransfer { .. }) if attempt < TRANSPORT_ATTEMPTS => {
+                    attempt += 1;
+                    self.update_counters(|counters| counters.retries += 1);
+                    resend()?;
+                    self.post_command(command, timeout)?;
+                }
+                res => return res,
+            }
+        }
+    }
+
+    /// Ring the doorbell with a command and wait until the firmware has picked
+    /// it up, without waiting for the response.
+    fn post_command(&self, command: &Command, timeout: Option<Duration>) -> Result<()> {
         let bytes = command.to_bytes();
+        // Firmware predating the descriptor neither knows the new commands
+        // nor protects any command with a CRC
+        ensure!(
+            self.has_descriptor.get() || (command.is_original() && !self.safe_transport.get()),
+            UnsupportedCommand {
+                kind: u32::from_le(bytes[0])
+            }
+        );
+        let doorbell = self.layout.get().doorbell;
 
-        self.dss_write_data(DOORBELL_CMD_ARG2, bytes[3])?;
-        self.dss_write_data(DOORBELL_CMD_ARG1, bytes[2])?;
-        self.dss_write_data(DOORBELL_CMD_ARG0, bytes[1])?;
-        self.dss_write_data(DOORBELL_CMD_KIND, bytes[0])?;
+        self.update_counters(|counters| counters.commands += 1);
 
-        const DWELL_TIME: Duration = Duration::from_millis(100);
-        const DEFAULT_TIMEOUT: Duration = Duration::from_secs(3);
+        if self.safe_transport.get() {
+            if let Command::WriteBlock { crc: Some(crc), .. } = command {
+                self.dss_write_data(doorbell + DOORBELL_DATA_CRC, crc.to_le())?;
+            }
+            self.dss_write_data(doorbell + DOORBELL_CMD_CRC, words_crc(&bytes).to_le())?;
+        }
+        self.dss_write_data(doorbell + DOORBELL_CMD_ARG2, bytes[3])?;
+        self.dss_write_data(doorbell + DOORBELL_CMD_ARG1, bytes[2])?;
+        self.dss_write_data(doorbell + DOORBELL_CMD_ARG0, bytes[1])?;
+        self.dss_write_data(doorbell + DOORBELL_CMD_KIND, bytes[0])?;
+        self.trace(|recorder| recorder.note("CMD", format_args!("{:?}", command)));
 
         let timeout = timeout.unwrap_or(DEFAULT_TIMEOUT);
 
         let sys_time = SystemTime::now();
 
-        while self.dss_read_data(DOORBELL_CMD_KIND)? != 0
+        while self.dss_read_data(doorbell + DOORBELL_CMD_KIND)? != 0
             && sys_time.elapsed().unwrap_or_default() < timeout
         {
             thread::sleep(DWELL_TIME);
@@ -273,11 +1688,26 @@ impl<'a> Firmware<'a> {
             return FirmwareTimeout {}.fail();
         }
 
+        Ok(())
+    }
+
+    /// Wait for the response to a posted command.
+    fn wait_response(&self, command: &Command, timeout: Option<Duration>) -> Result<Response> {
+        let doorbell = self.layout.get().doorbell;
+        let timeout = timeout.unwrap_or(DEFAULT_TIMEOUT);
+
         let sys_time = SystemTime::now();
+        let mut abort_sent = false;
 
-        while self.dss_read_data(DOORBELL_RSP_KIND)? == 0
+        while self.dss_read_data(doorbell + DOORBELL_RSP_KIND)? == 0
             && sys_time.elapsed().unwrap_or_default() < timeout
         {
+            // Request the firmware to stop cleanly, it responds with an
+            // aborted error
+            if command.is_abortable() && !abort_sent && interrupt::is_interrupted() {
+                self.dss_write_data(doorbell + DOORBELL_CMD_KIND, Command::Abort.to_bytes()[0])?;
+                abort_sent = true;
+            }
             thread::sleep(DWELL_TIME);
         }
 
@@ -285,74 +1715,118 @@ impl<'a> Firmware<'a> {
             return FirmwareTimeout {}.fail();
         }
 
-        let bytes: [u32; 4] = [
-            self.dss_read_data(DOORBELL_RSP_KIND)?,
-            self.dss_read_data(DOORBELL_RSP_VAL0)?,
-            self.dss_read_data(DOORBELL_RSP_VAL1)?,
-            self.dss_read_data(DOORBELL_RSP_VAL2)?,
-        ];
+        let mut attempt = 1;
+        let bytes = loop {
+            let bytes: [u32; 4] = [
+                self.dss_read_data(doorbell + DOORBELL_RSP_KIND)?,
+                self.dss_read_data(doorbell + DOORBELL_RSP_VAL0)?,
+                self.dss_read_data(doorbell + DOORBELL_RSP_VAL1)?,
+                self.dss_read_data(doorbell + DOORBELL_RSP_VAL2)?,
+            ];
+            if !self.safe_transport.get()
+                || u32::from_le(self.dss_read_data(doorbell + DOORBELL_RSP_CRC)?)
+                    == words_crc(&bytes)
+            {
+                break bytes;
+            }
+
+            // The response stays in the doorbell until it is consumed, read
+            // it again
+            if attempt == TRANSPORT_ATTEMPTS {
+                self.dss_write_data(doorbell + DOORBELL_RSP_KIND, 0)?;
+                return CorruptTransfer {}.fail();
+            }
+            attempt += 1;
+            self.update_counters(|counters| counters.retries += 1);
+        };
 
-        self.dss_write_data(DOORBELL_RSP_KIND, 0)?;
+        self.dss_write_data(doorbell + DOORBELL_RSP_KIND, 0)?;
 
-        Ok(Response::from_bytes(&bytes)?)
+        let response = Response::from_bytes(&bytes)?;
+        self.trace(|recorder| recorder.note("RSP", format_args!("{:?}", response)));
+
+        Ok(response)
     }
 
     fn dss_write_data(&self, address: u32, value: u32) -> Result<()> {
-        self.memory
-            .write_data(0, address as _, value as _, 32)
+        self.backend
+            .write_word(address, value)
             .context(DssError {})?;
+        self.trace(|recorder| recorder.write_word(address, value));
         Ok(())
     }
 
     fn dss_write_datas(&self, address: u32, values: &[u8]) -> Result<()> {
-        let datas: Vec<_> = values.iter().map(|n| *n as _).collect();
-        self.memory
-            .write_datas(0, address as _, &datas, 8)
+        self.backend
+            .write_bytes(address, values)
             .context(DssError {})?;
+        self.trace(|recorder| recorder.write_bytes(address, values));
         Ok(())
     }
 
     fn dss_read_data(&self, address: u32) -> Result<u32> {
-        let data = self
-            .memory
-            .read_data(0, address as _, 32, false as _)
-            .context(DssError {})?;
-        Ok(data as _)
+        let data = self.backend.read_word(address).context(DssError {})?;
+        self.trace(|recorder| recorder.read_word(address, data));
+        Ok(data)
     }
 
     fn dss_read_datas(&self, address: u32, size: u32) -> Result<Vec<u8>> {
-        let datas = self
-            .memory
-            .read_datas(0, address as _, 8, size as _, false as _)
+        let values = self
+            .backend
+            .read_bytes(address, size)
             .context(DssError {})?;
-        let values = datas.iter().map(|n| *n as _).collect();
+        self.trace(|recorder| recorder.read_bytes(address, &values));
         Ok(values)
     }
 
-    fn dss_load_raw(&self, file_name: &str) -> Result<()> {
-        self.memory
-            .load_raw(0, SRAM_START as _, file_name, 32, false as _)
+    fn dss_is_ram(&self, address: u32) -> Result<bool> {
+        let is_ram = self.backend.is_ram(address).context(DssError {})?;
+        self.trace(|recorder| recorder.note("RAM", format_args!("0x{:08X} {}", address, is_ram)));
+        Ok(is_ram)
+    }
+
+    fn dss_load_raw(&self, address: u32, path: &Path) -> Result<()> {
+        self.backend
+            .load_raw(address, path, 32)
             .context(DssError {})?;
+        self.trace(|recorder| {
+            recorder.note("LOAD", format_args!("0x{:08X} {}", address, path.display()))
+        });
         Ok(())
     }
 
+    fn dss_load_program(&self, path: &Path) -> Result<()> {
+        self.backend.load_program(path).context(DssError {})?;
+        self.trace(|recorder| recorder.note("PROGRAM", path.display()));
+        Ok(())
+    }
+
+    fn dss_read_register(&self, register: Register) -> Result<u32> {
+        let value = self.backend.read_register(register).context(DssError {})?;
+        self.trace(|recorder| {
+            recorder.note(
+                "REG",
+                format_args!("{} {:08X}", register.to_string(), value),
+            )
+        });
+        Ok(value)
+    }
+
     fn dss_write_register(&self, register: Register, value: u32) -> Result<()> {
-        self.memory
-            .write_register(register, value as _)
+        self.backend
+            .write_register(register, value)
             .context(DssError {})?;
+        self.trace(|recorder| {
+            recorder.note(
+                "REG",
+                format_args!("{} {:08X}", register.to_string(), value),
+            )
+        });
         Ok(())
     }
 
-    fn create_firmware_binary(device: Device) -> Result<TempPath> {
-        let asset = assets::get_firmware(device)
-            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Firmware asset not found"))
-            .context(FirmwareAsset {})?;
-
-        let mut firmware = tempfile::Builder::new()
-            .prefix("flash-rover.fw.")
-            .suffix(".bin")
-            .tempfile()
-            .context(FirmwareAsset {})?;
+    fn create_firmware_binary(asset: Cow<'static, [u8]>, xds_id: &str) -> Result<TempPath> {
+        let mut firmware = temp::create(xds_id, "fw.", ".bin").context(FirmwareAsset {})?;
         firmware.write_all(&asset).context(FirmwareAsset {})?;
         let (file, path) = firmware.into_parts();
         // Drop file in order to ensure file is closed and written changes are
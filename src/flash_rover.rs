@@ -3,20 +3,46 @@
 // (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
 // notice may not be copied, modified, or distributed except according to those terms.
 
+use std::cell::{Cell, RefCell};
+use std::fs;
 use std::io::{self, Read, Write};
-use std::time::Duration;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use dss::com::ti::{
     ccstudio::scripting::environment::ScriptingEnvironment,
-    debug::engine::scripting::{DebugServer, DebugSession},
+    debug::engine::scripting::{DebugServer, Register},
 };
-use snafu::{Backtrace, ResultExt, Snafu};
-use tempfile::TempPath;
+use serialport::{ClearBuffer, SerialPort};
+use snafu::{Backtrace, IntoError, OptionExt, ResultExt, Snafu};
 
 use crate::assets;
-use crate::command::{Command, Subcommand};
+use crate::backend::{Backend, DssBackend};
+use crate::board::{Board, InitStep};
+use crate::cache;
+use crate::ccxml::Ccxml;
+use crate::checksum;
+use crate::command::{
+    Command, InternalAction, MemAction, Recheck, StatusAction, Subcommand, Verify,
+};
 use crate::firmware::{self, Firmware};
-use crate::types::Device;
+use crate::history;
+use crate::image::{self, Relocation};
+use crate::interrupt;
+use crate::json;
+use crate::manifest::{Manifest, Step};
+use crate::oad;
+use crate::progress::Progress;
+use crate::sector_cache::SectorCache;
+use crate::stats::{Stats, WriteSummary};
+use crate::temp;
+use crate::trace::Recorder;
+use crate::types::{CrcAlgorithm, DeviceFamily, InputFormat};
+use crate::xds110::Xds110;
+use crate::xflash::Xflash;
 
 #[derive(Debug, Snafu)]
 pub enum Error {
@@ -30,15 +56,237 @@ pub enum Error {
         source: dss::Error,
         backtrace: Backtrace,
     },
+    #[snafu(display(
+        "Unable to connect to the device: {}\n\
+         The debug interface of the device is disabled in CCFG, e.g. by a secured application \
+         image. The device must be recovered by a mass erase of the internal flash through the \
+         debugger (e.g. with UniFlash) before flash-rover can access it",
+        source
+    ))]
+    DebugLocked {
+        source: dss::Error,
+        backtrace: Backtrace,
+    },
     #[snafu(display("A Firmware error occured: {}", source))]
     FirmwareError {
         source: firmware::Error,
         backtrace: Backtrace,
     },
+    #[snafu(display("{}", source))]
+    ImageError {
+        source: image::Error,
+        backtrace: Backtrace,
+    },
     #[snafu(display("Received too few bytes from input"))]
     InvalidInputLength { backtrace: Backtrace },
+    #[snafu(display(
+        "Range 0x{:08X}..0x{:08X} is outside the external flash of {} bytes",
+        start,
+        end,
+        size
+    ))]
+    OutOfBounds {
+        start: u32,
+        end: u32,
+        size: u32,
+        backtrace: Backtrace,
+    },
+    #[snafu(display(
+        "Input at 0x{:08X} is larger than the {} bytes left of the external flash of {} bytes",
+        offset,
+        size.saturating_sub(*offset),
+        size
+    ))]
+    InputTooLarge {
+        offset: u32,
+        size: u32,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("The internal flash has no bank {}, it has {} bank(s)", bank, banks))]
+    InvalidBank {
+        bank: u32,
+        banks: usize,
+        backtrace: Backtrace,
+    },
+    #[snafu(display(
+        "Range 0x{:08X}..0x{:08X} is outside internal flash bank {} of {} bytes",
+        start,
+        end,
+        bank,
+        size
+    ))]
+    InternalOutOfBounds {
+        bank: u32,
+        start: u32,
+        end: u32,
+        size: u32,
+        backtrace: Backtrace,
+    },
+    #[snafu(display(
+        "{} {} of the external flash does not match the {} of the input",
+        algorithm.label(),
+        algorithm.format(*actual),
+        algorithm.format(*expected)
+    ))]
+    CrcMismatch {
+        algorithm: CrcAlgorithm,
+        expected: u32,
+        actual: u32,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("No blank region of {} bytes found in the external flash", length))]
+    NoBlankRegion { length: u32, backtrace: Backtrace },
+    #[snafu(display("The size of the external flash must be known to search for a blank region"))]
+    UnknownSize { backtrace: Backtrace },
+    #[snafu(display("The external flash has no configuration register, only Macronix parts do"))]
+    NoConfigRegister { backtrace: Backtrace },
+    #[snafu(display(
+        "Register reads back 0x{:X} after writing 0x{:X}, check the status register write \
+         protect bit (SRWD) and the WP pin",
+        actual,
+        expected
+    ))]
+    RegisterNotWritten {
+        expected: u16,
+        actual: u16,
+        backtrace: Backtrace,
+    },
     #[snafu(display("Verification of written data failed"))]
     VerificationFailed { backtrace: Backtrace },
+    #[snafu(display(
+        "Verification of the sampled sector at 0x{:08X} failed (seed 0x{:08X})",
+        address,
+        seed
+    ))]
+    SampleVerificationFailed {
+        address: u32,
+        seed: u32,
+        backtrace: Backtrace,
+    },
+    #[snafu(display(
+        "Verification of {} of {} manifest regions failed, see the report",
+        failed,
+        total
+    ))]
+    ManifestVerificationFailed {
+        failed: usize,
+        total: usize,
+        backtrace: Backtrace,
+    },
+    #[snafu(display(
+        "Range 0x{:08X}..0x{:08X} is write protected by the external flash status register \
+         (0x{:02X}), erase and write would be ignored by the flash. Clear the block protect bits, \
+         or use --force to proceed anyway",
+        start,
+        end,
+        status
+    ))]
+    WriteProtected {
+        start: u32,
+        end: u32,
+        status: u8,
+        backtrace: Backtrace,
+    },
+    #[snafu(display(
+        "Range overlaps the protected partition '{}' at 0x{:08X}..0x{:08X}, which holds per-unit \
+         data that would be destroyed. Use --force to proceed anyway",
+        name,
+        start,
+        end
+    ))]
+    ProtectedPartition {
+        name: String,
+        start: u32,
+        end: u32,
+        backtrace: Backtrace,
+    },
+    #[snafu(display(
+        "No partitions to checksum, give a partition table with --partitions or a manifest \
+         with partitions"
+    ))]
+    NoPartitions { backtrace: Backtrace },
+    #[snafu(display(
+        "Range 0x{:08X}..0x{:08X} is mapped for execute-in-place by the paused application, \
+         which would resume in code erased or rewritten under it. Use --force to proceed anyway",
+        start,
+        end
+    ))]
+    ExecutingRegion {
+        start: u32,
+        end: u32,
+        backtrace: Backtrace,
+    },
+    #[snafu(display(
+        "Target supply voltage of {:.2} V is below the minimum of {:.2} V for erase and program \
+         of the external flash, data may be corrupted. Check the power supply or battery, or use \
+         --force to proceed anyway",
+        *vdds as f64 / 1000.0,
+        *min as f64 / 1000.0
+    ))]
+    LowVoltage {
+        vdds: u32,
+        min: u32,
+        backtrace: Backtrace,
+    },
+    #[snafu(display(
+        "Re-check of written data at 0x{:08X} failed after {} ms, the external flash may have \
+         been corrupted by an unstable supply",
+        address,
+        delay.as_millis()
+    ))]
+    RecheckFailed {
+        address: u32,
+        delay: Duration,
+        backtrace: Backtrace,
+    },
+    #[snafu(display(
+        "{} is not a known part, refusing to erase or program it in strict mode",
+        xflash
+    ))]
+    UnknownPart {
+        xflash: String,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("Unable to open data port {}: {}", path, source))]
+    DataPortError {
+        path: String,
+        source: serialport::Error,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("Unable to record the trace to {}: {}", path.display(), source))]
+    RecordError {
+        path: PathBuf,
+        source: io::Error,
+        backtrace: Backtrace,
+    },
+    #[snafu(display(
+        "The application did not grant access to the external flash within {} through the \
+         arbitration word at 0x{:08X}",
+        humantime::format_duration(*timeout),
+        address
+    ))]
+    AccessNotGranted {
+        address: u32,
+        timeout: Duration,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("The {} subcommand is not supported in cooperative mode", name))]
+    CooperativeUnsupported {
+        name: &'static str,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("No factory configuration page is described for {}", device))]
+    FcfgUnsupported {
+        device: String,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("No CCFG MAC address overrides are described for {}", device))]
+    MacOverrideUnsupported {
+        device: String,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("{}", source))]
+    AssetError { source: assets::Error },
     #[snafu(display("Unable to create CCXML file: {}", source))]
     CreateCcxmlError {
         source: io::Error,
@@ -53,262 +301,2414 @@ pub enum Error {
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
+impl Error {
+    /// Stable identifier of the error, for automation.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::IoError { .. } => "io_error",
+            Error::DssError { .. } => "dss_error",
+            Error::DebugLocked { .. } => "debug_locked",
+            Error::FirmwareError { source, .. } => source.code(),
+            Error::ImageError { .. } => "invalid_image",
+            Error::InvalidInputLength { .. } => "input_too_short",
+            Error::InvalidBank { .. } => "invalid_bank",
+            Error::InternalOutOfBounds { .. } => "out_of_bounds",
+            Error::OutOfBounds { .. } => "out_of_bounds",
+            Error::InputTooLarge { .. } => "input_too_large",
+            Error::CrcMismatch { .. } => "crc_mismatch",
+            Error::NoBlankRegion { .. } => "no_blank_region",
+            Error::UnknownSize { .. } => "unknown_size",
+            Error::NoConfigRegister { .. } => "no_config_register",
+            Error::RegisterNotWritten { .. } => "register_not_written",
+            Error::VerificationFailed { .. } | Error::SampleVerificationFailed { .. } => {
+                "verification_failed"
+            }
+            Error::ManifestVerificationFailed { .. } => "manifest_verification_failed",
+            Error::WriteProtected { .. } => "write_protected",
+            Error::ProtectedPartition { .. } => "protected_partition",
+            Error::NoPartitions { .. } => "no_partitions",
+            Error::ExecutingRegion { .. } => "executing_region",
+            Error::LowVoltage { .. } => "low_voltage",
+            Error::RecheckFailed { .. } => "recheck_failed",
+            Error::UnknownPart { .. } => "unknown_part",
+            Error::DataPortError { .. } => "data_port_error",
+            Error::RecordError { .. } => "record_failed",
+            Error::AccessNotGranted { .. } => "access_not_granted",
+            Error::CooperativeUnsupported { .. } => "cooperative_unsupported",
+            Error::FcfgUnsupported { .. } => "fcfg_unsupported",
+            Error::MacOverrideUnsupported { .. } => "mac_override_unsupported",
+            Error::AssetError { .. } => "asset_error",
+            Error::CreateCcxmlError { .. } => "create_ccxml_failed",
+            Error::CreateFirmwareError { .. } => "create_firmware_failed",
+        }
+    }
+
+    /// Whether the error is a failed call through the debugger, as when the
+    /// probe drops off USB, which reconnecting may recover from.
+    fn is_disconnect(&self) -> bool {
+        matches!(
+            self,
+            Error::DssError {
+                source: dss::Error::JavaException { .. },
+                ..
+            } | Error::FirmwareError {
+                source: firmware::Error::DssError {
+                    source: dss::Error::JavaException { .. },
+                    ..
+                },
+                ..
+            }
+        )
+    }
+
+    /// External flash offset the error relates to, if any.
+    pub fn offset(&self) -> Option<u32> {
+        match self {
+            Error::OutOfBounds { start, .. }
+            | Error::WriteProtected { start, .. }
+            | Error::ProtectedPartition { start, .. }
+            | Error::ExecutingRegion { start, .. } => Some(*start),
+            Error::RecheckFailed { address, .. }
+            | Error::SampleVerificationFailed { address, .. } => Some(*address),
+            Error::InputTooLarge { offset, .. } => Some(*offset),
+            _ => None,
+        }
+    }
+}
+
 const DEBUG_SERVER_NAME: &str = "DebugServer.1";
 const SCRIPT_TIMEOUT: Duration = Duration::from_secs(15);
-const SESSION_PATTERN: &str = "Texas Instruments XDS110 USB Debug Probe/Cortex_M(3|4|33)_0";
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const CONNECT_ATTEMPTS: u32 = 3;
+/// Delay before the first attempt to reconnect after the probe dropped off,
+/// doubled for every further attempt up to the maximum
+const RECONNECT_BACKOFF: Duration = Duration::from_secs(1);
+const RECONNECT_BACKOFF_MAX: Duration = Duration::from_secs(16);
+/// Values of the arbitration word in cooperative mode, where the application
+/// owns the external flash while the word is idle
+const ARBITRATION_IDLE: u32 = 0;
+const ARBITRATION_REQUESTED: u32 = 1;
+const ARBITRATION_GRANTED: u32 = 2;
+const ARBITRATION_TIMEOUT: Duration = Duration::from_secs(5);
+const ARBITRATION_POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// Time allowed between bytes on the data port, which covers programming of a
+/// block by the firmware
+const DATA_PORT_TIMEOUT: Duration = Duration::from_secs(3);
 
-fn create_ccxml(xds: &str, device: Device) -> Result<TempPath> {
-    let asset = assets::get_ccxml_template(device)
-        .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
-        .context(CreateCcxmlError {})?;
+// Cores of the JTAG chain in the target configurations of all device families,
+// behind which the debug access port and the CPU are
+const ICEPICK_CORE: &str = "IcePick_C_0";
+const DAP_CORE: &str = "CS_DAP_0";
 
-    let patterns = &[
-        ("<<<SERIAL NUMBER>>>", xds),
-        ("<<<DEVICE DESC>>>", device.ccxml_desc()),
-        ("<<<DEVICE XML>>>", device.ccxml_xml()),
-        ("<<<DEVICE ID>>>", device.ccxml_id()),
-    ];
-
-    let content = String::from_utf8_lossy(&asset[..]).to_string();
-    let content = patterns.iter().fold(content, |state, pattern| {
-        state.replace(pattern.0, pattern.1)
-    });
-
-    let mut ccxml = tempfile::Builder::new()
-        .prefix("flash-rover.ccxml.")
-        .suffix(".ccxml")
-        .tempfile()
-        .context(CreateCcxmlError {})?;
-    ccxml
-        .write_all(content.as_bytes())
-        .context(CreateCcxmlError {})?;
+// Battery monitor of the always-on domain, identical on all device families
+const AON_BATMON_CTL: u32 = 0x4009_5000;
+const AON_BATMON_BAT: u32 = 0x4009_5028;
+const AON_BATMON_CTL_EN: u32 = 0x03;
+const BATMON_SETTLE_TIME: Duration = Duration::from_millis(10);
+
+// Line buffers of the flash cache, which may hold stale instructions or data
+// after the internal flash is programmed through the debugger
+const VIMS_CTL: u32 = 0x4003_4004;
+const VIMS_CTL_LB_DIS: u32 = 0x30;
+
+/// Target configuration of the probe, device and connection options.
+fn ccxml_content(command: &Command) -> Result<String> {
+    let template = assets::get_ccxml_template(command.device, command.asset_dir.as_deref())
+        .context(AssetError {})?;
 
-    let (file, path) = ccxml.into_parts();
-    drop(file);
+    Ok(ccxml_from(command, template))
+}
+
+/// Target configuration from the template of `--asset-dir`, if any, or
+/// generated from the options.
+fn ccxml_from(command: &Command, template: Option<String>) -> String {
+    match template {
+        Some(template) => template.replace(assets::CCXML_SERIAL_PLACEHOLDER, &command.xds_id),
+        None => {
+            let mut builder = Ccxml::new(command.device, &command.xds_id)
+                .interface(command.interface)
+                .jtag_speed(command.jtag_speed);
+            if let Some(probe_voltage) = &command.probe_voltage {
+                builder = builder.probe_voltage(Some(probe_voltage));
+            }
+            builder.to_string()
+        }
+    }
+}
+
+fn create_ccxml(command: &Command) -> Result<PathBuf> {
+    let template = assets::get_ccxml_template(command.device, command.asset_dir.as_deref())
+        .context(AssetError {})?;
+
+    // The ccxml is cached between runs per probe and device, keyed by the
+    // inputs it is generated from, so that it is only generated again when
+    // they change
+    let name = format!("{}.{}", command.xds_id, command.device.to_string());
+    let key = format!(
+        "{} {:?} {:?} {:?} {:?}",
+        env!("CARGO_PKG_VERSION"),
+        command.interface,
+        command.jtag_speed,
+        command.probe_voltage,
+        template
+    );
+    if let Some(path) = cache::lookup(&name, "ccxml", key.as_bytes()) {
+        return Ok(path);
+    }
+
+    let content = ccxml_from(command, template);
+    let path = cache::store(&name, "ccxml", key.as_bytes(), content.as_bytes())
+        .context(CreateCcxmlError {})?;
 
     Ok(path)
 }
 
+/// Whether the debug interface of the device is disabled in CCFG, after
+/// connecting to the core failed: the ICEPick router of the JTAG chain answers,
+/// but the debug access port behind it, which the CCFG disables, cannot be
+/// connected to. Other failures, e.g. an unpowered device or a broken cable,
+/// fail to connect to the ICEPick as well.
+fn is_debug_locked(debug_server: &DebugServer, session_pattern: &str) -> bool {
+    let probe = match session_pattern.split_once('/') {
+        Some((probe, _)) => probe,
+        None => return false,
+    };
+    let connects = |core: &str| {
+        debug_server
+            .open_session(&format!("{}/{}", probe, core))
+            .and_then(|session| {
+                session.target.connect()?;
+                session.target.disconnect()
+            })
+            .is_ok()
+    };
+
+    connects(ICEPICK_CORE) && !connects(DAP_CORE)
+}
+
+/// Fail if the subcommand cannot be run in cooperative mode, before
+/// connecting.
+fn check_cooperative(command: &Command) -> Result<()> {
+    ensure!(
+        command.cooperative.is_none()
+            || matches!(
+                command.subcommand,
+                Subcommand::Read { .. }
+                    | Subcommand::Checksum { .. }
+                    | Subcommand::PartitionChecksums { .. }
+                    | Subcommand::Info
+                    | Subcommand::SectorErase { .. }
+                    | Subcommand::Write { .. }
+                    | Subcommand::Patch { .. }
+            ),
+        CooperativeUnsupported {
+            name: command.subcommand.name()
+        }
+    );
+
+    Ok(())
+}
+
+fn configure_debug_server<'a>(
+    script: &'a ScriptingEnvironment<'a>,
+    command: &Command,
+) -> Result<DebugServer<'a>> {
+    let ccxml = create_ccxml(command)?;
+
+    match command.script_timeout.unwrap_or(SCRIPT_TIMEOUT) {
+        timeout if timeout == Duration::from_secs(0) => script.disable_script_timeout(),
+        timeout => script.set_script_timeout(timeout),
+    }
+    .context(DssError {})?;
+
+    let debug_server = script.get_server(DEBUG_SERVER_NAME).context(DssError {})?;
+    let ccxml = dss::path::to_dss(&ccxml).context(DssError {})?;
+    debug_server.set_config(&ccxml).context(DssError {})?;
+
+    Ok(debug_server)
+}
+
+/// Write the target configuration of the probe, device and connection options
+/// to `output`, for colleagues using UniFlash, which opens it as is.
+pub fn export_uniflash(command: &Command, output: &Path) -> Result<()> {
+    let content = ccxml_content(command)?;
+    fs::write(output, content).context(CreateCcxmlError {})?;
+
+    println!("Wrote target configuration {}", output.display());
+    println!(
+        "Open it in UniFlash, or flash with the UniFlash command line: dslite --config={}",
+        output.display()
+    );
+    if command.board.is_some() || command.spi_pins.is_some() || command.mux_select.is_some() {
+        eprintln!(
+            "Warning: The board profile, SPI pins and mux select are specific to flash-rover and \
+             not part of the target configuration"
+        );
+    }
+
+    Ok(())
+}
+
+/// Print the debug sessions available in the target configuration, without
+/// connecting to the device.
+pub fn list_sessions(script: &ScriptingEnvironment, command: &Command) -> Result<()> {
+    let debug_server = configure_debug_server(script, command)?;
+
+    let sessions = debug_server.get_list_of_cpus().context(DssError {})?;
+    for session in sessions {
+        println!("{}", session);
+    }
+
+    debug_server.stop().context(DssError {})?;
+
+    Ok(())
+}
+
 pub struct FlashRover<'a> {
     command: Command,
-    debug_server: DebugServer<'a>,
-    debug_session: DebugSession<'a>,
+    /// The device, shared with the firmware
+    backend: Rc<dyn Backend + 'a>,
     firmware: Firmware<'a>,
+    /// Serial port used for data transfers instead of the debug probe
+    data_port: RefCell<Option<Box<dyn SerialPort>>>,
+    stats: Cell<Stats>,
+    write_summary: Cell<WriteSummary>,
+    transfer: RefCell<Option<Transfer>>,
+    /// Trace of the memory accesses, shared with the firmware
+    recorder: Option<Rc<Recorder>>,
+    /// Attempts left to reconnect after the probe dropped off
+    reconnects: Cell<u32>,
+    /// Whether the application granted access to the external flash and is
+    /// halted, in cooperative mode
+    access_granted: Cell<bool>,
+}
+
+/// Data read from or written to the flash by the operation, for the history
+/// file.
+struct Transfer {
+    offset: u32,
+    length: u32,
+    digest: String,
 }
 
 impl<'a> FlashRover<'a> {
     pub fn new(script: &'a ScriptingEnvironment<'a>, command: Command) -> Result<Self> {
-        let ccxml = create_ccxml(&command.xds_id, command.device)?;
+        let setup_start = Instant::now();
 
-        script
-            .set_script_timeout(SCRIPT_TIMEOUT)
-            .context(DssError {})?;
+        check_cooperative(&command)?;
 
-        let debug_server = script.get_server(DEBUG_SERVER_NAME).context(DssError {})?;
-        debug_server
-            .set_config(&ccxml.to_string_lossy().to_owned())
-            .context(DssError {})?;
+        let debug_server = configure_debug_server(script, &command)?;
 
+        let session_pattern = command
+            .core
+            .as_deref()
+            .unwrap_or_else(|| DeviceFamily::from(command.device).session_pattern());
         let debug_session = debug_server
-            .open_session(SESSION_PATTERN)
+            .open_session(session_pattern)
             .context(DssError {})?;
-        debug_session.target.connect().context(DssError {})?;
+        let retries = debug_session
+            .target
+            .connect_with_retry(CONNECT_TIMEOUT, CONNECT_ATTEMPTS)
+            .map_err(|err| match err {
+                dss::Error::JavaException { .. }
+                    if is_debug_locked(&debug_server, session_pattern) =>
+                {
+                    DebugLocked {}.into_error(err)
+                }
+                err => DssError {}.into_error(err),
+            })?;
 
-        let firmware = Firmware::new(debug_session.memory.clone(), command.device)
-            .context(FirmwareError {})?;
+        let backend = DssBackend::new(debug_server, debug_session);
+        let rover = Self::with_backend(Rc::new(backend), command)?;
+        rover.update_stats(|stats| {
+            stats.session_setup = setup_start.elapsed();
+            stats.retries = retries;
+        });
+
+        Ok(rover)
+    }
+
+    /// Run `command` on the connected device of `backend`, e.g. a
+    /// [`crate::mock::MockDevice`].
+    pub fn with_backend(backend: Rc<dyn Backend + 'a>, command: Command) -> Result<Self> {
+        let setup_start = Instant::now();
+
+        check_cooperative(&command)?;
+
+        let firmware = Firmware::new(
+            backend.clone(),
+            command.device,
+            &command.xds_id,
+            command.firmware_elf.as_deref(),
+            command.asset_dir.as_deref(),
+        )
+        .context(FirmwareError {})?;
+        if let Some(format) = command.progress_format {
+            firmware.set_progress(Progress::new(format));
+        }
+        firmware.set_safe_transport(command.safe_transport);
+        firmware.set_throttle(command.throttle);
+
+        let recorder = match &command.record {
+            Some(path) => Some(Rc::new(
+                Recorder::open(path, command.device, &command.xds_id)
+                    .context(RecordError { path })?,
+            )),
+            None => None,
+        };
+        if let Some(recorder) = &recorder {
+            firmware.set_recorder(recorder.clone());
+        }
+
+        let data_port = match &command.data_port {
+            Some(data_port) => Some(
+                serialport::new(&data_port.path, data_port.baud_rate)
+                    .timeout(DATA_PORT_TIMEOUT)
+                    .open()
+                    .context(DataPortError {
+                        path: &data_port.path,
+                    })?,
+            ),
+            None => None,
+        };
+
+        let stats = Stats {
+            session_setup: setup_start.elapsed(),
+            ..Default::default()
+        };
+
+        let reconnects = Cell::new(command.reconnect);
 
         Ok(Self {
             command,
-            debug_server,
-            debug_session,
+            backend,
             firmware,
+            data_port: RefCell::new(data_port),
+            stats: Cell::new(stats),
+            write_summary: Cell::new(WriteSummary::default()),
+            transfer: RefCell::new(None),
+            recorder,
+            reconnects,
+            access_granted: Cell::new(false),
         })
     }
 
-    fn reset_into_firmware(&self) -> Result<()> {
-        const EXPRESSION_BOARD_RESET: &str =
-            "GEL_AdvancedReset(\"Board Reset (automatic connect/disconnect)\")";
+    fn update_stats(&self, f: impl FnOnce(&mut Stats)) {
+        let mut stats = self.stats.get();
+        f(&mut stats);
+        self.stats.set(stats);
+    }
 
-        if !self.debug_session.target.is_halted().context(DssError {})? {
-            self.debug_session.target.halt().context(DssError {})?;
+    fn trace(&self, f: impl FnOnce(&Recorder)) {
+        if let Some(recorder) = self.recorder.as_deref() {
+            f(recorder);
         }
+    }
 
-        self.debug_session.target.reset().context(DssError {})?;
-        self.debug_session
-            .expression
-            .evaluate(EXPRESSION_BOARD_RESET)
-            .context(DssError {})?;
-
-        self.firmware
-            .inject(self.command.spi_pins)
-            .context(FirmwareError {})?;
+    /// Read a 32-bit word directly through the debugger.
+    fn dss_read_word(&self, address: u32) -> Result<u32> {
+        let value = self.backend.read_word(address).context(DssError {})?;
+        self.trace(|recorder| recorder.read_word(address, value));
+        Ok(value)
+    }
 
-        self.debug_session
-            .target
-            .run_asynch()
+    fn dss_read_words(&self, address: u32, count: u32) -> Result<Vec<u32>> {
+        let values = self
+            .backend
+            .read_words(address, count)
             .context(DssError {})?;
+        self.trace(|recorder| {
+            for (i, value) in values.iter().enumerate() {
+                recorder.read_word(address + 4 * i as u32, *value);
+            }
+        });
+        Ok(values)
+    }
 
+    fn dss_write_word(&self, address: u32, value: u32) -> Result<()> {
+        self.backend
+            .write_word(address, value)
+            .context(DssError {})?;
+        self.trace(|recorder| recorder.write_word(address, value));
         Ok(())
     }
 
-    pub fn run(self) -> Result<()> {
-        use Subcommand::*;
+    fn dss_read_bytes(&self, address: u32, length: u32) -> Result<Vec<u8>> {
+        let values = self
+            .backend
+            .read_bytes(address, length)
+            .context(DssError {})?;
+        self.trace(|recorder| recorder.read_bytes(address, &values));
+        Ok(values)
+    }
 
-        self.reset_into_firmware()?;
+    fn dss_read_register(&self, register: Register) -> Result<u32> {
+        let value = self.backend.read_register(register).context(DssError {})?;
+        self.trace(|recorder| {
+            recorder.note(
+                "REG",
+                format_args!("{} {:08X}", register.to_string(), value),
+            )
+        });
+        Ok(value)
+    }
 
-        match &self.command.subcommand {
-            Info => self.info()?,
-            SectorErase { offset, length } => self.sector_erase(*offset, *length)?,
-            MassErase => self.mass_erase()?,
-            Read {
-                offset,
-                length,
-                output,
-            } => self.read(*offset, *length, output.borrow_mut().as_mut())?,
-            Write {
-                verify,
-                in_place,
-                offset,
-                length,
-                input,
-            } => self.write(
-                *verify,
-                *in_place,
-                *offset,
-                *length,
-                input.borrow_mut().as_mut(),
-            )?,
-        }
+    pub(crate) fn reset_into_firmware(&self) -> Result<()> {
+        const EXPRESSION_BOARD_RESET: &str =
+            "GEL_AdvancedReset(\"Board Reset (automatic connect/disconnect)\")";
 
-        Ok(())
-    }
+        let injection_start = Instant::now();
 
-    fn info(&self) -> Result<()> {
-        let xflash_info = self.firmware.get_xflash_info().context(FirmwareError {})?;
+        // The log of the running firmware is lost with the re-injection
+        self.print_firmware_log();
 
-        println!("{}", xflash_info);
+        if let Some(address) = self.command.cooperative {
+            // The application is paused rather than reset, and keeps the
+            // external flash powered and configured
+            self.request_access(address)?;
+        } else {
+            if !self.backend.is_halted().context(DssError {})? {
+                self.backend.halt().context(DssError {})?;
+            }
 
-        Ok(())
-    }
+            self.backend.reset().context(DssError {})?;
+            self.backend
+                .evaluate(EXPRESSION_BOARD_RESET)
+                .context(DssError {})?;
+
+            if let Some(board) = &self.command.board {
+                self.run_board_init(board)?;
+            }
+        }
 
-    fn sector_erase(&self, offset: u32, length: u32) -> Result<()> {
+        let spi_pins = self
+            .command
+            .spi_pins
+            .or_else(|| self.command.device.launchpad_spi_pins());
         self.firmware
-            .sector_erase(offset, length)
+            .inject(spi_pins, self.command.mux_select)
             .context(FirmwareError {})?;
 
-        Ok(())
-    }
+        self.backend.run_asynch().context(DssError {})?;
 
-    fn mass_erase(&self) -> Result<()> {
-        print!("Starting mass erase, this may take some time... ");
-        io::stdout().flush().context(IoError {})?;
+        if let Some(data_port) = &self.command.data_port {
+            let [rx, tx] = data_port
+                .pins
+                .unwrap_or_else(|| DeviceFamily::from(self.command.device).default_uart_pins());
+            self.firmware
+                .uart_config(rx, tx, data_port.baud_rate)
+                .context(FirmwareError {})?;
+        }
+        // Identify the external flash right away, for the firmware to report
+        // whether it had to take the flash over from XIP mode, and to check
+        // the part against the board once. Failures are left to the operation
+        // to report.
+        let identified = self.firmware.xflash().is_some();
+        let took_over_xip = self.firmware.took_over_xip();
+        if let Ok(xflash) = self.firmware.get_xflash_info() {
+            if !took_over_xip && self.firmware.took_over_xip() {
+                eprintln!(
+                    "Warning: The external flash was in the XIP (memory-mapped) mode left by the \
+                     application, it has been taken over and is handed back before the device is \
+                     reset"
+                );
+            }
+            if !identified {
+                self.check_voltage_domain(&xflash);
+                self.check_launchpad_flash(&xflash);
+            }
+        }
+        if !identified && self.command.fw_log && !self.firmware.has_log() {
+            eprintln!("Warning: The firmware has no debug log, it predates --fw-log");
+        }
 
-        self.firmware.mass_erase().context(FirmwareError {})?;
+        if let Some(port) = self.data_port.borrow_mut().as_mut() {
+            // Drop anything sent by the application before the reset
+            port.clear(ClearBuffer::Input).context(DataPortError {
+                path: port.name().unwrap_or_default(),
+            })?;
+        }
+
+        self.update_stats(|stats| stats.firmware_injection += injection_start.elapsed());
 
-        println!("Done.");
         Ok(())
     }
 
-    fn read(&self, offset: u32, length: u32, output: &mut dyn Write) -> Result<()> {
-        let data = self
-            .firmware
-            .read_data(offset, length)
-            .context(FirmwareError {})?;
-        io::copy(&mut data.as_slice(), output).context(IoError {})?;
+    /// Request the external flash from the running application through the
+    /// arbitration word, and halt the application once it has granted access,
+    /// saving the RAM and registers the firmware overwrites. After a
+    /// reconnect the application is already saved and the firmware is halted
+    /// instead.
+    fn request_access(&self, address: u32) -> Result<()> {
+        if self.access_granted.get() {
+            self.backend.halt().context(DssError {})?;
+            return Ok(());
+        }
+
+        self.dss_write_word(address, ARBITRATION_REQUESTED)?;
+        let start = Instant::now();
+        while self.dss_read_word(address)? != ARBITRATION_GRANTED {
+            if start.elapsed() > ARBITRATION_TIMEOUT || interrupt::is_interrupted() {
+                // Withdraw the request, for the application to carry on
+                self.dss_write_word(address, ARBITRATION_IDLE)
+                    .unwrap_or_default();
+                return AccessNotGranted {
+                    address,
+                    timeout: ARBITRATION_TIMEOUT,
+                }
+                .fail();
+            }
+            thread::sleep(ARBITRATION_POLL_INTERVAL);
+        }
+
+        self.backend.halt().context(DssError {})?;
+        self.access_granted.set(true);
+        self.firmware.save_application().context(FirmwareError {})?;
 
         Ok(())
     }
 
-    fn write(
-        &self,
-        verify: bool,
-        in_place: bool,
-        offset: u32,
-        length: Option<u32>,
-        input: &mut dyn Read,
-    ) -> Result<()> {
-        let input_buf: Vec<u8> = if let Some(length) = length {
-            let mut vec = Vec::with_capacity(length as _);
-            let read_bytes = input.take(length as _).read(&mut vec).context(IoError {})?;
-            ensure!(read_bytes == length as _, InvalidInputLength {});
-            vec
-        } else {
-            let mut vec = Vec::new();
-            input.read_to_end(&mut vec).context(IoError {})?;
-            vec
+    /// Resume the application halted by [`FlashRover::request_access`] where
+    /// it was halted, handing the external flash back to it.
+    fn resume_application(&self) -> Result<()> {
+        let address = match self.command.cooperative {
+            Some(address) if self.access_granted.get() => address,
+            _ => return Ok(()),
         };
 
-        let length = input_buf.len() as u32;
+        self.backend.halt().context(DssError {})?;
+        self.firmware
+            .restore_application()
+            .context(FirmwareError {})?;
+        self.dss_write_word(address, ARBITRATION_IDLE)?;
+        self.access_granted.set(false);
+        self.backend.run_asynch().context(DssError {})?;
 
-        if in_place {
-            self.firmware
-                .write_data(offset, &input_buf)
-                .context(FirmwareError {})?;
+        Ok(())
+    }
 
-            if verify {
-                self.reset_into_firmware()?;
+    /// Recover from `err` if the probe dropped off, by waiting for it to be
+    /// enumerated again, reconnecting and injecting the firmware anew, with
+    /// exponential backoff between the attempts. Returns `err` if it is not a
+    /// disconnect or all attempts failed.
+    fn reconnect(&self, err: Error) -> Result<()> {
+        if !err.is_disconnect() || self.reconnects.get() == 0 {
+            return Err(err);
+        }
 
-                let read_back = self
-                    .firmware
-                    .read_data(offset, length)
-                    .context(FirmwareError {})?;
+        let xds110 = Xds110::new(&self.command.ccs_path, &self.command.xds_id);
+        let mut backoff = RECONNECT_BACKOFF;
+        while self.reconnects.get() > 0 && !interrupt::is_interrupted() {
+            self.reconnects.set(self.reconnects.get() - 1);
+            eprintln!(
+                "Warning: Lost the connection to XDS110 {} ({}), reconnecting in {}",
+                self.command.xds_id,
+                err,
+                humantime::format_duration(backoff)
+            );
+            thread::sleep(backoff);
+            backoff = std::cmp::min(backoff * 2, RECONNECT_BACKOFF_MAX);
 
-                ensure!(input_buf.eq(&read_back), VerificationFailed {});
+            if !xds110.is_enumerated().unwrap_or(false) {
+                continue;
             }
-        } else {
-            let first_address = offset - offset % firmware::BUF_SIZE;
-            let first_length = offset % firmware::BUF_SIZE;
-            let last_address = offset + length;
-            let last_length =
-                (firmware::BUF_SIZE - last_address % firmware::BUF_SIZE) % firmware::BUF_SIZE;
 
-            let first_sector_part: Vec<u8> = self
-                .firmware
-                .read_data(first_address, first_length)
-                .context(FirmwareError {})?;
-            let last_sector_part: Vec<u8> = self
-                .firmware
-                .read_data(last_address, last_length)
-                .context(FirmwareError {})?;
-
-            let total_input: Vec<u8> = first_sector_part
-                .into_iter()
-                .chain(input_buf.into_iter())
-                .chain(last_sector_part.into_iter())
-                .collect();
-            let total_length = total_input.len() as u32;
-
-            self.firmware
-                .sector_erase(first_address, total_length)
-                .context(FirmwareError {})?;
-            self.firmware
-                .write_data(first_address, &total_input)
-                .context(FirmwareError {})?;
+            // The previous connection is stale, failing to close it is
+            // expected
+            self.backend.disconnect().unwrap_or_default();
+            let status = self
+                .backend
+                .connect_with_retry(CONNECT_TIMEOUT, 1)
+                .context(DssError {})
+                .and_then(|_| self.reset_into_firmware());
+            match status {
+                Ok(()) => {
+                    self.update_stats(|stats| stats.retries += 1);
+                    eprintln!("Reconnected, resuming the operation");
+                    return Ok(());
+                }
+                Err(err) if err.is_disconnect() => {}
+                Err(err) => return Err(err),
+            }
+        }
 
-            if verify {
-                self.reset_into_firmware()?;
+        Err(err)
+    }
 
-                let read_back = self
-                    .firmware
-                    .read_data(first_address, total_length)
-                    .context(FirmwareError {})?;
+    /// Recover from `err` of a transfer through the debugger for it to be
+    /// resumed: with smaller chunks if a chunk failed, as over flaky wires to
+    /// the external flash, or by reconnecting if the probe dropped off.
+    fn retry_transfer(&self, err: Error) -> Result<()> {
+        match &err {
+            Error::FirmwareError { source, .. } if source.is_transfer_failure() => {
+                match self.firmware.shrink_chunk_size() {
+                    Some(size) => {
+                        eprintln!(
+                            "Warning: Transfer failed ({}), retrying with chunks of {} bytes",
+                            err, size
+                        );
+                        self.update_stats(|stats| stats.retries += 1);
+                        Ok(())
+                    }
+                    None => Err(err),
+                }
+            }
+            _ => self.reconnect(err),
+        }
+    }
 
-                ensure!(total_input.eq(&read_back), VerificationFailed {});
+    /// Run the init sequence of the board, with the core halted after reset.
+    fn run_board_init(&self, board: &Board) -> Result<()> {
+        for step in &board.init {
+            match *step {
+                InitStep::Write {
+                    address,
+                    value,
+                    mask,
+                } => {
+                    let value = if mask == 0xFFFF_FFFF {
+                        value
+                    } else {
+                        (self.dss_read_word(address)? & !mask) | (value & mask)
+                    };
+                    self.dss_write_word(address, value)?;
+                }
+                InitStep::Delay(delay) => thread::sleep(delay),
             }
         }
 
         Ok(())
     }
-}
 
-impl<'a> Drop for FlashRover<'a> {
-    fn drop(&mut self) {
-        let f = || -> Result<(), Box<dyn std::error::Error>> {
-            self.debug_session.target.halt()?;
-            self.debug_session.target.reset()?;
-            self.debug_session.target.disconnect()?;
+    pub fn run(self) -> Result<()> {
+        let mut operation_start = Instant::now();
+
+        let status = if self.command.subcommand.injects_firmware() {
+            self.reset_into_firmware()
+        } else {
+            Ok(())
+        }
+        .and_then(|_| {
+            operation_start = Instant::now();
+            self.run_operation()
+        });
 
-            self.debug_server.stop()?;
+        // Hand back the external flash and restore the internal flash
+        // regardless of the outcome of the operation
+        self.print_firmware_log();
+        self.release_xflash();
+        self.power_down_xflash();
+        let status = match (status, self.restore_internal_flash()) {
+            (Err(err), _) | (Ok(()), Err(err)) => Err(err),
+            (Ok(()), Ok(())) => Ok(()),
+        };
+        let status = match (status, self.resume_application()) {
+            (Err(err), _) | (Ok(()), Err(err)) => Err(err),
+            (Ok(()), Ok(())) => Ok(()),
+        };
+
+        if let Some(path) = &self.command.history {
+            self.append_history(path, &status);
+        }
+        status?;
+
+        if let Subcommand::Write { .. } = self.command.subcommand {
+            let counters = self.firmware.counters();
+            let mut summary = self.write_summary.get();
+            summary.bytes_programmed = counters.bytes_written;
+            summary.retries = counters.retries;
+            summary.duration = operation_start.elapsed();
+            eprintln!("{}", summary);
+        }
+
+        if self.command.stats {
+            let counters = self.firmware.counters();
+            self.update_stats(|stats| {
+                stats.operation = operation_start.elapsed();
+                stats.bytes_read = counters.bytes_read;
+                stats.bytes_written = counters.bytes_written;
+                stats.doorbell_commands = counters.commands;
+                stats.retries += counters.retries;
+            });
+            eprintln!("{}", self.stats.get());
+        }
+
+        Ok(())
+    }
+
+    /// Hand the external flash back in its power-on state if it was taken over
+    /// from XIP mode, for the application to map it again after the reset.
+    /// Failing to do so is not an error, as the operation has completed.
+    pub(crate) fn release_xflash(&self) {
+        if !self.firmware.took_over_xip() {
+            return;
+        }
+
+        if let Err(err) = self.firmware.release_xflash() {
+            eprintln!("Warning: Unable to release the external flash: {}", err);
+        }
+    }
+
+    /// Print the messages the firmware logged since the last drain, if
+    /// requested. Failing to do so is not an error, as the operation has
+    /// completed.
+    fn print_firmware_log(&self) {
+        if !self.command.fw_log {
+            return;
+        }
+
+        match self.firmware.drain_log() {
+            Ok(Some(log)) => {
+                if log.lost > 0 {
+                    eprintln!("fw: ({} bytes lost)", log.lost);
+                }
+                for line in log.text.lines() {
+                    eprintln!("fw: {}", line);
+                }
+            }
+            Ok(None) => {}
+            Err(err) => eprintln!("Warning: Unable to read the firmware log: {}", err),
+        }
+    }
+
+    /// Put the external flash in deep power-down if requested, unless it is
+    /// handed back for XIP. Failing to do so is not an error, as the operation
+    /// has completed.
+    fn power_down_xflash(&self) {
+        if !self.command.power_down || self.firmware.xflash().is_none() {
+            return;
+        }
+        if self.firmware.took_over_xip() {
+            eprintln!(
+                "Warning: Not powering down the external flash, which the application maps for XIP"
+            );
+            return;
+        }
+
+        if let Err(err) = self.firmware.power_down_xflash() {
+            eprintln!("Warning: Unable to power down the external flash: {}", err);
+        }
+    }
+
+    pub(crate) fn restore_internal_flash(&self) -> Result<()> {
+        if !self.firmware.needs_restore() {
+            return Ok(());
+        }
+
+        self.backend.halt().context(DssError {})?;
+        self.firmware.restore().context(FirmwareError {})?;
+
+        Ok(())
+    }
+
+    fn run_operation(&self) -> Result<()> {
+        use Subcommand::*;
+
+        match &self.command.subcommand {
+            Info => self.info()?,
+            Checksum { offset, length } => self.print_checksum(*offset, *length)?,
+            PartitionChecksums { output } => {
+                self.write_partition_checksums(output.borrow_mut().as_mut())?
+            }
+            SectorErase { offset, length } => self.sector_erase(*offset, *length)?,
+            MassErase => self.mass_erase()?,
+            Read {
+                offset,
+                length,
+                output,
+            } => self.read(*offset, *length, output.borrow_mut().as_mut())?,
+            Write {
+                verify,
+                recheck,
+                in_place,
+                input,
+                format,
+                relocation,
+                ..
+            } if format.has_addresses() => self.write_image(
+                *verify,
+                *recheck,
+                *in_place,
+                *format,
+                *relocation,
+                input.borrow_mut().as_mut(),
+            )?,
+            Write {
+                verify,
+                recheck,
+                in_place,
+                auto_offset: true,
+                length,
+                input,
+                ..
+            } => self.write_auto_offset(
+                *verify,
+                *recheck,
+                *in_place,
+                *length,
+                input.borrow_mut().as_mut(),
+            )?,
+            Write {
+                verify,
+                recheck,
+                in_place,
+                offset,
+                length,
+                input,
+                ..
+            } => self.write(
+                *verify,
+                *recheck,
+                *in_place,
+                *offset,
+                *length,
+                input.borrow_mut().as_mut(),
+            )?,
+            Flash { offset, input } => self.flash(*offset, input.borrow_mut().as_mut())?,
+            Patch {
+                offset,
+                bytes,
+                mask,
+            } => self.patch(*offset, bytes, mask.as_deref())?,
+            Apply { manifest, report } => self.apply(manifest, report.as_deref())?,
+            OadList => self.oad_list()?,
+            Status {
+                action: StatusAction::Read,
+                config,
+            } => self.status_read(*config)?,
+            Status {
+                action: StatusAction::Write(value),
+                config,
+            } => self.status_write(*value, *config)?,
+            Regs => self.regs()?,
+            FcfgDump { output } => self.fcfg_dump(output.borrow_mut().as_mut())?,
+            ProvisionMac { ieee, ble } => self.provision_mac(ieee.as_deref(), ble.as_deref())?,
+            Mem { action } => self.mem(*action)?,
+            Internal {
+                action: InternalAction::Read { length, output },
+                bank,
+                offset,
+            } => self.internal_read(*bank, *offset, *length, output.borrow_mut().as_mut())?,
+            Internal {
+                action: InternalAction::Write { input },
+                bank,
+                offset,
+            } => self.internal_write(*bank, *offset, input.borrow_mut().as_mut())?,
+            Recover => self.recover()?,
+            Ping { count } => self.ping(*count)?,
+            Power { .. }
+            | Sessions
+            | ExportUniflash { .. }
+            | Tui
+            | Production { .. }
+            | HilTest { .. }
+            | Session { .. }
+            | Daemon
+            | External { .. } => {
+                unreachable!("Handled without a debug session")
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Record the operation in the history file. Failing to do so is not an
+    /// error, as the operation itself has already completed.
+    fn append_history(&self, path: &Path, status: &Result<()>) {
+        use Subcommand::*;
+
+        let transfer = self.transfer.borrow();
+        let range = match (&*transfer, &self.command.subcommand) {
+            (Some(transfer), _) => Some((transfer.offset, transfer.length)),
+            (None, SectorErase { offset, length }) | (None, Read { offset, length, .. }) => {
+                Some((*offset, *length))
+            }
+            (None, Write { offset, length, .. }) => length.map(|length| (*offset, length)),
+            (None, Patch { offset, bytes, .. }) => Some((*offset, bytes.len() as u32)),
+            _ => None,
+        };
+
+        // A manifest spans several ranges, and is identified by its digest
+        let (range, digest) = match &self.command.subcommand {
+            Apply { manifest, .. } => (None, Some(manifest.digest.as_str())),
+            _ => (
+                range,
+                transfer.as_ref().map(|transfer| transfer.digest.as_str()),
+            ),
+        };
+
+        let entry = history::Entry {
+            probe: &self.command.xds_id,
+            device: self.command.device,
+            operation: self.command.subcommand.name(),
+            range,
+            digest,
+            error: status.as_ref().err().map(ToString::to_string),
+        };
+        if let Err(err) = history::append(path, &entry) {
+            eprintln!(
+                "Warning: Unable to append to history file {}: {}",
+                path.display(),
+                err
+            );
+        }
+    }
+
+    fn read_config(&self) -> Result<u16> {
+        ensure!(
+            self.xflash_info()?.has_config_register(),
+            NoConfigRegister {}
+        );
+        self.firmware.read_config().context(FirmwareError {})
+    }
+
+    /// Print the status register, or the configuration register of Macronix
+    /// parts with its fields affecting reads at higher SPI clocks.
+    fn status_read(&self, config: bool) -> Result<()> {
+        if config {
+            let value = self.read_config()?;
+            println!("Configuration register: 0x{:04X}", value);
+            println!("  Dummy cycle (DC): {}", (value >> 6) & 1);
+            println!(
+                "  L/H switch: {}",
+                if value & 0x0200 != 0 {
+                    "high performance"
+                } else {
+                    "ultra low power"
+                }
+            );
+        } else {
+            let status = self.firmware.read_status().context(FirmwareError {})?;
+            println!("Status register: 0x{:02X}", status);
+        }
+
+        Ok(())
+    }
+
+    /// Write the status register, or the configuration register of Macronix
+    /// parts, and check that it reads back the written value.
+    fn status_write(&self, value: u16, config: bool) -> Result<()> {
+        self.check_known(&self.xflash_info()?)?;
+
+        let actual = if config {
+            ensure!(
+                self.xflash_info()?.has_config_register(),
+                NoConfigRegister {}
+            );
+
+            // The configuration register is written along with the status
+            // register, which is kept as is
+            let status = self.firmware.read_status().context(FirmwareError {})?;
+            self.firmware
+                .write_status(status, Some(value))
+                .context(FirmwareError {})?;
+            self.read_config()?
+        } else {
+            self.firmware
+                .write_status(value as u8, None)
+                .context(FirmwareError {})?;
+            u16::from(self.firmware.read_status().context(FirmwareError {})?)
+        };
+
+        // The write in progress and write enable latch bits are volatile
+        let mask = if config { 0xFFFF } else { 0xFC };
+        ensure!(
+            actual & mask == value & mask,
+            RegisterNotWritten {
+                expected: value,
+                actual
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Print the images found in the metadata pages of the OAD external flash
+    /// layout.
+    fn oad_list(&self) -> Result<()> {
+        println!(
+            "{:<4}  {:<14} {:>3}  {:<8} {:>10}  {:<10}  {:<10}  CRC",
+            "PAGE", "TYPE", "NO", "VERSION", "LENGTH", "ADDRESS", "CRC32"
+        );
+
+        for page in 0..oad::META_PAGES {
+            let meta = self.read_data(page * oad::META_PAGE_SIZE, oad::META_LEN)?;
+            if let Some(info) = oad::ImageInfo::parse(&meta) {
+                println!("{:<4}  {}", page, info);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn regs(&self) -> Result<()> {
+        if !self.backend.is_halted().context(DssError {})? {
+            self.backend.halt().context(DssError {})?;
+        }
+
+        for register in Register::ALL {
+            let value = self.dss_read_register(*register)?;
+            println!("{:<4} 0x{:08X}", register.to_string(), value);
+        }
+
+        Ok(())
+    }
+
+    /// Read the factory configuration page and write its fields as JSON, MAC
+    /// addresses most significant byte first and the other fields as
+    /// little-endian words.
+    fn fcfg_dump(&self, output: &mut dyn Write) -> Result<()> {
+        let fcfg = DeviceFamily::from(self.command.device)
+            .fcfg()
+            .context(FcfgUnsupported {
+                device: self.command.device.to_string(),
+            })?;
+
+        if !self.backend.is_halted().context(DssError {})? {
+            self.backend.halt().context(DssError {})?;
+        }
+
+        let page = self.dss_read_bytes(fcfg.base, fcfg.size)?;
+
+        let fields: Vec<String> = fcfg
+            .fields
+            .iter()
+            .map(|field| {
+                let start = field.offset as usize;
+                let bytes = &page[start..start + field.length as usize];
+                let value = if field.mac {
+                    bytes
+                        .iter()
+                        .rev()
+                        .map(|byte| format!("{:02X}", byte))
+                        .collect::<Vec<_>>()
+                        .join(":")
+                } else {
+                    let mut value = String::from("0x");
+                    for byte in bytes.iter().rev() {
+                        value.push_str(&format!("{:02X}", byte));
+                    }
+                    value
+                };
+                format!(
+                    "    {}: {}",
+                    json::string(&field.name),
+                    json::string(&value)
+                )
+            })
+            .collect();
+
+        writeln!(
+            output,
+            "{{\n  \"device\": {},\n  \"base\": \"0x{:08X}\",\n  \"fields\": {{\n{}\n  }},\n  \
+             \"raw\": \"{}\"\n}}",
+            json::string(&self.command.device.to_string()),
+            fcfg.base,
+            fields.join(",\n"),
+            base64::encode(&page),
+        )
+        .context(IoError {})?;
+        output.flush().context(IoError {})?;
+
+        Ok(())
+    }
+
+    /// Read or write 32-bit words of memory-mapped registers, e.g. to drive a
+    /// board specific GPIO.
+    fn mem(&self, action: MemAction) -> Result<()> {
+        if !self.backend.is_halted().context(DssError {})? {
+            self.backend.halt().context(DssError {})?;
+        }
+
+        match action {
+            MemAction::Read { address, count } => {
+                let values = self.dss_read_words(address, count)?;
+                for (i, value) in values.iter().enumerate() {
+                    println!("0x{:08X}: 0x{:08X}", address + 4 * i as u32, value);
+                }
+            }
+            MemAction::Write { address, value } => self.dss_write_word(address, value)?,
+        }
+
+        Ok(())
+    }
+
+    /// Address of the range at the offset into the internal flash bank.
+    fn internal_address(&self, bank: u32, offset: u32, length: u32) -> Result<u32> {
+        let banks = DeviceFamily::from(self.command.device).internal_flash_banks();
+        let (start, end) = *banks.get(bank as usize).context(InvalidBank {
+            bank,
+            banks: banks.len(),
+        })?;
+
+        let size = end - start;
+        ensure!(
+            offset.saturating_add(length) <= size,
+            InternalOutOfBounds {
+                bank,
+                start: offset,
+                end: offset.saturating_add(length),
+                size
+            }
+        );
+
+        Ok(start + offset)
+    }
+
+    fn internal_read(
+        &self,
+        bank: u32,
+        offset: u32,
+        length: u32,
+        output: &mut dyn Write,
+    ) -> Result<()> {
+        let address = self.internal_address(bank, offset, length)?;
+
+        if !self.backend.is_halted().context(DssError {})? {
+            self.backend.halt().context(DssError {})?;
+        }
+
+        let data = self.dss_read_bytes(address, length)?;
+        self.record_transfer(address, &data);
+
+        output.write_all(&data).context(IoError {})?;
+        output.flush().context(IoError {})?;
+
+        Ok(())
+    }
+
+    /// Program the internal flash with the flash loader of the debugger,
+    /// invalidate the flash cache line buffers and verify the data read back.
+    fn internal_write(&self, bank: u32, offset: u32, input: &mut dyn Read) -> Result<()> {
+        let mut data = Vec::new();
+        input.read_to_end(&mut data).context(IoError {})?;
+        let address = self.internal_address(bank, offset, data.len() as u32)?;
+        self.record_transfer(address, &data);
+
+        if !self.backend.is_halted().context(DssError {})? {
+            self.backend.halt().context(DssError {})?;
+        }
+
+        self.internal_program(address, &data)?;
+
+        println!(
+            "Wrote {} bytes to internal flash bank {} at 0x{:08X}",
+            data.len(),
+            bank,
+            address
+        );
+
+        Ok(())
+    }
+
+    /// Program the internal flash of the halted device with the flash loader
+    /// of the debugger, invalidate the flash cache line buffers and verify the
+    /// data read back.
+    fn internal_program(&self, address: u32, data: &[u8]) -> Result<()> {
+        let mut file =
+            temp::create(&self.command.xds_id, "internal.", ".bin").context(IoError {})?;
+        file.write_all(data).context(IoError {})?;
+        let path = file.into_temp_path();
+
+        self.backend
+            .load_raw(address, &path, 8)
+            .context(DssError {})?;
+        self.trace(|recorder| recorder.write_bytes(address, data));
+
+        // Disabling the line buffers flushes them, after which they are
+        // restored to their previous state
+        let ctl = self.dss_read_word(VIMS_CTL)?;
+        self.dss_write_word(VIMS_CTL, ctl | VIMS_CTL_LB_DIS)?;
+        self.dss_write_word(VIMS_CTL, ctl)?;
+
+        let read_back = self.dss_read_bytes(address, data.len() as u32)?;
+        ensure!(data == read_back.as_slice(), VerificationFailed {});
+
+        Ok(())
+    }
+
+    /// Write the MAC address overrides into the CCFG sector, which is read
+    /// and programmed as a whole as the flash loader erases whole sectors.
+    fn provision_mac(&self, ieee: Option<&[u8]>, ble: Option<&[u8]>) -> Result<()> {
+        let ccfg =
+            DeviceFamily::from(self.command.device)
+                .ccfg()
+                .context(MacOverrideUnsupported {
+                    device: self.command.device.to_string(),
+                })?;
+
+        if !self.backend.is_halted().context(DssError {})? {
+            self.backend.halt().context(DssError {})?;
+        }
+
+        let (start, end) = ccfg.sector;
+        let mut sector = self.dss_read_bytes(start, end - start)?;
+        let current = sector.clone();
+
+        // The overrides are stored least significant byte first
+        for (name, address, mac) in &[("IEEE", ccfg.ieee_mac, ieee), ("BLE", ccfg.ble_mac, ble)] {
+            if let Some(mac) = mac {
+                let offset = (address - start) as usize;
+                for (i, byte) in mac.iter().rev().enumerate() {
+                    sector[offset + i] = *byte;
+                }
+                let text: Vec<String> = mac.iter().map(|byte| format!("{:02X}", byte)).collect();
+                println!(
+                    "{} MAC override {} at 0x{:08X}",
+                    name,
+                    text.join(":"),
+                    address
+                );
+            }
+        }
+
+        if sector == current {
+            println!("CCFG already holds the MAC address overrides, nothing was written");
+            return Ok(());
+        }
+
+        self.record_transfer(start, &sector);
+        self.internal_program(start, &sector)?;
+        println!("Wrote CCFG sector at 0x{:08X}", start);
+
+        Ok(())
+    }
+
+    /// Recover from a firmware which no longer completes commands, without
+    /// power-cycling the board.
+    fn recover(&self) -> Result<()> {
+        if !self.backend.is_halted().context(DssError {})? {
+            self.backend.halt().context(DssError {})?;
+        }
+        println!("Core halted");
+
+        // A stale command or response left by the hung firmware would be
+        // taken up by the re-injected firmware or the tool
+        self.firmware.clear_doorbell().context(FirmwareError {})?;
+        println!("Doorbell cleared");
+
+        self.reset_into_firmware()?;
+        println!("Firmware re-injected");
+
+        println!("{}", self.xflash_info()?);
+
+        Ok(())
+    }
+
+    fn record_transfer(&self, offset: u32, data: &[u8]) {
+        self.transfer.replace(Some(Transfer {
+            offset,
+            length: data.len() as u32,
+            digest: history::digest(data),
+        }));
+    }
+
+    fn ping(&self, count: u32) -> Result<()> {
+        let mut latencies = Vec::with_capacity(count as _);
+
+        for seq in 0..count {
+            // Vary the nonce between runs, so a stale response left in the
+            // doorbell is not mistaken for a reply
+            let nonce = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .subsec_nanos()
+                ^ seq;
+
+            let start = Instant::now();
+            self.firmware.echo(nonce).context(FirmwareError {})?;
+            let latency = start.elapsed().as_secs_f64() * 1000.0;
+
+            println!("Reply from firmware: seq={} time={:.1} ms", seq, latency);
+            latencies.push(latency);
+        }
+
+        let min = latencies.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = latencies.iter().copied().fold(0.0, f64::max);
+        let avg = latencies.iter().sum::<f64>() / latencies.len() as f64;
+        println!(
+            "{} replies, min/avg/max = {:.1}/{:.1}/{:.1} ms",
+            latencies.len(),
+            min,
+            avg,
+            max
+        );
+
+        Ok(())
+    }
+
+    pub(crate) fn xflash_info(&self) -> Result<Xflash> {
+        self.firmware.get_xflash_info().context(FirmwareError {})
+    }
+
+    fn info(&self) -> Result<()> {
+        let xflash_info = self.xflash_info()?;
+
+        println!("{}", xflash_info);
+        if let Some(page_size) = xflash_info.page_size() {
+            println!("Page program size: {} bytes", page_size);
+            let timing = xflash_info.timing();
+            println!(
+                "Typical erase time: {} per sector, {} for the chip",
+                humantime::format_duration(timing.sector_erase.typical),
+                humantime::format_duration(timing.chip_erase.typical)
+            );
+        }
+        if let Some(range) = xflash_info.voltage_range() {
+            println!(
+                "Supply voltage: {:.2} V to {:.2} V",
+                *range.start() as f64 / 1000.0,
+                *range.end() as f64 / 1000.0
+            );
+        }
+        println!("Target VDDS: {:.2} V", self.measure_vdds()? as f64 / 1000.0);
+        // The embedded binaries predate the release in the descriptor
+        let release = self
+            .firmware
+            .release()
+            .unwrap_or_else(|| "unknown release".to_string());
+        match &self.command.firmware_elf {
+            Some(firmware_elf) => println!("Firmware: {} ({})", release, firmware_elf.display()),
+            None => println!(
+                "Firmware: {} ({})",
+                release,
+                assets::firmware_description(
+                    self.command.device,
+                    self.command.asset_dir.as_deref()
+                )
+            ),
+        }
+
+        Ok(())
+    }
+
+    /// Measure the supply voltage of the device in millivolts, with the
+    /// battery monitor.
+    fn measure_vdds(&self) -> Result<u32> {
+        let ctl = self.dss_read_word(AON_BATMON_CTL)?;
+        if ctl & AON_BATMON_CTL_EN != AON_BATMON_CTL_EN {
+            self.dss_write_word(AON_BATMON_CTL, AON_BATMON_CTL_EN)?;
+            thread::sleep(BATMON_SETTLE_TIME);
+        }
+
+        // Integer part in bit 10..8, fractional part in 1/256 V in bit 7..0
+        let bat = self.dss_read_word(AON_BATMON_BAT)?;
+
+        Ok((bat & 0x7FF) * 1000 / 256)
+    }
+
+    /// Fail, or warn if forced, if the external flash can not be safely
+    /// erased and programmed in the given range.
+    fn check_writable(&self, range: Range<u32>) -> Result<()> {
+        let xflash = self.firmware.get_xflash_info().context(FirmwareError {})?;
+
+        self.check_known(&xflash)?;
+        self.check_supply_voltage(&xflash)?;
+        self.check_write_protection(&xflash, range.clone())?;
+        self.check_partitions(range.clone())?;
+        self.check_executing(range)?;
+
+        Ok(())
+    }
+
+    /// Fail in strict mode if the external flash is not a known part, of which
+    /// the size, page size and timing would be assumed. Not overridden by
+    /// `--force`.
+    fn check_known(&self, xflash: &Xflash) -> Result<()> {
+        ensure!(
+            !self.command.strict || xflash.is_known(),
+            UnknownPart {
+                xflash: xflash.to_string()
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Fail, or warn if forced, if the supply voltage is below the minimum
+    /// for erase and program of the external flash.
+    fn check_supply_voltage(&self, xflash: &Xflash) -> Result<()> {
+        let min = match xflash.min_voltage() {
+            Some(min) => min,
+            None => return Ok(()),
+        };
+
+        let vdds = self.measure_vdds()?;
+        if vdds >= min {
+            return Ok(());
+        }
+
+        let err = LowVoltage { vdds, min }.build();
+        if self.command.force {
+            eprintln!("Warning: {}", err);
+            Ok(())
+        } else {
+            Err(err)
+        }
+    }
+
+    /// Warn if the supply voltage of the board profile is outside the supply
+    /// voltage range of the part, e.g. a 1.8 V part on a 3.3 V board.
+    fn check_voltage_domain(&self, xflash: &Xflash) {
+        let supply = match self
+            .command
+            .board
+            .as_ref()
+            .and_then(|board| board.supply_voltage)
+        {
+            Some(supply) => supply,
+            None => return,
+        };
+        let range = match xflash.voltage_range() {
+            Some(range) => range,
+            None => return,
+        };
+
+        if !range.contains(&supply) {
+            eprintln!(
+                "Warning: The board supplies the external flash with {:.2} V, outside the \
+                 {:.2} V to {:.2} V supply range of the {}",
+                supply as f64 / 1000.0,
+                *range.start() as f64 / 1000.0,
+                *range.end() as f64 / 1000.0,
+                xflash
+            );
+        }
+    }
+
+    /// Warn if the external flash is not the part fitted on the LaunchPad
+    /// given by `--device`, e.g. when it is the wrong LaunchPad or the board
+    /// was reworked.
+    fn check_launchpad_flash(&self, xflash: &Xflash) {
+        let (launchpad, expected) = match (
+            self.command.device.launchpad(),
+            self.command.device.launchpad_flash(),
+        ) {
+            (Some(launchpad), Some(expected)) => (launchpad, expected),
+            _ => return,
+        };
+
+        if xflash.name() != Some(expected) {
+            eprintln!(
+                "Warning: The {} has a {} external flash, but found {}",
+                launchpad, expected, xflash
+            );
+        }
+    }
+
+    /// Fail, or warn if forced, if the range overlaps blocks protected by the
+    /// status register of the external flash. Skipped with a warning with
+    /// firmware which cannot read the status register, as none of the
+    /// original commands returns it.
+    fn check_write_protection(&self, xflash: &Xflash, range: Range<u32>) -> Result<()> {
+        if !self.firmware.has_descriptor() {
+            eprintln!(
+                "Warning: The write protection of the external flash is not checked, the \
+                 firmware predates reading the status register"
+            );
+            return Ok(());
+        }
+
+        let status = self.firmware.read_status().context(FirmwareError {})?;
+
+        let protected = match xflash.protected_range(status) {
+            Some(protected) if protected.start < range.end && range.start < protected.end => {
+                protected
+            }
+            _ => return Ok(()),
+        };
+
+        let err = WriteProtected {
+            start: protected.start,
+            end: protected.end,
+            status,
+        }
+        .build();
+        if self.command.force {
+            eprintln!("Warning: {}", err);
+            Ok(())
+        } else {
+            Err(err)
+        }
+    }
+
+    /// Fail, or warn if forced, if the range overlaps a protected partition of
+    /// the partition table.
+    fn check_partitions(&self, range: Range<u32>) -> Result<()> {
+        let partitions = match &self.command.partitions {
+            Some(partitions) => partitions,
+            None => return Ok(()),
+        };
+
+        for partition in partitions.protected_in(range) {
+            let err = ProtectedPartition {
+                name: partition.name.clone(),
+                start: partition.offset,
+                end: partition.range().end,
+            }
+            .build();
+            if self.command.force {
+                eprintln!("Warning: {}", err);
+            } else {
+                return Err(err);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fail, or warn if forced, if the range is mapped for execute-in-place
+    /// by the application, which is paused rather than reset in cooperative
+    /// mode. The mapped ranges are the partitions flagged `xip`, or the whole
+    /// range if none is flagged and the firmware found the flash in XIP mode.
+    fn check_executing(&self, range: Range<u32>) -> Result<()> {
+        if self.command.cooperative.is_none() {
+            return Ok(());
+        }
+
+        let mapped: Vec<Range<u32>> = match &self.command.partitions {
+            Some(partitions) if partitions.has_xip() => partitions
+                .xip_in(range)
+                .map(|partition| partition.range())
+                .collect(),
+            _ if self.firmware.took_over_xip() => vec![range],
+            _ => Vec::new(),
+        };
+
+        for mapped in mapped {
+            let err = ExecutingRegion {
+                start: mapped.start,
+                end: mapped.end,
+            }
+            .build();
+            if self.command.force {
+                eprintln!("Warning: {}", err);
+            } else {
+                return Err(err);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn sector_erase(&self, offset: u32, length: u32) -> Result<()> {
+        self.check_writable(offset..offset.saturating_add(length))?;
+
+        self.firmware
+            .sector_erase(offset, length)
+            .context(FirmwareError {})?;
+
+        Ok(())
+    }
+
+    pub(crate) fn mass_erase(&self) -> Result<()> {
+        self.check_writable(0..u32::MAX)?;
+
+        print!("Starting mass erase, this may take some time... ");
+        io::stdout().flush().context(IoError {})?;
+
+        self.firmware.mass_erase().context(FirmwareError {})?;
+
+        println!("Done.");
+        Ok(())
+    }
+
+    /// Read from the external flash, through the data port if any. A read
+    /// through the debugger is resumed as by [`FlashRover::retry_transfer`].
+    fn read_data(&self, offset: u32, length: u32) -> Result<Vec<u8>> {
+        if let Some(port) = self.data_port.borrow_mut().as_mut() {
+            return self
+                .firmware
+                .read_data_uart(port, offset, length)
+                .context(FirmwareError {});
+        }
+
+        let mut data = Vec::with_capacity(length as _);
+        while let Err(err) = self
+            .firmware
+            .read_data_into(offset, length, &mut data)
+            .context(FirmwareError {})
+        {
+            self.retry_transfer(err)?;
+        }
+
+        Ok(data)
+    }
+
+    /// Write to the external flash, through the data port if any. A write
+    /// through the debugger is resumed from the last chunk acknowledged by
+    /// the firmware as by [`FlashRover::retry_transfer`], which rewrites the
+    /// same data over a partly programmed chunk.
+    fn write_data(&self, offset: u32, values: &[u8]) -> Result<()> {
+        if let Some(port) = self.data_port.borrow_mut().as_mut() {
+            return self
+                .firmware
+                .write_data_uart(port, offset, values)
+                .context(FirmwareError {});
+        }
+
+        let mut written = 0;
+        while let Err(err) = self
+            .firmware
+            .write_data_from(offset, values, &mut written)
+            .context(FirmwareError {})
+        {
+            self.retry_transfer(err)?;
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn read(&self, offset: u32, length: u32, output: &mut dyn Write) -> Result<()> {
+        let data = self.read_data(offset, length)?;
+        self.record_transfer(offset, &data);
+        io::copy(&mut data.as_slice(), output).context(IoError {})?;
+
+        Ok(())
+    }
+
+    /// CRC of the range, computed by the firmware, or of the range read back
+    /// with firmware without the checksum command.
+    pub(crate) fn checksum(
+        &self,
+        offset: u32,
+        length: u32,
+        algorithm: CrcAlgorithm,
+    ) -> Result<u32> {
+        if !self.firmware.has_descriptor() {
+            let data = self.read_data(offset, length)?;
+            return Ok(checksum::crc(algorithm, &data));
+        }
+
+        self.firmware
+            .checksum(offset, length, algorithm)
+            .context(FirmwareError {})
+    }
+
+    /// Print the CRC of the range, with the algorithm of `--crc-algorithm`.
+    fn print_checksum(&self, offset: u32, length: u32) -> Result<()> {
+        let end = offset.saturating_add(length);
+        if let Some(size) = self.xflash_info()?.size() {
+            ensure!(
+                end <= size,
+                OutOfBounds {
+                    start: offset,
+                    end,
+                    size
+                }
+            );
+        }
+
+        let algorithm = self.command.crc_algorithm;
+        let crc = self.checksum(offset, length, algorithm)?;
+        println!(
+            "{} {} of 0x{:08X}..0x{:08X}",
+            algorithm.label(),
+            algorithm.format(crc),
+            offset,
+            end
+        );
+
+        Ok(())
+    }
+
+    /// Write a JSON manifest of the CRC of every partition, to compare the
+    /// device against a golden manifest without reading the flash back.
+    fn write_partition_checksums(&self, output: &mut dyn Write) -> Result<()> {
+        let partitions = match &self.command.partitions {
+            Some(table) if !table.partitions.is_empty() => &table.partitions,
+            _ => return NoPartitions {}.fail(),
+        };
+
+        let xflash_info = self.xflash_info()?;
+        if let Some(size) = xflash_info.size() {
+            for partition in partitions {
+                let Range { start, end } = partition.range();
+                ensure!(end <= size, OutOfBounds { start, end, size });
+            }
+        }
+
+        let algorithm = self.command.crc_algorithm;
+        let mut entries = Vec::new();
+        for partition in partitions {
+            let crc = self.checksum(partition.offset, partition.length, algorithm)?;
+            entries.push(format!(
+                r#"    {{"name":{},"offset":{},"length":{},"crc":"{}"}}"#,
+                json::string(&partition.name),
+                partition.offset,
+                partition.length,
+                algorithm.format(crc)
+            ));
+        }
+
+        let flash = xflash_info
+            .name()
+            .map_or_else(|| "null".to_string(), json::string);
+        writeln!(
+            output,
+            "{{\n  \"device\": {},\n  \"flash\": {},\n  \"algorithm\": \"{}\",\n  \
+             \"partitions\": [\n{}\n  ]\n}}",
+            json::string(&self.command.device.to_string()),
+            flash,
+            algorithm.name(),
+            entries.join(",\n")
+        )
+        .context(IoError {})?;
+
+        Ok(())
+    }
+
+    /// Offset of the first non-blank byte in the range, if any.
+    /// Firmware without the blank check has the range read back instead.
+    pub(crate) fn blank_check(&self, offset: u32, length: u32) -> Result<Option<u32>> {
+        if !self.firmware.has_descriptor() {
+            let data = self.read_data(offset, length)?;
+            return Ok(data
+                .iter()
+                .position(|byte| *byte != 0xFF)
+                .map(|index| offset + index as u32));
+        }
+
+        self.firmware
+            .blank_check(offset, length)
+            .context(FirmwareError {})
+    }
+
+    pub(crate) fn write(
+        &self,
+        verify: Verify,
+        recheck: Option<Recheck>,
+        in_place: bool,
+        offset: u32,
+        length: Option<u32>,
+        input: &mut dyn Read,
+    ) -> Result<()> {
+        let input_buf = self.read_input(offset, length, input)?;
+
+        let length = input_buf.len() as u32;
+        self.record_transfer(offset, &input_buf);
+
+        self.check_writable(offset..offset.saturating_add(length))?;
+
+        let (written_address, written) = if in_place {
+            self.write_data(offset, &input_buf)?;
+
+            (offset, input_buf)
+        } else {
+            let first_address = offset - offset % firmware::BUF_SIZE;
+            let first_length = offset % firmware::BUF_SIZE;
+            let last_address = offset + length;
+            let last_length =
+                (firmware::BUF_SIZE - last_address % firmware::BUF_SIZE) % firmware::BUF_SIZE;
+
+            let first_sector_part: Vec<u8> = self.read_data(first_address, first_length)?;
+            let last_sector_part: Vec<u8> = self.read_data(last_address, last_length)?;
+
+            let total_input: Vec<u8> = first_sector_part
+                .into_iter()
+                .chain(input_buf)
+                .chain(last_sector_part)
+                .collect();
+            let total_length = total_input.len() as u32;
+
+            // Sectors which are already blank are not erased, and sectors
+            // to be left blank are not programmed
+            let dirty = self.dirty_sectors(first_address, total_length)?;
+            for (start, end) in sector_runs(dirty.len(), |index| dirty[index]) {
+                self.firmware
+                    .sector_erase(
+                        first_address + start as u32 * firmware::BUF_SIZE,
+                        (end - start) as u32 * firmware::BUF_SIZE,
+                    )
+                    .context(FirmwareError {})?;
+            }
+            let sectors: Vec<_> = total_input.chunks(firmware::BUF_SIZE as _).collect();
+            for (start, end) in sector_runs(sectors.len(), |index| {
+                sectors[index].iter().any(|byte| *byte != 0xFF)
+            }) {
+                let data_start = start * firmware::BUF_SIZE as usize;
+                let data_end = (end * firmware::BUF_SIZE as usize).min(total_input.len());
+                self.write_data(
+                    first_address + data_start as u32,
+                    &total_input[data_start..data_end],
+                )?;
+            }
+
+            let erased = dirty.iter().filter(|dirty| **dirty).count() as u32;
+            self.update_write_summary(|summary| {
+                summary.sectors_erased += erased;
+                summary.sectors_skipped += dirty.len() as u32 - erased;
+            });
+
+            (first_address, total_input)
+        };
+
+        match verify {
+            Verify::Off => {}
+            Verify::Full => {
+                self.reset_into_firmware()?;
+
+                let read_back = self.read_data(written_address, written.len() as u32)?;
+
+                ensure!(written.eq(&read_back), VerificationFailed {});
+            }
+            Verify::Sample { percent, seed } => {
+                self.verify_sample(percent, seed, written_address, &written)?
+            }
+        }
+
+        if let Some(recheck) = recheck {
+            self.recheck(recheck, written_address, &written)?;
+        }
+
+        Ok(())
+    }
+
+    /// Read the input to write at the offset, of `length` bytes if given, and
+    /// check that it fits the external flash before anything is erased. At
+    /// most one byte more than fits is buffered, so an oversized stream fails
+    /// without being read to its end.
+    fn read_input(
+        &self,
+        offset: u32,
+        length: Option<u32>,
+        input: &mut dyn Read,
+    ) -> Result<Vec<u8>> {
+        let size = self.xflash_info()?.size();
+
+        if let Some(size) = size {
+            // An input written from the end of the flash or past it has no
+            // room at all, whatever its length
+            ensure!(
+                offset < size,
+                OutOfBounds {
+                    start: offset,
+                    end: offset.saturating_add(length.unwrap_or(1)),
+                    size
+                }
+            );
+        }
+        if let (Some(length), Some(size)) = (length, size) {
+            let end = offset.saturating_add(length);
+            ensure!(
+                end <= size,
+                OutOfBounds {
+                    start: offset,
+                    end,
+                    size
+                }
+            );
+        }
+
+        let limit = match (length, size) {
+            (Some(length), _) => u64::from(length),
+            (None, Some(size)) => u64::from(size.saturating_sub(offset)) + 1,
+            (None, None) => u64::MAX,
+        };
+        let mut data = Vec::new();
+        input
+            .take(limit)
+            .read_to_end(&mut data)
+            .context(IoError {})?;
+
+        if let Some(length) = length {
+            ensure!(data.len() == length as usize, InvalidInputLength {});
+        }
+        if let Some(size) = size {
+            ensure!(
+                offset.saturating_add(data.len() as u32) <= size,
+                InputTooLarge { offset, size }
+            );
+        }
+
+        Ok(data)
+    }
+
+    fn update_write_summary(&self, f: impl FnOnce(&mut WriteSummary)) {
+        let mut summary = self.write_summary.get();
+        f(&mut summary);
+        self.write_summary.set(summary);
+    }
+
+    /// Which of the sectors of the sector-aligned range hold data and must be
+    /// erased before programming, found with the blank check of the firmware.
+    /// All sectors are erased with firmware without the blank check.
+    fn dirty_sectors(&self, address: u32, length: u32) -> Result<Vec<bool>> {
+        let count = length.div_ceil(firmware::BUF_SIZE) as usize;
+        if !self.firmware.has_descriptor() {
+            return Ok(vec![true; count]);
+        }
+
+        let mut dirty = vec![false; count];
+
+        let end = address + length;
+        let mut offset = address;
+        while offset < end {
+            match self.blank_check(offset, end - offset)? {
+                None => break,
+                // Continue at the sector after the first non-blank byte
+                Some(first) => {
+                    let index = (first - address) / firmware::BUF_SIZE;
+                    dirty[index as usize] = true;
+                    offset = address + (index + 1) * firmware::BUF_SIZE;
+                }
+            }
+        }
+
+        Ok(dirty)
+    }
+
+    /// Write at the first blank region large enough for the input, and print
+    /// the chosen offset.
+    fn write_auto_offset(
+        &self,
+        verify: Verify,
+        recheck: Option<Recheck>,
+        in_place: bool,
+        length: Option<u32>,
+        input: &mut dyn Read,
+    ) -> Result<()> {
+        let data = self.read_input(0, length, input)?;
+
+        let offset = self.find_blank_region(data.len() as u32)?;
+        println!("Writing at offset 0x{:08X}", offset);
+
+        self.write(
+            verify,
+            recheck,
+            in_place,
+            offset,
+            None,
+            &mut data.as_slice(),
+        )
+    }
+
+    /// Offset of the first sector-aligned blank region of `length` bytes,
+    /// found with the blank check of the firmware.
+    fn find_blank_region(&self, length: u32) -> Result<u32> {
+        let size = self.xflash_info()?.size().context(UnknownSize {})?;
+
+        let mut offset = 0_u32;
+        while offset.saturating_add(length) <= size {
+            match self.blank_check(offset, length)? {
+                None => return Ok(offset),
+                // Continue at the sector after the first non-blank byte
+                Some(dirty) => offset = (dirty / firmware::BUF_SIZE + 1) * firmware::BUF_SIZE,
+            }
+        }
+
+        NoBlankRegion { length }.fail()
+    }
+
+    /// Write the segments of an address-bearing image at the external flash
+    /// offsets of their addresses.
+    fn write_image(
+        &self,
+        verify: Verify,
+        recheck: Option<Recheck>,
+        in_place: bool,
+        format: InputFormat,
+        relocation: Relocation,
+        input: &mut dyn Read,
+    ) -> Result<()> {
+        let mut buf = Vec::new();
+        input.read_to_end(&mut buf).context(IoError {})?;
+
+        let segments = image::parse(format, &buf).context(ImageError {})?;
+
+        // Check every segment up front, so nothing is erased for an image
+        // which does not fit
+        if let Some(size) = self.xflash_info()?.size() {
+            for segment in &segments {
+                let start = relocation.apply(segment.address).context(ImageError {})?;
+                let end = start.saturating_add(segment.data.len() as u32);
+                ensure!(end <= size, OutOfBounds { start, end, size });
+            }
+        }
+
+        if in_place {
+            for segment in segments {
+                let offset = relocation.apply(segment.address).context(ImageError {})?;
+                self.write(
+                    verify,
+                    recheck,
+                    in_place,
+                    offset,
+                    None,
+                    &mut segment.data.as_slice(),
+                )?;
+            }
+            return Ok(());
+        }
+
+        // Coalesce the segments into the sectors they touch, so that sectors
+        // shared by several segments are read, erased and programmed once
+        let mut cache = SectorCache::new(firmware::BUF_SIZE);
+        for segment in segments {
+            let offset = relocation.apply(segment.address).context(ImageError {})?;
+            cache.write(offset, &segment.data);
+        }
+        for address in cache.partial_sectors() {
+            let content = self.read_data(address, firmware::BUF_SIZE)?;
+            cache.fill(address, &content);
+        }
+
+        for (offset, data) in cache.runs() {
+            self.write(
+                verify,
+                recheck,
+                in_place,
+                offset,
+                None,
+                &mut data.as_slice(),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the input after detecting the external flash and checking that
+    /// it fits, then verify the CRC32 of the read back data and report.
+    fn flash(&self, offset: u32, input: &mut dyn Read) -> Result<()> {
+        let start = Instant::now();
+
+        let xflash = self.xflash_info()?;
+        println!("Detected {}", xflash);
+
+        let data = self.read_input(offset, None, input)?;
+        let length = data.len() as u32;
+        // The size of the flash is not always known, but the range must at
+        // least be addressable
+        let end = offset.checked_add(length).context(OutOfBounds {
+            start: offset,
+            end: u32::MAX,
+            size: u32::MAX,
+        })?;
+
+        self.write(Verify::Off, None, false, offset, None, &mut data.as_slice())?;
+
+        self.reset_into_firmware()?;
+        let algorithm = self.command.crc_algorithm;
+        let expected = checksum::crc(algorithm, &data);
+        let actual = self.checksum(offset, length, algorithm)?;
+        ensure!(
+            actual == expected,
+            CrcMismatch {
+                algorithm,
+                expected,
+                actual
+            }
+        );
+
+        println!(
+            "Flashed {} bytes at 0x{:08X}..0x{:08X}, {} {} verified in {:.1} s",
+            length,
+            offset,
+            end,
+            algorithm.label(),
+            algorithm.format(expected),
+            start.elapsed().as_secs_f64()
+        );
+
+        Ok(())
+    }
+
+    /// Change the bytes at the offset, only in the bits selected by the mask if
+    /// any. The touched sectors are erased only if a bit is to be set, as
+    /// programming can only clear bits, and are verified in full.
+    fn patch(&self, offset: u32, bytes: &[u8], mask: Option<&[u8]>) -> Result<()> {
+        let length = bytes.len() as u32;
+        let end = offset.saturating_add(length);
+
+        let current = self.read_data(offset, length)?;
+        let patched: Vec<u8> = match mask {
+            Some(mask) => current
+                .iter()
+                .zip(bytes)
+                .zip(mask)
+                .map(|((current, byte), mask)| (current & !mask) | (byte & mask))
+                .collect(),
+            None => bytes.to_vec(),
+        };
+
+        if patched == current {
+            println!(
+                "0x{:08X}..0x{:08X} already holds the patched bytes, nothing written",
+                offset, end
+            );
+            return Ok(());
+        }
+
+        let in_place = current
+            .iter()
+            .zip(&patched)
+            .all(|(current, patched)| patched & !current == 0);
+        self.write(
+            Verify::Full,
+            None,
+            in_place,
+            offset,
+            None,
+            &mut patched.as_slice(),
+        )?;
+
+        let changed = current
+            .iter()
+            .zip(&patched)
+            .filter(|(current, patched)| current != patched)
+            .count();
+        println!(
+            "Patched {} of {} bytes at 0x{:08X}..0x{:08X}{}, verified",
+            changed,
+            length,
+            offset,
+            end,
+            if in_place {
+                " in place"
+            } else {
+                ", rewriting the touched sectors"
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Write the images and fill regions of the manifest, then read back every
+    /// region and print a verification report. The report ends with its own
+    /// SHA-256 digest, so an archived copy can be checked for alterations.
+    fn apply(&self, manifest: &Manifest, report_path: Option<&Path>) -> Result<()> {
+        let xflash = self.xflash_info()?;
+        println!("Detected {}", xflash);
+
+        // Check every step up front, so nothing is written for a manifest
+        // which does not fit
+        if let Some(size) = xflash.size() {
+            for step in &manifest.steps {
+                let range = step.range();
+                ensure!(
+                    range.end <= size,
+                    OutOfBounds {
+                        start: range.start,
+                        end: range.end,
+                        size
+                    }
+                );
+            }
+        }
+        for step in manifest.steps.iter().filter(|step| step.writes()) {
+            self.check_writable(step.range())?;
+        }
+
+        for step in &manifest.steps {
+            let range = step.range();
+            let data = match step {
+                Step::Image { path, data, .. } => {
+                    println!(
+                        "Writing {} to 0x{:08X}..0x{:08X}",
+                        path.display(),
+                        range.start,
+                        range.end
+                    );
+                    data.clone()
+                }
+                Step::Fill { length, value, .. } => {
+                    println!(
+                        "Filling 0x{:08X}..0x{:08X} with 0x{:02X}",
+                        range.start, range.end, value
+                    );
+                    vec![*value; *length as usize]
+                }
+                Step::Expect { .. } => continue,
+            };
+            self.write(
+                Verify::Off,
+                None,
+                false,
+                range.start,
+                None,
+                &mut data.as_slice(),
+            )?;
+        }
+
+        self.reset_into_firmware()?;
+
+        let mut report = format!(
+            "Verification report\n\
+             Manifest: {} (sha256:{})\n\
+             Device:   {}\n\
+             Probe:    {}\n\
+             Flash:    {}\n\
+             Operator: {}\n\
+             Date:     {}\n\n",
+            manifest.path.display(),
+            manifest.digest,
+            self.command.device.to_string(),
+            self.command.xds_id,
+            xflash,
+            history::user(),
+            humantime::format_rfc3339_seconds(SystemTime::now()),
+        );
+        let mut failed = 0;
+        for step in &manifest.steps {
+            let range = step.range();
+            let expected = step.expected_digest();
+            let actual = history::digest(&self.read_data(range.start, range.end - range.start)?);
+            let (kind, detail) = match step {
+                Step::Image { path, .. } => ("image", path.display().to_string()),
+                Step::Fill { value, .. } => ("fill", format!("0x{:02X}", value)),
+                Step::Expect { .. } => ("expect", String::new()),
+            };
+            let result = if actual == expected {
+                "PASS".to_string()
+            } else {
+                failed += 1;
+                format!("FAIL (read sha256:{})", actual)
+            };
+            report += &format!(
+                "{:<6}  0x{:08X}..0x{:08X}  sha256:{}  {}  {}\n",
+                kind, range.start, range.end, expected, result, detail
+            );
+        }
+        report += &format!(
+            "\nResult:   {} ({} of {} regions verified)\n",
+            if failed == 0 { "PASS" } else { "FAIL" },
+            manifest.steps.len() - failed,
+            manifest.steps.len()
+        );
+        let digest = history::digest(report.as_bytes());
+        report += &format!("Report:   sha256:{}\n", digest);
+
+        print!("{}", report);
+        if let Some(path) = report_path {
+            std::fs::write(path, &report).context(IoError {})?;
+        }
+
+        ensure!(
+            failed == 0,
+            ManifestVerificationFailed {
+                failed,
+                total: manifest.steps.len()
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Compare the CRC32 of a random percentage of the written sectors, chosen
+    /// by the seed, which is printed for reproducing the sample.
+    fn verify_sample(&self, percent: u32, seed: u32, address: u32, written: &[u8]) -> Result<()> {
+        self.reset_into_firmware()?;
+
+        let sectors: Vec<_> = written.chunks(firmware::BUF_SIZE as _).collect();
+        let count = (sectors.len() * percent as usize).div_ceil(100);
+        let mut indices = sample_indices(sectors.len(), count, seed);
+        indices.sort_unstable();
+
+        for index in indices {
+            let sector_address = address + index as u32 * firmware::BUF_SIZE;
+            let expected = sectors[index];
+
+            // Compared by CRC, computed by the firmware without reading the
+            // sector back, or over the read back sector with firmware
+            // predating the checksum command
+            let algorithm = self.command.crc_algorithm;
+            let read_back = self.checksum(sector_address, expected.len() as u32, algorithm)?;
+
+            ensure!(
+                checksum::crc(algorithm, expected) == read_back,
+                SampleVerificationFailed {
+                    address: sector_address,
+                    seed,
+                }
+            );
+        }
+
+        println!(
+            "Verified {} of {} written sectors, sampled with seed 0x{:08X}",
+            count,
+            sectors.len(),
+            seed
+        );
+
+        Ok(())
+    }
+
+    /// Read back an evenly spread percentage of the written sectors after a
+    /// delay, to catch corruption that appears after programming.
+    fn recheck(&self, recheck: Recheck, address: u32, written: &[u8]) -> Result<()> {
+        thread::sleep(recheck.delay);
+
+        // Check for supply sag before the reset, as the firmware is needed to
+        // identify the external flash
+        let xflash = self.firmware.get_xflash_info().context(FirmwareError {})?;
+        self.check_supply_voltage(&xflash)?;
+
+        // The device may have been reset by a brown-out in the meantime
+        self.reset_into_firmware()?;
+
+        let sectors: Vec<_> = written.chunks(firmware::BUF_SIZE as _).collect();
+        let count = (sectors.len() * recheck.percent as usize).div_ceil(100);
+
+        for i in 0..count {
+            let index = i * sectors.len() / count;
+            let sector_address = address + index as u32 * firmware::BUF_SIZE;
+            let expected = sectors[index];
+
+            let read_back = self.read_data(sector_address, expected.len() as u32)?;
+
+            ensure!(
+                expected == read_back.as_slice(),
+                RecheckFailed {
+                    address: sector_address,
+                    delay: recheck.delay,
+                }
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Runs of adjacent sectors of the `count` sectors, as ranges of sector
+/// indices, for which `selected` holds.
+fn sector_runs(count: usize, selected: impl Fn(usize) -> bool) -> Vec<(usize, usize)> {
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    for index in (0..count).filter(|index| selected(*index)) {
+        match runs.last_mut() {
+            Some((_, end)) if *end == index => *end += 1,
+            _ => runs.push((index, index + 1)),
+        }
+    }
+
+    runs
+}
+
+/// `count` distinct indices below `len`, chosen by a xorshift generator from
+/// the seed.
+fn sample_indices(len: usize, count: usize, seed: u32) -> Vec<usize> {
+    let mut indices: Vec<usize> = (0..len).collect();
+    // The state of a xorshift generator must not be zero
+    let mut state = if seed == 0 { 0x9E37_79B9 } else { seed };
+    for i in 0..count.min(len) {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        indices.swap(i, i + state as usize % (len - i));
+    }
+    indices.truncate(count);
+
+    indices
+}
+
+impl<'a> Drop for FlashRover<'a> {
+    fn drop(&mut self) {
+        let f = || -> Result<(), Box<dyn std::error::Error>> {
+            // The application is left running in cooperative mode
+            if self.command.cooperative.is_none() {
+                self.backend.halt()?;
+                self.backend.reset()?;
+            }
+            self.backend.disconnect()?;
 
             Ok(())
         };
@@ -9,14 +9,56 @@
 #include <stddef.h>
 #include <stdint.h>
 
+#include "log.hpp"
 #include "spi.hpp"
 
+#include <ti/devices/DeviceFamily.h>
+#include DeviceFamily_constructPath(driverlib/ioc.h)
+
 namespace bsp {
 
 struct Conf
 {
     uint32_t valid{ 0 };
     SpiPins spiPins{};
+    // Non-zero if the doorbell and the transfer buffers are protected by a
+    // CRC, independent of valid
+    uint32_t safeTransport{ 0 };
+    // DIO driven to muxLevel before accessing the external flash, on boards
+    // where its pins are shared through a mux, independent of valid
+    uint32_t muxDio{ IOID_UNUSED };
+    uint32_t muxLevel{ 0 };
+} __attribute__((packed));
+
+// Descriptor of the sections shared with the host, exported at a fixed offset
+// of 0x80 from the configuration. The host reads it after loading the firmware
+// instead of assuming the addresses of the doorbell and the transfer buffers.
+struct Descriptor
+{
+    static constexpr uint32_t Magic   = 0x56524C46;  // "FLRV"
+    static constexpr uint32_t Version = 1;
+
+    static constexpr uint32_t ReleaseMagic = 0x4C455246;  // "FREL"
+    // Major, minor and patch in bits 23..16, 15..8 and 7..0, kept the same as
+    // the version in Cargo.toml
+    static constexpr uint32_t Release = (0 << 16) | (3 << 8) | 3;
+
+    uint32_t magic;
+    uint32_t version;
+    volatile const void* doorbell;
+    const void* buf;
+    uint32_t bufSize;
+    uint32_t bufCount;
+    // The debug log ring has its own magic, as the descriptor of firmware
+    // predating it ends at bufCount
+    uint32_t logMagic;
+    const LogRing* log;
+    uint32_t logSize;
+    // Release of flash-rover the firmware is built from, shown by the host.
+    // It has its own magic, as the descriptor of firmware predating it ends at
+    // logSize
+    uint32_t releaseMagic;
+    uint32_t release;
 } __attribute__((packed));
 
 } /* namespace bsp */
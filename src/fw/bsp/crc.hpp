@@ -0,0 +1,97 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+#ifndef CRC_HPP_
+#define CRC_HPP_
+
+#include <stddef.h>
+#include <stdint.h>
+
+namespace bsp {
+
+// CRC-32 (IEEE 802.3), as computed by the host. Bitwise, as a lookup table
+// would not fit next to the transfer buffers on all devices.
+inline uint32_t crc32(const volatile void* data, size_t length)
+{
+    const volatile uint8_t* bytes = static_cast<const volatile uint8_t*>(data);
+    uint32_t crc = 0xFFFFFFFF;
+
+    for (size_t i = 0; i < length; ++i)
+    {
+        crc ^= bytes[i];
+        for (int bit = 0; bit < 8; ++bit)
+        {
+            crc = (crc >> 1) ^ (0xEDB88320 & (0 - (crc & 1)));
+        }
+    }
+
+    return ~crc;
+}
+
+// Algorithms of the Checksum command, as numbered by the host
+enum class CrcAlgorithm : uint32_t
+{
+    Crc32      = 0,  // CRC-32/ISO-HDLC, the same as crc32()
+    Crc32c     = 1,  // CRC-32/ISCSI
+    Crc16Ccitt = 2,  // CRC-16/CCITT-FALSE
+};
+
+// CRC of data passed in pieces, e.g. block by block from the external flash
+class RunningCrc
+{
+private:
+    CrcAlgorithm algorithm_;
+    uint32_t crc_;
+
+public:
+    explicit RunningCrc(CrcAlgorithm algorithm)
+        : algorithm_{ algorithm }
+        , crc_{ algorithm == CrcAlgorithm::Crc16Ccitt ? 0xFFFFu : 0xFFFFFFFFu }
+    {
+    }
+
+    bool valid() const
+    {
+        return algorithm_ == CrcAlgorithm::Crc32
+            || algorithm_ == CrcAlgorithm::Crc32c
+            || algorithm_ == CrcAlgorithm::Crc16Ccitt;
+    }
+
+    void update(const uint8_t* bytes, size_t length)
+    {
+        if (algorithm_ == CrcAlgorithm::Crc16Ccitt)
+        {
+            for (size_t i = 0; i < length; ++i)
+            {
+                crc_ ^= static_cast<uint32_t>(bytes[i]) << 8;
+                for (int bit = 0; bit < 8; ++bit)
+                {
+                    crc_ = ((crc_ << 1) ^ (0x1021 & (0 - ((crc_ >> 15) & 1)))) & 0xFFFF;
+                }
+            }
+            return;
+        }
+
+        // Both CRC-32 variants are reflected, with the reversed polynomial
+        uint32_t poly = algorithm_ == CrcAlgorithm::Crc32c ? 0x82F63B78 : 0xEDB88320;
+        for (size_t i = 0; i < length; ++i)
+        {
+            crc_ ^= bytes[i];
+            for (int bit = 0; bit < 8; ++bit)
+            {
+                crc_ = (crc_ >> 1) ^ (poly & (0 - (crc_ & 1)));
+            }
+        }
+    }
+
+    uint32_t value() const
+    {
+        return algorithm_ == CrcAlgorithm::Crc16Ccitt ? crc_ : ~crc_;
+    }
+};
+
+} /* namespace bsp */
+
+#endif /* CRC_HPP_ */
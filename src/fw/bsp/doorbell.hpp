@@ -11,6 +11,7 @@
 
 #include <algorithm>
 
+#include "crc.hpp"
 #include "spi.hpp"
 
 #include <ti/devices/DeviceFamily.h>
@@ -20,6 +21,35 @@ namespace bsp {
 
 #define ATTR_PACKED  __attribute__((packed))
 
+// Commands are handled one at a time.
+//
+// ReadConfig and WriteStatus access the configuration register of Macronix
+// parts, and fail with Error on other parts. WriteStatus writes the status
+// register alone unless withConfig is set.
+//
+// Checksum responds with Crc, the CRC of the range read block by block through
+// the first transfer buffer, with the algorithm numbered as by CrcAlgorithm.
+//
+// UartRead and UartWrite transfer the data over the UART configured with
+// UartConfig instead of the transfer buffers. For UartWrite, the firmware sends
+// a credit byte (0x06) each time it is ready to receive the next block of up
+// to XFLASH_BUF_SIZE bytes, as the UART has no hardware flow control.
+//
+// XflashInfo responds with flags in arg2, bit 0 set if the part was identified
+// by its electronic signature only, and bit 1 set if the part was found in the
+// XIP mode left by the application and taken over. Release hands such a part
+// back in its power-on state, before the host resets the device. PowerDown
+// puts the part in deep power-down, in which it stays until the application
+// wakes it up with Release Power-Down.
+//
+// Bit 2 of the XflashInfo flags is set if the JEDEC ID was read, which is then
+// in bit 31..8 of arg0 as manufacturer ID, memory type and capacity, with the
+// count of continuation codes before the manufacturer ID in bit 15..8 of arg2.
+//
+// With safe transport, each command and response is followed by the CRC of its
+// four words, written before the kind. Commands with a bad CRC are answered
+// with ErrorCrc without being handled. WriteBlock additionally takes the CRC of
+// the buffer data, and ReadBlock responds with Crc instead of Ok.
 struct Command
 {
     enum class Kind : uint32_t
@@ -29,8 +59,20 @@ struct Command
         XflashInfo  = 0xC0,
         SectorErase = 0xC1,  // <offset (u32), length (u32)>
         MassErase   = 0xC2,
-        ReadBlock   = 0xC3,  // <offset (u32), length (u32)>
-        WriteBlock  = 0xC4,  // <offset (u32), length (u32)> <data... (u8)>
+        ReadBlock   = 0xC3,  // <offset (u32), length (u32), buffer (u32)>
+        WriteBlock  = 0xC4,  // <offset (u32), length (u32), buffer (u32)> <data... (u8)>
+        Echo        = 0xC5,  // <nonce (u32)>
+        Abort       = 0xC6,  // Written while a command is in progress
+        ReadStatus  = 0xC7,
+        UartConfig  = 0xC8,  // <rx (u32), tx (u32), baudRate (u32)>
+        UartRead    = 0xC9,  // <offset (u32), length (u32)>, data is sent over the UART
+        UartWrite   = 0xCA,  // <offset (u32), length (u32)>, data is received over the UART
+        BlankCheck  = 0xCB,  // <offset (u32), length (u32)>
+        ReadConfig  = 0xCC,
+        WriteStatus = 0xCD,  // <status (u32), config (u32), withConfig (u32)>
+        Release     = 0xCE,
+        PowerDown   = 0xCF,
+        Checksum    = 0xB0,  // <offset (u32), length (u32), algorithm (u32)>
     };
 
     Kind kind{ Kind::None };
@@ -46,12 +88,19 @@ struct Response
         None             = 0x00,
 
         Ok               = 0xD0,
-        XflashInfo       = 0xD1,  // <manfId (u8), devId (u8)>
+        XflashInfo       = 0xD1,  // <manfId (u8) jedecId (u24), devId (u8), flags (u8) bank (u8)>
+        Echo             = 0xD2,  // <nonce (u32)>
+        Status           = 0xD3,  // <status (u8)>
+        Crc              = 0xD4,  // <crc (u32)>, of the data read into the buffer or of the Checksum range
+        Blank            = 0xD5,  // <offset (u32)>, of the first non-blank byte, or the end of the range
+        Config           = 0xD6,  // <config (u32)>, with the first configuration byte in bit 7..0
 
         Error            = 0x80,
         ErrorSpi         = 0x81,
         ErrorXflash      = 0x82,
         ErrorBufOverflow = 0x83,
+        ErrorAborted     = 0x84,
+        ErrorCrc         = 0x85,
     };
 
     Kind kind{ Kind::None };
@@ -62,20 +111,33 @@ struct Response
 
 
 
+// Only used with safe transport
+struct Crc
+{
+    uint32_t cmd{ 0 };
+    uint32_t rsp{ 0 };
+    uint32_t data{ 0 };  // Of the WriteBlock buffer data
+} ATTR_PACKED;
+
 struct Doorbell
 {
     Command cmd;
     Response rsp;
+    Crc crc;
 } ATTR_PACKED;
 
 class Server
 {
 private:
     volatile Doorbell& doorbell_;
+    bool safeTransport_;
+    bool cmdIntact_{ true };
+    uint32_t dataCrc_{ 0 };
 
 public:
-    Server(volatile Doorbell& doorbell)
+    Server(volatile Doorbell& doorbell, bool safeTransport)
         : doorbell_{ doorbell }
+        , safeTransport_{ safeTransport }
     {
         doorbell_.cmd.kind = Command::Kind::None;
         doorbell_.rsp.kind = Response::Kind::None;
@@ -88,30 +150,84 @@ public:
     Command waitForCommand()
     {
         Command cmd{};
-        for (;;)
-        {
-            while (doorbell_.cmd.kind == Command::Kind::None);
+        while (!tryTakeCommand(cmd));
+        return cmd;
+    }
 
-            switch (doorbell_.cmd.kind)
+    // Take the pending command, if any, without waiting
+    bool tryTakeCommand(Command& cmd)
+    {
+        switch (doorbell_.cmd.kind)
+        {
+        case Command::Kind::None:
+            return false;
+
+        case Command::Kind::XflashInfo:
+        case Command::Kind::MassErase:
+        case Command::Kind::SectorErase:
+        case Command::Kind::ReadBlock:
+        case Command::Kind::WriteBlock:
+        case Command::Kind::Echo:
+        case Command::Kind::ReadStatus:
+        case Command::Kind::UartConfig:
+        case Command::Kind::UartRead:
+        case Command::Kind::UartWrite:
+        case Command::Kind::BlankCheck:
+        case Command::Kind::ReadConfig:
+        case Command::Kind::WriteStatus:
+        case Command::Kind::Release:
+        case Command::Kind::PowerDown:
+        case Command::Kind::Checksum:
+            cmd.kind = doorbell_.cmd.kind;
+            cmd.arg0 = doorbell_.cmd.arg0;
+            cmd.arg1 = doorbell_.cmd.arg1;
+            cmd.arg2 = doorbell_.cmd.arg2;
+
+            if (safeTransport_)
             {
-            case Command::Kind::XflashInfo:
-            case Command::Kind::MassErase:
-            case Command::Kind::SectorErase:
-            case Command::Kind::ReadBlock:
-            case Command::Kind::WriteBlock:
-                cmd.kind = doorbell_.cmd.kind;
-                cmd.arg0 = doorbell_.cmd.arg0;
-                cmd.arg1 = doorbell_.cmd.arg1;
-                cmd.arg2 = doorbell_.cmd.arg2;
-
-                doorbell_.cmd.kind = Command::Kind::None;
-                return cmd;
-
-            default:
-                // Invalid command, clear command and wait again.
-                doorbell_.cmd.kind = Command::Kind::None;
-                continue;
+                cmdIntact_ = crc32(&cmd, sizeof(cmd)) == doorbell_.crc.cmd;
+                dataCrc_ = doorbell_.crc.data;
             }
+
+            doorbell_.cmd.kind = Command::Kind::None;
+            return true;
+
+        default:
+            // Invalid command, clear command and wait again.
+            doorbell_.cmd.kind = Command::Kind::None;
+            return false;
+        }
+    }
+
+    bool safeTransport() const
+    {
+        return safeTransport_;
+    }
+
+    // Whether the last taken command matched its CRC, always true without
+    // safe transport
+    bool commandIntact() const
+    {
+        return cmdIntact_;
+    }
+
+    // CRC of the buffer data sent along with the last taken command
+    uint32_t dataCrc() const
+    {
+        return dataCrc_;
+    }
+
+    // Whether the host has requested to abort the command in progress
+    bool abortRequested() const
+    {
+        return doorbell_.cmd.kind == Command::Kind::Abort;
+    }
+
+    void clearAbort()
+    {
+        if (abortRequested())
+        {
+            doorbell_.cmd.kind = Command::Kind::None;
         }
     }
 
@@ -120,6 +236,10 @@ public:
         doorbell_.rsp.arg0 = rsp.arg0;
         doorbell_.rsp.arg1 = rsp.arg1;
         doorbell_.rsp.arg2 = rsp.arg2;
+        if (safeTransport_)
+        {
+            doorbell_.crc.rsp = crc32(&rsp, sizeof(rsp));
+        }
         // Kind must be set last, this will trigger the response
         doorbell_.rsp.kind = rsp.kind;
         // Wait until response has been consumed
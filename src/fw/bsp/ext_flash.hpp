@@ -11,6 +11,7 @@
 
 #include <array>
 
+#include "log.hpp"
 #include "power.hpp"
 #include "spi.hpp"
 
@@ -32,7 +33,19 @@ struct XflashInfo
     uint32_t deviceSize{ 0 };
     uint8_t manfId{ 0 };
     uint8_t devId{ 0 };
+    // Size of the page program buffer, programs never cross a page boundary
+    uint32_t pageSize{ 0 };
     bool supported{ false };
+    // Identified by the electronic signature (RES) only, manfId is not valid
+    bool signatureOnly{ false };
+    // Found in continuous read (XIP) or QPI mode left by the application, and
+    // brought back to standard SPI mode
+    bool tookOverXip{ false };
+    // JEDEC ID read with RDID, as manufacturer ID, memory type and capacity,
+    // with the manufacturer ID preceded by jedecBank continuation codes (0x7F)
+    bool hasJedecId{ false };
+    uint8_t jedecBank{ 0 };
+    uint8_t jedecId[3]{ 0, 0, 0 };
 };
 
 static constexpr std::array<XflashInfo, 4> supportedHw = {{
@@ -40,25 +53,29 @@ static constexpr std::array<XflashInfo, 4> supportedHw = {{
     {
         0x200000,  // 2 MByte (16 Mbit)
         0xC2,
-        0x15
+        0x15,
+        256
     },
     // Macronics MX25R8035F
     {
         0x100000,  // 1 MByte (8 Mbit)
         0xC2,
-        0x14
+        0x14,
+        256
     },
     // WinBond W25X40CL
     {
         0x080000,  // 512 KByte (4 Mbit)
         0xEF,
-        0x12
+        0x12,
+        256
     },
     // WinBond W25X20CL
     {
         0x040000,  // 256 KByte (2 Mbit)
         0xEF,
-        0x11
+        0x11,
+        256
     },
 }};
 
@@ -80,19 +97,30 @@ class Xflash
 private:
     struct OpCode
     {
+        static constexpr uint8_t write_status = 0x01;  // Write status (and configuration) register
         static constexpr uint8_t program      = 0x02;  // Page program
         static constexpr uint8_t read         = 0x03;  // Read data
         static constexpr uint8_t read_status  = 0x05;  // Read status register
         static constexpr uint8_t write_enable = 0x06;  // Write enable
+        static constexpr uint8_t mx_read_cr   = 0x15;  // Macronix read configuration register
         static constexpr uint8_t erase_4k     = 0x20;  // Sector erase 4K bytes
         static constexpr uint8_t erase_32k    = 0x52;  // Sector erase 32K bytes
         static constexpr uint8_t erase_64k    = 0xD8;  // Sector erase 64K bytes
         static constexpr uint8_t erase_all    = 0xC7;  // Sector erase all bytes
         static constexpr uint8_t mdid         = 0x90;  // Manufacturer Device ID
+        static constexpr uint8_t rdid         = 0x9F;  // Read JEDEC ID
         static constexpr uint8_t dp           = 0xB9;  // Power down
         static constexpr uint8_t rdp          = 0xAB;  // Power standby
+        static constexpr uint8_t res          = 0xAB;  // Read electronic signature
         static constexpr uint8_t rsten        = 0x66;  // Reset-Enable
         static constexpr uint8_t rst          = 0x99;  // Reset
+        static constexpr uint8_t mode_reset   = 0xFF;  // Continuous read mode reset, WinBond exit QPI
+        static constexpr uint8_t mx_rstqio    = 0xF5;  // Macronix exit QPI
+    };
+
+    struct ManfId
+    {
+        static constexpr uint8_t macronix = 0xC2;
     };
 
     struct StatusCode
@@ -119,7 +147,9 @@ private:
     } xflash_;
 
 public:
-    static constexpr uint32_t programPageSize = 256;
+    // Page size assumed for parts missing from supportedHw, small enough for
+    // the page program buffer of any part
+    static constexpr uint32_t fallbackPageSize = 64;
     static constexpr uint32_t eraseSectorSize = 4096;
 
     Xflash(const XflashObj& obj, Spi& spi, Power& power)
@@ -133,13 +163,24 @@ public:
 
         if (!powerStandby())
         {
+            debugLog("Xflash: no response to release from power-down");
             close();
             return;
         }
 
         if (!verifyPart())
         {
-            close();
+            // The device reset does not reset the part, which may still be in
+            // the XIP mode the application left it in
+            debugLog("Xflash: part not verified, exiting XIP mode");
+            if (!exitXip() || !verifyPart())
+            {
+                debugLog("Xflash: part not verified after exiting XIP mode");
+                close();
+                return;
+            }
+
+            xflash_.info.tookOverXip = true;
         }
     }
 
@@ -160,6 +201,98 @@ public:
         }
     }
 
+    bool readStatus(uint8_t& status)
+    {
+        // Wait till previous erase/program operation completes
+        bool ret = waitReady();
+        if (!ret)
+        {
+            return false;
+        }
+
+        const uint8_t wbuf[] = { OpCode::read_status };
+
+        select();
+
+        ret = spi_.write(wbuf, sizeof(wbuf));
+        if (ret)
+        {
+            ret = spi_.read(&status, sizeof(status));
+        }
+
+        deselect();
+
+        return ret;
+    }
+
+    // Whether the part has the configuration register of Macronix parts,
+    // holding e.g. the dummy cycle and L/H switch (high performance) bits
+    bool hasConfigRegister() const
+    {
+        return xflash_.valid && !xflash_.info.signatureOnly && xflash_.info.manfId == ManfId::macronix;
+    }
+
+    // Read the two bytes of the Macronix configuration register
+    bool readConfig(uint8_t (&config)[2])
+    {
+        bool ret = waitReady();
+        if (!ret)
+        {
+            return false;
+        }
+
+        const uint8_t wbuf[] = { OpCode::mx_read_cr };
+
+        select();
+
+        ret = spi_.write(wbuf, sizeof(wbuf));
+        if (ret)
+        {
+            ret = spi_.read(config, sizeof(config));
+        }
+
+        deselect();
+
+        return ret;
+    }
+
+    // Write the status register, followed by the two bytes of the Macronix
+    // configuration register if config is given
+    bool writeStatus(uint8_t status, const uint8_t* config)
+    {
+        bool ret = waitReady();
+        if (!ret)
+        {
+            return false;
+        }
+
+        ret = writeEnable();
+        if (!ret)
+        {
+            return false;
+        }
+
+        const uint8_t wbuf[] = {
+            OpCode::write_status,
+            status,
+            config != nullptr ? config[0] : uint8_t{ 0 },
+            config != nullptr ? config[1] : uint8_t{ 0 },
+        };
+
+        select();
+
+        ret = spi_.write(wbuf, config != nullptr ? sizeof(wbuf) : 2);
+
+        deselect();
+
+        if (!ret)
+        {
+            return false;
+        }
+
+        return waitReady();
+    }
+
     bool read(uint8_t* buf, size_t len, size_t offset)
     {
         // Wait till previous erase/program operation completes
@@ -192,12 +325,20 @@ public:
         return ret;
     }
 
-    bool write(const uint8_t* buf, size_t len, size_t offset)
+    // Program data, stopping early with failure if shouldAbort returns true
+    template <typename ShouldAbort>
+    bool write(const uint8_t* buf, size_t len, size_t offset, ShouldAbort&& shouldAbort)
     {
         bool ret;
 
         while (len > 0)
         {
+            if (shouldAbort())
+            {
+                waitReady();
+                return false;
+            }
+
             // Wait till previous erase/program operation completes
             ret = waitReady();
             if (!ret)
@@ -212,7 +353,7 @@ public:
             }
 
             // interim length per instruction
-            size_t ilen = programPageSize - (offset % programPageSize);
+            size_t ilen = pageSize() - (offset % pageSize());
             if (len < ilen)
             {
                 ilen = len;
@@ -254,7 +395,12 @@ public:
         return true;
     }
 
-    bool erase(size_t len, size_t offset)
+    // Erase sectors, stopping early with failure if shouldAbort returns true.
+    // shouldAbort is polled while a sector erase is in progress. The sector
+    // erase in progress is always completed, leaving the part in a defined
+    // state.
+    template <typename ShouldAbort>
+    bool erase(size_t len, size_t offset, ShouldAbort&& shouldAbort)
     {
         // Note that Block erase might be more efficient when the floor map
         // is well planned for OTA but to simplify for the temporary implementation,
@@ -267,10 +413,17 @@ public:
 
         for (size_t i = 0; i < numsectors; i++)
         {
+            if (shouldAbort())
+            {
+                waitReady();
+                return false;
+            }
+
             // Wait till previous erase/program operation completes
-            ret = waitReady();
+            ret = waitReady(shouldAbort);
             if (!ret)
             {
+                waitReady();
                 return false;
             }
 
@@ -301,10 +454,20 @@ public:
             offset += eraseSectorSize;
         }
 
-        return waitReady();
+        ret = waitReady(shouldAbort);
+        if (!ret)
+        {
+            waitReady();
+        }
+
+        return ret;
     }
 
-    bool massErase()
+    // Erase the entire chip. If shouldAbort returns true while the erase is in
+    // progress, the part is reset, which terminates the erase on most parts
+    // and leaves the chip partially erased.
+    template <typename ShouldAbort>
+    bool massErase(ShouldAbort&& shouldAbort)
     {
         bool ret;
 
@@ -329,7 +492,14 @@ public:
 
         deselect();
 
-        return waitReady();
+        ret = waitReady(shouldAbort);
+        if (!ret && shouldAbort())
+        {
+            softwareReset();
+            return false;
+        }
+
+        return ret;
     }
 
     bool reset()
@@ -342,6 +512,99 @@ public:
             return false;
         }
 
+        return softwareReset();
+    }
+
+    uint32_t pageSize() const
+    {
+        if (xflash_.info.supported)
+        {
+            return xflash_.info.pageSize;
+        }
+        else
+        {
+            return fallbackPageSize;
+        }
+    }
+
+    // Hand the part back to the application in its power-on state, from
+    // which the application enters XIP mode again after the device reset
+    bool release()
+    {
+        return reset();
+    }
+
+    // Put the part in deep power-down, the state battery-powered applications
+    // leave it in between accesses
+    bool sleep()
+    {
+        return powerDown() && waitPowerDown();
+    }
+
+    void close()
+    {
+        // Put the part in low power mode
+        powerDown();
+        waitPowerDown();
+
+    }
+
+private:
+    static void delay(uint32_t us)
+    {
+        // ui32Count = [delay in us] * [CPU clock in MHz] / [cycles per loop]
+        CPUdelay((us * 48) / 4);
+    }
+
+    void select()
+    {
+        GPIO_clearDio(obj_.csn);
+    }
+
+    void deselect()
+    {
+        GPIO_setDio(obj_.csn);
+    }
+
+    // Leave continuous read and QPI mode, in which the part ignores standard
+    // SPI instructions, and reset the part
+    bool exitXip()
+    {
+        // Clocking ones through the mode bits ends continuous read mode
+        const uint8_t mode_reset_buf[] = { OpCode::mode_reset, OpCode::mode_reset };
+        const uint8_t rstqio_buf[] = { OpCode::mx_rstqio };
+
+        select();
+
+        bool ret = spi_.write(mode_reset_buf, sizeof(mode_reset_buf));
+
+        deselect();
+
+        if (!ret)
+        {
+            return false;
+        }
+
+        select();
+
+        ret = spi_.write(rstqio_buf, sizeof(rstqio_buf));
+
+        deselect();
+
+        if (!ret)
+        {
+            return false;
+        }
+
+        return softwareReset();
+    }
+
+    // Reset the part without waiting for an operation in progress, which
+    // terminates the operation
+    bool softwareReset()
+    {
+        bool ret;
+
         const uint8_t rsten_buf[] = { OpCode::rsten };
         const uint8_t rst_buf[] = { OpCode::rst };
 
@@ -386,35 +649,93 @@ public:
         return waitReady();
     }
 
-    void close()
+    bool readInfo()
     {
-        // Put the part in low power mode
-        powerDown();
-        waitPowerDown();
+        const uint8_t wbuf[] = { OpCode::mdid, 0xFF, 0xFF, 0x00 };
+        uint8_t rbuf[2];
 
-    }
+        select();
 
-private:
-    static void delay(uint32_t us)
-    {
-        // ui32Count = [delay in us] * [CPU clock in MHz] / [cycles per loop]
-        CPUdelay((us * 48) / 4);
-    }
+        bool ret = spi_.write(wbuf, sizeof(wbuf));
+        if (ret)
+        {
+            ret = spi_.read(rbuf, sizeof(rbuf));
+            if (ret)
+            {
+                xflash_.info.manfId = rbuf[0];
+                xflash_.info.devId = rbuf[1];
+            }
+        }
+        xflash_.valid = ret;
 
-    void select()
-    {
-        GPIO_clearDio(obj_.csn);
+        deselect();
+
+        // Legacy parts without the manufacturer/device ID instruction shift
+        // out all zeros or all ones, fall back to the electronic signature
+        if (ret && (xflash_.info.manfId == 0x00 || xflash_.info.manfId == 0xFF))
+        {
+            ret = readSignature();
+        }
+        else if (ret)
+        {
+            ret = readJedecId();
+        }
+
+        return ret;
     }
 
-    void deselect()
+    bool readJedecId()
     {
-        GPIO_setDio(obj_.csn);
+        // Manufacturers beyond the first bank of JEP106 are preceded by a
+        // continuation code per bank
+        static constexpr size_t maxBank = 8;
+        static constexpr uint8_t continuation = 0x7F;
+        const uint8_t wbuf[] = { OpCode::rdid };
+        uint8_t rbuf[maxBank + 3];
+
+        xflash_.info.hasJedecId = false;
+
+        select();
+
+        bool ret = spi_.write(wbuf, sizeof(wbuf));
+        if (ret)
+        {
+            ret = spi_.read(rbuf, sizeof(rbuf));
+        }
+
+        deselect();
+
+        if (!ret)
+        {
+            return false;
+        }
+
+        size_t bank = 0;
+        while (bank < maxBank && rbuf[bank] == continuation)
+        {
+            bank++;
+        }
+
+        // Parts without the instruction shift out all zeros or all ones, and
+        // are identified by the manufacturer/device ID alone
+        if (rbuf[bank] == 0x00 || rbuf[bank] == 0xFF)
+        {
+            return true;
+        }
+
+        xflash_.info.hasJedecId = true;
+        xflash_.info.jedecBank = static_cast<uint8_t>(bank);
+        xflash_.info.jedecId[0] = rbuf[bank];
+        xflash_.info.jedecId[1] = rbuf[bank + 1];
+        xflash_.info.jedecId[2] = rbuf[bank + 2];
+
+        return true;
     }
 
-    bool readInfo()
+    bool readSignature()
     {
-        const uint8_t wbuf[] = { OpCode::mdid, 0xFF, 0xFF, 0x00 };
-        uint8_t rbuf[2];
+        const uint8_t wbuf[] = { OpCode::res, 0xFF, 0xFF, 0xFF };
+        uint8_t rbuf[1];
 
         select();
 
@@ -424,8 +745,9 @@ private:
             ret = spi_.read(rbuf, sizeof(rbuf));
             if (ret)
             {
-                xflash_.info.manfId = rbuf[0];
-                xflash_.info.devId = rbuf[1];
+                xflash_.info.manfId = 0x00;
+                xflash_.info.devId = rbuf[0];
+                xflash_.info.signatureOnly = true;
             }
         }
         xflash_.valid = ret;
@@ -449,10 +771,13 @@ private:
 
         for (const XflashInfo& hw : supportedHw)
         {
-            if (xflash_.info.manfId == hw.manfId && xflash_.info.devId == hw.devId)
+            const bool manfIdMatches =
+                xflash_.info.signatureOnly || xflash_.info.manfId == hw.manfId;
+            if (manfIdMatches && xflash_.info.devId == hw.devId)
             {
                 xflash_.info.supported = true;
                 xflash_.info.deviceSize = hw.deviceSize;
+                xflash_.info.pageSize = hw.pageSize;
                 return true;
             }
         }
@@ -499,6 +824,13 @@ private:
     }
 
     bool waitReady()
+    {
+        return waitReady([]() { return false; });
+    }
+
+    // Wait till the part is ready, failing early if shouldAbort returns true
+    template <typename ShouldAbort>
+    bool waitReady(ShouldAbort&& shouldAbort)
     {
         const uint8_t wbuf[1] = { OpCode::read_status };
 
@@ -531,6 +863,11 @@ private:
                 /* Now ready */
                 return true;
             }
+
+            if (shouldAbort())
+            {
+                return false;
+            }
         }
     }
 
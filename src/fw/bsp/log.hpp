@@ -0,0 +1,66 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+#ifndef LOG_HPP_
+#define LOG_HPP_
+
+#include <stddef.h>
+#include <stdint.h>
+
+namespace bsp {
+
+// Ring buffer of debug messages, one per line, which the host drains through
+// the debugger with --fw-log. head counts all bytes ever written, so the host
+// finds both the new bytes and those overwritten before it drained them.
+struct LogRing
+{
+    static constexpr uint32_t Magic = 0x474F4C46;  // "FLOG"
+    static constexpr uint32_t Size  = 512;
+
+    volatile uint32_t head;
+    char data[Size];
+} __attribute__((packed));
+
+extern LogRing logRing;
+
+inline void logPut(char c)
+{
+    uint32_t head = logRing.head;
+    logRing.data[head % LogRing::Size] = c;
+    logRing.head = head + 1;
+}
+
+inline void logWrite(const char* msg)
+{
+    while (*msg != '\0')
+    {
+        logPut(*msg++);
+    }
+}
+
+// Append a message
+inline void debugLog(const char* msg)
+{
+    logWrite(msg);
+    logPut('\n');
+}
+
+// Append a message followed by a value in hexadecimal
+inline void debugLog(const char* msg, uint32_t value)
+{
+    static constexpr char digits[] = "0123456789ABCDEF";
+
+    logWrite(msg);
+    logWrite(" 0x");
+    for (int shift = 28; shift >= 0; shift -= 4)
+    {
+        logPut(digits[(value >> shift) & 0xF]);
+    }
+    logPut('\n');
+}
+
+} /* namespace bsp */
+
+#endif /* LOG_HPP_ */
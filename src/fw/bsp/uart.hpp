@@ -0,0 +1,111 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+#ifndef UART_HPP_
+#define UART_HPP_
+
+#include <stddef.h>
+#include <stdint.h>
+
+#include "power.hpp"
+
+#include <ti/devices/DeviceFamily.h>
+#include DeviceFamily_constructPath(driverlib/ioc.h)
+#include DeviceFamily_constructPath(driverlib/uart.h)
+#include DeviceFamily_constructPath(inc/hw_memmap.h)
+
+namespace bsp {
+
+// UART used as an alternative data path to the host, e.g. through the XDS110
+// backchannel UART, while the doorbell only carries control
+class Uart
+{
+private:
+    uint32_t            base_;
+    Power::PeriphHandle periph_;
+    bool                configured_{ false };
+
+public:
+    Uart(Power& power)
+        : base_{ UART0_BASE }
+        , periph_{ power.openPeriph(Power::Periph::Uart0) }
+    {
+    }
+
+    ~Uart()
+    {
+        if (configured_)
+        {
+            UARTDisable(base_);
+        }
+    }
+
+    void configure(uint32_t rx, uint32_t tx, uint32_t baudRate)
+    {
+        UARTDisable(base_);
+        UARTIntDisable(base_, UART_INT_ALL);
+        UARTConfigSetExpClk(base_,
+            48000000,   /* CPU rate */
+            baudRate,   /* baud rate */
+            UART_CONFIG_WLEN_8 | UART_CONFIG_STOP_ONE | UART_CONFIG_PAR_NONE
+        );
+        IOCPinTypeUart(base_, rx, tx, IOID_UNUSED, IOID_UNUSED);
+        UARTFIFOEnable(base_);
+        UARTEnable(base_);
+
+        // Get rid of residual data from the UART
+        while (UARTCharGetNonBlocking(base_) >= 0);
+
+        configured_ = true;
+    }
+
+    bool configured() const
+    {
+        return configured_;
+    }
+
+    void write(const uint8_t* buf, size_t len)
+    {
+        while (len > 0)
+        {
+            UARTCharPut(base_, *buf++);
+            len--;
+        }
+    }
+
+    // Wait until all data has been shifted out
+    void flush()
+    {
+        while (UARTBusy(base_));
+    }
+
+    // Receive len bytes, stopping early with failure if shouldAbort returns
+    // true
+    template <typename ShouldAbort>
+    bool read(uint8_t* buf, size_t len, ShouldAbort&& shouldAbort)
+    {
+        while (len > 0)
+        {
+            int32_t c = UARTCharGetNonBlocking(base_);
+            if (c < 0)
+            {
+                if (shouldAbort())
+                {
+                    return false;
+                }
+                continue;
+            }
+
+            *buf++ = static_cast<uint8_t>(c);
+            len--;
+        }
+
+        return true;
+    }
+};
+
+} /* namespace bsp */
+
+#endif /* UART_HPP_ */
@@ -5,16 +5,22 @@
 
 #include <stdint.h>
 
+#include <algorithm>
 #include <limits>
 
 #include <ti/devices/DeviceFamily.h>
+#include DeviceFamily_constructPath(driverlib/gpio.h)
 #include DeviceFamily_constructPath(driverlib/interrupt.h)
+#include DeviceFamily_constructPath(driverlib/ioc.h)
 
 #include "bsp/conf.hpp"
+#include "bsp/crc.hpp"
 #include "bsp/doorbell.hpp"
 #include "bsp/ext_flash.hpp"
+#include "bsp/log.hpp"
 #include "bsp/power.hpp"
 #include "bsp/spi.hpp"
+#include "bsp/uart.hpp"
 #include "hard_fault.hpp"
 
 using namespace bsp;
@@ -29,21 +35,48 @@ volatile Doorbell doorbell;
 
 #define XFLASH_BUF_SIZE  0x1000
 
+// Two buffers allow the host to transfer one buffer while the other is being
+// read or programmed, except on devices where SRAM only fits one
+#if defined(DeviceFamily_CC13X0) || defined(DeviceFamily_CC26X0) || defined(DeviceFamily_CC26X0R2)
+#define XFLASH_BUF_COUNT  1
+#else
+#define XFLASH_BUF_COUNT  2
+#endif
+
 __attribute__((section (".xflashbuf")))
-uint8_t xflashbuf[XFLASH_BUF_SIZE];
+uint8_t xflashbuf[XFLASH_BUF_COUNT][XFLASH_BUF_SIZE];
+
+LogRing bsp::logRing;
+
+__attribute__((section (".descriptor"), used))
+const Descriptor descriptor{
+    Descriptor::Magic,
+    Descriptor::Version,
+    &doorbell,
+    xflashbuf,
+    XFLASH_BUF_SIZE,
+    XFLASH_BUF_COUNT,
+    LogRing::Magic,
+    &logRing,
+    LogRing::Size,
+    Descriptor::ReleaseMagic,
+    Descriptor::Release,
+};
 
 class Loop
 {
 private:
     Spi     spi_;
     Xflash  xflash_;
+    Uart    uart_;
     Server  server_;
 
 public:
-    Loop(Power& power, const SpiObj& spiObj, const XflashObj& xflashObj)
+    Loop(Power& power, const SpiObj& spiObj, const XflashObj& xflashObj, bool safeTransport)
         : spi_{ spiObj, power }
         , xflash_{ xflashObj, spi_, power }
-        , server_{ doorbell }
+        , uart_{ power }
+        , server_{ doorbell, safeTransport }
     {
     }
 
@@ -56,23 +89,58 @@ public:
         while (true)
         {
             auto cmd = server_.waitForCommand();
-            Response rsp;
+            Response rsp = handleIntact(cmd);
 
-            switch (cmd.kind)
+            if (static_cast<uint32_t>(rsp.kind) < static_cast<uint32_t>(Response::Kind::Ok))
             {
-            case Command::Kind::XflashInfo:  rsp = xflashInfo(cmd);  break;
-            case Command::Kind::MassErase:   rsp = massErase(cmd);   break;
-            case Command::Kind::SectorErase: rsp = sectorErase(cmd); break;
-            case Command::Kind::ReadBlock:   rsp = readBlock(cmd);   break;
-            case Command::Kind::WriteBlock:  rsp = writeBlock(cmd);  break;
-            default:                         rsp = error();          break;
+                debugLog("Command failed", static_cast<uint32_t>(cmd.kind));
+                debugLog("  with error", static_cast<uint32_t>(rsp.kind));
             }
 
+            // An abort request arriving after the command completed is
+            // stale, drop it
+            server_.clearAbort();
             server_.sendResponse(rsp);
         }
     }
 
 private:
+    // Handle the command unless it was corrupted on its way from the host
+    Response handleIntact(const Command& cmd)
+    {
+        if (!server_.commandIntact())
+        {
+            debugLog("Command CRC mismatch");
+            return error(Response::Kind::ErrorCrc);
+        }
+
+        return handle(cmd);
+    }
+
+    Response handle(const Command& cmd)
+    {
+        switch (cmd.kind)
+        {
+        case Command::Kind::XflashInfo:  return xflashInfo(cmd);
+        case Command::Kind::MassErase:   return massErase(cmd);
+        case Command::Kind::SectorErase: return sectorErase(cmd);
+        case Command::Kind::ReadBlock:   return readBlock(cmd);
+        case Command::Kind::WriteBlock:  return writeBlock(cmd);
+        case Command::Kind::Echo:        return echo(cmd);
+        case Command::Kind::ReadStatus:  return readStatus(cmd);
+        case Command::Kind::UartConfig:  return uartConfig(cmd);
+        case Command::Kind::UartRead:    return uartRead(cmd);
+        case Command::Kind::UartWrite:   return uartWrite(cmd);
+        case Command::Kind::BlankCheck:  return blankCheck(cmd);
+        case Command::Kind::ReadConfig:  return readConfig(cmd);
+        case Command::Kind::WriteStatus: return writeStatus(cmd);
+        case Command::Kind::Release:     return release(cmd);
+        case Command::Kind::PowerDown:   return powerDown(cmd);
+        case Command::Kind::Checksum:    return checksum(cmd);
+        default:                         return error();
+        }
+    }
+
     Response xflashInfo(const Command&)
     {
         const auto* maybe_info = xflash_.getInfo();
@@ -83,16 +151,26 @@ private:
 
         const auto& info = *maybe_info;
 
+        uint32_t jedecId = 0;
+        uint32_t flags = (info.signatureOnly ? 0x1u : 0x0u) | (info.tookOverXip ? 0x2u : 0x0u);
+        if (info.hasJedecId)
+        {
+            jedecId = (uint32_t(info.jedecId[0]) << 8) | (uint32_t(info.jedecId[1]) << 16) |
+                      (uint32_t(info.jedecId[2]) << 24);
+            flags |= 0x4u | (uint32_t(info.jedecBank) << 8);
+        }
+
         return {
             Response::Kind::XflashInfo,
-            info.manfId,
-            info.devId
+            info.manfId | jedecId,
+            info.devId,
+            flags
         };
     }
 
     Response massErase(const Command&)
     {
-        bool ret = xflash_.massErase();
+        bool ret = xflash_.massErase(shouldAbort());
 
         if (ret)
         {
@@ -100,7 +178,7 @@ private:
         }
         else
         {
-            return error(Response::Kind::ErrorXflash);
+            return xflashError();
         }
     }
 
@@ -109,7 +187,7 @@ private:
         uint32_t offset = cmd.arg0;
         uint32_t length = cmd.arg1;
 
-        bool ret = xflash_.erase(length, offset);
+        bool ret = xflash_.erase(length, offset, shouldAbort());
 
         if (ret)
         {
@@ -117,7 +195,7 @@ private:
         }
         else
         {
-            return error(Response::Kind::ErrorXflash);
+            return xflashError();
         }
     }
 
@@ -125,17 +203,22 @@ private:
     {
         uint32_t offset = cmd.arg0;
         uint32_t length = cmd.arg1;
+        uint32_t buffer = cmd.arg2;
 
-        if (length > XFLASH_BUF_SIZE)
+        if (length > XFLASH_BUF_SIZE || buffer >= XFLASH_BUF_COUNT)
         {
             return error(Response::Kind::ErrorBufOverflow);
         }
 
 
-        memset(xflashbuf, 0, sizeof(xflashbuf));
-        bool ret = xflash_.read(xflashbuf, length, offset);
+        memset(xflashbuf[buffer], 0, sizeof(xflashbuf[buffer]));
+        bool ret = xflash_.read(xflashbuf[buffer], length, offset);
 
-        if (ret)
+        if (ret && server_.safeTransport())
+        {
+            return { Response::Kind::Crc, crc32(xflashbuf[buffer], length) };
+        }
+        else if (ret)
         {
             return { Response::Kind::Ok };
         }
@@ -149,13 +232,116 @@ private:
     {
         uint32_t offset = cmd.arg0;
         uint32_t length = cmd.arg1;
+        uint32_t buffer = cmd.arg2;
 
-        if (length > XFLASH_BUF_SIZE)
+        if (length > XFLASH_BUF_SIZE || buffer >= XFLASH_BUF_COUNT)
         {
             return error(Response::Kind::ErrorBufOverflow);
         }
 
-        bool ret = xflash_.write(xflashbuf, length, offset);
+        // Never program data that was corrupted on its way from the host
+        if (server_.safeTransport() && crc32(xflashbuf[buffer], length) != server_.dataCrc())
+        {
+            return error(Response::Kind::ErrorCrc);
+        }
+
+        bool ret = xflash_.write(xflashbuf[buffer], length, offset, shouldAbort());
+
+        if (ret)
+        {
+            return { Response::Kind::Ok };
+        }
+        else
+        {
+            return xflashError();
+        }
+    }
+
+    Response echo(const Command& cmd)
+    {
+        uint32_t nonce = cmd.arg0;
+
+        return { Response::Kind::Echo, nonce };
+    }
+
+    Response readStatus(const Command&)
+    {
+        uint8_t status;
+        bool ret = xflash_.readStatus(status);
+
+        if (ret)
+        {
+            return { Response::Kind::Status, status };
+        }
+        else
+        {
+            return error(Response::Kind::ErrorXflash);
+        }
+    }
+
+    Response readConfig(const Command&)
+    {
+        if (!xflash_.hasConfigRegister())
+        {
+            return error();
+        }
+
+        uint8_t config[2];
+        bool ret = xflash_.readConfig(config);
+
+        if (ret)
+        {
+            return { Response::Kind::Config, config[0] | (uint32_t{ config[1] } << 8) };
+        }
+        else
+        {
+            return error(Response::Kind::ErrorXflash);
+        }
+    }
+
+    Response writeStatus(const Command& cmd)
+    {
+        uint8_t status = static_cast<uint8_t>(cmd.arg0);
+        const uint8_t config[2] = {
+            static_cast<uint8_t>(cmd.arg1),
+            static_cast<uint8_t>(cmd.arg1 >> 8),
+        };
+        bool withConfig = cmd.arg2 != 0;
+
+        if (withConfig && !xflash_.hasConfigRegister())
+        {
+            return error();
+        }
+
+        bool ret = xflash_.writeStatus(status, withConfig ? config : nullptr);
+
+        if (ret)
+        {
+            return { Response::Kind::Ok };
+        }
+        else
+        {
+            return error(Response::Kind::ErrorXflash);
+        }
+    }
+
+    Response release(const Command&)
+    {
+        bool ret = xflash_.release();
+
+        if (ret)
+        {
+            return { Response::Kind::Ok };
+        }
+        else
+        {
+            return error(Response::Kind::ErrorXflash);
+        }
+    }
+
+    Response powerDown(const Command&)
+    {
+        bool ret = xflash_.sleep();
 
         if (ret)
         {
@@ -167,6 +353,172 @@ private:
         }
     }
 
+    Response uartConfig(const Command& cmd)
+    {
+        uint32_t rx = cmd.arg0;
+        uint32_t tx = cmd.arg1;
+        uint32_t baudRate = cmd.arg2;
+
+        uart_.configure(rx, tx, baudRate);
+
+        return { Response::Kind::Ok };
+    }
+
+    Response uartRead(const Command& cmd)
+    {
+        uint32_t offset = cmd.arg0;
+        uint32_t length = cmd.arg1;
+
+        if (!uart_.configured())
+        {
+            return error();
+        }
+
+        // Read into the first transfer buffer and send it, block by block
+        while (length > 0)
+        {
+            if (server_.abortRequested())
+            {
+                return error(Response::Kind::ErrorAborted);
+            }
+
+            uint32_t ilength = std::min<uint32_t>(length, XFLASH_BUF_SIZE);
+            if (!xflash_.read(xflashbuf[0], ilength, offset))
+            {
+                return error(Response::Kind::ErrorXflash);
+            }
+
+            uart_.write(xflashbuf[0], ilength);
+
+            offset += ilength;
+            length -= ilength;
+        }
+
+        uart_.flush();
+
+        return { Response::Kind::Ok };
+    }
+
+    Response uartWrite(const Command& cmd)
+    {
+        static const uint8_t credit = 0x06;
+
+        uint32_t offset = cmd.arg0;
+        uint32_t length = cmd.arg1;
+
+        if (!uart_.configured())
+        {
+            return error();
+        }
+
+        while (length > 0)
+        {
+            uint32_t ilength = std::min<uint32_t>(length, XFLASH_BUF_SIZE);
+
+            // Ready to receive the next block
+            uart_.write(&credit, sizeof(credit));
+            if (!uart_.read(xflashbuf[0], ilength, shouldAbort()))
+            {
+                return error(Response::Kind::ErrorAborted);
+            }
+
+            if (!xflash_.write(xflashbuf[0], ilength, offset, shouldAbort()))
+            {
+                return xflashError();
+            }
+
+            offset += ilength;
+            length -= ilength;
+        }
+
+        return { Response::Kind::Ok };
+    }
+
+    Response blankCheck(const Command& cmd)
+    {
+        uint32_t offset = cmd.arg0;
+        uint32_t length = cmd.arg1;
+
+        // Read into the first transfer buffer, block by block, up to the first
+        // byte which is not erased
+        while (length > 0)
+        {
+            if (server_.abortRequested())
+            {
+                return error(Response::Kind::ErrorAborted);
+            }
+
+            uint32_t ilength = std::min<uint32_t>(length, XFLASH_BUF_SIZE);
+            if (!xflash_.read(xflashbuf[0], ilength, offset))
+            {
+                return error(Response::Kind::ErrorXflash);
+            }
+
+            const uint8_t* end = xflashbuf[0] + ilength;
+            const uint8_t* dirty = std::find_if(xflashbuf[0], end, [](uint8_t b) { return b != 0xFF; });
+            if (dirty != end)
+            {
+                return { Response::Kind::Blank, offset + static_cast<uint32_t>(dirty - xflashbuf[0]) };
+            }
+
+            offset += ilength;
+            length -= ilength;
+        }
+
+        return { Response::Kind::Blank, offset };
+    }
+
+    Response checksum(const Command& cmd)
+    {
+        uint32_t offset = cmd.arg0;
+        uint32_t length = cmd.arg1;
+
+        RunningCrc crc{ static_cast<CrcAlgorithm>(cmd.arg2) };
+        if (!crc.valid())
+        {
+            return error();
+        }
+
+        // Read into the first transfer buffer, block by block, so the range
+        // is not transferred to the host
+        while (length > 0)
+        {
+            if (server_.abortRequested())
+            {
+                return error(Response::Kind::ErrorAborted);
+            }
+
+            uint32_t ilength = std::min<uint32_t>(length, XFLASH_BUF_SIZE);
+            if (!xflash_.read(xflashbuf[0], ilength, offset))
+            {
+                return error(Response::Kind::ErrorXflash);
+            }
+            crc.update(xflashbuf[0], ilength);
+
+            offset += ilength;
+            length -= ilength;
+        }
+
+        return { Response::Kind::Crc, crc.value() };
+    }
+
+    auto shouldAbort()
+    {
+        return [this]() { return server_.abortRequested(); };
+    }
+
+    Response xflashError()
+    {
+        if (server_.abortRequested())
+        {
+            return error(Response::Kind::ErrorAborted);
+        }
+        else
+        {
+            return error(Response::Kind::ErrorXflash);
+        }
+    }
+
     Response error(Response::Kind kind = Response::Kind::Error)
     {
         return { kind };
@@ -189,7 +541,17 @@ void loop()
     }
 
     Power power;
-    Loop loop{ power, spiObj, xflashObj };
+
+    // Route the external flash pins through the mux before the first access,
+    // the GPIO is kept powered while the loop runs
+    auto gpioPeriph = power.openPeriph(Power::Periph::Gpio);
+    if (conf.muxDio != IOID_UNUSED)
+    {
+        IOCPinTypeGpioOutput(conf.muxDio);
+        GPIO_writeDio(conf.muxDio, conf.muxLevel != 0 ? 1 : 0);
+    }
+
+    Loop loop{ power, spiObj, xflashObj, conf.safeTransport != 0 };
     loop.run();
 }
 
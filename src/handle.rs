@@ -0,0 +1,482 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+//! Session reuse across invocations through a handle file.
+//!
+//! `session open` starts a background `session serve` process, which opens a
+//! [`Session`] and serves requests on a localhost TCP port. The address and
+//! an access token are written to the handle file. Requests are a single
+//! line `<token> <operation> [args...]`, followed by the data for writes.
+//! Responses are either `ok <length>` followed by the data, or
+//! `err <message>`.
+//!
+//! The hidden `daemon` subcommand serves the same requests without the token
+//! on its standard input and output, for [`crate::remote`] to tunnel them
+//! through ssh. It responds `ok 0` once the session is open.
+
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{self, Stdio};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use snafu::{Backtrace, OptionExt, ResultExt, Snafu};
+use tempfile::NamedTempFile;
+
+use crate::command::{Command, Subcommand, Verify};
+use crate::history;
+use crate::session::{self, Config, Session};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("A session is already open with handle file {}", path.display()))]
+    AlreadyOpen { path: PathBuf, backtrace: Backtrace },
+    #[snafu(display(
+        "Unable to read session handle file {}: {}, open a session with session open",
+        path.display(),
+        source
+    ))]
+    ReadHandle {
+        path: PathBuf,
+        source: io::Error,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("Invalid session handle file {}", path.display()))]
+    InvalidHandle { path: PathBuf, backtrace: Backtrace },
+    #[snafu(display("Unable to write session handle file {}: {}", path.display(), source))]
+    WriteHandle {
+        path: PathBuf,
+        source: io::Error,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("Unable to start session process: {}", source))]
+    SpawnError {
+        source: io::Error,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("Session process failed to start: {}", message))]
+    StartFailed {
+        message: String,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("Unable to communicate with the session process: {}", source))]
+    ConnectionError {
+        source: io::Error,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("Session error: {}", message))]
+    RemoteError {
+        message: String,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("The {} subcommand is not supported through a session handle", name))]
+    Unsupported {
+        name: &'static str,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("An IO error occured: {}", source))]
+    IoError {
+        source: io::Error,
+        backtrace: Backtrace,
+    },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Time allowed for the session process to start the JVM, connect and
+/// inject the firmware.
+const OPEN_TIMEOUT: Duration = Duration::from_secs(120);
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Start `session serve` in the background with the same options, and wait
+/// until it has opened the session.
+pub fn open(path: &Path) -> Result<()> {
+    ensure!(!path.exists(), AlreadyOpen { path });
+
+    // Rerun the same command line, with the open subcommand replaced
+    let mut args: Vec<OsString> = env::args_os().skip(1).collect();
+    let session_pos = args.iter().position(|arg| arg == "session");
+    let open_pos = session_pos
+        .and_then(|pos| {
+            args.iter()
+                .skip(pos)
+                .position(|arg| arg == "open")
+                .map(|i| pos + i)
+        })
+        .context(StartFailed {
+            message: "unable to find session open in the arguments",
+        })?;
+    args[open_pos] = "serve".into();
+
+    let exe = env::current_exe().context(SpawnError {})?;
+    // Detached from the standard streams, which would otherwise keep pipes of
+    // the calling script open
+    let mut server = process::Command::new(exe)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .context(SpawnError {})?;
+
+    let start = Instant::now();
+    loop {
+        if path.exists() {
+            // Startup failures are reported through the handle file
+            return match read_handle(path)? {
+                Handle::Failed(message) => {
+                    fs::remove_file(path).unwrap_or_default();
+                    StartFailed { message }.fail()
+                }
+                Handle::Open { .. } => Ok(()),
+            };
+        }
+
+        if let Some(status) = server.try_wait().context(SpawnError {})? {
+            return StartFailed {
+                message: format!("exited with {}", status),
+            }
+            .fail();
+        }
+
+        if start.elapsed() > OPEN_TIMEOUT {
+            server.kill().unwrap_or_default();
+            return StartFailed {
+                message: "timed out",
+            }
+            .fail();
+        }
+
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Open the session and serve requests until closed. Runs in the background
+/// process started by [`open`].
+pub fn serve(command: &Command, path: &Path) -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").context(IoError {})?;
+    let address = listener.local_addr().context(IoError {})?;
+
+    let session = match Session::open(Config::from_command(command)) {
+        Ok(session) => session,
+        Err(err) => return write_handle(path, &format!("error\t{}\n", one_line(&err))),
+    };
+
+    let token = new_token();
+    write_handle(
+        path,
+        &format!("open\t{}\t{}\t{}\n", address, token, process::id()),
+    )?;
+
+    for stream in listener.incoming() {
+        let mut writer = match stream {
+            Ok(stream) => stream,
+            Err(_) => continue,
+        };
+        let mut reader = match writer.try_clone() {
+            Ok(stream) => BufReader::new(stream),
+            Err(_) => continue,
+        };
+        // Errors only affect the client of the failed request
+        if let Ok(Served::Closed) = serve_request(&session, Some(&token), &mut reader, &mut writer)
+        {
+            // Respond once the device has been restored, so the next
+            // invocation finds it in its original state
+            drop(session);
+            writeln!(writer, "ok 0").unwrap_or_default();
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Open the session and serve requests on the standard input and output
+/// until closed, or until the input ends.
+pub fn daemon(command: &Command) -> Result<()> {
+    let stdin = io::stdin();
+    let mut reader = stdin.lock();
+    let stdout = io::stdout();
+    let mut writer = stdout.lock();
+
+    let session = match Session::open(Config::from_command(command)) {
+        Ok(session) => session,
+        Err(err) => {
+            writeln!(writer, "err {}", one_line(&err)).context(IoError {})?;
+            return Ok(());
+        }
+    };
+    writeln!(writer, "ok 0").context(IoError {})?;
+    writer.flush().context(IoError {})?;
+
+    loop {
+        match serve_request(&session, None, &mut reader, &mut writer).context(IoError {})? {
+            Served::Continue => writer.flush().context(IoError {})?,
+            Served::Closed => {
+                drop(session);
+                writeln!(writer, "ok 0").context(IoError {})?;
+                writer.flush().context(IoError {})?;
+                break;
+            }
+            Served::Ended => break,
+        }
+    }
+
+    Ok(())
+}
+
+enum Served {
+    Continue,
+    Closed,
+    /// The client went away without closing the session
+    Ended,
+}
+
+/// Serve a request, preceded by `token` if any.
+fn serve_request(
+    session: &Session,
+    token: Option<&str>,
+    reader: &mut impl BufRead,
+    writer: &mut impl Write,
+) -> io::Result<Served> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(Served::Ended);
+    }
+    let mut fields = line.split_whitespace();
+
+    if token.is_some() && fields.next() != token {
+        writeln!(writer, "err Invalid session token")?;
+        return Ok(Served::Continue);
+    }
+
+    let operation = fields.next().unwrap_or_default();
+    let args: Vec<u32> = fields.filter_map(|arg| arg.parse().ok()).collect();
+    let arg = |i: usize| args.get(i).copied().unwrap_or_default();
+
+    let res: session::Result<Vec<u8>> = match operation {
+        "info" => session.info().map(|xflash| xflash.to_string().into_bytes()),
+        "read" => session.read(arg(0), arg(1), |_| {}),
+        "write" => {
+            let mut data = vec![0; arg(1) as usize];
+            reader.read_exact(&mut data)?;
+            session
+                .write(arg(0), data, arg(2) != 0, |_| {})
+                .map(|_| Vec::new())
+        }
+        "erase" => session.erase(arg(0), arg(1)).map(|_| Vec::new()),
+        "mass-erase" => session.mass_erase().map(|_| Vec::new()),
+        "close" => return Ok(Served::Closed),
+        _ => {
+            writeln!(writer, "err Unknown operation {}", operation)?;
+            return Ok(Served::Continue);
+        }
+    };
+
+    match res {
+        Ok(data) => {
+            writeln!(writer, "ok {}", data.len())?;
+            writer.write_all(&data)?;
+        }
+        Err(err) => writeln!(writer, "err {}", one_line(&err))?,
+    }
+
+    Ok(Served::Continue)
+}
+
+/// Run the operation of `command` through the session of the handle file.
+pub fn run(command: &Command, path: &Path) -> Result<()> {
+    forward(command, |line, data| request(path, line, data))
+}
+
+/// Run the operation of `command` as requests to a session, sent by
+/// `request` with the data of a write.
+pub(crate) fn forward<F>(command: &Command, mut request: F) -> Result<()>
+where
+    F: FnMut(&str, &[u8]) -> Result<Vec<u8>>,
+{
+    use Subcommand::*;
+
+    match &command.subcommand {
+        Info => {
+            let info = request("info", &[])?;
+            println!("{}", String::from_utf8_lossy(&info));
+        }
+        SectorErase { offset, length } => {
+            request(&format!("erase {} {}", offset, length), &[])?;
+        }
+        MassErase => {
+            request("mass-erase", &[])?;
+        }
+        Read {
+            offset,
+            length,
+            output,
+        } => {
+            let data = request(&format!("read {} {}", offset, length), &[])?;
+            output.borrow_mut().write_all(&data).context(IoError {})?;
+        }
+        Write {
+            verify,
+            recheck: None,
+            in_place: false,
+            offset,
+            auto_offset: false,
+            length,
+            input,
+            format,
+            ..
+        } if !format.has_addresses() && !matches!(verify, Verify::Sample { .. }) => {
+            let mut data = Vec::new();
+            let mut input = input.borrow_mut();
+            match length {
+                Some(length) => input.as_mut().take(*length as _).read_to_end(&mut data),
+                None => input.read_to_end(&mut data),
+            }
+            .context(IoError {})?;
+
+            request(
+                &format!(
+                    "write {} {} {}",
+                    offset,
+                    data.len(),
+                    (*verify == Verify::Full) as u8
+                ),
+                &data,
+            )?;
+        }
+        subcommand => Unsupported {
+            name: subcommand.name(),
+        }
+        .fail()?,
+    }
+
+    Ok(())
+}
+
+/// Close the session of the handle file, restoring the device.
+pub fn close(path: &Path) -> Result<()> {
+    request(path, "close", &[])?;
+
+    // Removed by the client, as the session process may be gone already
+    fs::remove_file(path).context(WriteHandle { path })?;
+
+    Ok(())
+}
+
+enum Handle {
+    Open { address: String, token: String },
+    Failed(String),
+}
+
+fn read_handle(path: &Path) -> Result<Handle> {
+    let content = fs::read_to_string(path).context(ReadHandle { path })?;
+    let fields: Vec<_> = content.trim_end().split('\t').collect();
+
+    match fields.as_slice() {
+        ["open", address, token, ..] => Ok(Handle::Open {
+            address: address.to_string(),
+            token: token.to_string(),
+        }),
+        ["error", message] => Ok(Handle::Failed(message.to_string())),
+        _ => InvalidHandle { path }.fail(),
+    }
+}
+
+/// Write the handle file atomically, so it is never read partially written.
+fn write_handle(path: &Path, content: &str) -> Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    let mut file = NamedTempFile::new_in(dir).context(WriteHandle { path })?;
+    file.write_all(content.as_bytes())
+        .context(WriteHandle { path })?;
+    file.persist(path)
+        .map_err(|err| err.error)
+        .context(WriteHandle { path })?;
+
+    Ok(())
+}
+
+fn request(path: &Path, line: &str, data: &[u8]) -> Result<Vec<u8>> {
+    let (address, token) = match read_handle(path)? {
+        Handle::Open { address, token } => (address, token),
+        Handle::Failed(_) => InvalidHandle { path }.fail()?,
+    };
+
+    let mut writer = TcpStream::connect(address).context(ConnectionError {})?;
+    let mut reader = BufReader::new(writer.try_clone().context(ConnectionError {})?);
+
+    exchange(
+        &mut reader,
+        &mut writer,
+        &format!("{} {}", token, line),
+        data,
+    )
+}
+
+/// Send a request over a connection to a session and read the response,
+/// returning its data.
+pub(crate) fn exchange(
+    reader: &mut impl BufRead,
+    writer: &mut impl Write,
+    line: &str,
+    data: &[u8],
+) -> Result<Vec<u8>> {
+    writeln!(writer, "{}", line).context(ConnectionError {})?;
+    writer.write_all(data).context(ConnectionError {})?;
+    writer.flush().context(ConnectionError {})?;
+
+    read_response(reader)
+}
+
+/// Read the response to a request, returning its data. Other lines before
+/// the status, e.g. console output of the JVM of a daemon, are passed on to
+/// stderr.
+pub(crate) fn read_response(reader: &mut impl BufRead) -> Result<Vec<u8>> {
+    loop {
+        let mut status = String::new();
+        if reader.read_line(&mut status).context(ConnectionError {})? == 0 {
+            return RemoteError {
+                message: "invalid response",
+            }
+            .fail();
+        }
+
+        match status.trim_end().split_once(' ') {
+            Some(("ok", length)) => {
+                let length: usize = length.parse().ok().context(RemoteError {
+                    message: "invalid response length",
+                })?;
+                let mut data = vec![0; length];
+                reader.read_exact(&mut data).context(ConnectionError {})?;
+                return Ok(data);
+            }
+            Some(("err", message)) => return RemoteError { message }.fail(),
+            _ => eprint!("{}", status),
+        }
+    }
+}
+
+/// Token required in each request, so only clients able to read the handle
+/// file may use the session.
+fn new_token() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let seed = format!("{}.{}.{:p}", process::id(), nanos, &nanos);
+    history::digest(seed.as_bytes())[..32].to_string()
+}
+
+fn one_line(err: &dyn std::error::Error) -> String {
+    err.to_string().replace(['\n', '\t'], " ")
+}
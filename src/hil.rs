@@ -0,0 +1,295 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+//! Hardware-in-the-loop self test against an attached LaunchPad, for
+//! validating a release across device families. Every case connects to the
+//! device anew, the same way separate invocations of the tool would, and the
+//! results are written as a JUnit XML report for CI systems.
+
+use std::cell::RefCell;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use dss::com::ti::ccstudio::scripting::environment::ScriptingEnvironment;
+use snafu::{Backtrace, ResultExt, Snafu};
+
+use flash_rover::command::{Command, Subcommand, Verify};
+use flash_rover::flash_rover::FlashRover;
+use flash_rover::image::Relocation;
+use flash_rover::types::InputFormat;
+use flash_rover::xds110::Xds110;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Unable to write the test report {}: {}", path.display(), source))]
+    ReportError {
+        path: PathBuf,
+        source: io::Error,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("{} of {} test cases failed", failed, total))]
+    TestsFailed {
+        failed: usize,
+        total: usize,
+        backtrace: Backtrace,
+    },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+struct Case {
+    name: &'static str,
+    time: Duration,
+    failure: Option<String>,
+}
+
+/// Run the test cases on the region of the external flash given by the
+/// subcommand, which is erased and overwritten.
+pub fn run(script: &ScriptingEnvironment, command: &Command) -> Result<()> {
+    let (offset, length, report) = match &command.subcommand {
+        Subcommand::HilTest {
+            offset,
+            length,
+            report,
+        } => (*offset, *length, report),
+        _ => unreachable!("Only runs the hil-test subcommand"),
+    };
+
+    let seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.subsec_nanos() | 1)
+        .unwrap_or(1);
+    let data = random_data(seed, length);
+    let crc = crc32fast::hash(&data);
+
+    println!(
+        "Testing {} on XDS110 {}, region 0x{:08X}..0x{:08X}, seed 0x{:08X}",
+        command.device.to_string(),
+        command.xds_id,
+        offset,
+        offset.saturating_add(length),
+        seed
+    );
+
+    let start = Instant::now();
+    let mut cases = Vec::new();
+
+    run_case(&mut cases, "info", || {
+        operation(script, command, Subcommand::Info)
+    });
+    run_case(&mut cases, "erase", || {
+        operation(script, command, Subcommand::SectorErase { offset, length })?;
+        let blank = read_back(script, command, offset, length)?;
+        match blank.iter().position(|byte| *byte != 0xFF) {
+            Some(index) => Err(format!(
+                "Byte at 0x{:08X} is 0x{:02X} after erasing",
+                offset + index as u32,
+                blank[index]
+            )),
+            None => Ok(()),
+        }
+    });
+    run_case(&mut cases, "random-write", || {
+        let write = Subcommand::Write {
+            verify: Verify::Full,
+            recheck: None,
+            in_place: false,
+            offset,
+            auto_offset: false,
+            length: None,
+            input: RefCell::new(Box::new(io::Cursor::new(data.clone()))),
+            format: InputFormat::Bin,
+            relocation: Relocation::default(),
+        };
+        operation(script, command, write)
+    });
+    run_case(&mut cases, "crc-verify", || {
+        check_crc(script, command, offset, length, crc)
+    });
+    run_case(&mut cases, "power-cycle", || {
+        Xds110::new(&command.ccs_path, &command.xds_id)
+            .power_cycle()
+            .map_err(|err| err.to_string())?;
+        check_crc(script, command, offset, length, crc)
+    });
+
+    let failed = cases.iter().filter(|case| case.failure.is_some()).count();
+    println!(
+        "{} passed, {} failed in {:.1} s",
+        cases.len() - failed,
+        failed,
+        start.elapsed().as_secs_f64()
+    );
+
+    write_report(report, command, seed, start.elapsed(), &cases)?;
+    println!("JUnit report written to {}", report.display());
+
+    ensure!(
+        failed == 0,
+        TestsFailed {
+            failed,
+            total: cases.len()
+        }
+    );
+
+    Ok(())
+}
+
+fn run_case<F>(cases: &mut Vec<Case>, name: &'static str, test: F)
+where
+    F: FnOnce() -> Result<(), String>,
+{
+    println!("{} ...", name);
+    let start = Instant::now();
+    let failure = test().err();
+    match &failure {
+        Some(message) => println!("{} FAILED: {}", name, message),
+        None => println!("{} ok", name),
+    }
+
+    cases.push(Case {
+        name,
+        time: start.elapsed(),
+        failure,
+    });
+}
+
+/// Run the subcommand in a session of its own.
+fn operation(
+    script: &ScriptingEnvironment,
+    command: &Command,
+    subcommand: Subcommand,
+) -> Result<(), String> {
+    FlashRover::new(script, command.with_subcommand(subcommand))
+        .and_then(FlashRover::run)
+        .map_err(|err| err.to_string())
+}
+
+fn read_back(
+    script: &ScriptingEnvironment,
+    command: &Command,
+    offset: u32,
+    length: u32,
+) -> Result<Vec<u8>, String> {
+    let mut file = tempfile::tempfile().map_err(|err| err.to_string())?;
+    let output = file.try_clone().map_err(|err| err.to_string())?;
+    let read = Subcommand::Read {
+        offset,
+        length,
+        output: RefCell::new(Box::new(output)),
+    };
+    operation(script, command, read)?;
+
+    let mut data = Vec::new();
+    file.seek(SeekFrom::Start(0))
+        .and_then(|_| file.read_to_end(&mut data))
+        .map_err(|err| err.to_string())?;
+
+    Ok(data)
+}
+
+fn check_crc(
+    script: &ScriptingEnvironment,
+    command: &Command,
+    offset: u32,
+    length: u32,
+    expected: u32,
+) -> Result<(), String> {
+    let actual = crc32fast::hash(&read_back(script, command, offset, length)?);
+    if actual == expected {
+        Ok(())
+    } else {
+        Err(format!(
+            "CRC32 0x{:08X} of the read back data, expected 0x{:08X}",
+            actual, expected
+        ))
+    }
+}
+
+/// Data from a xorshift generator, reproducible from the seed printed in the
+/// report.
+fn random_data(seed: u32, length: u32) -> Vec<u8> {
+    let mut state = seed;
+    (0..length)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect()
+}
+
+fn write_report(
+    path: &Path,
+    command: &Command,
+    seed: u32,
+    time: Duration,
+    cases: &[Case],
+) -> Result<()> {
+    let device = command.device.to_string();
+    let suite = format!("flash-rover.hil-test.{}", device);
+    let failures = cases.iter().filter(|case| case.failure.is_some()).count();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"0\" time=\"{:.3}\" \
+         timestamp=\"{}\">\n",
+        escape(&suite),
+        cases.len(),
+        failures,
+        time.as_secs_f64(),
+        humantime::format_rfc3339_seconds(SystemTime::now())
+    ));
+    xml.push_str("    <properties>\n");
+    for (name, value) in &[
+        ("device", device.clone()),
+        ("probe", command.xds_id.clone()),
+        ("version", env!("CARGO_PKG_VERSION").to_string()),
+        ("seed", format!("0x{:08X}", seed)),
+    ] {
+        xml.push_str(&format!(
+            "        <property name=\"{}\" value=\"{}\"/>\n",
+            name,
+            escape(value)
+        ));
+    }
+    xml.push_str("    </properties>\n");
+    for case in cases {
+        let testcase = format!(
+            "    <testcase classname=\"{}\" name=\"{}\" time=\"{:.3}\"",
+            escape(&suite),
+            case.name,
+            case.time.as_secs_f64()
+        );
+        match &case.failure {
+            Some(message) => xml.push_str(&format!(
+                "{}>\n        <failure message=\"{}\"/>\n    </testcase>\n",
+                testcase,
+                escape(message)
+            )),
+            None => xml.push_str(&format!("{}/>\n", testcase)),
+        }
+    }
+    xml.push_str("</testsuite>\n");
+
+    fs::write(path, xml).context(ReportError { path })
+}
+
+fn escape(value: &str) -> String {
+    value.chars().fold(String::new(), |mut escaped, c| {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+        escaped
+    })
+}
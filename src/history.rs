@@ -0,0 +1,77 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+use std::env;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::SystemTime;
+
+use sha2::{Digest, Sha256};
+
+use crate::types::Device;
+
+/// A single operation recorded in the history file.
+pub struct Entry<'a> {
+    pub probe: &'a str,
+    pub device: Device,
+    pub operation: &'a str,
+    pub range: Option<(u32, u32)>,
+    pub digest: Option<&'a str>,
+    pub error: Option<String>,
+}
+
+/// Hex encoded SHA-256 digest of data read from or written to the flash.
+pub fn digest(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Name of the user running the operation, or `-` if unknown.
+pub fn user() -> String {
+    env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .unwrap_or_else(|_| "-".to_string())
+}
+
+/// Append an entry to the history file as a single tab separated line, with
+/// the fields timestamp, user, probe, device, operation, range, result and
+/// digest. The file is created if it does not exist.
+pub fn append(path: &Path, entry: &Entry) -> io::Result<()> {
+    let timestamp = humantime::format_rfc3339_seconds(SystemTime::now());
+    let user = user();
+    let range = match entry.range {
+        Some((offset, length)) => format!("0x{:08X}+0x{:X}", offset, length),
+        None => "-".to_string(),
+    };
+    let result = match &entry.error {
+        // Keep the entry on a single line
+        Some(error) => format!("error: {}", error.replace(['\n', '\t'], " ")),
+        None => "ok".to_string(),
+    };
+    let digest = entry
+        .digest
+        .map(|digest| format!("sha256:{}", digest))
+        .unwrap_or_else(|| "-".to_string());
+
+    let line = format!(
+        "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+        timestamp,
+        user,
+        entry.probe,
+        entry.device.to_string(),
+        entry.operation,
+        range,
+        result,
+        digest
+    );
+
+    // Write the line in one call, so concurrent runs on different probes do
+    // not interleave their entries
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(line.as_bytes())
+}
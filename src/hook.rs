@@ -0,0 +1,54 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+use std::io;
+use std::process::{self, ExitStatus, Stdio};
+
+use snafu::{Backtrace, ResultExt, Snafu};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Unable to run hook command '{}': {}", cmd, source))]
+    SpawnError {
+        cmd: String,
+        source: io::Error,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("Hook command '{}' failed with {}", cmd, status))]
+    HookFailed {
+        cmd: String,
+        status: ExitStatus,
+        backtrace: Backtrace,
+    },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Run a user supplied command through the system shell and wait for it to
+/// finish. The command's stdout is redirected to stderr, as stdout may be used
+/// for streaming data read from the external flash.
+pub fn run(cmd: &str) -> Result<()> {
+    let status = shell(cmd)
+        .stdin(Stdio::null())
+        .stdout(io::stderr())
+        .status()
+        .context(SpawnError { cmd })?;
+
+    ensure!(status.success(), HookFailed { cmd, status });
+
+    Ok(())
+}
+
+fn shell(cmd: &str) -> process::Command {
+    if cfg!(windows) {
+        let mut shell = process::Command::new("cmd");
+        shell.arg("/C").arg(cmd);
+        shell
+    } else {
+        let mut shell = process::Command::new("sh");
+        shell.arg("-c").arg(cmd);
+        shell
+    }
+}
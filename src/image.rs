@@ -0,0 +1,293 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+//! Address-bearing input images, which are written to the external flash at
+//! the addresses they carry instead of a given offset.
+
+use std::convert::TryInto;
+use std::str;
+
+use snafu::{Backtrace, OptionExt, Snafu};
+
+use crate::types::InputFormat;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Invalid Intel HEX input at line {}: {}", line, reason))]
+    InvalidHex {
+        line: usize,
+        reason: &'static str,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("Invalid TI-TXT input at line {}: {}", line, reason))]
+    InvalidTiTxt {
+        line: usize,
+        reason: &'static str,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("Invalid ELF input: {}", reason))]
+    InvalidElf {
+        reason: &'static str,
+        backtrace: Backtrace,
+    },
+    #[snafu(display(
+        "Image address 0x{:08X} is outside the external flash after relocation",
+        address
+    ))]
+    OutOfRange { address: u32, backtrace: Backtrace },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Contiguous data at an address of the image.
+#[derive(Clone, Debug)]
+pub struct Segment {
+    pub address: u32,
+    pub data: Vec<u8>,
+}
+
+/// Translation of image addresses into external flash offsets, for images
+/// linked at the memory-mapped address of the external flash.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Relocation {
+    /// Image address of external flash offset 0
+    pub base: u32,
+    /// Added to the offsets after subtracting the base
+    pub shift: i64,
+}
+
+impl Relocation {
+    pub fn apply(&self, address: u32) -> Result<u32> {
+        (i64::from(address) - i64::from(self.base) + self.shift)
+            .try_into()
+            .ok()
+            .context(OutOfRange { address })
+    }
+}
+
+/// Parse an address-bearing image into its segments, sorted by address with
+/// adjacent segments merged.
+pub fn parse(format: InputFormat, input: &[u8]) -> Result<Vec<Segment>> {
+    let mut segments = match format {
+        InputFormat::Bin | InputFormat::Base64 => vec![Segment {
+            address: 0,
+            data: input.to_vec(),
+        }],
+        InputFormat::Hex => parse_hex(input)?,
+        InputFormat::Elf => parse_elf(input)?,
+        InputFormat::TiTxt => parse_ti_txt(input)?,
+    };
+
+    segments.sort_by_key(|segment| segment.address);
+
+    let mut merged: Vec<Segment> = Vec::with_capacity(segments.len());
+    for segment in segments {
+        match merged.last_mut() {
+            Some(last) if last.address as usize + last.data.len() == segment.address as usize => {
+                last.data.extend_from_slice(&segment.data);
+            }
+            _ => merged.push(segment),
+        }
+    }
+
+    Ok(merged)
+}
+
+fn parse_hex(input: &[u8]) -> Result<Vec<Segment>> {
+    const DATA: u8 = 0x00;
+    const END_OF_FILE: u8 = 0x01;
+    const EXTENDED_SEGMENT_ADDRESS: u8 = 0x02;
+    const START_SEGMENT_ADDRESS: u8 = 0x03;
+    const EXTENDED_LINEAR_ADDRESS: u8 = 0x04;
+    const START_LINEAR_ADDRESS: u8 = 0x05;
+
+    let text = str::from_utf8(input).ok().context(InvalidHex {
+        line: 1_usize,
+        reason: "not a text file",
+    })?;
+
+    let mut segments: Vec<Segment> = Vec::new();
+    let mut base = 0_u32;
+
+    for (index, record) in text.lines().enumerate() {
+        let line = index + 1;
+        let record = record.trim();
+        if record.is_empty() {
+            continue;
+        }
+
+        let hex = record.strip_prefix(':').context(InvalidHex {
+            line,
+            reason: "missing start code",
+        })?;
+        let bytes = (0..hex.len())
+            .step_by(2)
+            .map(|i| {
+                hex.get(i..i + 2)
+                    .and_then(|b| u8::from_str_radix(b, 16).ok())
+            })
+            .collect::<Option<Vec<u8>>>()
+            .context(InvalidHex {
+                line,
+                reason: "invalid hex digits",
+            })?;
+
+        ensure!(
+            bytes.len() >= 5 && bytes.len() == bytes[0] as usize + 5,
+            InvalidHex {
+                line,
+                reason: "invalid record length",
+            }
+        );
+        ensure!(
+            bytes.iter().fold(0_u8, |sum, b| sum.wrapping_add(*b)) == 0,
+            InvalidHex {
+                line,
+                reason: "checksum mismatch",
+            }
+        );
+
+        let address = u32::from(u16::from_be_bytes([bytes[1], bytes[2]]));
+        let data = &bytes[4..bytes.len() - 1];
+
+        match bytes[3] {
+            DATA => {
+                let address = base.wrapping_add(address);
+                match segments.last_mut() {
+                    Some(last) if last.address as usize + last.data.len() == address as usize => {
+                        last.data.extend_from_slice(data);
+                    }
+                    _ => segments.push(Segment {
+                        address,
+                        data: data.to_vec(),
+                    }),
+                }
+            }
+            END_OF_FILE => break,
+            EXTENDED_SEGMENT_ADDRESS if data.len() == 2 => {
+                base = u32::from(u16::from_be_bytes([data[0], data[1]])) << 4;
+            }
+            EXTENDED_LINEAR_ADDRESS if data.len() == 2 => {
+                base = u32::from(u16::from_be_bytes([data[0], data[1]])) << 16;
+            }
+            START_SEGMENT_ADDRESS | START_LINEAR_ADDRESS => {}
+            _ => InvalidHex {
+                line,
+                reason: "unsupported record",
+            }
+            .fail()?,
+        }
+    }
+
+    Ok(segments)
+}
+
+/// TI-TXT, with sections starting at an `@ADDR` line followed by lines of hex
+/// bytes, up to the terminating `q`.
+fn parse_ti_txt(input: &[u8]) -> Result<Vec<Segment>> {
+    let text = str::from_utf8(input).ok().context(InvalidTiTxt {
+        line: 1_usize,
+        reason: "not a text file",
+    })?;
+
+    let mut segments: Vec<Segment> = Vec::new();
+
+    for (index, record) in text.lines().enumerate() {
+        let line = index + 1;
+        let record = record.trim();
+
+        if let Some(address) = record.strip_prefix('@') {
+            let address = u32::from_str_radix(address, 16)
+                .ok()
+                .context(InvalidTiTxt {
+                    line,
+                    reason: "invalid address",
+                })?;
+            segments.push(Segment {
+                address,
+                data: Vec::new(),
+            });
+        } else if record.eq_ignore_ascii_case("q") {
+            break;
+        } else if !record.is_empty() {
+            let segment = segments.last_mut().context(InvalidTiTxt {
+                line,
+                reason: "data before the first address",
+            })?;
+            for byte in record.split_whitespace() {
+                segment
+                    .data
+                    .push(u8::from_str_radix(byte, 16).ok().context(InvalidTiTxt {
+                        line,
+                        reason: "invalid hex byte",
+                    })?);
+            }
+        }
+    }
+
+    Ok(segments)
+}
+
+/// Loadable segments of a 32-bit little-endian ELF file, at their physical
+/// (load) addresses.
+fn parse_elf(input: &[u8]) -> Result<Vec<Segment>> {
+    const ELFCLASS32: u8 = 1;
+    const ELFDATA2LSB: u8 = 1;
+    const PT_LOAD: u32 = 1;
+
+    let u16_at = |offset: usize| {
+        input
+            .get(offset..offset + 2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+    };
+    let u32_at = |offset: usize| {
+        input
+            .get(offset..offset + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    };
+
+    ensure!(
+        input.starts_with(b"\x7FELF"),
+        InvalidElf {
+            reason: "missing ELF magic",
+        }
+    );
+    ensure!(
+        input.get(4) == Some(&ELFCLASS32) && input.get(5) == Some(&ELFDATA2LSB),
+        InvalidElf {
+            reason: "only 32-bit little-endian files are supported",
+        }
+    );
+
+    let truncated = InvalidElf {
+        reason: "truncated file",
+    };
+    let phoff = u32_at(0x1C).context(truncated)? as usize;
+    let phentsize = u16_at(0x2A).context(truncated)? as usize;
+    let phnum = u16_at(0x2C).context(truncated)? as usize;
+
+    let mut segments = Vec::new();
+    for index in 0..phnum {
+        let header = phoff + index * phentsize;
+        let p_type = u32_at(header).context(truncated)?;
+        let p_offset = u32_at(header + 0x04).context(truncated)? as usize;
+        let p_paddr = u32_at(header + 0x0C).context(truncated)?;
+        let p_filesz = u32_at(header + 0x10).context(truncated)? as usize;
+
+        if p_type != PT_LOAD || p_filesz == 0 {
+            continue;
+        }
+
+        let data = input
+            .get(p_offset..p_offset + p_filesz)
+            .context(truncated)?;
+        segments.push(Segment {
+            address: p_paddr,
+            data: data.to_vec(),
+        });
+    }
+
+    Ok(segments)
+}
@@ -0,0 +1,61 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static DEADLINE_EXCEEDED: AtomicBool = AtomicBool::new(false);
+
+/// Exit code of a process terminated by SIGINT.
+const EXIT_CODE: i32 = 130;
+/// Exit code of a process which did not stop after exceeding its time
+/// budget, as used by timeout(1).
+const DEADLINE_EXIT_CODE: i32 = 124;
+/// Time allowed for the operation to stop cleanly after the time budget is
+/// exceeded, before the process exits, e.g. when stuck in a call to a wedged
+/// probe.
+const ABORT_GRACE_TIME: Duration = Duration::from_secs(30);
+
+/// Install a Ctrl-C handler which requests the operation in progress to be
+/// aborted cleanly. A second Ctrl-C exits immediately.
+pub fn install() -> Result<(), ctrlc::Error> {
+    ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            process::exit(EXIT_CODE);
+        }
+        eprintln!("Aborting, press Ctrl-C again to exit immediately");
+    })
+}
+
+/// Whether Ctrl-C has been pressed, or the time budget is exceeded.
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Request the operation in progress to be aborted cleanly once `budget` has
+/// elapsed, the same way as Ctrl-C. Exits if it has not stopped in time.
+pub fn set_deadline(budget: Duration) {
+    thread::spawn(move || {
+        thread::sleep(budget);
+        DEADLINE_EXCEEDED.store(true, Ordering::SeqCst);
+        INTERRUPTED.store(true, Ordering::SeqCst);
+        eprintln!(
+            "Time budget of {} exceeded, aborting",
+            humantime::format_duration(budget)
+        );
+
+        thread::sleep(ABORT_GRACE_TIME);
+        eprintln!("Error: Operation did not stop after exceeding its time budget, exiting");
+        process::exit(DEADLINE_EXIT_CODE);
+    });
+}
+
+/// Whether the time budget given to [`set_deadline`] is exceeded.
+pub fn is_deadline_exceeded() -> bool {
+    DEADLINE_EXCEEDED.load(Ordering::SeqCst)
+}
@@ -0,0 +1,67 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+//! Library core of flash-rover, shared by the `ti-xflash` executable and the
+//! language bindings. [`session::Session`] is the entry point for driving the
+//! external flash programmatically.
+
+extern crate base64;
+extern crate byte_unit;
+extern crate crc32fast;
+extern crate ctrlc;
+extern crate dirs;
+extern crate dss;
+extern crate fs2;
+extern crate humantime;
+extern crate path_clean;
+extern crate path_slash;
+extern crate rust_embed;
+extern crate serialport;
+extern crate sha2;
+#[macro_use]
+extern crate snafu;
+extern crate tempfile;
+extern crate toml;
+extern crate ureq;
+
+mod assets;
+pub mod backend;
+pub mod base64_stream;
+pub mod board;
+pub mod byte_swap;
+mod cache;
+mod ccxml;
+pub mod checksum;
+pub mod command;
+pub mod dss_logger;
+pub mod fetch;
+mod firmware;
+pub mod flash_rover;
+pub mod handle;
+mod history;
+pub mod hook;
+pub mod image;
+pub mod interrupt;
+pub mod json;
+pub mod lock;
+pub mod manifest;
+pub mod mock;
+mod oad;
+pub mod partition;
+pub mod plugin;
+pub mod profile;
+mod progress;
+pub mod remote;
+pub mod replay;
+mod sector_cache;
+pub mod session;
+pub mod split;
+mod stats;
+mod temp;
+pub mod ti_txt;
+mod trace;
+pub mod types;
+pub mod xds110;
+pub mod xflash;
@@ -0,0 +1,88 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::process;
+
+use fs2::FileExt;
+use snafu::{Backtrace, ResultExt, Snafu};
+
+use crate::temp;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Unable to create probe lock file {}: {}", path.display(), source))]
+    CreateLock {
+        path: PathBuf,
+        source: io::Error,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("Probe {} is busy (held by PID {})", serial, pid))]
+    ProbeBusy {
+        serial: String,
+        pid: String,
+        backtrace: Backtrace,
+    },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl Error {
+    /// Stable identifier of the error, for automation.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::CreateLock { .. } => "create_lock_failed",
+            Error::ProbeBusy { .. } => "probe_busy",
+        }
+    }
+}
+
+/// Exclusive lock on a XDS110 probe, preventing concurrent flash-rover
+/// invocations from accessing the same probe. The lock is released when
+/// dropped, or when the process exits.
+pub struct ProbeLock {
+    _file: File,
+}
+
+impl ProbeLock {
+    pub fn acquire(serial: &str) -> Result<Self> {
+        let path = env::temp_dir().join(format!("flash-rover.{}.lock", serial));
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .context(CreateLock { path: &path })?;
+
+        if file.try_lock_exclusive().is_err() {
+            let mut pid = String::new();
+            file.read_to_string(&mut pid).unwrap_or_default();
+            let pid = match pid.trim() {
+                "" => "unknown".to_string(),
+                pid => pid.to_string(),
+            };
+            return ProbeBusy { serial, pid }.fail();
+        }
+
+        // Record the holder of the lock, for the error message of any
+        // concurrent invocation
+        file.set_len(0)
+            .and_then(|_| file.seek(SeekFrom::Start(0)))
+            .and_then(|_| write!(file, "{}", process::id()))
+            .and_then(|_| file.flush())
+            .context(CreateLock { path })?;
+
+        // No other run uses the probe while the lock is held, any temporary
+        // files of the probe are left from a run which was killed
+        temp::remove_stale(serial);
+
+        Ok(Self { _file: file })
+    }
+}
@@ -3,39 +3,39 @@
 // (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
 // notice may not be copied, modified, or distributed except according to those terms.
 
-extern crate byte_unit;
 #[macro_use]
 extern crate clap;
+extern crate ctrlc;
 extern crate dss;
-extern crate path_clean;
-extern crate path_slash;
-extern crate rust_embed;
+extern crate flash_rover;
 #[macro_use]
 extern crate snafu;
-extern crate tempfile;
 
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process;
 use std::str::FromStr;
+use std::time::Duration;
 
 use dss::{com::ti::ccstudio::scripting::environment::TraceLevel, Dss};
 
 use snafu::{Backtrace, ErrorCompat, OptionExt, ResultExt, Snafu};
 
+use flash_rover::command::{SessionAction, Subcommand};
+use flash_rover::dss_logger::{self, DssLogger};
+use flash_rover::flash_rover::{self as rover, FlashRover};
+use flash_rover::lock::{self, ProbeLock};
+use flash_rover::types::{Device, DeviceFamily, ErrorFormat};
+use flash_rover::xds110::{self, FirmwareVersion, Xds110};
+use flash_rover::{handle, hook, interrupt, json, plugin, remote, replay};
+
 use args::Args;
-use dss_logger::DssLogger;
-use flash_rover::FlashRover;
 
 mod app;
 mod args;
-mod assets;
-mod command;
-mod dss_logger;
-mod firmware;
-mod flash_rover;
-mod types;
-mod xflash;
+mod hil;
+mod production;
+mod tui;
 
 #[allow(clippy::enum_variant_names)]
 #[derive(Debug, Snafu)]
@@ -44,8 +44,9 @@ enum Error {
         source: args::Error,
     },
     CurrentDirError,
-    #[snafu(display("Unable to find CCS root"))]
+    #[snafu(display("Unable to find CCS root, specify it with --ccs or CCS_ROOT"))]
     NoCCSDir,
+    #[snafu(display("A DSS error occured: {}", source))]
     DssError {
         source: dss::Error,
         backtrace: Backtrace,
@@ -55,28 +56,254 @@ enum Error {
         backtrace: Backtrace,
     },
     FlashRoverError {
-        source: flash_rover::Error,
+        source: rover::Error,
         backtrace: Backtrace,
     },
+    #[snafu(display("{}", source))]
+    HookError {
+        source: hook::Error,
+    },
+    #[snafu(display("{}", source))]
+    HandleError {
+        source: handle::Error,
+    },
+    #[snafu(display("{}", source))]
+    RemoteError {
+        source: remote::Error,
+    },
+    #[snafu(display("{}", source))]
+    ReplayError {
+        source: replay::Error,
+    },
+    #[snafu(display("{}", source))]
+    PluginError {
+        source: plugin::Error,
+    },
+    #[snafu(display("{}", source))]
+    HilError {
+        source: hil::Error,
+    },
+    #[snafu(display("{}", source))]
+    TuiError {
+        source: tui::Error,
+    },
+    #[snafu(display("{}", source))]
+    LockError {
+        source: lock::Error,
+    },
+    Xds110Error {
+        source: xds110::Error,
+        backtrace: Backtrace,
+    },
+    #[snafu(display(
+        "Aborted after exceeding the time budget of {}",
+        humantime::format_duration(*budget)
+    ))]
+    DeadlineExceeded {
+        budget: Duration,
+    },
+    #[snafu(display("Unable to install Ctrl-C handler: {}", source))]
+    InterruptHandler {
+        source: ctrlc::Error,
+    },
 }
 
 type Result<T, E = Error> = std::result::Result<T, E>;
 
+impl Error {
+    /// Stable identifier of the error, for automation.
+    fn code(&self) -> &'static str {
+        match self {
+            Error::ArgsError { source } => source.code(),
+            Error::CurrentDirError => "current_dir_error",
+            Error::NoCCSDir => "no_ccs_dir",
+            Error::DssError { .. } => "dss_error",
+            Error::DssLoggerError { .. } => "dss_logger_error",
+            Error::FlashRoverError { source, .. } => source.code(),
+            Error::HookError { .. } => "hook_failed",
+            Error::HandleError { .. } => "session_error",
+            Error::RemoteError { .. } => "remote_error",
+            Error::ReplayError { source } => source.code(),
+            Error::PluginError { .. } => "plugin_error",
+            Error::HilError {
+                source: hil::Error::TestsFailed { .. },
+            } => "hil_test_failed",
+            Error::HilError { .. } => "hil_test_error",
+            Error::TuiError { .. } => "tui_error",
+            Error::LockError { source } => source.code(),
+            Error::Xds110Error {
+                source: xds110::Error::ProbeNotFound { .. },
+                ..
+            } => "probe_not_found",
+            Error::Xds110Error {
+                source: xds110::Error::DriverNotBound { .. },
+                ..
+            } => "driver_not_bound",
+            Error::Xds110Error {
+                source: xds110::Error::WaitTimeout { .. },
+                ..
+            } => "probe_wait_timeout",
+            Error::Xds110Error { .. } => "xds110_error",
+            Error::DeadlineExceeded { .. } => "deadline_exceeded",
+            Error::InterruptHandler { .. } => "interrupt_handler",
+        }
+    }
+
+    /// External flash offset the error relates to, if any.
+    fn offset(&self) -> Option<u32> {
+        match self {
+            Error::FlashRoverError { source, .. } => source.offset(),
+            Error::ReplayError {
+                source: replay::Error::FlashRoverError { source },
+            } => source.offset(),
+            _ => None,
+        }
+    }
+}
+
 fn main() {
-    if let Err(err) = run() {
-        eprintln!("Error: {}", err);
-        if let Some(backtrace) = ErrorCompat::backtrace(&err) {
-            eprintln!("{}", backtrace);
+    let args = match Args::parse().context(ArgsError {}) {
+        Ok(args) => args,
+        Err(err) => exit_with_error(&err, None),
+    };
+
+    if let Err(err) = run(&args) {
+        exit_with_error(&err, Some(&args));
+    }
+}
+
+/// Print the error in the error format of the run and exit.
+fn exit_with_error(err: &Error, args: Option<&Args>) -> ! {
+    match args.map_or(ErrorFormat::Human, Args::error_format) {
+        ErrorFormat::Human => {
+            eprintln!("Error: {}", err);
+            if let Some(backtrace) = ErrorCompat::backtrace(err) {
+                eprintln!("{}", backtrace);
+            }
+        }
+        ErrorFormat::Json => {
+            let (chip, probe) = args.map_or((None, None), Args::error_context);
+            let mut context = Vec::new();
+            if let Some(offset) = err.offset() {
+                context.push(format!(r#""offset":{}"#, offset));
+            }
+            if let Some(chip) = chip {
+                context.push(format!(r#""chip":{}"#, json::string(&chip)));
+            }
+            if let Some(probe) = probe {
+                context.push(format!(r#""probe":{}"#, json::string(&probe)));
+            }
+            eprintln!(
+                r#"{{"code":"{}","message":{},"context":{{{}}}}}"#,
+                err.code(),
+                json::string(&err.to_string()),
+                context.join(",")
+            );
         }
-        process::exit(1);
     }
+
+    process::exit(1);
 }
 
-fn run() -> Result<()> {
-    let args = Args::parse().context(ArgsError {})?;
+fn run(args: &Args) -> Result<()> {
+    // The probe and CCS are on the remote host
+    if let Some(remote) = args.remote() {
+        let command = args.command(Path::new("")).context(ArgsError {})?;
+        return remote::run(&command, &remote, &args.global_args()).context(RemoteError {});
+    }
 
-    let ccs_root = get_ccs_root().context(NoCCSDir {})?;
-    let command = args.command(&ccs_root).context(ArgsError {})?;
+    // The device is replaced by the trace of an earlier run
+    if let Some(path) = args.replay() {
+        let command = args.command(Path::new("")).context(ArgsError {})?;
+        return replay::run(&path, command).context(ReplayError {});
+    }
+
+    let ccs_root = match args.ccs_path().context(ArgsError {})? {
+        Some(ccs_root) => ccs_root,
+        None => find_ccs_root().context(NoCCSDir {})?,
+    };
+    let mut command = args.command(&ccs_root).context(ArgsError {})?;
+
+    if let Some(budget) = command.max_duration {
+        interrupt::set_deadline(budget);
+    }
+
+    // Scripts may be started before the board is plugged in, in which case
+    // the probe is only known once it is enumerated if --xds is not given
+    if let Some(timeout) = command.wait_for_probe {
+        let serial = Some(command.xds_id.as_str()).filter(|serial| !serial.is_empty());
+        match serial {
+            Some(serial) => eprintln!("Waiting for XDS110 {}", serial),
+            None => eprintln!("Waiting for an XDS110"),
+        }
+        command.xds_id =
+            xds110::wait_for_probe(&command.ccs_path, serial, timeout).context(Xds110Error {})?;
+    }
+
+    if let Subcommand::External { name, args } = &command.subcommand {
+        let code = plugin::run(&command, name, args).context(PluginError {})?;
+        process::exit(code);
+    }
+
+    if let Subcommand::ExportUniflash { output } = &command.subcommand {
+        return rover::export_uniflash(&command, output).context(FlashRoverError {});
+    }
+
+    // Operations through an open session, which holds the probe lock
+    match &command.subcommand {
+        Subcommand::Session {
+            action: SessionAction::Open,
+            handle: path,
+        } => return handle::open(path).context(HandleError {}),
+        Subcommand::Session {
+            action: SessionAction::Close,
+            handle: path,
+        } => return handle::close(path).context(HandleError {}),
+        Subcommand::Session { .. } => {}
+        _ => {
+            if let Some(path) = &command.handle {
+                return handle::run(&command, path).context(HandleError {});
+            }
+        }
+    }
+
+    // Held until the end of the run
+    let _probe_lock = ProbeLock::acquire(&command.xds_id).context(LockError {})?;
+
+    if let Subcommand::Session {
+        action: SessionAction::Serve,
+        handle: path,
+    } = &command.subcommand
+    {
+        return handle::serve(&command, path).context(HandleError {});
+    }
+
+    if let Subcommand::Daemon = &command.subcommand {
+        return handle::daemon(&command).context(HandleError {});
+    }
+
+    if let Subcommand::Tui = &command.subcommand {
+        return tui::run(&command).context(TuiError {});
+    }
+
+    let xds110 = Xds110::new(&command.ccs_path, &command.xds_id);
+
+    if let Subcommand::Power { power } = command.subcommand {
+        return xds110.set_power(power).context(Xds110Error {});
+    }
+
+    if let Some(pre_cmd) = &command.pre_cmd {
+        hook::run(pre_cmd).context(HookError {})?;
+    }
+
+    if command.power_cycle {
+        xds110.power_cycle().context(Xds110Error {})?;
+    }
+
+    xds110.check_present().context(Xds110Error {})?;
+    check_probe_firmware(&xds110, command.device);
+    let post_cmd = command.post_cmd.clone();
+    let max_duration = command.max_duration;
 
     let trace_level = TraceLevel::from_str(&command.log_dss).unwrap_or(TraceLevel::Off);
     let mut dss_log = DssLogger::new(trace_level);
@@ -86,9 +313,25 @@ fn run() -> Result<()> {
 
     dss_log.start(&script).context(DssLoggerError {})?;
 
-    let status = FlashRover::new(&script, command)
-        .and_then(|cli| cli.run())
-        .context(FlashRoverError {});
+    // Installed after the JVM is started, as the JVM installs its own signal
+    // handlers which would terminate the process mid-operation
+    interrupt::install().context(InterruptHandler {})?;
+
+    let status = match command.subcommand {
+        Subcommand::Sessions => rover::list_sessions(&script, &command).context(FlashRoverError {}),
+        Subcommand::Production { .. } => production::run(&script, &command).context(Xds110Error {}),
+        Subcommand::HilTest { .. } => hil::run(&script, &command).context(HilError {}),
+        _ => FlashRover::new(&script, command)
+            .and_then(|cli| cli.run())
+            .context(FlashRoverError {}),
+    };
+
+    // The debug session is disconnected at this point, run the post hook
+    // regardless of the outcome of the operation
+    let post_status = match &post_cmd {
+        Some(post_cmd) => hook::run(post_cmd).context(HookError {}),
+        None => Ok(()),
+    };
 
     if let Err(err) = status {
         if let Some(dss_log_path) = dss_log.keep() {
@@ -97,23 +340,44 @@ fn run() -> Result<()> {
                 dss_log_path.display()
             );
         }
+        if let Err(post_err) = post_status {
+            eprintln!("Error: {}", post_err);
+        }
+        if let (true, Some(budget)) = (interrupt::is_deadline_exceeded(), max_duration) {
+            return DeadlineExceeded { budget }.fail();
+        }
         return Err(err);
     };
 
+    post_status?;
+
     dss_log.stop(&script).context(DssLoggerError {})?;
 
     Ok(())
 }
 
-fn get_ccs_root() -> Option<PathBuf> {
-    if cfg!(debug_assertions) {
-        env::var_os("CCS_ROOT").map(Into::into)
-    } else {
-        // Find <SDK> in ancestors where <SDK>/ccs_base and <SDK>/eclipse exists
-        let current_dir: PathBuf = env::current_exe().ok()?.parent()?.into();
-        current_dir
-            .ancestors()
-            .find(|p| p.join("ccs_base").exists() && p.join("eclipse").exists())
-            .map(Into::into)
+/// Warn if the XDS110 firmware is older than known to work with the device.
+/// Failing to query the version is not an error, as it is only advisory.
+fn check_probe_firmware(xds110: &Xds110, device: Device) {
+    let min_version = FirmwareVersion(DeviceFamily::from(device).min_xds110_firmware());
+
+    if let Ok(Some(version)) = xds110.firmware_version() {
+        if version < min_version {
+            eprintln!(
+                "Warning: XDS110 firmware version {} is older than the minimum recommended \
+                 version {} for this device, consider updating the XDS110 firmware with xdsdfu",
+                version, min_version
+            );
+        }
     }
 }
+
+/// Find the CCS installation the executable is located in.
+fn find_ccs_root() -> Option<PathBuf> {
+    // Find <SDK> in ancestors where <SDK>/ccs_base and <SDK>/eclipse exists
+    let current_dir: PathBuf = env::current_exe().ok()?.parent()?.into();
+    current_dir
+        .ancestors()
+        .find(|p| p.join("ccs_base").exists() && p.join("eclipse").exists())
+        .map(Into::into)
+}
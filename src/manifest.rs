@@ -0,0 +1,322 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+//! Manifest of a production programming step, describing everything written
+//! to the external flash of a unit and how to verify it.
+//!
+//! The manifest is a TOML file with arrays of tables for partitions, images,
+//! fill regions and expected hashes of regions which are not written. Image
+//! paths are relative to the manifest:
+//!
+//! ```toml
+//! [[partition]]
+//! name = "factory-cal"
+//! offset = 0xF0000
+//! length = 0x1000
+//! protected = true
+//!
+//! [[image]]
+//! file = "app.bin"
+//! offset = 0x0
+//! sha256 = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"
+//!
+//! [[fill]]
+//! offset = 0x80000
+//! length = 0x2000
+//! value = 0xFF
+//!
+//! [[expect]]
+//! offset = 0xF0000
+//! length = 0x1000
+//! sha256 = "..."
+//! ```
+
+use std::convert::TryFrom;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use snafu::{Backtrace, OptionExt, ResultExt, Snafu};
+use toml::value::{Table, Value};
+
+use crate::history;
+use crate::partition::{Partition, PartitionTable};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Unable to read {}: {}", path.display(), source))]
+    ReadError {
+        path: PathBuf,
+        source: io::Error,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("Invalid manifest: {}", source))]
+    ParseError {
+        source: toml::de::Error,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("Invalid manifest entry {}[{}]: {}", table, index, reason))]
+    InvalidEntry {
+        table: &'static str,
+        index: usize,
+        reason: String,
+        backtrace: Backtrace,
+    },
+    #[snafu(display(
+        "Image {} SHA-256 digest {} does not match the expected {}, nothing was written",
+        path.display(),
+        actual,
+        expected
+    ))]
+    DigestMismatch {
+        path: PathBuf,
+        expected: String,
+        actual: String,
+        backtrace: Backtrace,
+    },
+    #[snafu(display(
+        "Regions 0x{:08X}..0x{:08X} and 0x{:08X}..0x{:08X} overlap",
+        first.start,
+        first.end,
+        second.start,
+        second.end
+    ))]
+    Overlap {
+        first: Range<u32>,
+        second: Range<u32>,
+        backtrace: Backtrace,
+    },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// A single region of the external flash handled by the manifest.
+#[derive(Clone, Debug)]
+pub enum Step {
+    /// Contents of a file, checked against its expected digest when loading
+    /// the manifest
+    Image {
+        path: PathBuf,
+        offset: u32,
+        data: Vec<u8>,
+    },
+    Fill {
+        offset: u32,
+        length: u32,
+        value: u8,
+    },
+    /// Region which is only read back and compared, e.g. a bootloader
+    /// programmed at an earlier station
+    Expect {
+        offset: u32,
+        length: u32,
+        sha256: String,
+    },
+}
+
+impl Step {
+    pub fn range(&self) -> Range<u32> {
+        let (offset, length) = match self {
+            Step::Image { offset, data, .. } => (*offset, data.len() as u32),
+            Step::Fill { offset, length, .. } | Step::Expect { offset, length, .. } => {
+                (*offset, *length)
+            }
+        };
+        offset..offset.saturating_add(length)
+    }
+
+    /// Whether the step writes to the flash.
+    pub fn writes(&self) -> bool {
+        !matches!(self, Step::Expect { .. })
+    }
+
+    /// Hex encoded SHA-256 digest the region must have after the step.
+    pub fn expected_digest(&self) -> String {
+        match self {
+            Step::Image { data, .. } => history::digest(data),
+            Step::Fill { length, value, .. } => history::digest(&vec![*value; *length as usize]),
+            Step::Expect { sha256, .. } => sha256.to_lowercase(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Manifest {
+    pub path: PathBuf,
+    /// Hex encoded SHA-256 digest of the manifest file, identifying it in the
+    /// verification report
+    pub digest: String,
+    pub partitions: PartitionTable,
+    /// Images first, then fill regions, then expected regions
+    pub steps: Vec<Step>,
+}
+
+impl Manifest {
+    /// Read the manifest and the images it refers to, verifying the image
+    /// digests and that no two steps overlap.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read(path).context(ReadError { path })?;
+        let digest = history::digest(&text);
+        let value: Value = String::from_utf8_lossy(&text)
+            .parse()
+            .context(ParseError {})?;
+        let base = path.parent().unwrap_or_else(|| Path::new(""));
+
+        let mut partitions = Vec::new();
+        for (index, entry) in tables(&value, "partition")?.enumerate() {
+            partitions.push(Partition {
+                name: string(entry, "partition", index, "name")?,
+                offset: number(entry, "partition", index, "offset")?,
+                length: number(entry, "partition", index, "length")?,
+                protected: match entry.get("protected") {
+                    Some(protected) => protected.as_bool().context(InvalidEntry {
+                        table: "partition",
+                        index,
+                        reason: "'protected' must be a boolean",
+                    })?,
+                    None => false,
+                },
+                xip: match entry.get("xip") {
+                    Some(xip) => xip.as_bool().context(InvalidEntry {
+                        table: "partition",
+                        index,
+                        reason: "'xip' must be a boolean",
+                    })?,
+                    None => false,
+                },
+            });
+        }
+
+        let mut steps = Vec::new();
+        for (index, entry) in tables(&value, "image")?.enumerate() {
+            let path = base.join(string(entry, "image", index, "file")?);
+            let data = fs::read(&path).context(ReadError { path: &path })?;
+            if let Some(expected) = optional_string(entry, "image", index, "sha256")? {
+                let actual = history::digest(&data);
+                ensure!(
+                    actual.eq_ignore_ascii_case(&expected),
+                    DigestMismatch {
+                        path,
+                        expected,
+                        actual
+                    }
+                );
+            }
+            steps.push(Step::Image {
+                offset: number(entry, "image", index, "offset")?,
+                path,
+                data,
+            });
+        }
+        for (index, entry) in tables(&value, "fill")?.enumerate() {
+            let value = match entry.get("value") {
+                Some(value) => value
+                    .as_integer()
+                    .filter(|value| (0..=0xFF).contains(value))
+                    .context(InvalidEntry {
+                        table: "fill",
+                        index,
+                        reason: "'value' must be a byte",
+                    })? as u8,
+                None => 0xFF,
+            };
+            steps.push(Step::Fill {
+                offset: number(entry, "fill", index, "offset")?,
+                length: number(entry, "fill", index, "length")?,
+                value,
+            });
+        }
+        for (index, entry) in tables(&value, "expect")?.enumerate() {
+            steps.push(Step::Expect {
+                offset: number(entry, "expect", index, "offset")?,
+                length: number(entry, "expect", index, "length")?,
+                sha256: string(entry, "expect", index, "sha256")?,
+            });
+        }
+
+        for (i, first) in steps.iter().enumerate() {
+            for second in &steps[i + 1..] {
+                let (first, second) = (first.range(), second.range());
+                ensure!(
+                    first.end <= second.start || second.end <= first.start,
+                    Overlap { first, second }
+                );
+            }
+        }
+
+        Ok(Self {
+            path: path.into(),
+            digest,
+            partitions: PartitionTable { partitions },
+            steps,
+        })
+    }
+}
+
+fn tables<'a>(value: &'a Value, name: &'static str) -> Result<impl Iterator<Item = &'a Table>> {
+    let entries = match value.get(name) {
+        Some(Value::Array(entries)) => entries.as_slice(),
+        Some(_) => InvalidEntry {
+            table: name,
+            index: 0_usize,
+            reason: "expected an array of tables",
+        }
+        .fail()?,
+        None => &[],
+    };
+    for (index, entry) in entries.iter().enumerate() {
+        ensure!(
+            entry.is_table(),
+            InvalidEntry {
+                table: name,
+                index,
+                reason: "expected a table",
+            }
+        );
+    }
+    Ok(entries.iter().filter_map(Value::as_table))
+}
+
+fn optional_string(
+    entry: &Table,
+    table: &'static str,
+    index: usize,
+    key: &str,
+) -> Result<Option<String>> {
+    match entry.get(key) {
+        Some(value) => Ok(Some(
+            value
+                .as_str()
+                .context(InvalidEntry {
+                    table,
+                    index,
+                    reason: format!("'{}' must be a string", key),
+                })?
+                .to_string(),
+        )),
+        None => Ok(None),
+    }
+}
+
+fn string(entry: &Table, table: &'static str, index: usize, key: &str) -> Result<String> {
+    optional_string(entry, table, index, key)?.context(InvalidEntry {
+        table,
+        index,
+        reason: format!("missing '{}'", key),
+    })
+}
+
+fn number(entry: &Table, table: &'static str, index: usize, key: &str) -> Result<u32> {
+    entry
+        .get(key)
+        .and_then(Value::as_integer)
+        .and_then(|value| u32::try_from(value).ok())
+        .context(InvalidEntry {
+            table,
+            index,
+            reason: format!("'{}' must be an unsigned 32-bit integer", key),
+        })
+}
@@ -0,0 +1,573 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+//! Device running the firmware, emulated in memory, for the subcommands to be
+//! driven without a probe, e.g. in the integration tests.
+//!
+//! The doorbell protocol is emulated as implemented by the firmware sources in
+//! `src/fw`, independently of the host side in [`crate::firmware`], so that a
+//! change to either side of the protocol is caught. A command is served as
+//! soon as the doorbell is rung while the firmware runs. The external flash is
+//! a Macronix MX25R8035F, as on the LaunchPads, where programming only clears
+//! bits as on the real part.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use dss::com::ti::debug::engine::scripting::Register;
+use dss::Result;
+
+use crate::backend::Backend;
+use crate::checksum;
+use crate::types::{CrcAlgorithm, Device, DeviceFamily};
+
+const SRAM_START: u32 = 0x2000_0000;
+
+const CONF: u32 = 0x2000_3000;
+const CONF_SAFE_TRANSPORT: u32 = 0x14;
+
+const DESCRIPTOR: u32 = CONF + 0x80;
+const FIRMWARE_MAGIC: u32 = 0x5652_4C46;
+const RELEASE_MAGIC: u32 = 0x4C45_5246;
+
+const DOORBELL: u32 = 0x2000_3100;
+const DOORBELL_CMD_KIND: u32 = 0x00;
+const DOORBELL_CMD_ARG0: u32 = 0x04;
+const DOORBELL_CMD_ARG1: u32 = 0x08;
+const DOORBELL_CMD_ARG2: u32 = 0x0C;
+const DOORBELL_RSP_KIND: u32 = 0x10;
+const DOORBELL_RSP_VAL0: u32 = 0x14;
+const DOORBELL_RSP_VAL1: u32 = 0x18;
+const DOORBELL_RSP_VAL2: u32 = 0x1C;
+const DOORBELL_CMD_CRC: u32 = 0x20;
+const DOORBELL_RSP_CRC: u32 = 0x24;
+const DOORBELL_DATA_CRC: u32 = 0x28;
+
+const BUF: u32 = 0x2000_4000;
+const BUF_SIZE: u32 = 0x1000;
+
+const AON_BATMON_CTL: u32 = 0x4009_5000;
+const AON_BATMON_BAT: u32 = 0x4009_5028;
+/// 3.30 V, in the 3.8 fixed point format of the battery monitor
+const BATTERY_3V3: u32 = (3 << 8) | 77;
+
+const RSP_OK: u32 = 0xD0;
+const RSP_XFLASH_INFO: u32 = 0xD1;
+const RSP_ECHO: u32 = 0xD2;
+const RSP_STATUS: u32 = 0xD3;
+const RSP_CRC: u32 = 0xD4;
+const RSP_BLANK: u32 = 0xD5;
+const RSP_CONFIG: u32 = 0xD6;
+const RSP_ERROR: u32 = 0x80;
+const RSP_ERROR_XFLASH: u32 = 0x82;
+const RSP_ERROR_BUF_OVERFLOW: u32 = 0x83;
+const RSP_ERROR_CRC: u32 = 0x85;
+
+const FLAG_JEDEC_ID: u32 = 0x4;
+
+/// Macronix MX25R8035F
+const XFLASH_MID: u8 = 0xC2;
+const XFLASH_DID: u8 = 0x14;
+const XFLASH_JEDEC: [u8; 3] = [0xC2, 0x28, 0x14];
+const XFLASH_SIZE: usize = 8 * 1024 * 1024;
+const XFLASH_SECTOR_SIZE: u32 = 0x1000;
+
+pub struct MockDevice {
+    family: DeviceFamily,
+    /// The firmware predates the descriptor, and only knows the original
+    /// commands
+    legacy: bool,
+    sram: RefCell<Vec<u8>>,
+    /// Memory outside of the SRAM, e.g. peripheral registers, by address
+    memory: RefCell<HashMap<u32, u8>>,
+    registers: RefCell<HashMap<String, u32>>,
+    halted: Cell<bool>,
+    /// The firmware has been loaded, and serves the doorbell while running
+    loaded: Cell<bool>,
+    flash: RefCell<Vec<u8>>,
+    /// Bits of the flash which stay set when programmed, by offset
+    stuck_bits: RefCell<HashMap<u32, u8>>,
+    status: Cell<u8>,
+    config: Cell<u16>,
+    /// Reads of a transfer buffer left to return with a flipped bit
+    corrupt_reads: Cell<u32>,
+    commands: RefCell<Vec<u32>>,
+}
+
+impl MockDevice {
+    /// Device of the family of `device`, running firmware built from the
+    /// current sources, with an erased external flash.
+    pub fn new(device: Device) -> Self {
+        let family = DeviceFamily::from(device);
+        let mut memory = HashMap::new();
+        for (address, value) in [(AON_BATMON_CTL, 0x03), (AON_BATMON_BAT, BATTERY_3V3)] {
+            for (index, byte) in u32::to_le_bytes(value).iter().enumerate() {
+                memory.insert(address + index as u32, *byte);
+            }
+        }
+
+        Self {
+            family,
+            legacy: false,
+            sram: RefCell::new(vec![0; family.sram_size() as usize]),
+            memory: RefCell::new(memory),
+            registers: RefCell::new(HashMap::new()),
+            halted: Cell::new(false),
+            loaded: Cell::new(false),
+            flash: RefCell::new(vec![0xFF; XFLASH_SIZE]),
+            stuck_bits: RefCell::new(HashMap::new()),
+            status: Cell::new(0x00),
+            config: Cell::new(0x0000),
+            corrupt_reads: Cell::new(0),
+            commands: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Device running firmware which predates the descriptor, as the shipped
+    /// binaries do.
+    pub fn legacy(device: Device) -> Self {
+        Self {
+            legacy: true,
+            ..Self::new(device)
+        }
+    }
+
+    /// Contents of the external flash.
+    pub fn flash(&self, offset: u32, length: u32) -> Vec<u8> {
+        let range = offset as usize..(offset + length) as usize;
+        self.flash.borrow()[range].to_vec()
+    }
+
+    /// Set the contents of the external flash, as left by an earlier write.
+    pub fn set_flash(&self, offset: u32, data: &[u8]) {
+        let start = offset as usize;
+        self.flash.borrow_mut()[start..start + data.len()].copy_from_slice(data);
+    }
+
+    /// Contents of the memory of the device outside of the external flash,
+    /// e.g. the internal flash or the factory configuration.
+    pub fn memory(&self, address: u32, length: u32) -> Vec<u8> {
+        (0..length)
+            .map(|index| self.read_byte(address + index))
+            .collect()
+    }
+
+    /// Set the contents of the memory of the device, e.g. as programmed in
+    /// production.
+    pub fn set_memory(&self, address: u32, data: &[u8]) {
+        for (index, byte) in data.iter().enumerate() {
+            self.write_byte(address + index as u32, *byte);
+        }
+    }
+
+    /// Keep the bits of `mask` set whenever the byte at `offset` is
+    /// programmed, as in a worn out cell.
+    pub fn stick_bits(&self, offset: u32, mask: u8) {
+        self.stuck_bits.borrow_mut().insert(offset, mask);
+    }
+
+    /// Flip a bit of the next `count` reads of a transfer buffer, as over
+    /// noisy wires.
+    pub fn corrupt_reads(&self, count: u32) {
+        self.corrupt_reads.set(count);
+    }
+
+    pub fn status(&self) -> u8 {
+        self.status.get()
+    }
+
+    pub fn set_status(&self, status: u8) {
+        self.status.set(status);
+    }
+
+    /// Kinds of the commands served by the firmware, in order.
+    pub fn commands(&self) -> Vec<u32> {
+        self.commands.borrow().clone()
+    }
+
+    fn sram_index(&self, address: u32) -> Option<usize> {
+        address
+            .checked_sub(SRAM_START)
+            .filter(|offset| *offset < self.family.sram_size())
+            .map(|offset| offset as usize)
+    }
+
+    fn read_byte(&self, address: u32) -> u8 {
+        match self.sram_index(address) {
+            Some(index) => self.sram.borrow()[index],
+            None => self
+                .memory
+                .borrow()
+                .get(&address)
+                .copied()
+                .unwrap_or_default(),
+        }
+    }
+
+    fn write_byte(&self, address: u32, value: u8) {
+        match self.sram_index(address) {
+            Some(index) => self.sram.borrow_mut()[index] = value,
+            None => {
+                self.memory.borrow_mut().insert(address, value);
+            }
+        }
+    }
+
+    fn load(&self, address: u32) -> u32 {
+        let bytes = [0, 1, 2, 3].map(|index| self.read_byte(address + index));
+        u32::from_le_bytes(bytes)
+    }
+
+    fn store(&self, address: u32, word: u32) {
+        for (index, byte) in word.to_le_bytes().iter().enumerate() {
+            self.write_byte(address + index as u32, *byte);
+        }
+    }
+
+    fn buf_count(&self) -> u32 {
+        if self.legacy {
+            1
+        } else {
+            self.family.firmware_buf_count()
+        }
+    }
+
+    /// Export the descriptor of the firmware once its image is loaded.
+    fn load_firmware(&self) {
+        self.loaded.set(true);
+
+        if self.legacy {
+            self.store(DESCRIPTOR, 0);
+            return;
+        }
+
+        let release = [
+            env!("CARGO_PKG_VERSION_MAJOR"),
+            env!("CARGO_PKG_VERSION_MINOR"),
+            env!("CARGO_PKG_VERSION_PATCH"),
+        ]
+        .iter()
+        .fold(0, |release, part| {
+            (release << 8) | part.parse::<u32>().unwrap_or_default()
+        });
+        let descriptor = [
+            FIRMWARE_MAGIC,
+            1,
+            DOORBELL,
+            BUF,
+            BUF_SIZE,
+            self.buf_count(),
+            // No debug log
+            0,
+            0,
+            0,
+            RELEASE_MAGIC,
+            release,
+        ];
+        for (index, word) in descriptor.iter().enumerate() {
+            self.store(DESCRIPTOR + 4 * index as u32, *word);
+        }
+    }
+
+    /// Serve the command of the doorbell, as the main loop of the firmware
+    /// does.
+    fn serve(&self) {
+        let words = [
+            self.load(DOORBELL + DOORBELL_CMD_KIND),
+            self.load(DOORBELL + DOORBELL_CMD_ARG0),
+            self.load(DOORBELL + DOORBELL_CMD_ARG1),
+            self.load(DOORBELL + DOORBELL_CMD_ARG2),
+        ];
+        self.store(DOORBELL + DOORBELL_CMD_KIND, 0);
+        self.commands.borrow_mut().push(words[0]);
+
+        // Firmware without a descriptor predates the safe transport, and
+        // ignores its configuration
+        let safe_transport = !self.legacy && self.load(CONF + CONF_SAFE_TRANSPORT) != 0;
+        let response =
+            if safe_transport && self.load(DOORBELL + DOORBELL_CMD_CRC) != words_crc(&words) {
+                Some([RSP_ERROR_CRC, 0, 0, 0])
+            } else {
+                self.execute(words, safe_transport)
+            };
+
+        // Firmware without a descriptor clears unknown commands without a
+        // response
+        if let Some(response) = response {
+            self.store(DOORBELL + DOORBELL_RSP_VAL0, response[1]);
+            self.store(DOORBELL + DOORBELL_RSP_VAL1, response[2]);
+            self.store(DOORBELL + DOORBELL_RSP_VAL2, response[3]);
+            if safe_transport {
+                self.store(DOORBELL + DOORBELL_RSP_CRC, words_crc(&response));
+            }
+            self.store(DOORBELL + DOORBELL_RSP_KIND, response[0]);
+        }
+    }
+
+    fn execute(&self, words: [u32; 4], safe_transport: bool) -> Option<[u32; 4]> {
+        let [kind, arg0, arg1, arg2] = words;
+
+        let response = match kind {
+            0xC0 => {
+                let mid = u32::from(XFLASH_MID)
+                    | u32::from(XFLASH_JEDEC[0]) << 8
+                    | u32::from(XFLASH_JEDEC[1]) << 16
+                    | u32::from(XFLASH_JEDEC[2]) << 24;
+                [RSP_XFLASH_INFO, mid, u32::from(XFLASH_DID), FLAG_JEDEC_ID]
+            }
+            0xC1 => match flash_range(arg0, arg1) {
+                Some(_) => {
+                    let start = arg0 - arg0 % XFLASH_SECTOR_SIZE;
+                    let end = (arg0 + arg1).div_ceil(XFLASH_SECTOR_SIZE) * XFLASH_SECTOR_SIZE;
+                    self.flash.borrow_mut()[start as usize..end as usize].fill(0xFF);
+                    [RSP_OK, 0, 0, 0]
+                }
+                None => [RSP_ERROR_XFLASH, 0, 0, 0],
+            },
+            0xC2 => {
+                self.flash.borrow_mut().fill(0xFF);
+                [RSP_OK, 0, 0, 0]
+            }
+            0xC3 => {
+                // Firmware without a descriptor has a single buffer, and
+                // ignores the buffer index
+                let buffer = if self.legacy { 0 } else { arg2 };
+                if arg1 > BUF_SIZE || buffer >= self.buf_count() {
+                    return Some([RSP_ERROR_BUF_OVERFLOW, 0, 0, 0]);
+                }
+                let range = match flash_range(arg0, arg1) {
+                    Some(range) => range,
+                    None => return Some([RSP_ERROR_XFLASH, 0, 0, 0]),
+                };
+
+                let data = self.flash.borrow()[range].to_vec();
+                let address = BUF + buffer * BUF_SIZE;
+                for (index, byte) in data.iter().enumerate() {
+                    self.write_byte(address + index as u32, *byte);
+                }
+                if safe_transport {
+                    [RSP_CRC, crc32fast::hash(&data), 0, 0]
+                } else {
+                    [RSP_OK, 0, 0, 0]
+                }
+            }
+            0xC4 => {
+                let buffer = if self.legacy { 0 } else { arg2 };
+                if arg1 > BUF_SIZE || buffer >= self.buf_count() {
+                    return Some([RSP_ERROR_BUF_OVERFLOW, 0, 0, 0]);
+                }
+                let range = match flash_range(arg0, arg1) {
+                    Some(range) => range,
+                    None => return Some([RSP_ERROR_XFLASH, 0, 0, 0]),
+                };
+
+                let address = BUF + buffer * BUF_SIZE;
+                let data: Vec<_> = (0..arg1)
+                    .map(|index| self.read_byte(address + index))
+                    .collect();
+                if safe_transport
+                    && self.load(DOORBELL + DOORBELL_DATA_CRC) != crc32fast::hash(&data)
+                {
+                    return Some([RSP_ERROR_CRC, 0, 0, 0]);
+                }
+
+                let stuck_bits = self.stuck_bits.borrow();
+                let mut flash = self.flash.borrow_mut();
+                for (offset, byte) in range.zip(data) {
+                    let stuck = stuck_bits
+                        .get(&(offset as u32))
+                        .copied()
+                        .unwrap_or_default();
+                    flash[offset] &= byte | stuck;
+                }
+                [RSP_OK, 0, 0, 0]
+            }
+            _ if self.legacy => return None,
+            0xC5 => [RSP_ECHO, arg0, 0, 0],
+            0xC7 => [RSP_STATUS, u32::from(self.status.get()), 0, 0],
+            0xCB => match flash_range(arg0, arg1) {
+                Some(range) => {
+                    let start = range.start;
+                    let blank = self.flash.borrow()[range]
+                        .iter()
+                        .position(|byte| *byte != 0xFF)
+                        .map_or(arg0 + arg1, |index| (start + index) as u32);
+                    [RSP_BLANK, blank, 0, 0]
+                }
+                None => [RSP_ERROR_XFLASH, 0, 0, 0],
+            },
+            0xCC => [RSP_CONFIG, u32::from(self.config.get()), 0, 0],
+            0xCD => {
+                self.status.set(arg0 as u8);
+                if arg2 != 0 {
+                    self.config.set(arg1 as u16);
+                }
+                [RSP_OK, 0, 0, 0]
+            }
+            0xCE | 0xCF => [RSP_OK, 0, 0, 0],
+            0xB0 => {
+                let algorithm = [
+                    CrcAlgorithm::Crc32,
+                    CrcAlgorithm::Crc32c,
+                    CrcAlgorithm::Crc16Ccitt,
+                ]
+                .iter()
+                .copied()
+                .find(|algorithm| algorithm.id() == arg2);
+                match (algorithm, flash_range(arg0, arg1)) {
+                    (Some(algorithm), Some(range)) => {
+                        let crc = checksum::crc(algorithm, &self.flash.borrow()[range]);
+                        [RSP_CRC, crc, 0, 0]
+                    }
+                    (None, _) => [RSP_ERROR, 0, 0, 0],
+                    (_, None) => [RSP_ERROR_XFLASH, 0, 0, 0],
+                }
+            }
+            // The data port is not emulated
+            _ => [RSP_ERROR, 0, 0, 0],
+        };
+
+        Some(response)
+    }
+}
+
+/// Range of the flash of `length` bytes from `offset`, if it is within the
+/// flash.
+fn flash_range(offset: u32, length: u32) -> Option<std::ops::Range<usize>> {
+    let end = offset.checked_add(length)?;
+    (end as usize <= XFLASH_SIZE).then_some(offset as usize..end as usize)
+}
+
+/// CRC of the words of a command or response, as computed by the firmware.
+fn words_crc(words: &[u32; 4]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    for word in words {
+        hasher.update(&word.to_le_bytes());
+    }
+    hasher.finalize()
+}
+
+impl Backend for MockDevice {
+    fn read_word(&self, address: u32) -> Result<u32> {
+        Ok(self.load(address))
+    }
+
+    fn read_words(&self, address: u32, count: u32) -> Result<Vec<u32>> {
+        Ok((0..count)
+            .map(|index| self.load(address + 4 * index))
+            .collect())
+    }
+
+    fn write_word(&self, address: u32, value: u32) -> Result<()> {
+        self.store(address, value);
+
+        if address == DOORBELL + DOORBELL_CMD_KIND
+            && value != 0
+            && self.loaded.get()
+            && !self.halted.get()
+        {
+            self.serve();
+        }
+
+        Ok(())
+    }
+
+    fn read_bytes(&self, address: u32, length: u32) -> Result<Vec<u8>> {
+        let mut values: Vec<_> = (0..length)
+            .map(|index| self.read_byte(address + index))
+            .collect();
+
+        let buffers = BUF..BUF + self.buf_count() * BUF_SIZE;
+        if buffers.contains(&address) && !values.is_empty() && self.corrupt_reads.get() > 0 {
+            self.corrupt_reads.set(self.corrupt_reads.get() - 1);
+            values[0] ^= 0x01;
+        }
+
+        Ok(values)
+    }
+
+    fn write_bytes(&self, address: u32, values: &[u8]) -> Result<()> {
+        for (index, value) in values.iter().enumerate() {
+            self.write_byte(address + index as u32, *value);
+        }
+        Ok(())
+    }
+
+    fn is_ram(&self, address: u32) -> Result<bool> {
+        Ok(self.sram_index(address).is_some())
+    }
+
+    fn load_raw(&self, address: u32, path: &Path, _type_size: u32) -> Result<()> {
+        let data = fs::read(path).unwrap_or_default();
+        self.write_bytes(address, &data)?;
+
+        if address == SRAM_START {
+            self.load_firmware();
+        }
+
+        Ok(())
+    }
+
+    fn load_program(&self, _path: &Path) -> Result<()> {
+        self.load_firmware();
+        Ok(())
+    }
+
+    fn read_register(&self, register: Register) -> Result<u32> {
+        Ok(self
+            .registers
+            .borrow()
+            .get(&register.to_string())
+            .copied()
+            .unwrap_or_default())
+    }
+
+    fn write_register(&self, register: Register, value: u32) -> Result<()> {
+        self.registers
+            .borrow_mut()
+            .insert(register.to_string(), value);
+        Ok(())
+    }
+
+    fn evaluate(&self, _expression: &str) -> Result<i64> {
+        Ok(0)
+    }
+
+    fn connect_with_retry(&self, _timeout: Duration, _attempts: u32) -> Result<u32> {
+        Ok(0)
+    }
+
+    fn disconnect(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn reset(&self) -> Result<()> {
+        self.loaded.set(false);
+        Ok(())
+    }
+
+    fn halt(&self) -> Result<()> {
+        self.halted.set(true);
+        Ok(())
+    }
+
+    fn is_halted(&self) -> Result<bool> {
+        Ok(self.halted.get())
+    }
+
+    fn run_asynch(&self) -> Result<()> {
+        self.halted.set(false);
+
+        // A command posted while halted is served once the firmware runs
+        if self.loaded.get() && self.load(DOORBELL + DOORBELL_CMD_KIND) != 0 {
+            self.serve();
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,115 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+//! Layout of the external flash used by TI off-chip OAD, where the metadata of
+//! each stored image is kept in its own page at the start of the flash.
+
+use std::fmt;
+
+/// Metadata pages scanned at the start of the external flash.
+pub const META_PAGES: u32 = 16;
+/// Size of the metadata page of an image.
+pub const META_PAGE_SIZE: u32 = 0x1000;
+/// Length of the metadata at the start of a page.
+pub const META_LEN: u32 = 51;
+
+/// Identification of a metadata page, "OAD NVM" followed by the version.
+const EXT_FLASH_ID: &[u8] = b"OAD NVM";
+
+const CRC_VALID: u8 = 0xFE;
+const CRC_INVALID: u8 = 0xFC;
+const CRC_NOT_CALCULATED: u8 = 0xFF;
+
+/// Metadata of an image stored in the external flash, `ExtImageInfo_t` of the
+/// OAD profile.
+#[derive(Clone, Debug)]
+pub struct ImageInfo {
+    pub crc32: u32,
+    pub crc_status: u8,
+    pub image_type: u8,
+    pub image_no: u8,
+    pub length: u32,
+    pub soft_ver: [u8; 4],
+    /// Address of the image data in the external flash
+    pub address: u32,
+}
+
+impl ImageInfo {
+    /// Parse the metadata at the start of a metadata page, if the page holds
+    /// any.
+    pub fn parse(page: &[u8]) -> Option<Self> {
+        if page.len() < META_LEN as usize || !page.starts_with(EXT_FLASH_ID) {
+            return None;
+        }
+
+        let u32_at = |offset: usize| {
+            u32::from_le_bytes([
+                page[offset],
+                page[offset + 1],
+                page[offset + 2],
+                page[offset + 3],
+            ])
+        };
+
+        Some(Self {
+            crc32: u32_at(8),
+            crc_status: page[16],
+            image_type: page[17],
+            image_no: page[18],
+            length: u32_at(23),
+            soft_ver: [page[31], page[32], page[33], page[34]],
+            address: u32_at(43),
+        })
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self.image_type {
+            0 => "persistent-app",
+            1 => "app",
+            2 => "stack",
+            3 => "app-stack",
+            4 => "network-proc",
+            5 => "factory",
+            6 => "bim",
+            7 => "merged",
+            16..=31 => "user",
+            _ => "unknown",
+        }
+    }
+
+    pub fn crc_status_name(&self) -> &'static str {
+        match self.crc_status {
+            CRC_VALID => "valid",
+            CRC_INVALID => "invalid",
+            CRC_NOT_CALCULATED => "not checked",
+            _ => "unknown",
+        }
+    }
+
+    /// Software version, as text if printable and in hex otherwise.
+    pub fn version(&self) -> String {
+        if self.soft_ver.iter().all(|b| b.is_ascii_graphic()) {
+            String::from_utf8_lossy(&self.soft_ver).into_owned()
+        } else {
+            self.soft_ver.iter().map(|b| format!("{:02X}", b)).collect()
+        }
+    }
+}
+
+impl fmt::Display for ImageInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:<14} {:>3}  {:<8} {:>10}  0x{:08X}  0x{:08X}  {}",
+            self.type_name(),
+            self.image_no,
+            self.version(),
+            self.length,
+            self.address,
+            self.crc32,
+            self.crc_status_name()
+        )
+    }
+}
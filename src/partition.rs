@@ -0,0 +1,148 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+//! Partition table of the external flash, naming the regions of the flash and
+//! marking those holding per-unit data which must not be overwritten.
+//!
+//! The table is a text file with one partition per line, as name, offset,
+//! length and optional flags separated by whitespace. Offset and length are
+//! decimal or hexadecimal with a `0x` prefix, and `#` starts a comment:
+//!
+//! ```text
+//! # name       offset    length   flags
+//! oad-meta     0x0       0x10000
+//! app          0x10000   0x40000  xip=true
+//! factory-cal  0xF0000   0x1000   protected=true
+//! ```
+
+use std::ops::Range;
+use std::str::FromStr;
+
+use snafu::{Backtrace, OptionExt, Snafu};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Invalid partition table line {}: {}", line, reason))]
+    InvalidLine {
+        line: usize,
+        reason: String,
+        backtrace: Backtrace,
+    },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Clone, Debug)]
+pub struct Partition {
+    pub name: String,
+    pub offset: u32,
+    pub length: u32,
+    /// Holds per-unit data, e.g. factory calibration, which raw writes must
+    /// not overwrite
+    pub protected: bool,
+    /// Mapped by the application for execute-in-place, which must not be
+    /// erased or written under the application while it is paused
+    pub xip: bool,
+}
+
+impl Partition {
+    pub fn range(&self) -> Range<u32> {
+        self.offset..self.offset.saturating_add(self.length)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct PartitionTable {
+    pub partitions: Vec<Partition>,
+}
+
+impl PartitionTable {
+    /// Protected partitions overlapping the range.
+    pub fn protected_in(&self, range: Range<u32>) -> impl Iterator<Item = &Partition> {
+        self.partitions.iter().filter(move |partition| {
+            let protected = partition.range();
+            partition.protected && protected.start < range.end && range.start < protected.end
+        })
+    }
+
+    /// Whether any partition is mapped for execute-in-place.
+    pub fn has_xip(&self) -> bool {
+        self.partitions.iter().any(|partition| partition.xip)
+    }
+
+    /// Partitions mapped for execute-in-place overlapping the range.
+    pub fn xip_in(&self, range: Range<u32>) -> impl Iterator<Item = &Partition> {
+        self.partitions.iter().filter(move |partition| {
+            let mapped = partition.range();
+            partition.xip && mapped.start < range.end && range.start < mapped.end
+        })
+    }
+}
+
+impl FromStr for PartitionTable {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut partitions = Vec::new();
+
+        for (i, line) in s.lines().enumerate() {
+            let line_no = i + 1;
+            let line = line.split('#').next().unwrap_or_default();
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.is_empty() {
+                continue;
+            }
+            ensure!(
+                fields.len() >= 3,
+                InvalidLine {
+                    line: line_no,
+                    reason: "expected name, offset and length",
+                }
+            );
+
+            let offset = parse_number(fields[1]).context(InvalidLine {
+                line: line_no,
+                reason: format!("invalid offset '{}'", fields[1]),
+            })?;
+            let length = parse_number(fields[2]).context(InvalidLine {
+                line: line_no,
+                reason: format!("invalid length '{}'", fields[2]),
+            })?;
+
+            let mut protected = false;
+            let mut xip = false;
+            for flag in &fields[3..] {
+                match *flag {
+                    "protected" | "protected=true" => protected = true,
+                    "protected=false" => protected = false,
+                    "xip" | "xip=true" => xip = true,
+                    "xip=false" => xip = false,
+                    _ => InvalidLine {
+                        line: line_no,
+                        reason: format!("unknown flag '{}'", flag),
+                    }
+                    .fail()?,
+                }
+            }
+
+            partitions.push(Partition {
+                name: fields[0].to_string(),
+                offset,
+                length,
+                protected,
+                xip,
+            });
+        }
+
+        Ok(Self { partitions })
+    }
+}
+
+fn parse_number(val: &str) -> Option<u32> {
+    match val.strip_prefix("0x").or_else(|| val.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => val.parse().ok(),
+    }
+}
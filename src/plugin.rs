@@ -0,0 +1,96 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+use std::env;
+use std::io;
+use std::path::PathBuf;
+use std::process;
+
+use snafu::{Backtrace, OptionExt, ResultExt, Snafu};
+
+use crate::command::Command;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display(
+        "Unknown subcommand '{}', and no {} executable was found in PATH",
+        name,
+        executable
+    ))]
+    PluginNotFound {
+        name: String,
+        executable: String,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("Unable to run {}: {}", path.display(), source))]
+    SpawnError {
+        path: PathBuf,
+        source: io::Error,
+        backtrace: Backtrace,
+    },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Run the `flash-rover-<name>` executable found in PATH with `args`, and
+/// return its exit code. The global options are passed in `FLASH_ROVER_*`
+/// environment variables, and the path of this executable in
+/// `FLASH_ROVER_EXE` so the plugin can call back into it.
+///
+/// Must be called before acquiring the probe lock, as the plugin will usually
+/// acquire it itself.
+pub fn run(command: &Command, name: &str, args: &[String]) -> Result<i32> {
+    let executable = format!("flash-rover-{}{}", name, env::consts::EXE_SUFFIX);
+    let path = find_in_path(&executable).context(PluginNotFound { name, executable })?;
+
+    let mut plugin = process::Command::new(&path);
+    plugin
+        .args(args)
+        .env("FLASH_ROVER_CCS_ROOT", &command.ccs_path)
+        .env("FLASH_ROVER_LOG_DSS", &command.log_dss)
+        .env("FLASH_ROVER_XDS", &command.xds_id)
+        .env("FLASH_ROVER_DEVICE", command.device.to_string())
+        .env("FLASH_ROVER_INTERFACE", command.interface.name());
+    if let Ok(exe) = env::current_exe() {
+        plugin.env("FLASH_ROVER_EXE", exe);
+    }
+    if let Some(jtag_speed) = command.jtag_speed {
+        plugin.env("FLASH_ROVER_JTAG_SPEED", jtag_speed.to_string());
+    }
+    if let Some(probe_voltage) = &command.probe_voltage {
+        plugin.env("FLASH_ROVER_PROBE_VOLTAGE", probe_voltage);
+    }
+    if let Some(spi_pins) = command
+        .spi_pins
+        .or_else(|| command.device.launchpad_spi_pins())
+    {
+        let pins: Vec<_> = spi_pins.0.iter().map(ToString::to_string).collect();
+        plugin.env("FLASH_ROVER_SPI_PINS", pins.join(","));
+    }
+    if let Some(mux_select) = &command.mux_select {
+        plugin.env(
+            "FLASH_ROVER_MUX_SELECT",
+            format!("{},{}", mux_select.dio, mux_select.level as u8),
+        );
+    }
+    if let Some(core) = &command.core {
+        plugin.env("FLASH_ROVER_CORE", core);
+    }
+    if let Some(board) = &command.board {
+        plugin.env("FLASH_ROVER_BOARD", &board.name);
+    }
+
+    let status = plugin.status().context(SpawnError { path })?;
+
+    // Terminated by a signal, if there is no exit code
+    Ok(status.code().unwrap_or(1))
+}
+
+fn find_in_path(executable: &str) -> Option<PathBuf> {
+    let paths = env::var_os("PATH")?;
+    env::split_paths(&paths)
+        .map(|dir| dir.join(executable))
+        .find(|path| path.is_file())
+}
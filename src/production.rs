@@ -0,0 +1,127 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+//! Production mode, applying the manifest of a profile to one unit after the
+//! other with as little operator interaction as possible.
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use dss::com::ti::ccstudio::scripting::environment::ScriptingEnvironment;
+use ratatui::crossterm::style::Stylize;
+
+use flash_rover::command::{Command, Subcommand};
+use flash_rover::flash_rover::FlashRover;
+use flash_rover::interrupt;
+use flash_rover::xds110;
+
+/// Interval between probe enumerations while waiting for a unit.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Rows of the large letters of the result banner.
+const FONT_HEIGHT: usize = 5;
+
+/// Run until Ctrl-C is pressed. Failing units are reported and logged, only
+/// failing to enumerate the probes is an error.
+pub fn run(script: &ScriptingEnvironment, command: &Command) -> xds110::Result<()> {
+    let profile = match &command.subcommand {
+        Subcommand::Production { profile } => profile,
+        _ => unreachable!("Only runs the production subcommand"),
+    };
+    let history = profile.log.clone().or_else(|| command.history.clone());
+
+    println!(
+        "Production profile {}, manifest {} (sha256:{})",
+        profile.path.display(),
+        profile.manifest.path.display(),
+        profile.manifest.digest
+    );
+
+    let (mut passed, mut failed) = (0, 0);
+    loop {
+        println!("\nWaiting for a unit on XDS110 {} ...", command.xds_id);
+        if !wait_for_probe(command, true)? {
+            break;
+        }
+
+        let mut unit = command.with_subcommand(Subcommand::Apply {
+            manifest: profile.manifest.clone(),
+            report: None,
+        });
+        unit.history = history.clone();
+
+        let start = Instant::now();
+        let status = FlashRover::new(script, unit).and_then(FlashRover::run);
+        match &status {
+            Ok(()) => passed += 1,
+            Err(err) => {
+                failed += 1;
+                eprintln!("Error: {}", err);
+            }
+        }
+        print_banner(status.is_ok());
+        println!(
+            "Unit done in {:.1} s, {} passed, {} failed",
+            start.elapsed().as_secs_f64(),
+            passed,
+            failed
+        );
+
+        println!("Disconnect the unit");
+        if !wait_for_probe(command, false)? {
+            break;
+        }
+    }
+
+    println!("Stopped, {} passed, {} failed", passed, failed);
+
+    Ok(())
+}
+
+/// Wait until the probe of the command is connected, or disconnected.
+/// Returns false if interrupted by Ctrl-C.
+fn wait_for_probe(command: &Command, connected: bool) -> xds110::Result<bool> {
+    loop {
+        if interrupt::is_interrupted() {
+            return Ok(false);
+        }
+        let found = xds110::enumerate(&command.ccs_path)?
+            .iter()
+            .any(|probe| probe.serial == command.xds_id);
+        if found == connected {
+            return Ok(true);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Print PASS or FAIL in large colored letters, readable from a distance.
+fn print_banner(pass: bool) {
+    let text = if pass { "PASS" } else { "FAIL" };
+    for row in 0..FONT_HEIGHT {
+        let line = text
+            .chars()
+            .map(|c| glyph(c)[row])
+            .collect::<Vec<_>>()
+            .join("  ");
+        if pass {
+            println!("{}", line.green().bold());
+        } else {
+            println!("{}", line.red().bold());
+        }
+    }
+}
+
+fn glyph(c: char) -> [&'static str; FONT_HEIGHT] {
+    match c {
+        'P' => ["█████ ", "██  ██", "█████ ", "██    ", "██    "],
+        'A' => [" ████ ", "██  ██", "██████", "██  ██", "██  ██"],
+        'S' => [" █████", "██    ", " ████ ", "    ██", "█████ "],
+        'F' => ["██████", "██    ", "█████ ", "██    ", "██    "],
+        'I' => ["██████", "  ██  ", "  ██  ", "  ██  ", "██████"],
+        'L' => ["██    ", "██    ", "██    ", "██    ", "██████"],
+        _ => ["      "; FONT_HEIGHT],
+    }
+}
@@ -0,0 +1,107 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+//! Profiles of production stations, naming the manifest programmed into
+//! every unit and the log the results are recorded in.
+//!
+//! A profile is a TOML file, either given by path or by name from the
+//! `flash-rover/profiles` directory of the user configuration directory.
+//! Paths are relative to the profile:
+//!
+//! ```toml
+//! manifest = "station.toml"
+//! log = "results.log"
+//! ```
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use snafu::{Backtrace, OptionExt, ResultExt, Snafu};
+use toml::Value;
+
+use crate::manifest::{self, Manifest};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Unable to read profile {}: {}", path.display(), source))]
+    ReadError {
+        path: PathBuf,
+        source: io::Error,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("Invalid profile {}: {}", path.display(), source))]
+    ParseError {
+        path: PathBuf,
+        source: toml::de::Error,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("Invalid profile {}: {}", path.display(), reason))]
+    InvalidProfile {
+        path: PathBuf,
+        reason: String,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("{}", source))]
+    ManifestError { source: manifest::Error },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Clone, Debug)]
+pub struct Profile {
+    pub path: PathBuf,
+    pub manifest: Manifest,
+    /// History file the result of every unit is appended to
+    pub log: Option<PathBuf>,
+}
+
+impl Profile {
+    /// Load the profile given by path, or else by name from the profiles
+    /// directory, and the manifest it refers to.
+    pub fn load(name: &str) -> Result<Self> {
+        let path = if Path::new(name).is_file() {
+            PathBuf::from(name)
+        } else {
+            profiles_dir().join(format!("{}.toml", name))
+        };
+
+        let text = fs::read_to_string(&path).context(ReadError { path: &path })?;
+        let value: Value = text.parse().context(ParseError { path: &path })?;
+        let base = path.parent().unwrap_or_else(|| Path::new(""));
+
+        let manifest = value
+            .get("manifest")
+            .and_then(Value::as_str)
+            .context(InvalidProfile {
+                path: &path,
+                reason: "'manifest' must be the path of a manifest",
+            })?;
+        let manifest = Manifest::load(&base.join(manifest)).context(ManifestError {})?;
+
+        let log = match value.get("log") {
+            Some(log) => Some(base.join(log.as_str().context(InvalidProfile {
+                path: &path,
+                reason: "'log' must be the path of a file",
+            })?)),
+            None => None,
+        };
+
+        Ok(Self {
+            path,
+            manifest,
+            log,
+        })
+    }
+}
+
+/// Directory profiles given by name are looked up in.
+pub fn profiles_dir() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(env::temp_dir)
+        .join("flash-rover")
+        .join("profiles")
+}
@@ -0,0 +1,94 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+use std::time::Instant;
+
+use byte_unit::Byte;
+
+use crate::types::ProgressFormat;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Phase {
+    Read,
+    Write,
+    Erase,
+}
+
+impl Phase {
+    fn name(&self) -> &'static str {
+        match self {
+            Phase::Read => "read",
+            Phase::Write => "write",
+            Phase::Erase => "erase",
+        }
+    }
+}
+
+/// Prints progress updates of transfers to stderr.
+pub struct Progress {
+    format: ProgressFormat,
+    phase: Option<(Phase, Instant)>,
+}
+
+impl Progress {
+    pub fn new(format: ProgressFormat) -> Self {
+        Self {
+            format,
+            phase: None,
+        }
+    }
+
+    /// Report `done` out of `total` bytes of `phase` completed. The
+    /// throughput is measured from the first update of the phase.
+    pub fn update(&mut self, phase: Phase, done: u32, total: u32) {
+        let start = match self.phase {
+            Some((current, start)) if current == phase => start,
+            _ => {
+                let start = Instant::now();
+                self.phase = Some((phase, start));
+                start
+            }
+        };
+
+        let elapsed = start.elapsed().as_secs_f64();
+        let bytes_per_sec = if elapsed > 0.0 {
+            done as f64 / elapsed
+        } else {
+            0.0
+        };
+
+        match self.format {
+            ProgressFormat::Jsonl => eprintln!(
+                r#"{{"phase":"{}","done":{},"total":{},"bytes_per_sec":{:.0}}}"#,
+                phase.name(),
+                done,
+                total,
+                bytes_per_sec
+            ),
+            ProgressFormat::Human => {
+                let percent = if total > 0 {
+                    done as u64 * 100 / total as u64
+                } else {
+                    100
+                };
+                eprint!(
+                    "\r{:<5} {:>3}% ({}/{} bytes, {}/s)   ",
+                    phase.name(),
+                    percent,
+                    done,
+                    total,
+                    Byte::from_bytes(bytes_per_sec as u128).get_appropriate_unit(true)
+                );
+                if done >= total {
+                    eprintln!();
+                }
+            }
+        }
+
+        if done >= total {
+            self.phase = None;
+        }
+    }
+}
@@ -0,0 +1,203 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+//! Operations on a probe attached to another machine, e.g. a shared lab PC.
+//!
+//! With `--remote ssh://[user@]host[:port][/path/to/ti-xflash]` the hidden
+//! `daemon` subcommand is run on the remote host through `ssh`, with the global
+//! options of the local invocation. The daemon opens a session on the probe of
+//! the remote host and serves the requests of [`crate::handle`] on its
+//! standard input and output, which ssh tunnels. The operation itself and its
+//! input and output files stay on the local machine.
+
+use std::ffi::OsString;
+use std::io::{self, BufReader};
+use std::process::{self, Child, ChildStdin, ChildStdout, Stdio};
+
+use snafu::{Backtrace, ResultExt, Snafu};
+
+use crate::command::Command;
+use crate::handle;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Unable to run ssh: {}", source))]
+    SpawnError {
+        source: io::Error,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("Unable to open a session on {}: {}", host, source))]
+    OpenFailed { host: String, source: handle::Error },
+    #[snafu(display("{}", source))]
+    RequestError { source: handle::Error },
+    #[snafu(display("ssh to {} exited with {}", host, status))]
+    SshFailed {
+        host: String,
+        status: process::ExitStatus,
+        backtrace: Backtrace,
+    },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Name of the executable run on the remote host if the URL has no path.
+const DEFAULT_PROGRAM: &str = "ti-xflash";
+
+/// Global options of the local invocation which are not passed on, as they
+/// refer to the local machine.
+const LOCAL_OPTIONS: &[&str] = &["--remote", "--ccs", "--handle"];
+
+/// Remote host given by an `ssh://` URL.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Remote {
+    /// Host name, with the user name if given
+    pub host: String,
+    pub port: Option<u16>,
+    /// Path of flash-rover on the remote host
+    pub program: String,
+}
+
+impl Remote {
+    /// Parse `ssh://[user@]host[:port][/path/to/ti-xflash]`.
+    pub fn parse(url: &str) -> Option<Self> {
+        let rest = url.strip_prefix("ssh://")?;
+        let (authority, program) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, ""),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port.parse().ok()?)),
+            None => (authority, None),
+        };
+        if host.is_empty() || host.ends_with('@') {
+            return None;
+        }
+
+        Some(Self {
+            host: host.to_string(),
+            port,
+            program: match program {
+                "" | "/" => DEFAULT_PROGRAM.to_string(),
+                program => program.to_string(),
+            },
+        })
+    }
+}
+
+/// Run the operation of `command` through a daemon on the remote host, started
+/// with the global options in `args`, the arguments of the local invocation
+/// before the subcommand.
+pub fn run(command: &Command, remote: &Remote, args: &[OsString]) -> Result<()> {
+    let mut tunnel = Tunnel::open(remote, args)?;
+
+    let status =
+        handle::forward(command, |line, data| tunnel.request(line, data)).context(RequestError {});
+
+    // Close the session regardless of the outcome, restoring the device
+    let closed = tunnel.close(remote);
+    status.and(closed)
+}
+
+struct Tunnel {
+    ssh: Child,
+    writer: ChildStdin,
+    reader: BufReader<ChildStdout>,
+}
+
+impl Tunnel {
+    fn open(remote: &Remote, args: &[OsString]) -> Result<Self> {
+        let mut ssh = process::Command::new("ssh");
+        if let Some(port) = remote.port {
+            ssh.arg("-p").arg(port.to_string());
+        }
+        // The remote command is run by the shell of the remote host, so every
+        // argument is quoted
+        ssh.arg(&remote.host).arg("--").arg(quote(&remote.program));
+        for arg in forwarded_args(args) {
+            ssh.arg(quote(&arg.to_string_lossy()));
+        }
+        ssh.arg("daemon");
+
+        let mut ssh = ssh
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .context(SpawnError {})?;
+        let writer = ssh.stdin.take().expect("stdin of ssh is piped");
+        let reader = BufReader::new(ssh.stdout.take().expect("stdout of ssh is piped"));
+
+        let mut tunnel = Self {
+            ssh,
+            writer,
+            reader,
+        };
+        // The daemon responds once it has opened the session
+        if let Err(source) = handle::read_response(&mut tunnel.reader) {
+            // Without a response, ssh or flash-rover failed on the remote
+            // host, which explained why on stderr
+            drop(tunnel.writer);
+            let status = tunnel.ssh.wait().context(SpawnError {})?;
+            ensure!(
+                status.success(),
+                SshFailed {
+                    host: &remote.host,
+                    status
+                }
+            );
+            return Err(source).context(OpenFailed { host: &remote.host });
+        }
+
+        Ok(tunnel)
+    }
+
+    fn request(&mut self, line: &str, data: &[u8]) -> handle::Result<Vec<u8>> {
+        handle::exchange(&mut self.reader, &mut self.writer, line, data)
+    }
+
+    fn close(mut self, remote: &Remote) -> Result<()> {
+        self.request("close", &[]).context(RequestError {})?;
+        drop(self.writer);
+
+        let status = self.ssh.wait().context(SpawnError {})?;
+        ensure!(
+            status.success(),
+            SshFailed {
+                host: &remote.host,
+                status
+            }
+        );
+
+        Ok(())
+    }
+}
+
+/// Arguments of the local invocation to pass on to the daemon, without the
+/// options which refer to the local machine.
+fn forwarded_args(args: &[OsString]) -> Vec<OsString> {
+    let mut forwarded = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let arg_str = arg.to_string_lossy();
+        match LOCAL_OPTIONS
+            .iter()
+            .find(|option| arg_str.starts_with(*option))
+        {
+            // Given as separate argument, the value is skipped as well
+            Some(option) if arg_str == *option => {
+                args.next();
+            }
+            Some(option) if arg_str[option.len()..].starts_with('=') => {}
+            _ => forwarded.push(arg.clone()),
+        }
+    }
+
+    forwarded
+}
+
+/// Quote an argument for a POSIX shell.
+fn quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
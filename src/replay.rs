@@ -0,0 +1,322 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+//! Replay of a trace recorded with `--record`, standing in for the device of
+//! the recorded run, so that a bug report can be reproduced without the
+//! board.
+//!
+//! The accesses of the run are matched one by one against the last session
+//! of the trace: reads return the recorded data, and writes must write the
+//! recorded data. The run fails at the first access which differs, which is
+//! reported instead of the error the run ends with. Execution control and GEL
+//! expressions are not recorded, and always succeed.
+
+use std::cell::{Cell, RefCell};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::Duration;
+
+use dss::com::ti::debug::engine::scripting::Register;
+use snafu::{Backtrace, ResultExt, Snafu};
+
+use crate::backend::Backend;
+use crate::command::{Command, Subcommand};
+use crate::flash_rover::{self, FlashRover};
+use crate::trace::{self, Access};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Unable to read trace {}: {}", path.display(), source))]
+    ReadTrace {
+        path: PathBuf,
+        source: io::Error,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("Line {} of trace {} is not a recorded access", line, path.display()))]
+    InvalidTrace {
+        path: PathBuf,
+        line: usize,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("The {} subcommand cannot be replayed", name))]
+    ReplayUnsupported { name: &'static str },
+    #[snafu(display(
+        "The run diverged from the trace at line {}, which recorded {} where the run made {}",
+        line,
+        recorded,
+        actual
+    ))]
+    Diverged {
+        line: usize,
+        recorded: String,
+        actual: String,
+    },
+    #[snafu(display("The run made {} past the end of the trace", actual))]
+    TraceEnded { actual: String },
+    #[snafu(display("The run ended before the trace, at line {}", line))]
+    RunEnded { line: usize },
+    #[snafu(display("{}", source))]
+    FlashRoverError { source: flash_rover::Error },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl Error {
+    /// Stable identifier of the error, for automation.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::ReadTrace { .. } => "read_trace_failed",
+            Error::InvalidTrace { .. } => "invalid_trace",
+            Error::ReplayUnsupported { .. } => "replay_unsupported",
+            Error::Diverged { .. } | Error::TraceEnded { .. } | Error::RunEnded { .. } => {
+                "replay_diverged"
+            }
+            Error::FlashRoverError { source } => source.code(),
+        }
+    }
+}
+
+/// Run `command` against the trace at `path` instead of a device.
+pub fn run(path: &Path, command: Command) -> Result<()> {
+    use Subcommand::*;
+
+    if let Power { .. }
+    | Sessions
+    | ExportUniflash { .. }
+    | Tui
+    | Production { .. }
+    | HilTest { .. }
+    | Session { .. }
+    | Daemon
+    | External { .. } = command.subcommand
+    {
+        return ReplayUnsupported {
+            name: command.subcommand.name(),
+        }
+        .fail();
+    }
+
+    let device = Rc::new(ReplayDevice::open(path)?);
+    let status = FlashRover::with_backend(device.clone(), command).and_then(|rover| rover.run());
+
+    // The divergence is the cause of any error of the run
+    device.finish()?;
+    status.context(FlashRoverError {})
+}
+
+/// Device of a recorded run.
+pub struct ReplayDevice {
+    accesses: Vec<(usize, Access)>,
+    /// Index of the next access to replay
+    next: Cell<usize>,
+    divergence: RefCell<Option<Error>>,
+}
+
+impl ReplayDevice {
+    /// Replay the last session of the trace at `path`.
+    pub fn open(path: &Path) -> Result<Self> {
+        let trace = fs::read_to_string(path).context(ReadTrace { path })?;
+        let accesses = trace::parse_last_session(&trace)
+            .map_err(|line| InvalidTrace { path, line }.build())?;
+
+        Ok(Self {
+            accesses,
+            next: Cell::new(0),
+            divergence: RefCell::new(None),
+        })
+    }
+
+    /// Check that the run made all of the recorded accesses, and no others.
+    pub fn finish(&self) -> Result<()> {
+        if let Some(divergence) = self.divergence.borrow_mut().take() {
+            return Err(divergence);
+        }
+        if let Some((line, _)) = self.accesses.get(self.next.get()) {
+            return RunEnded { line: *line }.fail();
+        }
+
+        Ok(())
+    }
+
+    /// Replay the next recorded access, if `replay` accepts it as the access
+    /// made by the run, described by `actual`. Once the run has diverged,
+    /// every access fails as if the debugger had stopped.
+    fn replay<T>(
+        &self,
+        actual: impl FnOnce() -> String,
+        replay: impl FnOnce(&Access) -> Option<T>,
+    ) -> dss::Result<T> {
+        if self.divergence.borrow().is_some() {
+            return Err(dss::Error::WorkerStopped);
+        }
+
+        let index = self.next.get();
+        let divergence = match self.accesses.get(index) {
+            Some((_, access)) => match replay(access) {
+                Some(value) => {
+                    self.next.set(index + 1);
+                    return Ok(value);
+                }
+                None => Diverged {
+                    line: self.accesses[index].0,
+                    recorded: access.to_string(),
+                    actual: actual(),
+                }
+                .build(),
+            },
+            None => TraceEnded { actual: actual() }.build(),
+        };
+        self.divergence.replace(Some(divergence));
+
+        Err(dss::Error::WorkerStopped)
+    }
+}
+
+impl Backend for ReplayDevice {
+    fn read_word(&self, address: u32) -> dss::Result<u32> {
+        self.replay(
+            || format!("R32 0x{:08X}", address),
+            |access| match access {
+                Access::ReadWord {
+                    address: recorded,
+                    value,
+                } if *recorded == address => Some(*value),
+                _ => None,
+            },
+        )
+    }
+
+    fn read_words(&self, address: u32, count: u32) -> dss::Result<Vec<u32>> {
+        (0..count)
+            .map(|index| self.read_word(address + 4 * index))
+            .collect()
+    }
+
+    fn write_word(&self, address: u32, value: u32) -> dss::Result<()> {
+        let written = Access::WriteWord { address, value };
+        self.replay(
+            || written.to_string(),
+            |access| (*access == written).then_some(()),
+        )
+    }
+
+    fn read_bytes(&self, address: u32, length: u32) -> dss::Result<Vec<u8>> {
+        self.replay(
+            || format!("R8 0x{:08X} ({} bytes)", address, length),
+            |access| match access {
+                Access::ReadBytes {
+                    address: recorded,
+                    values,
+                } if *recorded == address && values.len() == length as usize => {
+                    Some(values.clone())
+                }
+                _ => None,
+            },
+        )
+    }
+
+    fn write_bytes(&self, address: u32, values: &[u8]) -> dss::Result<()> {
+        let written = Access::WriteBytes {
+            address,
+            values: values.to_vec(),
+        };
+        self.replay(
+            || written.to_string(),
+            |access| (*access == written).then_some(()),
+        )
+    }
+
+    fn is_ram(&self, address: u32) -> dss::Result<bool> {
+        self.replay(
+            || format!("RAM 0x{:08X}", address),
+            |access| match access {
+                Access::IsRam {
+                    address: recorded,
+                    is_ram,
+                } if *recorded == address => Some(*is_ram),
+                _ => None,
+            },
+        )
+    }
+
+    fn load_raw(&self, address: u32, path: &Path, _type_size: u32) -> dss::Result<()> {
+        // The internal flash is recorded with the data programmed, any other
+        // load with its address only, as the file is temporary
+        let data = fs::read(path).unwrap_or_default();
+        self.replay(
+            || format!("LOAD 0x{:08X}", address),
+            |access| match access {
+                Access::Load { address: recorded } if *recorded == address => Some(()),
+                Access::WriteBytes {
+                    address: recorded,
+                    values,
+                } if *recorded == address && *values == data => Some(()),
+                _ => None,
+            },
+        )
+    }
+
+    fn load_program(&self, _path: &Path) -> dss::Result<()> {
+        self.replay(
+            || "PROGRAM".to_string(),
+            |access| (*access == Access::Program).then_some(()),
+        )
+    }
+
+    fn read_register(&self, register: Register) -> dss::Result<u32> {
+        let name = register.to_string();
+        self.replay(
+            || format!("REG {}", name),
+            |access| match access {
+                Access::Register {
+                    name: recorded,
+                    value,
+                } if *recorded == name => Some(*value),
+                _ => None,
+            },
+        )
+    }
+
+    fn write_register(&self, register: Register, value: u32) -> dss::Result<()> {
+        let written = Access::Register {
+            name: register.to_string(),
+            value,
+        };
+        self.replay(
+            || written.to_string(),
+            |access| (*access == written).then_some(()),
+        )
+    }
+
+    fn evaluate(&self, _expression: &str) -> dss::Result<i64> {
+        Ok(0)
+    }
+
+    fn connect_with_retry(&self, _timeout: Duration, _attempts: u32) -> dss::Result<u32> {
+        Ok(0)
+    }
+
+    fn disconnect(&self) -> dss::Result<()> {
+        Ok(())
+    }
+
+    fn reset(&self) -> dss::Result<()> {
+        Ok(())
+    }
+
+    fn halt(&self) -> dss::Result<()> {
+        Ok(())
+    }
+
+    fn is_halted(&self) -> dss::Result<bool> {
+        Ok(true)
+    }
+
+    fn run_asynch(&self) -> dss::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,96 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+//! Host-side cache of the sectors touched by several writes, so that each
+//! sector is read, erased and programmed once instead of once per write.
+
+use std::collections::BTreeMap;
+
+/// Content of a cached sector, and which of its bytes have been written.
+struct Sector {
+    data: Vec<u8>,
+    written: Vec<bool>,
+}
+
+pub struct SectorCache {
+    sector_size: u32,
+    sectors: BTreeMap<u32, Sector>,
+}
+
+impl SectorCache {
+    pub fn new(sector_size: u32) -> Self {
+        Self {
+            sector_size,
+            sectors: BTreeMap::new(),
+        }
+    }
+
+    /// Write `data` at `offset`, overwriting earlier writes to the same
+    /// bytes.
+    pub fn write(&mut self, offset: u32, data: &[u8]) {
+        let size = self.sector_size as usize;
+        let mut address = offset as usize;
+        let mut data = data;
+
+        while !data.is_empty() {
+            let start = address % size;
+            let len = data.len().min(size - start);
+
+            let sector = self
+                .sectors
+                .entry((address - start) as u32)
+                .or_insert_with(|| Sector {
+                    data: vec![0xFF; size],
+                    written: vec![false; size],
+                });
+            sector.data[start..start + len].copy_from_slice(&data[..len]);
+            sector.written[start..start + len]
+                .iter_mut()
+                .for_each(|written| *written = true);
+
+            address += len;
+            data = &data[len..];
+        }
+    }
+
+    /// Addresses of the sectors of which only a part has been written, and
+    /// which must be filled with the current content of the flash.
+    pub fn partial_sectors(&self) -> Vec<u32> {
+        self.sectors
+            .iter()
+            .filter(|(_, sector)| sector.written.iter().any(|written| !written))
+            .map(|(address, _)| *address)
+            .collect()
+    }
+
+    /// Fill the bytes of the sector at `address` which have not been written
+    /// with `content`.
+    pub fn fill(&mut self, address: u32, content: &[u8]) {
+        if let Some(sector) = self.sectors.get_mut(&address) {
+            for (i, byte) in content.iter().enumerate().take(sector.data.len()) {
+                if !sector.written[i] {
+                    sector.data[i] = *byte;
+                }
+            }
+        }
+    }
+
+    /// Runs of adjacent sectors with their content, each to be erased and
+    /// programmed at once.
+    pub fn runs(&self) -> Vec<(u32, Vec<u8>)> {
+        let mut runs: Vec<(u32, Vec<u8>)> = Vec::new();
+
+        for (address, sector) in &self.sectors {
+            match runs.last_mut() {
+                Some((start, data)) if *start as usize + data.len() == *address as usize => {
+                    data.extend_from_slice(&sector.data);
+                }
+                _ => runs.push((*address, sector.data.clone())),
+            }
+        }
+
+        runs
+    }
+}
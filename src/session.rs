@@ -0,0 +1,464 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+use std::io;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use dss::Dss;
+use snafu::{Backtrace, OptionExt, ResultExt, Snafu};
+
+use crate::board::Board;
+use crate::command::{Command, DataPort, Subcommand, Verify};
+use crate::firmware::BUF_SIZE;
+use crate::flash_rover::{self, FlashRover};
+use crate::partition::PartitionTable;
+use crate::types::{CrcAlgorithm, Device, Interface, MuxSelect, SpiPins};
+use crate::xflash::Xflash;
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("A DSS error occured: {}", source))]
+    DssError {
+        source: dss::Error,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("{}", source))]
+    FlashRoverError { source: flash_rover::Error },
+    #[snafu(display("Unable to start session thread: {}", source))]
+    SpawnError {
+        source: io::Error,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("The session thread has stopped"))]
+    SessionStopped { backtrace: Backtrace },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Size of the chunks transfers are split into, which is the granularity of
+/// the progress reports.
+const CHUNK_SIZE: u32 = 16 * BUF_SIZE;
+
+/// Connection options of a [`Session`].
+#[derive(Clone, Debug)]
+pub struct Config {
+    ccs_path: PathBuf,
+    xds_id: String,
+    device: Device,
+    interface: Interface,
+    jtag_speed: Option<u32>,
+    probe_voltage: Option<String>,
+    spi_pins: Option<SpiPins>,
+    mux_select: Option<MuxSelect>,
+    board: Option<Board>,
+    firmware_elf: Option<PathBuf>,
+    asset_dir: Option<PathBuf>,
+    data_port: Option<DataPort>,
+    script_timeout: Option<Duration>,
+    core: Option<String>,
+    force: bool,
+    strict: bool,
+    partitions: Option<PartitionTable>,
+    safe_transport: bool,
+    throttle: Option<u32>,
+}
+
+impl Config {
+    pub fn new(ccs_path: impl Into<PathBuf>, xds_id: &str, device: Device) -> Self {
+        Self {
+            ccs_path: ccs_path.into(),
+            xds_id: xds_id.to_string(),
+            device,
+            interface: Interface::Cjtag,
+            jtag_speed: None,
+            probe_voltage: None,
+            spi_pins: None,
+            mux_select: None,
+            board: None,
+            firmware_elf: None,
+            asset_dir: None,
+            data_port: None,
+            script_timeout: None,
+            core: None,
+            force: false,
+            strict: false,
+            partitions: None,
+            safe_transport: false,
+            throttle: None,
+        }
+    }
+
+    /// Connection options of the command line.
+    pub fn from_command(command: &Command) -> Self {
+        Self {
+            ccs_path: command.ccs_path.clone(),
+            xds_id: command.xds_id.clone(),
+            device: command.device,
+            interface: command.interface,
+            jtag_speed: command.jtag_speed,
+            probe_voltage: command.probe_voltage.clone(),
+            spi_pins: command.spi_pins,
+            mux_select: command.mux_select,
+            board: command.board.clone(),
+            firmware_elf: command.firmware_elf.clone(),
+            asset_dir: command.asset_dir.clone(),
+            data_port: command.data_port.clone(),
+            script_timeout: command.script_timeout,
+            core: command.core.clone(),
+            force: command.force,
+            strict: command.strict,
+            partitions: command.partitions.clone(),
+            safe_transport: command.safe_transport,
+            throttle: command.throttle,
+        }
+    }
+
+    pub fn interface(mut self, interface: Interface) -> Self {
+        self.interface = interface;
+        self
+    }
+
+    pub fn spi_pins(mut self, spi_pins: SpiPins) -> Self {
+        self.spi_pins = Some(spi_pins);
+        self
+    }
+
+    pub fn mux_select(mut self, mux_select: MuxSelect) -> Self {
+        self.mux_select = Some(mux_select);
+        self
+    }
+
+    pub fn board(mut self, board: Board) -> Self {
+        self.board = Some(board);
+        self
+    }
+
+    pub fn firmware_elf(mut self, firmware_elf: impl Into<PathBuf>) -> Self {
+        self.firmware_elf = Some(firmware_elf.into());
+        self
+    }
+
+    pub fn asset_dir(mut self, asset_dir: impl Into<PathBuf>) -> Self {
+        self.asset_dir = Some(asset_dir.into());
+        self
+    }
+
+    pub fn partitions(mut self, partitions: PartitionTable) -> Self {
+        self.partitions = Some(partitions);
+        self
+    }
+
+    /// Refuse to erase or program external flash parts which are not known.
+    pub fn strict(mut self, enabled: bool) -> Self {
+        self.strict = enabled;
+        self
+    }
+
+    pub fn safe_transport(mut self, enabled: bool) -> Self {
+        self.safe_transport = enabled;
+        self
+    }
+
+    /// Cap the transfer speed to and from the external flash, in KB/s.
+    pub fn throttle(mut self, kbps: u32) -> Self {
+        self.throttle = Some(kbps);
+        self
+    }
+
+    /// Command running `subcommand` with the connection options, e.g. for
+    /// [`FlashRover::with_backend`].
+    pub fn into_command(self, subcommand: Subcommand) -> Command {
+        Command {
+            subcommand,
+            ..self.command()
+        }
+    }
+
+    fn command(self) -> Command {
+        Command {
+            ccs_path: self.ccs_path,
+            log_dss: "OFF".to_string(),
+            xds_id: self.xds_id,
+            device: self.device,
+            interface: self.interface,
+            jtag_speed: self.jtag_speed,
+            probe_voltage: self.probe_voltage,
+            spi_pins: self.spi_pins,
+            mux_select: self.mux_select,
+            board: self.board,
+            firmware_elf: self.firmware_elf,
+            asset_dir: self.asset_dir,
+            data_port: self.data_port,
+            script_timeout: self.script_timeout,
+            max_duration: None,
+            reconnect: 0,
+            cooperative: None,
+            power_down: false,
+            fw_log: false,
+            core: self.core,
+            power_cycle: false,
+            wait_for_probe: None,
+            force: self.force,
+            strict: self.strict,
+            partitions: self.partitions,
+            safe_transport: self.safe_transport,
+            throttle: self.throttle,
+            crc_algorithm: CrcAlgorithm::Crc32,
+            stats: false,
+            progress_format: None,
+            pre_cmd: None,
+            post_cmd: None,
+            history: None,
+            record: None,
+            handle: None,
+            subcommand: Subcommand::Info,
+        }
+    }
+}
+
+/// Progress of a transfer, reported after each chunk.
+#[derive(Clone, Copy, Debug)]
+pub struct Progress {
+    pub done: u32,
+    pub total: u32,
+}
+
+type Job = Box<dyn FnOnce(&FlashRover) + Send>;
+
+enum Event<T> {
+    Progress(Progress),
+    Done(flash_rover::Result<T>),
+}
+
+/// A debug session with the firmware running on the device, kept open across
+/// operations.
+///
+/// The JVM and the debug session are bound to the thread they were created
+/// on, so all operations are run on a dedicated session thread. The internal
+/// flash is restored and the device is reset once the session is dropped.
+pub struct Session {
+    sender: Option<mpsc::Sender<Job>>,
+    worker: Option<thread::JoinHandle<()>>,
+}
+
+impl Session {
+    /// Start DSS, connect to the device and inject the firmware.
+    pub fn open(config: Config) -> Result<Self> {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let (ready_sender, ready_receiver) = mpsc::channel::<Result<()>>();
+
+        let worker = thread::Builder::new()
+            .name("flash-rover-session".to_string())
+            .spawn(move || {
+                let dss = match Dss::new(&config.ccs_path).context(DssError {}) {
+                    Ok(dss) => dss,
+                    Err(err) => return ready_sender.send(Err(err)).unwrap_or_default(),
+                };
+                let script = match dss.scripting_environment().context(DssError {}) {
+                    Ok(script) => script,
+                    Err(err) => return ready_sender.send(Err(err)).unwrap_or_default(),
+                };
+                let rover = FlashRover::new(&script, config.command()).and_then(|rover| {
+                    rover.reset_into_firmware()?;
+                    Ok(rover)
+                });
+                let rover = match rover.context(FlashRoverError {}) {
+                    Ok(rover) => rover,
+                    Err(err) => return ready_sender.send(Err(err)).unwrap_or_default(),
+                };
+                ready_sender.send(Ok(())).unwrap_or_default();
+
+                for job in receiver {
+                    job(&rover);
+                }
+
+                rover.release_xflash();
+                if let Err(err) = rover.restore_internal_flash() {
+                    eprintln!("Warning: Unable to restore internal flash: {}", err);
+                }
+            })
+            .context(SpawnError {})?;
+
+        ready_receiver.recv().ok().context(SessionStopped {})??;
+
+        Ok(Self {
+            sender: Some(sender),
+            worker: Some(worker),
+        })
+    }
+
+    /// Identify the external flash.
+    pub fn info(&self) -> Result<Xflash> {
+        self.call(|rover, _| rover.xflash_info(), |_| {})
+    }
+
+    /// Read `length` bytes starting at `offset`.
+    pub fn read(
+        &self,
+        offset: u32,
+        length: u32,
+        progress: impl FnMut(Progress),
+    ) -> Result<Vec<u8>> {
+        self.call(
+            move |rover, report| {
+                let mut data = Vec::with_capacity(length as _);
+                for (start, end) in chunks(offset, length) {
+                    rover.read(start, end - start, &mut data)?;
+                    report(Progress {
+                        done: end - offset,
+                        total: length,
+                    });
+                }
+                Ok(data)
+            },
+            progress,
+        )
+    }
+
+    /// Write `data` starting at `offset`, preserving the rest of the touched
+    /// sectors.
+    pub fn write(
+        &self,
+        offset: u32,
+        data: Vec<u8>,
+        verify: bool,
+        progress: impl FnMut(Progress),
+    ) -> Result<()> {
+        self.call(
+            move |rover, report| {
+                let length = data.len() as u32;
+                for (start, end) in chunks(offset, length) {
+                    let mut chunk = &data[(start - offset) as usize..(end - offset) as usize];
+                    let verify = if verify { Verify::Full } else { Verify::Off };
+                    rover.write(verify, None, false, start, None, &mut chunk)?;
+                    report(Progress {
+                        done: end - offset,
+                        total: length,
+                    });
+                }
+                Ok(())
+            },
+            progress,
+        )
+    }
+
+    /// Whether each block of `block_size` bytes in the `length` bytes starting
+    /// at `offset` is blank, found with the blank check of the firmware.
+    pub fn blank_blocks(
+        &self,
+        offset: u32,
+        length: u32,
+        block_size: u32,
+        progress: impl FnMut(Progress),
+    ) -> Result<Vec<bool>> {
+        self.call(
+            move |rover, report| {
+                let end = offset.saturating_add(length);
+                let blocks = length.div_ceil(block_size) as usize;
+                let mut blank = vec![true; blocks];
+
+                let mut start = offset;
+                while start < end {
+                    let dirty = match rover.blank_check(start, end - start)? {
+                        Some(dirty) => dirty,
+                        None => break,
+                    };
+                    // Continue at the block after the first non-blank byte
+                    let block = (dirty - offset) / block_size;
+                    blank[block as usize] = false;
+                    start = offset.saturating_add((block + 1).saturating_mul(block_size));
+                    report(Progress {
+                        done: start.min(end) - offset,
+                        total: length,
+                    });
+                }
+                report(Progress {
+                    done: length,
+                    total: length,
+                });
+
+                Ok(blank)
+            },
+            progress,
+        )
+    }
+
+    /// CRC of the `length` bytes starting at `offset`, computed by the
+    /// firmware without transferring them.
+    pub fn checksum(&self, offset: u32, length: u32, algorithm: CrcAlgorithm) -> Result<u32> {
+        self.call(
+            move |rover, _| rover.checksum(offset, length, algorithm),
+            |_| {},
+        )
+    }
+
+    /// Erase the sectors covering `length` bytes starting at `offset`.
+    pub fn erase(&self, offset: u32, length: u32) -> Result<()> {
+        self.call(move |rover, _| rover.sector_erase(offset, length), |_| {})
+    }
+
+    pub fn mass_erase(&self) -> Result<()> {
+        self.call(|rover, _| rover.mass_erase(), |_| {})
+    }
+
+    /// Run `f` on the session thread, forwarding its progress reports to
+    /// `progress` on the calling thread.
+    fn call<T, F>(&self, f: F, mut progress: impl FnMut(Progress)) -> Result<T>
+    where
+        F: FnOnce(&FlashRover, &dyn Fn(Progress)) -> flash_rover::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let (event_sender, event_receiver) = mpsc::channel();
+
+        let job: Job = Box::new(move |rover| {
+            let progress_sender = event_sender.clone();
+            let report = |p| progress_sender.send(Event::Progress(p)).unwrap_or_default();
+            let res = f(rover, &report);
+            event_sender.send(Event::Done(res)).unwrap_or_default();
+        });
+        self.sender
+            .as_ref()
+            .and_then(|sender| sender.send(job).ok())
+            .context(SessionStopped {})?;
+
+        for event in event_receiver {
+            match event {
+                Event::Progress(p) => progress(p),
+                Event::Done(res) => return res.context(FlashRoverError {}),
+            }
+        }
+
+        SessionStopped {}.fail()
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        // Closing the channel stops the session thread
+        self.sender.take();
+        if let Some(worker) = self.worker.take() {
+            worker.join().unwrap_or_default();
+        }
+    }
+}
+
+/// Split a range into chunks aligned to [`CHUNK_SIZE`], so that sectors are
+/// not shared between consecutive writes.
+fn chunks(offset: u32, length: u32) -> impl Iterator<Item = (u32, u32)> {
+    let end = offset.saturating_add(length);
+    let mut start = offset;
+    std::iter::from_fn(move || {
+        if start >= end {
+            return None;
+        }
+        let chunk_end = (start / CHUNK_SIZE + 1).saturating_mul(CHUNK_SIZE).min(end);
+        let chunk = (start, chunk_end);
+        start = chunk_end;
+        Some(chunk)
+    })
+}
@@ -0,0 +1,116 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+//! Data split over numbered part files, `<path>.000`, `<path>.001` and so on,
+//! for dumps too large for a single file on some file systems.
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Path of part `index` of the split file `path`.
+pub fn part_path(path: &Path, index: u32) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{:03}", index));
+    PathBuf::from(name)
+}
+
+/// Writer starting a new part file each time `size` bytes have been written
+/// to the current one.
+pub struct SplitWriter {
+    path: PathBuf,
+    size: u64,
+    index: u32,
+    part: Option<(File, u64)>,
+}
+
+impl SplitWriter {
+    pub fn new(path: impl Into<PathBuf>, size: u64) -> Self {
+        Self {
+            path: path.into(),
+            size,
+            index: 0,
+            part: None,
+        }
+    }
+}
+
+impl Write for SplitWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if data.is_empty() {
+            return Ok(0);
+        }
+
+        let full = match &self.part {
+            Some((_, written)) => *written >= self.size,
+            None => true,
+        };
+        if full {
+            let file = File::create(part_path(&self.path, self.index))?;
+            self.index += 1;
+            self.part = Some((file, 0));
+        }
+        let (file, written) = match &mut self.part {
+            Some(part) => part,
+            None => return Ok(0),
+        };
+
+        let len = data.len().min((self.size - *written) as usize);
+        let len = file.write(&data[..len])?;
+        *written += len as u64;
+
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.part {
+            Some((file, _)) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Reader concatenating the part files written by [`SplitWriter`], up to the
+/// first missing part.
+pub struct SplitReader {
+    path: PathBuf,
+    index: u32,
+    part: Option<File>,
+}
+
+impl SplitReader {
+    /// Open the split file `path`, which must have at least its first part.
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let part = File::open(part_path(&path, 0))?;
+
+        Ok(Self {
+            path,
+            index: 1,
+            part: Some(part),
+        })
+    }
+}
+
+impl Read for SplitReader {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        while let Some(part) = &mut self.part {
+            let read = part.read(out)?;
+            if read > 0 || out.is_empty() {
+                return Ok(read);
+            }
+
+            let next = part_path(&self.path, self.index);
+            self.part = if next.exists() {
+                Some(File::open(next)?)
+            } else {
+                None
+            };
+            self.index += 1;
+        }
+
+        Ok(0)
+    }
+}
@@ -0,0 +1,101 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+use std::fmt;
+use std::time::Duration;
+
+use byte_unit::Byte;
+
+/// Timing and transfer statistics of a flash-rover run.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Stats {
+    pub session_setup: Duration,
+    pub firmware_injection: Duration,
+    pub operation: Duration,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    pub doorbell_commands: u32,
+    pub retries: u32,
+}
+
+impl Stats {
+    /// Average throughput of data transferred to and from the external flash
+    /// during the operation, in bytes per second.
+    pub fn throughput(&self) -> Option<f64> {
+        let secs = self.operation.as_secs_f64();
+        if secs > 0.0 {
+            Some((self.bytes_read + self.bytes_written) as f64 / secs)
+        } else {
+            None
+        }
+    }
+}
+
+/// Summary of a write, printed at its end.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WriteSummary {
+    pub sectors_erased: u32,
+    /// Sectors which were already blank and needed no erase
+    pub sectors_skipped: u32,
+    pub bytes_programmed: u64,
+    pub retries: u32,
+    pub duration: Duration,
+}
+
+impl fmt::Display for WriteSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let secs = self.duration.as_secs_f64();
+        let throughput = if secs > 0.0 {
+            self.bytes_programmed as f64 / secs
+        } else {
+            0.0
+        };
+
+        write!(
+            f,
+            "Erased {} sectors, skipped {} blank sectors, programmed {} in {:.3} s ({}/s), {} retries",
+            self.sectors_erased,
+            self.sectors_skipped,
+            bytes(self.bytes_programmed),
+            secs,
+            bytes(throughput as u64),
+            self.retries
+        )
+    }
+}
+
+fn bytes(value: u64) -> String {
+    Byte::from_bytes(value as u128)
+        .get_appropriate_unit(true)
+        .to_string()
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Session setup:      {:.3} s",
+            self.session_setup.as_secs_f64()
+        )?;
+        writeln!(
+            f,
+            "Firmware injection: {:.3} s",
+            self.firmware_injection.as_secs_f64()
+        )?;
+        writeln!(
+            f,
+            "Operation:          {:.3} s",
+            self.operation.as_secs_f64()
+        )?;
+        writeln!(f, "Bytes read:         {}", bytes(self.bytes_read))?;
+        writeln!(f, "Bytes written:      {}", bytes(self.bytes_written))?;
+        match self.throughput() {
+            Some(throughput) => writeln!(f, "Throughput:         {}/s", bytes(throughput as u64))?,
+            None => writeln!(f, "Throughput:         -")?,
+        }
+        writeln!(f, "Doorbell commands:  {}", self.doorbell_commands)?;
+        write!(f, "Retries:            {}", self.retries)
+    }
+}
@@ -0,0 +1,46 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+//! Temporary files handed to DSS by path, e.g. the firmware binary. They are
+//! kept in a directory per probe, so that parallel runs against different
+//! probes on one host never share a directory, and files left behind by a run
+//! which was killed are removed by the next run on the same probe.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use tempfile::{Builder, NamedTempFile};
+
+const TEMP_DIR_NAME: &str = "flash-rover";
+
+/// Directory of the temporary files of runs on the probe.
+pub fn probe_dir(serial: &str) -> PathBuf {
+    env::temp_dir().join(TEMP_DIR_NAME).join(serial)
+}
+
+/// Create a temporary file in the directory of the probe, which is removed
+/// when dropped.
+pub fn create(serial: &str, prefix: &str, suffix: &str) -> io::Result<NamedTempFile> {
+    let dir = probe_dir(serial);
+    fs::create_dir_all(&dir)?;
+
+    Builder::new()
+        .prefix(prefix)
+        .suffix(suffix)
+        .tempfile_in(dir)
+}
+
+/// Remove the files left behind in the directory of the probe. Must only be
+/// called with the probe lock held, when no other run uses the directory.
+/// Files which cannot be removed are left for a later run.
+pub fn remove_stale(serial: &str) {
+    if let Ok(entries) = fs::read_dir(probe_dir(serial)) {
+        for entry in entries.flatten() {
+            fs::remove_file(entry.path()).unwrap_or_default();
+        }
+    }
+}
@@ -0,0 +1,69 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+//! TI-TXT output, as exchanged by TI manufacturing flows and legacy tools. The
+//! input is parsed along with the other address-bearing images in
+//! [`crate::image`].
+
+use std::io::{self, Write};
+
+const BYTES_PER_LINE: usize = 16;
+
+/// Writer formatting the data as a single TI-TXT section starting at
+/// `address`. The terminating `q` is written once the writer is dropped.
+pub struct TiTxtWriter<W: Write> {
+    inner: W,
+    address: u32,
+    started: bool,
+    line: Vec<u8>,
+}
+
+impl<W: Write> TiTxtWriter<W> {
+    pub fn new(inner: W, address: u32) -> Self {
+        Self {
+            inner,
+            address,
+            started: false,
+            line: Vec::with_capacity(BYTES_PER_LINE),
+        }
+    }
+
+    fn write_line(&mut self) -> io::Result<()> {
+        let text: Vec<String> = self.line.iter().map(|b| format!("{:02X}", b)).collect();
+        writeln!(self.inner, "{}", text.join(" "))?;
+        self.line.clear();
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for TiTxtWriter<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if !self.started {
+            writeln!(self.inner, "@{:04X}", self.address)?;
+            self.started = true;
+        }
+
+        let len = data.len().min(BYTES_PER_LINE - self.line.len());
+        self.line.extend_from_slice(&data[..len]);
+        if self.line.len() == BYTES_PER_LINE {
+            self.write_line()?;
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Drop for TiTxtWriter<W> {
+    fn drop(&mut self) {
+        if !self.line.is_empty() {
+            self.write_line().unwrap_or_default();
+        }
+        writeln!(self.inner, "q").unwrap_or_default();
+        self.inner.flush().unwrap_or_default();
+    }
+}
@@ -0,0 +1,226 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+//! Trace of the traffic between the tool and the firmware through DSS, to be
+//! attached to bug reports.
+//!
+//! The trace is a text file with one access per line, as the time since the
+//! start of the session in seconds, the kind of access, the address and the
+//! data in hex. Doorbell commands and responses are recorded decoded, after
+//! the accesses which posted or read them. Every session appends a header,
+//! starting with `#`:
+//!
+//! ```text
+//! # flash-rover 0.3.3, cc1352r on XDS110 L200005Z, 2020-06-01T12:00:00Z
+//! 0.000412 W32 0x20003F0C 000000C1
+//! 0.000530 R32 0x20003F00 00000000
+//! 0.000531 CMD SectorErase { offset: 0, length: 4096 }
+//! 0.002140 R8 0x20004000 FFFFFFFF...
+//! ```
+//!
+//! The last session of a trace is replayed by [`crate::replay`].
+
+use std::cell::RefCell;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::{Instant, SystemTime};
+
+use crate::types::Device;
+
+/// Access to the device recorded in a trace.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Access {
+    ReadWord {
+        address: u32,
+        value: u32,
+    },
+    WriteWord {
+        address: u32,
+        value: u32,
+    },
+    ReadBytes {
+        address: u32,
+        values: Vec<u8>,
+    },
+    WriteBytes {
+        address: u32,
+        values: Vec<u8>,
+    },
+    IsRam {
+        address: u32,
+        is_ram: bool,
+    },
+    /// A file loaded into memory, of which only the address is recorded
+    Load {
+        address: u32,
+    },
+    Program,
+    /// A core register read or written
+    Register {
+        name: String,
+        value: u32,
+    },
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Access::ReadWord { address, value } => write!(f, "R32 0x{:08X} {:08X}", address, value),
+            Access::WriteWord { address, value } => {
+                write!(f, "W32 0x{:08X} {:08X}", address, value)
+            }
+            Access::ReadBytes { address, values } => {
+                write!(f, "R8 0x{:08X} ({} bytes)", address, values.len())
+            }
+            Access::WriteBytes { address, values } => {
+                write!(f, "W8 0x{:08X} ({} bytes)", address, values.len())
+            }
+            Access::IsRam { address, is_ram } => write!(f, "RAM 0x{:08X} {}", address, is_ram),
+            Access::Load { address } => write!(f, "LOAD 0x{:08X}", address),
+            Access::Program => write!(f, "PROGRAM"),
+            Access::Register { name, value } => write!(f, "REG {} {:08X}", name, value),
+        }
+    }
+}
+
+/// Accesses of the last session of a trace, with their line numbers.
+/// Doorbell commands and responses are skipped, they are replayed by the
+/// accesses which exchanged them. Fails with the number of the first line
+/// which is not a valid access.
+pub fn parse_last_session(trace: &str) -> Result<Vec<(usize, Access)>, usize> {
+    let mut accesses = Vec::new();
+    for (index, line) in trace.lines().enumerate() {
+        if line.starts_with('#') {
+            accesses.clear();
+            continue;
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(access) = parse_access(line).ok_or(index + 1)? {
+            accesses.push((index + 1, access));
+        }
+    }
+
+    Ok(accesses)
+}
+
+fn parse_access(line: &str) -> Option<Option<Access>> {
+    let mut fields = line.split_whitespace().skip(1);
+    let kind = fields.next()?;
+
+    let access = match kind {
+        "CMD" | "RSP" => return Some(None),
+        "R32" => Access::ReadWord {
+            address: parse_address(fields.next()?)?,
+            value: u32::from_str_radix(fields.next()?, 16).ok()?,
+        },
+        "W32" => Access::WriteWord {
+            address: parse_address(fields.next()?)?,
+            value: u32::from_str_radix(fields.next()?, 16).ok()?,
+        },
+        // No data is recorded of an empty access
+        "R8" => Access::ReadBytes {
+            address: parse_address(fields.next()?)?,
+            values: parse_hex(fields.next().unwrap_or_default())?,
+        },
+        "W8" => Access::WriteBytes {
+            address: parse_address(fields.next()?)?,
+            values: parse_hex(fields.next().unwrap_or_default())?,
+        },
+        "RAM" => Access::IsRam {
+            address: parse_address(fields.next()?)?,
+            is_ram: fields.next()?.parse().ok()?,
+        },
+        "LOAD" => Access::Load {
+            address: parse_address(fields.next()?)?,
+        },
+        "PROGRAM" => Access::Program,
+        "REG" => Access::Register {
+            name: fields.next()?.to_string(),
+            value: u32::from_str_radix(fields.next()?, 16).ok()?,
+        },
+        _ => return None,
+    };
+
+    Some(Some(access))
+}
+
+fn parse_address(field: &str) -> Option<u32> {
+    u32::from_str_radix(field.strip_prefix("0x")?, 16).ok()
+}
+
+fn parse_hex(field: &str) -> Option<Vec<u8>> {
+    // A trailing half byte is out of range
+    (0..field.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(field.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+pub struct Recorder {
+    file: RefCell<BufWriter<File>>,
+    start: Instant,
+}
+
+impl Recorder {
+    /// Append the trace of a new session to the file.
+    pub fn open(path: &Path, device: Device, xds_id: &str) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let mut file = BufWriter::new(file);
+        writeln!(
+            file,
+            "# flash-rover {}, {} on XDS110 {}, {}",
+            env!("CARGO_PKG_VERSION"),
+            device.to_string(),
+            xds_id,
+            humantime::format_rfc3339_seconds(SystemTime::now())
+        )?;
+
+        Ok(Self {
+            file: RefCell::new(file),
+            start: Instant::now(),
+        })
+    }
+
+    pub fn read_word(&self, address: u32, value: u32) {
+        self.record(format_args!("R32 0x{:08X} {:08X}", address, value));
+    }
+
+    pub fn write_word(&self, address: u32, value: u32) {
+        self.record(format_args!("W32 0x{:08X} {:08X}", address, value));
+    }
+
+    pub fn read_bytes(&self, address: u32, values: &[u8]) {
+        self.record(format_args!("R8 0x{:08X} {}", address, hex(values)));
+    }
+
+    pub fn write_bytes(&self, address: u32, values: &[u8]) {
+        self.record(format_args!("W8 0x{:08X} {}", address, hex(values)));
+    }
+
+    /// Any other access, e.g. loading a file into memory.
+    pub fn note(&self, kind: &str, detail: impl fmt::Display) {
+        self.record(format_args!("{} {}", kind, detail));
+    }
+
+    /// Failing to record is not an error, the operation itself is unaffected.
+    fn record(&self, access: fmt::Arguments) {
+        let time = self.start.elapsed().as_secs_f64();
+        writeln!(self.file.borrow_mut(), "{:.6} {}", time, access).unwrap_or_default();
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        self.file.borrow_mut().flush().unwrap_or_default();
+    }
+}
+
+fn hex(values: &[u8]) -> String {
+    values.iter().map(|byte| format!("{:02X}", byte)).collect()
+}
@@ -0,0 +1,550 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+//! Interactive terminal dashboard of the `tui` subcommand, showing the
+//! external flash info and a map of its blank and used blocks. Regions are
+//! selected on the map with the keyboard or the mouse and read, erased or
+//! written through a session kept open until the dashboard is closed.
+
+use std::cell::Cell;
+use std::fs;
+use std::io::{self, stdout};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use flash_rover::command::Command;
+use flash_rover::session::{self, Config, Progress, Session};
+use flash_rover::xflash::Xflash;
+use ratatui::crossterm::event::{
+    self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind,
+    MouseButton, MouseEvent, MouseEventKind,
+};
+use ratatui::crossterm::execute;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Gauge, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use snafu::{Backtrace, OptionExt, ResultExt, Snafu};
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("{}", source))]
+    SessionError { source: session::Error },
+    #[snafu(display("Terminal error: {}", source))]
+    TerminalError {
+        source: io::Error,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("Unable to access {}: {}", path.display(), source))]
+    FileError {
+        path: PathBuf,
+        source: io::Error,
+        backtrace: Backtrace,
+    },
+    #[snafu(display(
+        "Input of {} bytes at 0x{:08X} does not fit in the external flash of {} bytes",
+        length,
+        offset,
+        size
+    ))]
+    OutOfBounds {
+        offset: u32,
+        length: u32,
+        size: u32,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("The size of the external flash must be known to show the block map"))]
+    UnknownSize { backtrace: Backtrace },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Erase granularity of the external flash.
+const SECTOR_SIZE: u32 = 0x1000;
+/// Most blocks shown on the map, larger flashes are mapped in blocks of
+/// several sectors.
+const MAX_BLOCKS: u32 = 1024;
+/// Most blocks on a row of the map.
+const MAX_COLUMNS: usize = 64;
+/// Width of the address column of the map.
+const ADDRESS_WIDTH: u16 = 11;
+
+#[derive(Clone, Copy)]
+enum Action {
+    Read,
+    Erase,
+    Write,
+}
+
+enum Mode {
+    Normal,
+    /// Entering the path of the file to read to or write from
+    Prompt {
+        action: Action,
+        input: String,
+    },
+    /// Waiting for the confirmation of an erase
+    Confirm,
+}
+
+enum Message {
+    Info(String),
+    Error(String),
+}
+
+/// Placement of the map on the screen, kept to map mouse clicks to blocks.
+#[derive(Clone, Copy)]
+struct MapGeometry {
+    area: Rect,
+    columns: usize,
+    first_row: usize,
+}
+
+struct App {
+    xflash: Xflash,
+    size: u32,
+    block_size: u32,
+    /// Whether each block is used, if scanned
+    blocks: Vec<Option<bool>>,
+    cursor: usize,
+    /// Other end of the selection, which is the cursor block only if unset
+    anchor: Option<usize>,
+    mode: Mode,
+    message: Message,
+    progress: Option<(&'static str, Progress)>,
+    geometry: Cell<Option<MapGeometry>>,
+}
+
+/// Open a session with the device and run the dashboard until it is quit.
+pub fn run(command: &Command) -> Result<()> {
+    eprintln!("Connecting to the device...");
+    let session = Session::open(Config::from_command(command)).context(SessionError {})?;
+    let xflash = session.info().context(SessionError {})?;
+    let size = xflash.size().context(UnknownSize {})?;
+
+    let mut app = App::new(xflash, size);
+    let mut terminal = ratatui::try_init().context(TerminalError {})?;
+    let status = execute!(stdout(), EnableMouseCapture)
+        .context(TerminalError {})
+        .and_then(|_| app.run(&session, &mut terminal));
+    execute!(stdout(), DisableMouseCapture).unwrap_or_default();
+    ratatui::restore();
+
+    status
+}
+
+impl App {
+    fn new(xflash: Xflash, size: u32) -> Self {
+        let sectors = size.div_ceil(SECTOR_SIZE);
+        let sectors_per_block = sectors.div_ceil(MAX_BLOCKS).max(1).next_power_of_two();
+        let block_size = SECTOR_SIZE * sectors_per_block;
+        let blocks = size.div_ceil(block_size) as usize;
+
+        Self {
+            xflash,
+            size,
+            block_size,
+            blocks: vec![None; blocks],
+            cursor: 0,
+            anchor: None,
+            mode: Mode::Normal,
+            message: Message::Info(String::from(
+                "Select blocks with the arrow keys and space, or click and drag",
+            )),
+            progress: None,
+            geometry: Cell::new(None),
+        }
+    }
+
+    fn run(&mut self, session: &Session, terminal: &mut DefaultTerminal) -> Result<()> {
+        let all = 0..self.blocks.len();
+        self.report(|app| app.scan(session, terminal, all));
+
+        loop {
+            terminal
+                .draw(|frame| self.render(frame))
+                .context(TerminalError {})?;
+
+            match event::read().context(TerminalError {})? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => {
+                    let keep_running = self.handle_key(key, session, terminal);
+                    if !keep_running {
+                        return Ok(());
+                    }
+                }
+                Event::Mouse(mouse) => self.handle_mouse(mouse),
+                _ => {}
+            }
+        }
+    }
+
+    /// Handle a key press, returning whether to keep running.
+    fn handle_key(
+        &mut self,
+        key: KeyEvent,
+        session: &Session,
+        terminal: &mut DefaultTerminal,
+    ) -> bool {
+        let last = self.blocks.len() - 1;
+        let columns = self.geometry.get().map_or(1, |geometry| geometry.columns);
+
+        match &mut self.mode {
+            Mode::Prompt { action, input } => match key.code {
+                KeyCode::Char(c) => input.push(c),
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Enter => {
+                    let action = *action;
+                    let path = PathBuf::from(input.trim());
+                    self.mode = Mode::Normal;
+                    self.report(|app| app.execute(action, &path, session, terminal));
+                }
+                KeyCode::Esc => self.mode = Mode::Normal,
+                _ => {}
+            },
+            Mode::Confirm => {
+                self.mode = Mode::Normal;
+                if let KeyCode::Char('y') = key.code {
+                    self.report(|app| app.execute(Action::Erase, Path::new(""), session, terminal));
+                }
+            }
+            Mode::Normal => match key.code {
+                KeyCode::Char('q') => return false,
+                KeyCode::Esc if self.anchor.is_none() => return false,
+                KeyCode::Esc => self.anchor = None,
+                KeyCode::Left | KeyCode::Char('h') => self.cursor = self.cursor.saturating_sub(1),
+                KeyCode::Right | KeyCode::Char('l') => self.cursor = (self.cursor + 1).min(last),
+                KeyCode::Up | KeyCode::Char('k') => {
+                    self.cursor = self.cursor.saturating_sub(columns)
+                }
+                KeyCode::Down | KeyCode::Char('j') => {
+                    self.cursor = (self.cursor + columns).min(last)
+                }
+                KeyCode::Home => self.cursor = 0,
+                KeyCode::End => self.cursor = last,
+                KeyCode::Char(' ') => {
+                    self.anchor = match self.anchor {
+                        Some(_) => None,
+                        None => Some(self.cursor),
+                    }
+                }
+                KeyCode::Char('s') => {
+                    let all = 0..self.blocks.len();
+                    self.report(|app| app.scan(session, terminal, all));
+                }
+                KeyCode::Char('r') => {
+                    self.mode = Mode::Prompt {
+                        action: Action::Read,
+                        input: String::new(),
+                    }
+                }
+                KeyCode::Char('w') => {
+                    self.mode = Mode::Prompt {
+                        action: Action::Write,
+                        input: String::new(),
+                    }
+                }
+                KeyCode::Char('e') => self.mode = Mode::Confirm,
+                _ => {}
+            },
+        }
+
+        true
+    }
+
+    /// Select the clicked block, extending the selection while dragging.
+    fn handle_mouse(&mut self, mouse: MouseEvent) {
+        let geometry = match self.geometry.get() {
+            Some(geometry) => geometry,
+            None => return,
+        };
+        let area = geometry.area;
+        let x = area.x + ADDRESS_WIDTH;
+        if mouse.column < x
+            || mouse.column >= x + geometry.columns as u16
+            || mouse.row < area.y
+            || mouse.row >= area.y + area.height
+        {
+            return;
+        }
+
+        let row = geometry.first_row + (mouse.row - area.y) as usize;
+        let block = row * geometry.columns + (mouse.column - x) as usize;
+        if block >= self.blocks.len() {
+            return;
+        }
+
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                self.cursor = block;
+                self.anchor = Some(block);
+            }
+            MouseEventKind::Drag(MouseButton::Left) => self.cursor = block,
+            _ => {}
+        }
+    }
+
+    /// Run an operation, showing its outcome in the status line.
+    fn report(&mut self, f: impl FnOnce(&mut Self) -> Result<String>) {
+        let result = f(self);
+        self.progress = None;
+        self.message = match result {
+            Ok(msg) => Message::Info(msg),
+            Err(err) => Message::Error(err.to_string()),
+        };
+    }
+
+    fn execute(
+        &mut self,
+        action: Action,
+        path: &Path,
+        session: &Session,
+        terminal: &mut DefaultTerminal,
+    ) -> Result<String> {
+        let blocks = self.selected_blocks();
+        let range = self.byte_range(blocks.clone());
+        let length = range.end - range.start;
+
+        match action {
+            Action::Read => {
+                let data = session
+                    .read(range.start, length, self.progress(terminal, "Reading"))
+                    .context(SessionError {})?;
+                fs::write(path, data).context(FileError { path })?;
+
+                Ok(format!(
+                    "Read {} bytes at 0x{:08X} to {}",
+                    length,
+                    range.start,
+                    path.display()
+                ))
+            }
+            Action::Erase => {
+                session
+                    .erase(range.start, length)
+                    .context(SessionError {})?;
+                for block in &mut self.blocks[blocks] {
+                    *block = Some(false);
+                }
+
+                Ok(format!("Erased {} bytes at 0x{:08X}", length, range.start))
+            }
+            Action::Write => {
+                let data = fs::read(path).context(FileError { path })?;
+                let length = data.len() as u32;
+                ensure!(
+                    range.start.saturating_add(length) <= self.size,
+                    OutOfBounds {
+                        offset: range.start,
+                        length,
+                        size: self.size,
+                    }
+                );
+                session
+                    .write(range.start, data, true, self.progress(terminal, "Writing"))
+                    .context(SessionError {})?;
+
+                let first = blocks.start;
+                let last = ((range.start + length.max(1) - 1) / self.block_size) as usize;
+                self.scan(session, terminal, first..last + 1)?;
+
+                Ok(format!(
+                    "Wrote and verified {} bytes from {} at 0x{:08X}",
+                    length,
+                    path.display(),
+                    range.start
+                ))
+            }
+        }
+    }
+
+    /// Update the map of the blocks with the blank check of the firmware.
+    fn scan(
+        &mut self,
+        session: &Session,
+        terminal: &mut DefaultTerminal,
+        blocks: Range<usize>,
+    ) -> Result<String> {
+        let range = self.byte_range(blocks.clone());
+        let block_size = self.block_size;
+        let blank = session
+            .blank_blocks(
+                range.start,
+                range.end - range.start,
+                block_size,
+                self.progress(terminal, "Scanning"),
+            )
+            .context(SessionError {})?;
+
+        for (block, blank) in self.blocks[blocks].iter_mut().zip(blank) {
+            *block = Some(!blank);
+        }
+
+        let used = self
+            .blocks
+            .iter()
+            .filter(|block| **block == Some(true))
+            .count();
+        Ok(format!("{} of {} blocks used", used, self.blocks.len()))
+    }
+
+    /// Progress report redrawing the dashboard with a progress bar.
+    fn progress<'a>(
+        &'a mut self,
+        terminal: &'a mut DefaultTerminal,
+        operation: &'static str,
+    ) -> impl FnMut(Progress) + 'a {
+        move |progress| {
+            self.progress = Some((operation, progress));
+            terminal.draw(|frame| self.render(frame)).ok();
+        }
+    }
+
+    fn selected_blocks(&self) -> Range<usize> {
+        let anchor = self.anchor.unwrap_or(self.cursor);
+        anchor.min(self.cursor)..anchor.max(self.cursor) + 1
+    }
+
+    fn byte_range(&self, blocks: Range<usize>) -> Range<u32> {
+        let start = blocks.start as u32 * self.block_size;
+        let end = (blocks.end as u32 * self.block_size).min(self.size);
+        start..end
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        let [info_area, map_area, status_area, help_area] = Layout::vertical([
+            Constraint::Length(5),
+            Constraint::Min(3),
+            Constraint::Length(3),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let selection = self.byte_range(self.selected_blocks());
+        let info = vec![
+            Line::from(format!("External flash:    {}", self.xflash)),
+            Line::from(format!(
+                "Size:              {} bytes, in blocks of 0x{:X} bytes",
+                self.size, self.block_size
+            )),
+            Line::from(format!(
+                "Selection:         0x{:08X}..0x{:08X} ({} bytes)",
+                selection.start,
+                selection.end,
+                selection.end - selection.start
+            )),
+        ];
+        frame.render_widget(
+            Paragraph::new(info).block(Block::default().borders(Borders::ALL).title("Info")),
+            info_area,
+        );
+
+        self.render_map(frame, map_area);
+
+        let status = Block::default().borders(Borders::ALL).title("Status");
+        match (&self.mode, self.progress) {
+            (_, Some((operation, progress))) => {
+                let ratio = if progress.total == 0 {
+                    1.0
+                } else {
+                    f64::from(progress.done) / f64::from(progress.total)
+                };
+                let gauge = Gauge::default()
+                    .block(status)
+                    .gauge_style(Style::default().fg(Color::Cyan))
+                    .label(format!("{} {:.0}%", operation, ratio * 100.0))
+                    .ratio(ratio.min(1.0));
+                frame.render_widget(gauge, status_area);
+            }
+            (Mode::Prompt { action, input }, None) => {
+                let prompt = match action {
+                    Action::Read => "Read selection to file",
+                    Action::Write => "Write file at selection",
+                    Action::Erase => "Erase selection",
+                };
+                let line = format!("{}: {}_", prompt, input);
+                frame.render_widget(Paragraph::new(line).block(status), status_area);
+            }
+            (Mode::Confirm, None) => {
+                let line = format!(
+                    "Erase 0x{:08X}..0x{:08X}? (y/n)",
+                    selection.start, selection.end
+                );
+                let paragraph = Paragraph::new(line).style(Style::default().fg(Color::Yellow));
+                frame.render_widget(paragraph.block(status), status_area);
+            }
+            (Mode::Normal, None) => {
+                let paragraph = match &self.message {
+                    Message::Info(msg) => Paragraph::new(msg.as_str()),
+                    Message::Error(msg) => {
+                        Paragraph::new(msg.as_str()).style(Style::default().fg(Color::Red))
+                    }
+                };
+                frame.render_widget(paragraph.block(status), status_area);
+            }
+        }
+
+        let help = "arrows/click: move  space/drag: select  r: read  e: erase  w: write  \
+                    s: rescan  q: quit";
+        frame.render_widget(
+            Paragraph::new(help).style(Style::default().add_modifier(Modifier::DIM)),
+            help_area,
+        );
+    }
+
+    fn render_map(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title("Blocks (# used, . blank, ? not scanned)");
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        // Power of two columns, keeping the rows aligned to round addresses
+        let available = (inner.width.saturating_sub(ADDRESS_WIDTH) as usize).max(1);
+        let mut columns = MAX_COLUMNS;
+        while columns > available {
+            columns /= 2;
+        }
+        let rows = self.blocks.len().div_ceil(columns);
+        let height = (inner.height as usize).max(1);
+        let first_row = (self.cursor / columns).saturating_sub(height - 1);
+
+        self.geometry.set(Some(MapGeometry {
+            area: inner,
+            columns,
+            first_row,
+        }));
+
+        let selected = self.selected_blocks();
+        let lines: Vec<Line> = (first_row..rows.min(first_row + height))
+            .map(|row| {
+                let first = row * columns;
+                let address = first as u32 * self.block_size;
+                let mut spans = vec![Span::raw(format!("0x{:08X} ", address))];
+                spans.extend((first..(first + columns).min(self.blocks.len())).map(|i| {
+                    let (symbol, color) = match self.blocks[i] {
+                        Some(true) => ("#", Color::Green),
+                        Some(false) => (".", Color::DarkGray),
+                        None => ("?", Color::DarkGray),
+                    };
+                    let mut style = Style::default().fg(color);
+                    if selected.contains(&i) {
+                        style = style.add_modifier(Modifier::REVERSED);
+                    }
+                    if i == self.cursor {
+                        style = style.fg(Color::Yellow);
+                    }
+                    Span::styled(symbol, style)
+                }));
+                Line::from(spans)
+            })
+            .collect();
+
+        frame.render_widget(Paragraph::new(lines), inner);
+    }
+}
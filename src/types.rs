@@ -4,183 +4,824 @@
 // notice may not be copied, modified, or distributed except according to those terms.
 
 use std::convert::TryFrom;
+use std::fmt;
 use std::ops;
 use std::str;
 use std::string;
+use std::sync::OnceLock;
 
 use snafu::{Backtrace, OptionExt, Snafu};
+use toml::Value;
 
+use crate::assets;
+use crate::xflash::Xflash;
+
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, Snafu)]
 pub enum Error {
+    #[snafu(display("Invalid device family descriptor {}: {}", name, reason))]
+    InvalidFamilyDescriptor {
+        name: String,
+        reason: String,
+        backtrace: Backtrace,
+    },
     #[snafu(display("Invalid string when parsing Device: {}", input))]
     InvalidDevice { input: String, backtrace: Backtrace },
+    #[snafu(display("Invalid string when parsing Interface: {}", input))]
+    InvalidInterface { input: String, backtrace: Backtrace },
+    #[snafu(display("Invalid string when parsing InputFormat: {}", input))]
+    InvalidInputFormat { input: String, backtrace: Backtrace },
+    #[snafu(display("Invalid string when parsing OutputFormat: {}", input))]
+    InvalidOutputFormat { input: String, backtrace: Backtrace },
+    #[snafu(display("Invalid string when parsing ProgressFormat: {}", input))]
+    InvalidProgressFormat { input: String, backtrace: Backtrace },
+    #[snafu(display("Invalid string when parsing ErrorFormat: {}", input))]
+    InvalidErrorFormat { input: String, backtrace: Backtrace },
+    #[snafu(display("Invalid string when parsing CrcAlgorithm: {}", input))]
+    InvalidCrcAlgorithm { input: String, backtrace: Backtrace },
     #[snafu(display("Unable to parse SPI pins: {}", msg))]
     InvalidSpiPins { msg: String, backtrace: Backtrace },
+    #[snafu(display("Unable to parse mux select: {}", msg))]
+    InvalidMuxSelect { msg: String, backtrace: Backtrace },
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
-#[allow(non_camel_case_types)]
-#[derive(Copy, Clone, Debug)]
-pub enum Device {
-    CC1310,
-    CC1312R,
-    CC1350,
-    CC1352P,
-    CC1352R,
-    CC2640,
-    CC2640R2F,
-    CC2642R,
-    CC2650,
-    CC2652P,
-    CC2652R,
-    CC2652RB,
-    CC1312R7,
-    CC1352P7,
-    CC2652P7,
-    CC2652R7,
-    CC1354P10,
-    CC1314R10,
+/// Device families, read once from the descriptor files embedded from
+/// `src/assets/families`. Supporting a new family only takes a descriptor and
+/// its firmware binary.
+static FAMILIES: OnceLock<Result<Vec<FamilyDescriptor>, (String, String)>> = OnceLock::new();
+
+fn families() -> Result<&'static [FamilyDescriptor]> {
+    let families = FAMILIES.get_or_init(|| {
+        let mut families = assets::family_descriptors()
+            .into_iter()
+            .map(|(name, text)| FamilyDescriptor::parse(&text).map_err(|reason| (name, reason)))
+            .collect::<Result<Vec<_>, _>>()?;
+        families.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(families)
+    });
+
+    match families {
+        Ok(families) => Ok(families),
+        Err((name, reason)) => InvalidFamilyDescriptor { name, reason }.fail(),
+    }
+}
+
+/// Per-family data: target configuration, firmware and memory layout.
+#[derive(Debug)]
+struct FamilyDescriptor {
+    name: String,
+    session: String,
+    uart_pins: [u8; 2],
+    min_xds110_firmware: [u32; 4],
+    firmware: String,
+    firmware_sha256: String,
+    firmware_stub_sha256: Option<String>,
+    ccxml_id: String,
+    ccxml_cpu_driver: String,
+    ccxml_probe_voltage: Option<String>,
+    sram_size: u32,
+    sram_buffers: u32,
+    flash_banks: Vec<(u32, u32)>,
+    flash_stub_region: (u32, u32),
+    fcfg: Option<Fcfg>,
+    ccfg: Option<Ccfg>,
+    devices: Vec<DeviceDescriptor>,
+    launchpads: Vec<LaunchPadDescriptor>,
+}
+
+/// Factory configuration page of a family, and the fields of it exported by
+/// `fcfg dump`.
+#[derive(Debug)]
+pub struct Fcfg {
+    pub base: u32,
+    pub size: u32,
+    pub fields: Vec<FcfgField>,
+}
+
+#[derive(Debug)]
+pub struct FcfgField {
+    pub name: String,
+    /// Offset into the page
+    pub offset: u32,
+    pub length: u32,
+    /// Whether the field is a MAC address, stored least significant byte
+    /// first
+    pub mac: bool,
+}
+
+/// Customer configuration sector of a family, holding the MAC address
+/// overrides written by `provision mac`.
+#[derive(Debug)]
+pub struct Ccfg {
+    /// Start and end address of the sector
+    pub sector: (u32, u32),
+    /// Address of the 8 byte IEEE 802.15.4 MAC address override
+    pub ieee_mac: u32,
+    /// Address of the 8 byte field of the BLE MAC address override, of which
+    /// the lower 6 bytes hold the address
+    pub ble_mac: u32,
+}
+
+#[derive(Debug)]
+struct DeviceDescriptor {
+    name: String,
+    ccxml_desc: String,
+    ccxml_xml: String,
+}
+
+/// LaunchPad accepted by `--device` in place of the device on it.
+#[derive(Debug)]
+struct LaunchPadDescriptor {
+    name: String,
+    device: String,
+    /// DIOs of the SPI pins of the external flash on the LaunchPad
+    spi_pins: [u8; 4],
+    /// Name of the external flash part fitted on the LaunchPad
+    flash: String,
+}
+
+impl FamilyDescriptor {
+    fn parse(text: &str) -> Result<Self, String> {
+        let value: Value = text
+            .parse()
+            .map_err(|err: toml::de::Error| err.to_string())?;
+
+        let firmware = table(&value, "firmware")?;
+        let ccxml = table(&value, "ccxml")?;
+        let sram = table(&value, "sram")?;
+        let flash = table(&value, "flash")?;
+
+        let uart_pins = numbers(&value, "uart_pins")?;
+        let min_xds110_firmware = numbers(&value, "min_xds110_firmware")?;
+        let stub_region = numbers(flash, "stub_region")?;
+        let flash_banks = array(flash, "banks")?
+            .iter()
+            .map(|bank| match number_list(bank, "banks")?.as_slice() {
+                [start, end] => Ok((*start, *end)),
+                _ => Err("'banks' entries must be start and end addresses".to_string()),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let fcfg = match value.get("fcfg") {
+            Some(_) => Some(fcfg(table(&value, "fcfg")?)?),
+            None => None,
+        };
+
+        let ccfg = match value.get("ccfg") {
+            Some(_) => Some(ccfg(table(&value, "ccfg")?)?),
+            None => None,
+        };
+
+        let devices = array(&value, "device")?
+            .iter()
+            .map(|device| {
+                Ok(DeviceDescriptor {
+                    name: string(device, "name")?,
+                    ccxml_desc: string(device, "ccxml_desc")?,
+                    ccxml_xml: string(device, "ccxml_xml")?,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        let launchpads = match value.get("launchpad") {
+            Some(_) => array(&value, "launchpad")?
+                .iter()
+                .map(|launchpad| launchpad_descriptor(launchpad, &devices))
+                .collect::<Result<Vec<_>, String>>()?,
+            None => Vec::new(),
+        };
+
+        match (
+            uart_pins.as_slice(),
+            min_xds110_firmware.as_slice(),
+            stub_region.as_slice(),
+        ) {
+            ([rx, tx], [major, minor, patch, build], [stub_start, stub_end]) => Ok(Self {
+                name: string(&value, "name")?,
+                session: string(&value, "session")?,
+                uart_pins: [
+                    u8::try_from(*rx).map_err(|err| err.to_string())?,
+                    u8::try_from(*tx).map_err(|err| err.to_string())?,
+                ],
+                min_xds110_firmware: [*major, *minor, *patch, *build],
+                firmware: string(firmware, "file")?,
+                firmware_sha256: string(firmware, "sha256")?,
+                firmware_stub_sha256: optional_string(firmware, "stub_sha256")?,
+                ccxml_id: string(ccxml, "id")?,
+                ccxml_cpu_driver: string(ccxml, "cpu_driver")?,
+                ccxml_probe_voltage: optional_string(ccxml, "probe_voltage")?,
+                sram_size: number(sram, "size")?,
+                sram_buffers: number(sram, "buffers")?,
+                flash_banks,
+                flash_stub_region: (*stub_start, *stub_end),
+                fcfg,
+                ccfg,
+                devices,
+                launchpads,
+            }),
+            _ => Err(
+                "'uart_pins', 'min_xds110_firmware' and 'stub_region' must be arrays of 2, 4 and \
+                 2 numbers"
+                    .to_string(),
+            ),
+        }
+    }
+}
+
+fn fcfg(value: &Value) -> Result<Fcfg, String> {
+    let base = number(value, "base")?;
+    let size = number(value, "size")?;
+
+    let fields = array(value, "field")?
+        .iter()
+        .map(|field| {
+            let field = FcfgField {
+                name: string(field, "name")?,
+                offset: number(field, "offset")?,
+                length: number(field, "length")?,
+                mac: match field.get("mac") {
+                    Some(mac) => mac
+                        .as_bool()
+                        .ok_or_else(|| "'mac' must be a boolean".to_string())?,
+                    None => false,
+                },
+            };
+            if field.offset.saturating_add(field.length) > size {
+                return Err(format!("'fcfg' field {} is outside the page", field.name));
+            }
+            Ok(field)
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    Ok(Fcfg { base, size, fields })
+}
+
+fn ccfg(value: &Value) -> Result<Ccfg, String> {
+    let sector = match numbers(value, "sector")?.as_slice() {
+        [start, end] => (*start, *end),
+        _ => return Err("'sector' must be start and end addresses".to_string()),
+    };
+    let ieee_mac = number(value, "ieee_mac")?;
+    let ble_mac = number(value, "ble_mac")?;
+
+    // The overrides are 8 byte fields of the sector, which are programmed
+    // in whole flash words
+    for (key, address) in &[("ieee_mac", ieee_mac), ("ble_mac", ble_mac)] {
+        if address % 8 != 0 || *address < sector.0 || address.saturating_add(8) > sector.1 {
+            return Err(format!(
+                "'{}' must be aligned to 8 bytes within the sector",
+                key
+            ));
+        }
+    }
+
+    Ok(Ccfg {
+        sector,
+        ieee_mac,
+        ble_mac,
+    })
+}
+
+fn launchpad_descriptor(
+    value: &Value,
+    devices: &[DeviceDescriptor],
+) -> Result<LaunchPadDescriptor, String> {
+    let name = string(value, "name")?;
+    let device = string(value, "device")?;
+    if !devices.iter().any(|desc| desc.name == device) {
+        return Err(format!(
+            "LaunchPad {} has device {}, which is not of the family",
+            name, device
+        ));
+    }
+    let spi_pins = numbers(value, "spi_pins")?
+        .into_iter()
+        .map(|dio| u8::try_from(dio).map_err(|err| err.to_string()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let spi_pins = match spi_pins.as_slice() {
+        [miso, mosi, clk, csn] => [*miso, *mosi, *clk, *csn],
+        _ => return Err("'spi_pins' must be the MISO, MOSI, CLK and CSN DIOs".to_string()),
+    };
+    let flash = string(value, "flash")?;
+    if !Xflash::is_known_name(&flash) {
+        return Err(format!(
+            "LaunchPad {} has external flash {}, which is not a known part",
+            name, flash
+        ));
+    }
+
+    Ok(LaunchPadDescriptor {
+        name,
+        device,
+        spi_pins,
+        flash,
+    })
+}
+
+fn get<'a>(value: &'a Value, key: &str) -> Result<&'a Value, String> {
+    value
+        .get(key)
+        .ok_or_else(|| format!("'{}' is missing", key))
+}
+
+fn table<'a>(value: &'a Value, key: &str) -> Result<&'a Value, String> {
+    let table = get(value, key)?;
+    match table {
+        Value::Table(_) => Ok(table),
+        _ => Err(format!("'{}' must be a table", key)),
+    }
+}
+
+fn array<'a>(value: &'a Value, key: &str) -> Result<&'a [Value], String> {
+    get(value, key)?
+        .as_array()
+        .map(Vec::as_slice)
+        .ok_or_else(|| format!("'{}' must be an array", key))
+}
+
+fn string(value: &Value, key: &str) -> Result<String, String> {
+    get(value, key)?
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| format!("'{}' must be a string", key))
+}
+
+fn optional_string(value: &Value, key: &str) -> Result<Option<String>, String> {
+    match value.get(key) {
+        Some(_) => string(value, key).map(Some),
+        None => Ok(None),
+    }
+}
+
+fn number(value: &Value, key: &str) -> Result<u32, String> {
+    get(value, key)?
+        .as_integer()
+        .and_then(|value| u32::try_from(value).ok())
+        .ok_or_else(|| format!("'{}' must be an unsigned 32-bit number", key))
+}
+
+fn numbers(value: &Value, key: &str) -> Result<Vec<u32>, String> {
+    number_list(get(value, key)?, key)
+}
+
+fn number_list(value: &Value, key: &str) -> Result<Vec<u32>, String> {
+    value
+        .as_array()
+        .and_then(|values| {
+            values
+                .iter()
+                .map(|value| {
+                    value
+                        .as_integer()
+                        .and_then(|value| u32::try_from(value).ok())
+                })
+                .collect::<Option<Vec<_>>>()
+        })
+        .ok_or_else(|| format!("'{}' must be an array of unsigned 32-bit numbers", key))
+}
+
+#[derive(Copy, Clone)]
+pub struct Device {
+    family: &'static FamilyDescriptor,
+    desc: &'static DeviceDescriptor,
+    /// LaunchPad the device was given by, if any
+    launchpad: Option<&'static LaunchPadDescriptor>,
 }
 
 impl Device {
-    pub fn ccxml_desc(&self) -> &str {
-        use Device::*;
+    /// Names of all supported devices and LaunchPads, as given on the command
+    /// line.
+    pub fn names() -> Vec<&'static str> {
+        let families = families().unwrap_or_default();
+        let devices = families
+            .iter()
+            .flat_map(|family| family.devices.iter().map(|device| device.name.as_str()));
+        let launchpads = families.iter().flat_map(|family| {
+            family
+                .launchpads
+                .iter()
+                .map(|launchpad| launchpad.name.as_str())
+        });
+        devices.chain(launchpads).collect()
+    }
 
-        match self {
-            CC1310 => "CC1310F128",
-            CC1312R => "CC1312R1F3",
-            CC1350 => "CC1350F128",
-            CC1352P => "CC1352P1F3",
-            CC1352R => "CC1352R1F3",
-            CC2640 => "CC2640F128",
-            CC2640R2F => "CC2640R2F",
-            CC2642R => "CC2642R1F",
-            CC2650 => "CC2650F128",
-            CC2652P => "CC2652P1F",
-            CC2652R => "CC2652R1F",
-            CC2652RB => "CC2652RB1F",
-            CC1312R7 => "CC1312R7",
-            CC1352P7 => "CC1352P7",
-            CC2652P7 => "CC2652P7",
-            CC2652R7 => "CC2652R7",
-            CC1354P10 => "CC1354P10",
-            CC1314R10 => "CC1314R10",
-        }
-    }
-
-    pub fn ccxml_id(&self) -> &str {
+    /// Name of the LaunchPad the device was given by.
+    pub fn launchpad(&self) -> Option<&'static str> {
+        self.launchpad.map(|launchpad| launchpad.name.as_str())
+    }
+
+    /// DIOs of the SPI pins of the external flash on the LaunchPad the device
+    /// was given by, used unless `--spi-pins` is given.
+    pub fn launchpad_spi_pins(&self) -> Option<SpiPins> {
+        self.launchpad.map(|launchpad| SpiPins(launchpad.spi_pins))
+    }
+
+    /// Name of the external flash part fitted on the LaunchPad the device was
+    /// given by.
+    pub fn launchpad_flash(&self) -> Option<&'static str> {
+        self.launchpad.map(|launchpad| launchpad.flash.as_str())
+    }
+
+    pub fn ccxml_desc(&self) -> &'static str {
+        &self.desc.ccxml_desc
+    }
+
+    pub fn ccxml_id(&self) -> &'static str {
         // Currently all devices have the same "desc" and "id" values
         self.ccxml_desc()
     }
 
-    pub fn ccxml_xml(&self) -> &str {
-        use Device::*;
+    pub fn ccxml_xml(&self) -> &'static str {
+        &self.desc.ccxml_xml
+    }
+}
 
-        match self {
-            CC1310 => "cc1310f128.xml",
-            CC1312R => "cc1312r1f3.xml",
-            CC1350 => "cc1350f128.xml",
-            CC1352P => "cc1352p1f3.xml",
-            CC1352R => "cc1352r1f3.xml",
-            CC2640 => "cc2640f128.xml",
-            CC2640R2F => "cc2640r2f.xml",
-            CC2642R => "cc2642r1f.xml",
-            CC2650 => "cc2650f128.xml",
-            CC2652P => "cc2652p1f.xml",
-            CC2652R => "cc2652r1f.xml",
-            CC2652RB => "cc2652rb1f.xml",
-            CC1312R7 => "cc1312r7.xml",
-            CC1352P7 => "cc1352p7.xml",
-            CC2652P7 => "cc2652p7.xml",
-            CC2652R7 => "cc2652r7.xml",
-            CC1354P10 => "cc1354p10.xml",
-            CC1314R10 => "cc1314r10.xml",
-        }
+impl fmt::Debug for Device {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Device").field(&self.desc.name).finish()
     }
 }
 
 impl string::ToString for Device {
     fn to_string(&self) -> String {
-        use Device::*;
+        self.desc.name.clone()
+    }
+}
+
+impl str::FromStr for Device {
+    type Err = Error;
+
+    /// Parse a device or LaunchPad name, ignoring case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let families = families()?;
+        let device = families.iter().find_map(|family| {
+            family
+                .devices
+                .iter()
+                .find(|device| device.name.eq_ignore_ascii_case(s))
+                .map(|desc| Device {
+                    family,
+                    desc,
+                    launchpad: None,
+                })
+        });
+        let launchpad = || {
+            families.iter().find_map(|family| {
+                let launchpad = family
+                    .launchpads
+                    .iter()
+                    .find(|launchpad| launchpad.name.eq_ignore_ascii_case(s))?;
+                family
+                    .devices
+                    .iter()
+                    .find(|device| device.name == launchpad.device)
+                    .map(|desc| Device {
+                        family,
+                        desc,
+                        launchpad: Some(launchpad),
+                    })
+            })
+        };
+        device
+            .or_else(launchpad)
+            .context(InvalidDevice { input: s })
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct DeviceFamily(&'static FamilyDescriptor);
+
+impl From<Device> for DeviceFamily {
+    fn from(device: Device) -> Self {
+        DeviceFamily(device.family)
+    }
+}
+
+impl fmt::Debug for DeviceFamily {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("DeviceFamily").field(&self.0.name).finish()
+    }
+}
+
+impl DeviceFamily {
+    /// Pattern of the debug session of the main core.
+    pub fn session_pattern(&self) -> &'static str {
+        &self.0.session
+    }
+
+    /// DIOs of the UART RX and TX pins connected to the XDS110 backchannel
+    /// UART on the LaunchPads, used as data port by default.
+    pub fn default_uart_pins(&self) -> [u8; 2] {
+        self.0.uart_pins
+    }
+
+    /// File name of the firmware asset, and its SHA-256 digest.
+    pub fn firmware(&self) -> (&'static str, &'static str) {
+        (&self.0.firmware, &self.0.firmware_sha256)
+    }
+
+    /// SHA-256 digest of the firmware stub asset, for families where one is
+    /// built.
+    pub fn firmware_stub_digest(&self) -> Option<&'static str> {
+        self.0.firmware_stub_sha256.as_deref()
+    }
+
+    pub fn ccxml_id(&self) -> &'static str {
+        &self.0.ccxml_id
+    }
+
+    pub fn ccxml_cpu_driver(&self) -> &'static str {
+        &self.0.ccxml_cpu_driver
+    }
+
+    /// Size of the SRAM in bytes, starting at 0x2000_0000.
+    pub fn sram_size(&self) -> u32 {
+        self.0.sram_size
+    }
+
+    /// Number of transfer buffers the firmware of the family is built with,
+    /// limited by the SRAM size. The firmware reports the buffers it has in
+    /// its descriptor.
+    pub fn firmware_buf_count(&self) -> u32 {
+        self.0.sram_buffers
+    }
+
+    /// Internal flash region, as start and end address, temporarily holding
+    /// the firmware stub on devices where the firmware is run from flash. The
+    /// region is placed at the end of the flash, below the CCFG sector.
+    pub fn flash_stub_region(&self) -> (u32, u32) {
+        self.0.flash_stub_region
+    }
+
+    /// Start and end addresses of the banks of the internal flash. The
+    /// CC13x4/CC26x4 internal flash is split into two banks of 512 KB.
+    pub fn internal_flash_banks(&self) -> &'static [(u32, u32)] {
+        &self.0.flash_banks
+    }
+
+    /// Oldest XDS110 firmware version known to reliably connect to devices
+    /// of the family.
+    pub fn min_xds110_firmware(&self) -> [u32; 4] {
+        self.0.min_xds110_firmware
+    }
+
+    /// Factory configuration page, if described for the family.
+    pub fn fcfg(&self) -> Option<&'static Fcfg> {
+        self.0.fcfg.as_ref()
+    }
+
+    /// Customer configuration sector holding the MAC address overrides, if
+    /// described for the family.
+    pub fn ccfg(&self) -> Option<&'static Ccfg> {
+        self.0.ccfg.as_ref()
+    }
+
+    /// Voltage level supplied by the probe, if the target configuration for
+    /// the device family uses probe supplied power.
+    pub fn ccxml_probe_voltage(&self) -> Option<&'static str> {
+        self.0.ccxml_probe_voltage.as_deref()
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum Interface {
+    Jtag,
+    Cjtag,
+    Swd,
+}
+
+impl Interface {
+    /// Name of the interface, as given on the command line.
+    pub fn name(&self) -> &'static str {
+        use Interface::*;
 
         match self {
-            CC1310 => "cc1310",
-            CC1312R => "cc1312r",
-            CC1350 => "cc1350",
-            CC1352P => "cc1352p",
-            CC1352R => "cc1352r",
-            CC2640 => "cc2640",
-            CC2640R2F => "cc2640r2f",
-            CC2642R => "cc2642r",
-            CC2650 => "cc2650",
-            CC2652P => "cc2652p",
-            CC2652R => "cc2652r",
-            CC2652RB => "cc2652rb",
-            CC1312R7 => "cc1312r7",
-            CC1352P7 => "cc1352p7",
-            CC2652P7 => "cc2652p7",
-            CC2652R7 => "cc2652r7",
-            CC1354P10 => "cc1354p10",
-            CC1314R10 => "cc1314r10",
-        }
-        .to_string()
+            Jtag => "jtag",
+            Cjtag => "cjtag",
+            Swd => "swd",
+        }
+    }
+
+    pub fn ccxml_swd_mode(&self) -> &str {
+        use Interface::*;
+
+        match self {
+            Jtag => "0",
+            Swd => "1",
+            Cjtag => "4",
+        }
     }
 }
 
-impl str::FromStr for Device {
+impl str::FromStr for Interface {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        use Device::*;
+        use Interface::*;
 
         match s {
-            "cc1310" => Ok(CC1310),
-            "cc1312r" => Ok(CC1312R),
-            "cc1350" => Ok(CC1350),
-            "cc1352p" => Ok(CC1352P),
-            "cc1352r" => Ok(CC1352R),
-            "cc2640" => Ok(CC2640),
-            "cc2640r2f" => Ok(CC2640R2F),
-            "cc2642r" => Ok(CC2642R),
-            "cc2650" => Ok(CC2650),
-            "cc2652p" => Ok(CC2652P),
-            "cc2652r" => Ok(CC2652R),
-            "cc2652rb" => Ok(CC2652RB),
-            "cc1312r7" => Ok(CC1312R7),
-            "cc1352p7" => Ok(CC1352P7),
-            "cc2652p7" => Ok(CC2652P7),
-            "cc2652r7" => Ok(CC2652R7),
-            "cc1354p10" => Ok(CC1354P10),
-            "cc1314r10" => Ok(CC1314R10),
-            input => InvalidDevice { input }.fail(),
-        }
-    }
-}
-
-#[allow(non_camel_case_types)]
-#[derive(Copy, Clone, Debug)]
-pub enum DeviceFamily {
-    CC13x0,
-    CC26x0,
-    CC26x0R2,
-    CC13x2_CC26x2,
-    CC13x2x7_CC26x2x7,
-    CC13x4_CC26x4,
+            "jtag" => Ok(Jtag),
+            "cjtag" => Ok(Cjtag),
+            "swd" => Ok(Swd),
+            input => InvalidInterface { input }.fail(),
+        }
+    }
 }
 
-impl From<Device> for DeviceFamily {
-    fn from(device: Device) -> Self {
-        use Device::*;
-        use DeviceFamily::*;
+/// Format of the progress updates printed to stderr during transfers.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ProgressFormat {
+    Human,
+    /// One JSON object per line, for GUI wrappers
+    Jsonl,
+}
+
+impl str::FromStr for ProgressFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use ProgressFormat::*;
+
+        match s {
+            "human" => Ok(Human),
+            "jsonl" => Ok(Jsonl),
+            input => InvalidProgressFormat { input }.fail(),
+        }
+    }
+}
+
+/// Format of the errors printed to stderr.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ErrorFormat {
+    Human,
+    /// One JSON object with a stable error code, for automation
+    Json,
+}
+
+impl str::FromStr for ErrorFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use ErrorFormat::*;
+
+        match s {
+            "human" => Ok(Human),
+            "json" => Ok(Json),
+            input => InvalidErrorFormat { input }.fail(),
+        }
+    }
+}
+
+/// Format of the data written to the external flash.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum InputFormat {
+    /// Raw binary written at the given offset
+    Bin,
+    /// Intel HEX, written at the addresses of its records
+    Hex,
+    /// ELF, of which the loadable segments are written at their load addresses
+    Elf,
+    /// Base64 encoded binary, written at the given offset
+    Base64,
+    /// TI-TXT, written at the addresses of its sections
+    TiTxt,
+}
+
+impl InputFormat {
+    /// Format implied by the extension of an input file name, defaulting to
+    /// raw binary.
+    pub fn from_file_name(name: &str) -> Self {
+        let name = name.to_ascii_lowercase();
+        if name.ends_with(".hex") || name.ends_with(".ihex") {
+            InputFormat::Hex
+        } else if name.ends_with(".elf") || name.ends_with(".out") || name.ends_with(".axf") {
+            InputFormat::Elf
+        } else if name.ends_with(".b64") || name.ends_with(".base64") {
+            InputFormat::Base64
+        } else {
+            InputFormat::Bin
+        }
+    }
+
+    /// Whether the input carries the addresses it is written to.
+    pub fn has_addresses(&self) -> bool {
+        matches!(
+            self,
+            InputFormat::Hex | InputFormat::Elf | InputFormat::TiTxt
+        )
+    }
+}
+
+impl str::FromStr for InputFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use InputFormat::*;
 
-        match device {
-            CC1310 | CC1350 => CC13x0,
-            CC2640 | CC2650 => CC26x0,
-            CC2640R2F => CC26x0R2,
-            CC1312R | CC1352P | CC1352R | CC2642R | CC2652P | CC2652R | CC2652RB => CC13x2_CC26x2,
-            CC1312R7 | CC1352P7 | CC2652P7 | CC2652R7 => CC13x2x7_CC26x2x7,
-            CC1354P10 | CC1314R10 => CC13x4_CC26x4,
+        match s {
+            "bin" => Ok(Bin),
+            "hex" => Ok(Hex),
+            "elf" => Ok(Elf),
+            "base64" => Ok(Base64),
+            "ti-txt" => Ok(TiTxt),
+            input => InvalidInputFormat { input }.fail(),
+        }
+    }
+}
+
+/// Format of the data read from the external flash.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum OutputFormat {
+    Bin,
+    /// Base64 encoded lines of 76 characters
+    Base64,
+    /// TI-TXT, with the external flash offsets as addresses
+    TiTxt,
+}
+
+impl str::FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use OutputFormat::*;
+
+        match s {
+            "bin" => Ok(Bin),
+            "base64" => Ok(Base64),
+            "ti-txt" => Ok(TiTxt),
+            input => InvalidOutputFormat { input }.fail(),
+        }
+    }
+}
+
+/// Algorithm of the checksums printed and compared by flash-rover, to match
+/// what the bootloader of the application expects.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CrcAlgorithm {
+    /// CRC-32/ISO-HDLC, as used by Ethernet and zlib
+    Crc32,
+    /// CRC-32/ISCSI, with the Castagnoli polynomial
+    Crc32c,
+    /// CRC-16/CCITT-FALSE, as used by many serial bootloaders
+    Crc16Ccitt,
+}
+
+impl CrcAlgorithm {
+    /// Name of the algorithm, as given on the command line.
+    pub fn name(&self) -> &'static str {
+        use CrcAlgorithm::*;
+
+        match self {
+            Crc32 => "crc32",
+            Crc32c => "crc32c",
+            Crc16Ccitt => "crc16-ccitt",
+        }
+    }
+
+    /// Label of the checksums in reports.
+    pub fn label(&self) -> &'static str {
+        use CrcAlgorithm::*;
+
+        match self {
+            Crc32 => "CRC32",
+            Crc32c => "CRC32C",
+            Crc16Ccitt => "CRC16",
+        }
+    }
+
+    /// Identifier of the algorithm in the Checksum command of the firmware.
+    pub fn id(&self) -> u32 {
+        use CrcAlgorithm::*;
+
+        match self {
+            Crc32 => 0,
+            Crc32c => 1,
+            Crc16Ccitt => 2,
+        }
+    }
+
+    /// Checksum in hexadecimal, with as many digits as the algorithm has.
+    pub fn format(&self, crc: u32) -> String {
+        match self {
+            CrcAlgorithm::Crc16Ccitt => format!("0x{:04X}", crc),
+            _ => format!("0x{:08X}", crc),
+        }
+    }
+}
+
+impl str::FromStr for CrcAlgorithm {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use CrcAlgorithm::*;
+
+        match s {
+            "crc32" => Ok(Crc32),
+            "crc32c" => Ok(Crc32c),
+            "crc16-ccitt" => Ok(Crc16Ccitt),
+            input => InvalidCrcAlgorithm { input }.fail(),
         }
     }
 }
@@ -234,3 +875,39 @@ impl TryFrom<Vec<String>> for SpiPins {
         Ok(Self(dios))
     }
 }
+
+/// GPIO selecting the external flash on boards where its pins are shared with
+/// other peripherals through a mux or an I/O expander. The firmware drives
+/// the DIO to the level before accessing the external flash.
+#[derive(Copy, Clone, Debug)]
+pub struct MuxSelect {
+    pub dio: u8,
+    pub level: bool,
+}
+
+impl TryFrom<Vec<String>> for MuxSelect {
+    type Error = Error;
+
+    fn try_from(s: Vec<String>) -> Result<Self, Self::Error> {
+        ensure!(
+            s.len() == 2,
+            InvalidMuxSelect {
+                msg: format!("mux select must be 2 values, got: {}", s.len())
+            }
+        );
+
+        let dio = s[0].parse().ok().context(InvalidMuxSelect {
+            msg: "DIO value is invalid",
+        })?;
+        let level = match s[1].as_str() {
+            "0" => false,
+            "1" => true,
+            _ => InvalidMuxSelect {
+                msg: "level must be 0 or 1",
+            }
+            .fail()?,
+        };
+
+        Ok(Self { dio, level })
+    }
+}
@@ -0,0 +1,308 @@
+// Copyright (c) 2020 , Texas Instruments.
+// Licensed under the BSD-3-Clause license
+// (see LICENSE or <https://opensource.org/licenses/BSD-3-Clause>) All files in the project
+// notice may not be copied, modified, or distributed except according to those terms.
+
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::str;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use snafu::{Backtrace, OptionExt, ResultExt, Snafu};
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Unable to run XDS110 utility {}: {}", path.display(), source))]
+    RunUtility {
+        path: PathBuf,
+        source: io::Error,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("XDS110 utility {} failed: {}", path.display(), stderr))]
+    UtilityFailed {
+        path: PathBuf,
+        stderr: String,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("XDS110 with serial number {} not found", serial))]
+    ProbeNotFound {
+        serial: String,
+        backtrace: Backtrace,
+    },
+    #[snafu(display(
+        "XDS110 with serial number {} is connected, but its USB driver is not working (device \
+         status {}), reinstall the XDS110 drivers bundled with CCS or UniFlash",
+        serial,
+        status
+    ))]
+    DriverNotBound {
+        serial: String,
+        status: String,
+        backtrace: Backtrace,
+    },
+    #[snafu(display(
+        "Timed out after {} waiting for {}",
+        humantime::format_duration(*timeout),
+        probe
+    ))]
+    WaitTimeout {
+        probe: String,
+        timeout: Duration,
+        backtrace: Backtrace,
+    },
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+const UTILITY_DIR: &str = "ccs_base/common/uscif/xds110";
+const RESET_UTILITY: &str = "xds110reset";
+const DFU_UTILITY: &str = "xdsdfu";
+/// USB vendor and product ID of the XDS110
+const USB_ID: &str = "VID_0451&PID_BEF3";
+
+const POWER_OFF_TIME: Duration = Duration::from_millis(500);
+const POWER_ON_SETTLE_TIME: Duration = Duration::from_millis(500);
+/// Interval between the enumerations while waiting for a probe
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Copy, Clone, Debug)]
+pub enum Power {
+    On,
+    Off,
+}
+
+/// XDS110 firmware version, e.g. 3.0.0.13
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FirmwareVersion(pub [u32; 4]);
+
+impl fmt::Display for FirmwareVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let [major, minor, patch, build] = self.0;
+        write!(f, "{}.{}.{}.{}", major, minor, patch, build)
+    }
+}
+
+impl str::FromStr for FirmwareVersion {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<u32> = s
+            .trim()
+            .split('.')
+            .map(|part| part.parse().map_err(|_| ()))
+            .collect::<Result<_, _>>()?;
+
+        match parts.as_slice() {
+            [major, minor, patch, build] => Ok(Self([*major, *minor, *patch, *build])),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A XDS110 enumerated on the USB bus.
+#[derive(Clone, Debug)]
+pub struct ProbeInfo {
+    pub serial: String,
+    pub version: Option<FirmwareVersion>,
+}
+
+/// Enumerate all XDS110 probes connected to the host.
+pub fn enumerate(ccs_path: &Path) -> Result<Vec<ProbeInfo>> {
+    let output = run_utility(ccs_path, DFU_UTILITY, &["-e"])?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    // Each device is listed as a block of "Key: Value" lines, where the
+    // firmware version precedes the serial number
+    let mut probes = Vec::new();
+    let mut version = None;
+    for line in stdout.lines() {
+        let line = line.trim();
+        if let Some(value) = line.strip_prefix("Version:") {
+            version = value.parse().ok();
+        } else if let Some(value) = line.strip_prefix("Serial Num:") {
+            probes.push(ProbeInfo {
+                serial: value.trim().to_string(),
+                version: version.take(),
+            });
+        }
+    }
+
+    Ok(probes)
+}
+
+/// Wait for the XDS110 with the serial number, or any XDS110 if none is
+/// given, to be enumerated, e.g. until the board is plugged in. Polls until
+/// the timeout if any, and returns the serial number of the probe.
+pub fn wait_for_probe(
+    ccs_path: &Path,
+    serial: Option<&str>,
+    timeout: Option<Duration>,
+) -> Result<String> {
+    let start = Instant::now();
+    loop {
+        let probes = match enumerate(ccs_path) {
+            Ok(probes) => probes,
+            // The utility fails while no probe is connected
+            Err(Error::UtilityFailed { .. }) => Vec::new(),
+            Err(err) => return Err(err),
+        };
+        if let Some(probe) = probes
+            .into_iter()
+            .find(|probe| serial.is_none_or(|serial| probe.serial == serial))
+        {
+            return Ok(probe.serial);
+        }
+
+        if let Some(timeout) = timeout {
+            ensure!(
+                start.elapsed() < timeout,
+                WaitTimeout {
+                    probe: match serial {
+                        Some(serial) => format!("XDS110 with serial number {}", serial),
+                        None => "an XDS110".to_string(),
+                    },
+                    timeout,
+                }
+            );
+        }
+        thread::sleep(WAIT_POLL_INTERVAL);
+    }
+}
+
+/// Handle to a XDS110 debug probe, controlled through the command line
+/// utilities bundled with CCS.
+pub struct Xds110<'a> {
+    ccs_path: &'a Path,
+    serial: &'a str,
+}
+
+impl<'a> Xds110<'a> {
+    pub fn new(ccs_path: &'a Path, serial: &'a str) -> Self {
+        Self { ccs_path, serial }
+    }
+
+    /// Switch the target power supplied by the probe. Only has an effect on
+    /// boards where the XDS110 power switch is wired to the target supply.
+    pub fn set_power(&self, power: Power) -> Result<()> {
+        let action = match power {
+            Power::On => "poweron",
+            Power::Off => "poweroff",
+        };
+
+        self.run(RESET_UTILITY, &["-s", self.serial, "-a", action])?;
+
+        Ok(())
+    }
+
+    pub fn power_cycle(&self) -> Result<()> {
+        self.set_power(Power::Off)?;
+        thread::sleep(POWER_OFF_TIME);
+        self.set_power(Power::On)?;
+        thread::sleep(POWER_ON_SETTLE_TIME);
+
+        Ok(())
+    }
+
+    /// Query the firmware version of the probe.
+    pub fn firmware_version(&self) -> Result<Option<FirmwareVersion>> {
+        let probe = enumerate(self.ccs_path)?
+            .into_iter()
+            .find(|probe| probe.serial == self.serial)
+            .context(ProbeNotFound {
+                serial: self.serial,
+            })?;
+
+        Ok(probe.version)
+    }
+
+    /// Whether the probe is enumerated on the USB bus, e.g. again after it
+    /// dropped off.
+    pub fn is_enumerated(&self) -> Result<bool> {
+        Ok(enumerate(self.ccs_path)?
+            .iter()
+            .any(|probe| probe.serial == self.serial))
+    }
+
+    /// Check that the probe is enumerated before the JVM is started, on which
+    /// DSS only fails after several seconds with an obscure error. On Windows
+    /// a probe present on the bus but not enumerated has no working driver.
+    /// The check is skipped if the probes cannot be enumerated.
+    pub fn check_present(&self) -> Result<()> {
+        if self.is_enumerated().unwrap_or(true) {
+            return Ok(());
+        }
+
+        if cfg!(windows) {
+            if let Some(status) = pnp_device_status(self.serial) {
+                return DriverNotBound {
+                    serial: self.serial,
+                    status,
+                }
+                .fail();
+            }
+        }
+
+        ProbeNotFound {
+            serial: self.serial,
+        }
+        .fail()
+    }
+
+    fn run(&self, utility: &str, args: &[&str]) -> Result<Output> {
+        run_utility(self.ccs_path, utility, args)
+    }
+}
+
+fn run_utility(ccs_path: &Path, utility: &str, args: &[&str]) -> Result<Output> {
+    let path = utility_path(ccs_path, utility);
+
+    let output = Command::new(&path)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .context(RunUtility { path: &path })?;
+
+    ensure!(
+        output.status.success(),
+        UtilityFailed {
+            path,
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        }
+    );
+
+    Ok(output)
+}
+
+/// Status of the USB device of the probe as reported by the Windows device
+/// manager, e.g. `Error` when no driver is bound, if it is present.
+fn pnp_device_status(serial: &str) -> Option<String> {
+    let query = format!(
+        "Get-PnpDevice -PresentOnly -InstanceId 'USB\\{}\\{}' -ErrorAction SilentlyContinue \
+         | ForEach-Object Status",
+        USB_ID, serial
+    );
+    let output = Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", &query])
+        .stdin(Stdio::null())
+        .output()
+        .ok()?;
+
+    let status = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if status.is_empty() {
+        None
+    } else {
+        Some(status)
+    }
+}
+
+fn utility_path(ccs_path: &Path, utility: &str) -> PathBuf {
+    let path = ccs_path.join(UTILITY_DIR).join(utility);
+    if cfg!(windows) {
+        path.with_extension("exe")
+    } else {
+        path
+    }
+}
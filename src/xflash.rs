@@ -4,6 +4,8 @@
 // notice may not be copied, modified, or distributed except according to those terms.
 
 use std::fmt;
+use std::ops::{Range, RangeInclusive};
+use std::time::Duration;
 
 use byte_unit::Byte;
 
@@ -11,18 +13,142 @@ use byte_unit::Byte;
 pub struct XflashId {
     mid: u32,
     did: u32,
+    /// JEDEC ID of the part, if it supports RDID
+    jedec: Option<JedecId>,
+}
+
+impl XflashId {
+    /// Whether the ID read from a part matches the ID of a known part. The
+    /// JEDEC ID tells apart parts of which the device IDs collide, and is
+    /// compared if both have one.
+    fn matches(&self, known: &XflashId) -> bool {
+        self.mid == known.mid
+            && self.did == known.did
+            && match (self.jedec, known.jedec) {
+                (Some(jedec), Some(known)) => jedec == known,
+                _ => true,
+            }
+    }
+}
+
+/// JEDEC ID read with RDID (0x9F).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct JedecId {
+    /// Continuation codes (0x7F) before the manufacturer ID, one per JEP106
+    /// bank beyond the first
+    pub bank: u8,
+    pub manufacturer: u8,
+    pub memory_type: u8,
+    pub capacity: u8,
+}
+
+impl JedecId {
+    /// JEDEC ID of a manufacturer in the first JEP106 bank.
+    const fn new(manufacturer: u32, memory_type: u8, capacity: u8) -> Self {
+        Self {
+            bank: 0,
+            manufacturer: manufacturer as u8,
+            memory_type,
+            capacity,
+        }
+    }
+}
+
+impl fmt::Display for JedecId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for _ in 0..self.bank {
+            write!(f, "7F ")?;
+        }
+        write!(
+            f,
+            "{:02X} {:02X} {:02X}",
+            self.manufacturer, self.memory_type, self.capacity
+        )
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
 pub struct XflashInfo {
     name: &'static str,
     size: u32,
+    /// Minimum supply voltage for erase and program, in millivolts
+    min_voltage: u32,
+    /// Maximum supply voltage, in millivolts
+    max_voltage: u32,
+    /// Size of the page program buffer, in bytes
+    page_size: u32,
+    timing: &'static Timing,
+}
+
+/// Typical and maximum duration of an erase or program operation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OpTime {
+    pub typical: Duration,
+    pub max: Duration,
+}
+
+impl OpTime {
+    const fn from_millis(typical: u64, max: u64) -> Self {
+        Self {
+            typical: Duration::from_millis(typical),
+            max: Duration::from_millis(max),
+        }
+    }
+}
+
+/// Erase and program times of a part, from its datasheet, from which the
+/// timeouts of the firmware commands are derived.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Timing {
+    /// Erase of a 4 KB sector
+    pub sector_erase: OpTime,
+    /// Erase of a 64 KB block
+    pub block_erase: OpTime,
+    pub chip_erase: OpTime,
+    /// Program of a page of up to the page program size
+    pub page_program: OpTime,
+}
+
+impl Timing {
+    const MX25R: Self = Self {
+        sector_erase: OpTime::from_millis(40, 240),
+        block_erase: OpTime::from_millis(400, 3000),
+        chip_erase: OpTime::from_millis(0, 0),
+        page_program: OpTime::from_millis(1, 4),
+    };
+
+    const W25X: Self = Self {
+        sector_erase: OpTime::from_millis(30, 300),
+        block_erase: OpTime::from_millis(150, 1000),
+        chip_erase: OpTime::from_millis(0, 0),
+        page_program: OpTime::from_millis(1, 3),
+    };
+
+    /// Conservative times for parts which are not known, covering the slowest
+    /// of the known parts with margin.
+    pub const UNKNOWN: Self = Self {
+        sector_erase: OpTime::from_millis(40, 500),
+        block_erase: OpTime::from_millis(400, 4000),
+        chip_erase: OpTime::from_millis(50_000, 240_000),
+        page_program: OpTime::from_millis(1, 10),
+    };
+
+    /// Chip erase time, which depends on the size of the part.
+    const fn with_chip_erase(self, typical: u64, max: u64) -> Self {
+        Self {
+            chip_erase: OpTime::from_millis(typical, max),
+            ..self
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
 pub enum Xflash {
     Known(XflashId, XflashInfo),
     Unknown(XflashId),
+    /// Identified by the electronic signature (RES) only, which does not
+    /// include the manufacturer ID
+    UnknownSignature(u32),
 }
 
 const SUPPORTED_HW: &[Xflash] = &[
@@ -31,80 +157,151 @@ const SUPPORTED_HW: &[Xflash] = &[
         XflashId {
             mid: 0xC2,
             did: 0x17,
+            jedec: Some(JedecId::new(0xC2, 0x28, 0x17)),
         },
         XflashInfo {
             name: "Macronix MX25R6435F",
             size: 0x0400_0000,
+            min_voltage: 1650,
+            max_voltage: 3600,
+            page_size: 256,
+            timing: &Timing::MX25R.with_chip_erase(50_000, 150_000),
         },
     ),
     Xflash::Known(
         XflashId {
             mid: 0xC2,
             did: 0x16,
+            jedec: Some(JedecId::new(0xC2, 0x28, 0x16)),
         },
         XflashInfo {
             name: "Macronix MX25R3235F",
             size: 0x0200_0000,
+            min_voltage: 1650,
+            max_voltage: 3600,
+            page_size: 256,
+            timing: &Timing::MX25R.with_chip_erase(25_000, 75_000),
         },
     ),
     Xflash::Known(
         XflashId {
             mid: 0xC2,
             did: 0x15,
+            jedec: Some(JedecId::new(0xC2, 0x28, 0x15)),
         },
         XflashInfo {
             name: "Macronix MX25R1635F",
             size: 0x0100_0000,
+            min_voltage: 1650,
+            max_voltage: 3600,
+            page_size: 256,
+            timing: &Timing::MX25R.with_chip_erase(12_500, 38_000),
         },
     ),
     Xflash::Known(
         XflashId {
             mid: 0xC2,
             did: 0x14,
+            jedec: Some(JedecId::new(0xC2, 0x28, 0x14)),
         },
         XflashInfo {
             name: "Macronix MX25R8035F",
             size: 0x0080_0000,
+            min_voltage: 1650,
+            max_voltage: 3600,
+            page_size: 256,
+            timing: &Timing::MX25R.with_chip_erase(6500, 20_000),
         },
     ),
     Xflash::Known(
         XflashId {
             mid: 0xC2,
             did: 0x13,
+            jedec: Some(JedecId::new(0xC2, 0x28, 0x13)),
         },
         XflashInfo {
             name: "Macronix MX25R4035F",
             size: 0x0040_0000,
+            min_voltage: 1650,
+            max_voltage: 3600,
+            page_size: 256,
+            timing: &Timing::MX25R.with_chip_erase(3500, 10_000),
         },
     ),
     Xflash::Known(
         XflashId {
             mid: 0xC2,
             did: 0x12,
+            jedec: Some(JedecId::new(0xC2, 0x28, 0x12)),
         },
         XflashInfo {
             name: "Macronix MX25R2035F",
             size: 0x0020_0000,
+            min_voltage: 1650,
+            max_voltage: 3600,
+            page_size: 256,
+            timing: &Timing::MX25R.with_chip_erase(2000, 6000),
         },
     ),
     Xflash::Known(
         XflashId {
             mid: 0xC2,
             did: 0x11,
+            jedec: Some(JedecId::new(0xC2, 0x28, 0x11)),
         },
         XflashInfo {
             name: "Macronix MX25R1035F",
             size: 0x0010_0000,
+            min_voltage: 1650,
+            max_voltage: 3600,
+            page_size: 256,
+            timing: &Timing::MX25R.with_chip_erase(1000, 3000),
         },
     ),
     Xflash::Known(
         XflashId {
             mid: 0xC2,
             did: 0x10,
+            jedec: Some(JedecId::new(0xC2, 0x28, 0x10)),
         },
         XflashInfo {
             name: "Macronix MX25R512F",
             size: 0x0008_0000,
+            min_voltage: 1650,
+            max_voltage: 3600,
+            page_size: 256,
+            timing: &Timing::MX25R.with_chip_erase(600, 2000),
+        },
+    ),
+    // Macronix 1.8 V
+    Xflash::Known(
+        XflashId {
+            mid: 0xC2,
+            did: 0x36,
+            jedec: Some(JedecId::new(0xC2, 0x25, 0x36)),
+        },
+        XflashInfo {
+            name: "Macronix MX25U3235F",
+            size: 0x0200_0000,
+            min_voltage: 1650,
+            max_voltage: 2000,
+            page_size: 256,
+            timing: &Timing::MX25R.with_chip_erase(25_000, 50_000),
+        },
+    ),
+    Xflash::Known(
+        XflashId {
+            mid: 0xC2,
+            did: 0x35,
+            jedec: Some(JedecId::new(0xC2, 0x25, 0x35)),
+        },
+        XflashInfo {
+            name: "Macronix MX25U1635F",
+            size: 0x0100_0000,
+            min_voltage: 1650,
+            max_voltage: 2000,
+            page_size: 256,
+            timing: &Timing::MX25R.with_chip_erase(14_000, 25_000),
         },
     ),
     // WinBond
@@ -112,76 +309,248 @@ const SUPPORTED_HW: &[Xflash] = &[
         XflashId {
             mid: 0xEF,
             did: 0x12,
+            jedec: Some(JedecId::new(0xEF, 0x30, 0x13)),
         },
         XflashInfo {
             name: "WinBond W25X40CL",
             size: 0x0040_0000,
+            min_voltage: 2300,
+            max_voltage: 3600,
+            page_size: 256,
+            timing: &Timing::W25X.with_chip_erase(1000, 4000),
         },
     ),
     Xflash::Known(
         XflashId {
             mid: 0xEF,
             did: 0x11,
+            jedec: Some(JedecId::new(0xEF, 0x30, 0x12)),
         },
         XflashInfo {
             name: "WinBond W25X20CL",
             size: 0x0020_0000,
+            min_voltage: 2300,
+            max_voltage: 3600,
+            page_size: 256,
+            timing: &Timing::W25X.with_chip_erase(500, 2000),
         },
     ),
     Xflash::Known(
         XflashId {
             mid: 0xEF,
             did: 0x10,
+            jedec: Some(JedecId::new(0xEF, 0x30, 0x11)),
         },
         XflashInfo {
             name: "WinBond W25X10CL",
             size: 0x0010_0000,
+            min_voltage: 2300,
+            max_voltage: 3600,
+            page_size: 256,
+            timing: &Timing::W25X.with_chip_erase(300, 1000),
         },
     ),
     Xflash::Known(
         XflashId {
             mid: 0xEF,
             did: 0x05,
+            jedec: Some(JedecId::new(0xEF, 0x30, 0x10)),
         },
         XflashInfo {
             name: "WinBond W25X05CL",
             size: 0x0008_0000,
+            min_voltage: 2300,
+            max_voltage: 3600,
+            page_size: 256,
+            timing: &Timing::W25X.with_chip_erase(200, 700),
         },
     ),
 ];
 
+impl fmt::Display for XflashId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MID: 0x{:X}, DID: 0x{:X}", self.mid, self.did)?;
+        if let Some(jedec) = self.jedec {
+            write!(f, ", JEDEC ID: {}", jedec)?;
+        }
+        Ok(())
+    }
+}
+
 impl fmt::Display for Xflash {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Xflash::Known(id, info) => write!(
                 f,
-                "{}, {} (MID: 0x{:X}, DID: 0x{:X})",
+                "{}, {} ({})",
                 info.name,
                 Byte::from_bytes(info.size as u128)
                     .get_appropriate_unit(true)
                     .to_string(),
-                id.mid,
-                id.did,
+                id,
             ),
-            Xflash::Unknown(id) => write!(
+            Xflash::Unknown(id) => write!(f, "Unknown external flash ({})", id),
+            Xflash::UnknownSignature(signature) => write!(
                 f,
-                "Unknown external flash (MID: 0x{:X}, DID: 0x{:X})",
-                id.mid, id.did,
+                "Unknown external flash (electronic signature: 0x{:X})",
+                signature,
             ),
         }
     }
 }
 
+const MID_MACRONIX: u32 = 0xC2;
+const MID_WINBOND: u32 = 0xEF;
+
+/// Size of the blocks protected by each step of the block protect bits.
+const PROTECT_BLOCK_SIZE: u32 = 0x1_0000;
+
 impl Xflash {
-    pub fn from_id(mid: u32, did: u32) -> Self {
-        let id = XflashId { mid, did };
+    /// Address range protected against erase and program by the block
+    /// protect bits of the status register, if any. Parts with an unknown
+    /// status register layout are assumed to be protected entirely when any
+    /// block protect bit is set.
+    pub fn protected_range(&self, status: u8) -> Option<Range<u32>> {
+        const ANY_BP: u8 = 0x3C;
+
+        let (id, info) = match self {
+            Xflash::Known(id, info) => (id, info),
+            _ if status & ANY_BP != 0 => return Some(0..u32::MAX),
+            _ => return None,
+        };
+
+        // Sizes are in bits
+        let size = info.size / 8;
+
+        // Macronix has BP3..BP0 in bit 5..2, while WinBond has BP2..BP0 in
+        // bit 4..2 and the top/bottom select in bit 5
+        let (bp, bottom) = match id.mid {
+            MID_MACRONIX => ((status >> 2) & 0x0F, false),
+            MID_WINBOND => ((status >> 2) & 0x07, status & 0x20 != 0),
+            _ if status & ANY_BP != 0 => return Some(0..u32::MAX),
+            _ => return None,
+        };
+
+        if bp == 0 {
+            return None;
+        }
+
+        // Each step doubles the protected area, starting at one block
+        let length = (PROTECT_BLOCK_SIZE << (bp - 1)).min(size);
+
+        if bottom {
+            Some(0..length)
+        } else {
+            Some(size - length..size)
+        }
+    }
+
+    /// Whether the part is in the table of known parts.
+    pub fn is_known(&self) -> bool {
+        matches!(self, Xflash::Known(..))
+    }
+
+    /// Name of the part, if it is known.
+    pub fn name(&self) -> Option<&'static str> {
+        match self {
+            Xflash::Known(_, info) => Some(info.name),
+            _ => None,
+        }
+    }
+
+    /// Whether a part of the given name is in the table of known parts.
+    pub fn is_known_name(name: &str) -> bool {
         SUPPORTED_HW
             .iter()
-            .find(|xflash| match xflash {
-                Xflash::Known(maybe_id, _) => &id == maybe_id,
-                _ => false,
+            .any(|xflash| xflash.name() == Some(name))
+    }
+
+    /// Size in bytes, if the part is known.
+    pub fn size(&self) -> Option<u32> {
+        match self {
+            // Sizes are in bits
+            Xflash::Known(_, info) => Some(info.size / 8),
+            _ => None,
+        }
+    }
+
+    /// Size of the page program buffer in bytes, if the part is known.
+    pub fn page_size(&self) -> Option<u32> {
+        match self {
+            Xflash::Known(_, info) => Some(info.page_size),
+            _ => None,
+        }
+    }
+
+    /// Whether the part has the configuration register of Macronix parts.
+    pub fn has_config_register(&self) -> bool {
+        match self {
+            Xflash::Known(id, _) | Xflash::Unknown(id) => id.mid == MID_MACRONIX,
+            Xflash::UnknownSignature(_) => false,
+        }
+    }
+
+    /// Erase and program times, or conservative times if the part is not
+    /// known.
+    pub fn timing(&self) -> Timing {
+        match self {
+            Xflash::Known(_, info) => *info.timing,
+            _ => Timing::UNKNOWN,
+        }
+    }
+
+    /// Minimum supply voltage for erase and program in millivolts, if the
+    /// part is known.
+    pub fn min_voltage(&self) -> Option<u32> {
+        match self {
+            Xflash::Known(_, info) => Some(info.min_voltage),
+            _ => None,
+        }
+    }
+
+    /// Supply voltage range in millivolts, if the part is known.
+    pub fn voltage_range(&self) -> Option<RangeInclusive<u32>> {
+        match self {
+            Xflash::Known(_, info) => Some(info.min_voltage..=info.max_voltage),
+            _ => None,
+        }
+    }
+
+    /// Identify a part by its manufacturer and device ID, and its JEDEC ID if
+    /// it supports RDID.
+    pub fn from_id(mid: u32, did: u32, jedec: Option<JedecId>) -> Self {
+        let id = XflashId { mid, did, jedec };
+        SUPPORTED_HW
+            .iter()
+            .find_map(|xflash| match xflash {
+                Xflash::Known(known, info) if id.matches(known) => Some(Xflash::Known(id, *info)),
+                _ => None,
             })
-            .copied()
             .unwrap_or(Xflash::Unknown(id))
     }
+
+    /// JEDEC ID read from the part, if it supports RDID.
+    pub fn jedec_id(&self) -> Option<JedecId> {
+        match self {
+            Xflash::Known(id, _) | Xflash::Unknown(id) => id.jedec,
+            Xflash::UnknownSignature(_) => None,
+        }
+    }
+
+    /// Identify a legacy part by its electronic signature, which usually
+    /// equals the device ID. Only unambiguous matches are known, as the
+    /// manufacturer is not part of the signature.
+    pub fn from_signature(signature: u32) -> Self {
+        let mut matches = SUPPORTED_HW.iter().filter(|xflash| match xflash {
+            Xflash::Known(id, _) => id.did == signature,
+            _ => false,
+        });
+
+        match (matches.next(), matches.next()) {
+            (Some(Xflash::Known(id, info)), None) => {
+                Xflash::Known(XflashId { jedec: None, ..*id }, *info)
+            }
+            _ => Xflash::UnknownSignature(signature),
+        }
+    }
 }